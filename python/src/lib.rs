@@ -1,13 +1,14 @@
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
 use pyo3::types::{PyDict, PyList};
 use std::collections::HashMap;
-use yahoo_finance_symbols::{get_symbols, update_database};
+use yahoo_finance_symbols::{get_all_symbols, get_symbols, update_database};
 use yahoo_finance_symbols::keys::{AssetClass, Exchange, Category};
 
 
 #[pymodule]
 #[pyo3(name = "yahoo_finance_symbols")]
-fn yahoo_finance_symbols_py(_py: Python, m: &PyModule) -> PyResult<()> {
+fn yahoo_finance_symbols_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(update_database_py, m)?).unwrap();
     m.add_function(wrap_pyfunction!(search_symbols_py, m)?).unwrap();
     m.add_function(wrap_pyfunction!(get_symbols_py, m)?).unwrap();
@@ -16,28 +17,34 @@ fn yahoo_finance_symbols_py(_py: Python, m: &PyModule) -> PyResult<()> {
    
     
 #[pyfunction]
-#[pyo3(name = "search_symbols")]
+#[pyo3(name = "search_symbols", signature = (query, asset_class = None))]
 /// Fetches ticker symbols that closely match the specified query and asset class
 ///
 /// # Arguments
 ///
 /// * `query` - `str` - ticker symbol query
-/// * `asset_class` - `str` - asset class (Equity, ETF, Mutual Fund, Index, Currency, Futures, Crypto)
+/// * `asset_class` - `str` - asset class (Equity, ETF, Mutual Fund, Index, Currency, Futures, Crypto, Options);
+///   or Common for the first four of them; omit it to search every asset class
 ///
 /// # Returns
 ///
 /// `dict` - dictionary of ticker symbols and names
 ///
+/// Raises `ValueError` for an unknown asset class.
+///
 /// # Example
 ///
 /// ```
 /// import yahoo_finance_symbols as ys
 ///
 /// symbols = ys.search_symbols("Apple", "Equity")
+/// everything = ys.search_symbols("Apple")
 /// print(symbols)
 /// ```
-pub fn search_symbols_py(query: String, asset_class: String) -> PyObject {
-    let asset_class = match asset_class.as_str() {
+pub fn search_symbols_py(query: String, asset_class: Option<String>) -> PyResult<PyObject> {
+    let asset_class = match asset_class.as_deref().unwrap_or("All") {
+        "All" => AssetClass::All,
+        "Common" => AssetClass::Common,
         "Equity" => AssetClass::Stocks,
         "ETF" => AssetClass::ETFs,
         "Mutual Fund" => AssetClass::MutualFunds,
@@ -45,7 +52,13 @@ pub fn search_symbols_py(query: String, asset_class: String) -> PyObject {
         "Currency" => AssetClass::Currencies,
         "Futures" => AssetClass::Futures,
         "Crypto" => AssetClass::Cryptocurrencies,
-        _ => panic!("Asset class must be one of: Equity, ETF, Mutual Fund, Index, Currency, Futures, Crypto"),
+        "Options" => AssetClass::Options,
+        other => {
+            return Err(PyValueError::new_err(format!(
+                "Unknown asset class {:?}; must be one of: Common, Equity, ETF, Mutual Fund, Index, Currency, Futures, Crypto, Options",
+                other
+            )))
+        }
     };
     let tickers = tokio::task::block_in_place(move || {
         tokio::runtime::Runtime::new().unwrap().block_on(
@@ -59,11 +72,11 @@ pub fn search_symbols_py(query: String, asset_class: String) -> PyObject {
         .map(|tc| (tc.symbol.clone(), tc.name.clone()))
         .collect::<HashMap<String, String>>();
     Python::with_gil(|py| {
-        let py_dict = PyDict::new(py);
+        let py_dict = PyDict::new_bound(py);
         for (symbol, name) in symbols {
-            py_dict.set_item(symbol, name).unwrap();
+            py_dict.set_item(symbol, name)?;
         }
-        py_dict.into()
+        Ok(py_dict.into())
     })
 }
 
@@ -87,15 +100,15 @@ pub fn search_symbols_py(query: String, asset_class: String) -> PyObject {
 pub fn get_symbols_py() -> PyObject {
     let symbols = tokio::task::block_in_place(move || {
         tokio::runtime::Runtime::new().unwrap().block_on(
-            get_symbols(AssetClass::All, Category::All, Exchange::All)
+            get_all_symbols()
        ).unwrap()   
        });
     Python::with_gil(|py| {
-        let pandas = py.import("pandas").expect("Failed to import pandas");
-        let list_of_dicts = PyList::empty(py);
+        let pandas = py.import_bound("pandas").expect("Failed to import pandas");
+        let list_of_dicts = PyList::empty_bound(py);
 
         for tc in symbols {
-            let row = PyDict::new(py);
+            let row = PyDict::new_bound(py);
             row.set_item("symbol", tc.symbol).unwrap();
             row.set_item("name", tc.name).unwrap();
             row.set_item("asset_class", tc.asset_class).unwrap();
@@ -117,7 +130,7 @@ pub fn update_database_py() {
     tokio::task::block_in_place(move || {
          tokio::runtime::Runtime::new().unwrap().block_on(
             update_database()
-        ).unwrap()   
+        ).unwrap();
         })
 
 }
\ No newline at end of file
@@ -0,0 +1,73 @@
+//! Fetching lookup pages from a local server with one shared `reqwest::Client`, as the scraper
+//! does, against a new client per request, as it did before.
+//!
+//! The server keeps connections alive, so the shared client sends every request over the same
+//! connection while a new client opens one each time. Against Yahoo Finance a new client also pays
+//! for a TLS handshake per request, which this plain HTTP server leaves out.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use reqwest::Client;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::Runtime;
+
+/// Requests per iteration, one per lookup page
+const REQUESTS: usize = 50;
+
+/// Serves an empty lookup page on a local port, returning its URL
+async fn serve() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/all?s=A&t=A&b=0&c=10000", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(answer(stream));
+        }
+    });
+    url
+}
+
+/// Answers every request received on `stream` until the client closes it
+async fn answer(mut stream: TcpStream) {
+    let body = "<table><tbody></tbody></table>";
+    let response = format!("HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+    let mut received = Vec::new();
+    let mut buffer = [0; 4096];
+    loop {
+        match stream.read(&mut buffer).await {
+            Ok(0) | Err(_) => return,
+            Ok(read) => received.extend_from_slice(&buffer[..read]),
+        }
+        while let Some(end) = received.windows(4).position(|w| w == b"\r\n\r\n") {
+            received.drain(..end + 4);
+            if stream.write_all(response.as_bytes()).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn http_client(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let url = runtime.block_on(serve());
+
+    let mut group = c.benchmark_group("lookup_requests");
+    group.bench_function("shared_client", |b| {
+        b.iter(|| runtime.block_on(async {
+            let client = Client::new();
+            for _ in 0..REQUESTS {
+                client.get(&url).send().await.unwrap().text().await.unwrap();
+            }
+        }))
+    });
+    group.bench_function("client_per_request", |b| {
+        b.iter(|| runtime.block_on(async {
+            for _ in 0..REQUESTS {
+                Client::new().get(&url).send().await.unwrap().text().await.unwrap();
+            }
+        }))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, http_client);
+criterion_main!(benches);
@@ -0,0 +1,36 @@
+//! Repeated `get_symbol` calls on the bundled `symbols.db`, with and without the prepared
+//! statement cache.
+//!
+//! `cached` opens the database as usual, so each pooled connection keeps its prepared statements
+//! between calls; `uncached` sets the cache capacity of its only connection to 0, so every call
+//! parses the SQL again, as before `prepare_cached`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use tokio::runtime::Runtime;
+use yahoo_finance_symbols::database::SymbolDatabase;
+
+const TICKERS: [&str; 8] = ["AAPL", "MSFT", "AMZN", "GOOGL", "META", "NVDA", "TSLA", "JPM"];
+
+fn get_symbol(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let cached = SymbolDatabase::open("symbols.db").unwrap();
+    let manager = SqliteConnectionManager::file("symbols.db").with_init(|conn| {
+        conn.set_prepared_statement_cache_capacity(0);
+        Ok(())
+    });
+    let uncached = SymbolDatabase::from_pool(Pool::builder().max_size(1).build(manager).unwrap());
+
+    let mut group = c.benchmark_group("get_symbol");
+    for (name, db) in [("cached", &cached), ("uncached", &uncached)] {
+        group.bench_function(name, |b| {
+            let mut tickers = TICKERS.iter().cycle();
+            b.iter(|| runtime.block_on(db.get_symbol(tickers.next().unwrap())).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, get_symbol);
+criterion_main!(benches);
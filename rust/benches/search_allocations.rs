@@ -0,0 +1,63 @@
+//! `search_refs` against `try_search_symbols` on the bundled `symbols.db`.
+//!
+//! A counting global allocator first reports how many allocations one call of each makes:
+//! `try_search_symbols` clones the ticker and name of every match into its map, while `search_refs`
+//! copies the matches into one growing buffer. The timings follow.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use criterion::{criterion_group, criterion_main, Criterion};
+use tokio::runtime::Runtime;
+use yahoo_finance_symbols::database::SymbolDatabase;
+use yahoo_finance_symbols::keys::AssetClass;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Number of allocations made while running `work`, not counting dropping its output
+fn allocations<T>(work: impl FnOnce() -> T) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let output = work();
+    let made = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    drop(output);
+    made
+}
+
+fn search(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let db = SymbolDatabase::open("symbols.db").unwrap();
+
+    let mut group = c.benchmark_group("search");
+    group.sample_size(20);
+    for query in ["bank", "a"] {
+        let owned = allocations(|| runtime.block_on(db.try_search_symbols(query, Some("Equity"))).unwrap());
+        let borrowed = allocations(|| runtime.block_on(db.search_refs(query, AssetClass::Stocks)).unwrap());
+        eprintln!("{:?}: search_symbols {} allocations, search_refs {} allocations", query, owned, borrowed);
+
+        group.bench_function(format!("search_symbols/{}", query), |b| {
+            b.iter(|| runtime.block_on(db.try_search_symbols(query, Some("Equity"))).unwrap())
+        });
+        group.bench_function(format!("search_refs/{}", query), |b| {
+            b.iter(|| runtime.block_on(db.search_refs(query, AssetClass::Stocks)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, search);
+criterion_main!(benches);
@@ -0,0 +1,42 @@
+//! Building the Polars DataFrame of `get_symbols_df` from the bundled `symbols.db`.
+//!
+//! `single_pass` is the library path, which fills the five column vectors in one loop over the
+//! rows; `five_passes` walks the rows once per column, as `get_symbols_df` used to. Both read the
+//! table first, and `read_only` measures that part alone.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use polars::prelude::*;
+use tokio::runtime::Runtime;
+use yahoo_finance_symbols::database::SymbolDatabase;
+use yahoo_finance_symbols::Symbol;
+
+fn five_passes(symbols: &[Symbol]) -> PolarsResult<DataFrame> {
+    let mut df = DataFrame::new(vec![
+        Series::new("symbol", symbols.iter().map(|s| s.symbol.as_str()).collect::<Vec<&str>>()),
+        Series::new("name", symbols.iter().map(|s| s.name.as_str()).collect::<Vec<&str>>()),
+        Series::new("category", symbols.iter().map(|s| s.category.as_str()).collect::<Vec<&str>>()),
+        Series::new("asset_class", symbols.iter().map(|s| s.asset_class.as_str()).collect::<Vec<&str>>()),
+        Series::new("exchange", symbols.iter().map(|s| s.exchange.as_str()).collect::<Vec<&str>>()),
+    ])?;
+    for column in ["category", "asset_class", "exchange"] {
+        df.try_apply(column, |s| s.cast(&DataType::Categorical(None, CategoricalOrdering::Physical)))?;
+    }
+    Ok(df)
+}
+
+fn symbols_df(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let db = SymbolDatabase::open("symbols.db").unwrap();
+
+    let mut group = c.benchmark_group("symbols_df");
+    group.sample_size(10);
+    group.bench_function("read_only", |b| b.iter(|| runtime.block_on(db.get_all_symbols()).unwrap()));
+    group.bench_function("single_pass", |b| b.iter(|| runtime.block_on(db.get_symbols_df()).unwrap()));
+    group.bench_function("five_passes", |b| {
+        b.iter(|| five_passes(&runtime.block_on(db.get_all_symbols()).unwrap()).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, symbols_df);
+criterion_main!(benches);
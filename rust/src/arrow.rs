@@ -0,0 +1,122 @@
+//! Symbols as an Arrow `RecordBatch`, for Arrow-based systems such as DataFusion or PyArrow.
+//!
+//! Only compiled with the `arrow` feature. The arrays are those of the `arrow-array` crate, the
+//! array layer of the `arrow` crate, re-exported here as [`arrow_array`] and [`arrow_schema`] so
+//! that callers use the matching version. `RecordBatch` converts into a `StructArray`, which
+//! `arrow_array::ffi::to_ffi` hands to any Arrow consumer through the C data interface without
+//! copying it.
+//!
+//! | Column | Arrow type |
+//! |--------|------------|
+//! | `symbol` | `Utf8` |
+//! | `name` | `Utf8` |
+//! | `category` | `Dictionary<UInt32, Utf8>` |
+//! | `asset_class` | `Dictionary<UInt32, Utf8>` |
+//! | `exchange` | `Dictionary<UInt32, Utf8>` |
+//!
+//! The dictionary columns hold a few hundred distinct values across all rows, so each row
+//! stores a 4-byte key instead of the string. No column is nullable. Every batch carries
+//! [`symbols_schema`] as its schema.
+
+use std::error::Error;
+use std::sync::Arc;
+pub use arrow_array;
+pub use arrow_schema;
+pub use arrow_array::RecordBatch;
+pub use arrow_schema::SchemaRef;
+use arrow_array::builder::StringDictionaryBuilder;
+use arrow_array::types::UInt32Type;
+use arrow_array::{ArrayRef, StringArray};
+use arrow_schema::{DataType, Field, Schema};
+use crate::Symbol;
+
+/// Names of the record batch columns, in order
+pub const COLUMNS: [&str; 5] = ["symbol", "name", "category", "asset_class", "exchange"];
+
+/// Columns stored as dictionaries
+const DICTIONARY_COLUMNS: [&str; 3] = ["category", "asset_class", "exchange"];
+
+type DictionaryBuilder = StringDictionaryBuilder<UInt32Type>;
+
+/// Schema of the batches built by [`symbols_to_record_batch`]
+pub fn symbols_schema() -> SchemaRef {
+    let fields = COLUMNS
+        .iter()
+        .map(|&column| {
+            let data_type = if DICTIONARY_COLUMNS.contains(&column) {
+                DataType::Dictionary(Box::new(DataType::UInt32), Box::new(DataType::Utf8))
+            } else {
+                DataType::Utf8
+            };
+            Field::new(column, data_type, false)
+        })
+        .collect::<Vec<_>>();
+    Arc::new(Schema::new(fields))
+}
+
+/// Builds a record batch with one column per `Symbol` field, laid out as [`symbols_schema`]
+///
+/// The five columns are filled in a single pass over `symbols`.
+pub fn symbols_to_record_batch(symbols: &[Symbol]) -> Result<RecordBatch, Box<dyn Error>> {
+    let mut category = DictionaryBuilder::new();
+    let mut asset_class = DictionaryBuilder::new();
+    let mut exchange = DictionaryBuilder::new();
+    for s in symbols {
+        category.append(&s.category)?;
+        asset_class.append(&s.asset_class)?;
+        exchange.append(&s.exchange)?;
+    }
+
+    let arrays: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(symbols.iter().map(|s| s.symbol.as_str()))),
+        Arc::new(StringArray::from_iter_values(symbols.iter().map(|s| s.name.as_str()))),
+        Arc::new(category.finish()),
+        Arc::new(asset_class.finish()),
+        Arc::new(exchange.finish()),
+    ];
+
+    Ok(RecordBatch::try_new(symbols_schema(), arrays)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::types::UInt32Type;
+    use arrow_array::{Array, DictionaryArray, StringArray};
+    use arrow_schema::DataType;
+    use crate::Symbol;
+    use super::{symbols_schema, symbols_to_record_batch, COLUMNS};
+
+    #[test]
+    fn check_record_batch() {
+        let symbol = |ticker: &str, exchange: &str| Symbol {
+            symbol: ticker.to_string(),
+            name: format!("{} Inc.", ticker),
+            category: "Technology".to_string(),
+            asset_class: "Stocks".to_string(),
+            exchange: exchange.to_string(),
+        };
+        let symbols = vec![symbol("AAPL", "NMS"), symbol("MSFT", "NMS"), symbol("SAP", "NYQ")];
+
+        let batch = symbols_to_record_batch(&symbols).unwrap();
+        assert_eq!(batch.num_rows(), 3);
+        let schema = symbols_schema();
+        assert_eq!(batch.schema(), schema);
+        assert_eq!(schema.fields().iter().map(|f| f.name().as_str()).collect::<Vec<_>>(), COLUMNS);
+        let dictionary = DataType::Dictionary(Box::new(DataType::UInt32), Box::new(DataType::Utf8));
+        let types: Vec<&DataType> = schema.fields().iter().map(|f| f.data_type()).collect();
+        assert_eq!(types, [&DataType::Utf8, &DataType::Utf8, &dictionary, &dictionary, &dictionary]);
+        assert!(schema.fields().iter().all(|f| !f.is_nullable()));
+
+        let tickers = batch.column(0).as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(tickers.iter().flatten().collect::<Vec<_>>(), ["AAPL", "MSFT", "SAP"]);
+
+        // Repeated values share one dictionary entry
+        let exchanges = batch.column(4).as_any().downcast_ref::<DictionaryArray<UInt32Type>>().unwrap();
+        assert_eq!(exchanges.values().len(), 2);
+        assert_eq!(exchanges.keys().values().as_ref(), [0, 0, 1]);
+
+        let empty = symbols_to_record_batch(&[]).unwrap();
+        assert_eq!(empty.num_rows(), 0);
+        assert_eq!(empty.schema(), schema);
+    }
+}
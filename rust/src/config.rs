@@ -0,0 +1,302 @@
+//! Crate-wide settings, loadable from a JSON file.
+//!
+//! Every field is optional in the file; anything left out keeps its default value.
+//!
+//! ```json
+//! {
+//!     "database": {
+//!         "path": "symbols.db",
+//!         "pool_size": 10,
+//!         "download_url": "https://github.com/Nnamdi-sys/yahoo-finance-symbols/raw/{ref}/rust/src/symbols.db",
+//!         "download_ref": "main",
+//!         "download_timeout_secs": 300,
+//!         "auto_provision": true,
+//!         "serve_while_scraping": false
+//!     },
+//!     "scrape": {
+//!         "page_size": 10000,
+//!         "start_offset": 0,
+//!         "concurrency": 5,
+//!         "min_name_len": 1,
+//!         "deterministic": false
+//!     },
+//!     "search": {
+//!         "max_results": 1000
+//!     }
+//! }
+//! ```
+//!
+//! Settings are applied with [`set_config`]. Database settings are read once, when the
+//! connection pool is first initialized, so they must be set before the first query. Search
+//! settings are read by every search, so they take effect immediately.
+//! Arguments passed explicitly to a function (e.g. the path given to `save_symbols`, or the
+//! `ScrapeConfig` given to `save_symbols_with_config`) take precedence over the global config.
+//!
+//! `download_ref` picks the branch or tag of this repository the database is downloaded from; see
+//! [`DatabaseConfig::download_ref`]. Pinning a release tag downloads the symbols as they were at
+//! that release, so that results can be reproduced against a known symbol universe.
+//!
+//! The download progress callback ([`DatabaseConfig::download_progress`]) cannot be expressed in
+//! JSON; set it in code on the loaded config before calling [`set_config`].
+
+use std::fmt;
+use std::fs;
+use std::error::Error;
+use std::sync::{Arc, RwLock};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use crate::error::YahooSymbolsError;
+use crate::scraper::ScrapeConfig;
+
+
+pub const DEFAULT_DATABASE_PATH: &str = "symbols.db";
+/// Where the database is downloaded from by default, with `{ref}` standing for the branch or tag
+pub const DOWNLOAD_URL_TEMPLATE: &str = "https://github.com/Nnamdi-sys/yahoo-finance-symbols/raw/{ref}/rust/src/symbols.db";
+/// Branch or tag the database is downloaded from by default
+pub const DEFAULT_DOWNLOAD_REF: &str = "main";
+/// `DOWNLOAD_URL_TEMPLATE` at `DEFAULT_DOWNLOAD_REF`
+pub const DEFAULT_DOWNLOAD_URL: &str = "https://github.com/Nnamdi-sys/yahoo-finance-symbols/raw/main/rust/src/symbols.db";
+pub const DEFAULT_DOWNLOAD_TIMEOUT_SECS: u64 = 300;
+pub const DEFAULT_MAX_SEARCH_RESULTS: usize = 1000;
+
+lazy_static! {
+    static ref CONFIG: RwLock<Config> = RwLock::new(Config::default());
+}
+
+/// Unified settings for the database, connection pool and scraper
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub database: DatabaseConfig,
+    pub scrape: ScrapeConfig,
+    pub search: SearchConfig,
+}
+
+/// Location of the symbols database and how it is opened and provisioned
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DatabaseConfig {
+    /// Path of the SQLite file
+    pub path: PathBuf,
+    /// Maximum number of pooled connections
+    pub pool_size: u32,
+    /// Where to download the database from when the file is missing. Any `{ref}` in it is
+    /// replaced by `download_ref`; use `resolved_download_url` for the URL actually fetched
+    pub download_url: String,
+    /// Branch or tag of the GitHub repository to download the database from, e.g. `v0.2.0`.
+    /// Defaults to `DEFAULT_DOWNLOAD_REF`, the latest published snapshot. Only used through the
+    /// `{ref}` placeholder of `download_url`, so a URL of your own without one ignores it
+    pub download_ref: String,
+    /// Seconds to wait for the whole download before falling back to scraping, 0 to wait forever
+    pub download_timeout_secs: u64,
+    /// Called as the database downloads; not read from or written to JSON
+    #[serde(skip)]
+    pub download_progress: Option<DownloadProgress>,
+    /// Whether the first query downloads (or scrapes) a missing database file. Defaults to true;
+    /// when false, a missing file fails with `YahooSymbolsError::ProvisioningDisabled` instead
+    pub auto_provision: bool,
+    /// Whether the first query, when the download fails, answers from the symbols scraped so far
+    /// while the scrape continues in the background, instead of waiting minutes for all of them.
+    /// Defaults to false, so that queries only ever see a complete database; see
+    /// `default_database`
+    pub serve_while_scraping: bool,
+}
+
+/// Callback receiving the number of bytes downloaded so far and the total size
+///
+/// The total is `None` when the server does not send a `Content-Length`, in which case the
+/// progress is indeterminate.
+#[derive(Clone)]
+pub struct DownloadProgress(pub Arc<dyn Fn(u64, Option<u64>) + Send + Sync>);
+
+impl DownloadProgress {
+    pub fn new(callback: impl Fn(u64, Option<u64>) + Send + Sync + 'static) -> DownloadProgress {
+        DownloadProgress(Arc::new(callback))
+    }
+
+    #[cfg(feature = "download")]
+    pub(crate) fn report(&self, downloaded: u64, total: Option<u64>) {
+        (self.0)(downloaded, total)
+    }
+}
+
+impl fmt::Debug for DownloadProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("DownloadProgress(..)")
+    }
+}
+
+/// Limits applied to the substring searches
+///
+/// A one-letter query matches most of the database. Rather than loading hundreds of thousands
+/// of rows, `search`, `search_symbols`, `search_sorted` and `search_refs` keep only the first
+/// `max_results` matches in the order of `search_sorted`: exact ticker first, then shorter
+/// tickers, then alphabetically. `search_stream` does not rank, so it stops after the first
+/// `max_results` matches in table order. Raise the cap to get more of them, or set it to 0 to get
+/// every match. `search_after`, `search_lite` and `search_count` are bounded by their own
+/// arguments and ignore it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchConfig {
+    /// Most matches a search returns, 0 for no limit. Defaults to `DEFAULT_MAX_SEARCH_RESULTS`
+    pub max_results: usize,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig { max_results: DEFAULT_MAX_SEARCH_RESULTS }
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            path: PathBuf::from(DEFAULT_DATABASE_PATH),
+            pool_size: 10,
+            download_url: DOWNLOAD_URL_TEMPLATE.to_string(),
+            download_ref: DEFAULT_DOWNLOAD_REF.to_string(),
+            download_timeout_secs: DEFAULT_DOWNLOAD_TIMEOUT_SECS,
+            download_progress: None,
+            auto_provision: true,
+            serve_while_scraping: false,
+        }
+    }
+}
+
+impl DatabaseConfig {
+    /// `download_timeout_secs` as a duration, or `None` when downloads may take forever
+    pub fn download_timeout(&self) -> Option<Duration> {
+        Some(Duration::from_secs(self.download_timeout_secs)).filter(|t| !t.is_zero())
+    }
+
+    /// `download_url` with its `{ref}` placeholder replaced by `download_ref`
+    ///
+    /// Fails with `YahooSymbolsError::InvalidDownloadUrl` if `download_ref` is not a plausible
+    /// git branch or tag name, or if the result is not an absolute HTTP(S) URL.
+    pub fn resolved_download_url(&self) -> Result<String, YahooSymbolsError> {
+        let invalid = |reason: String| YahooSymbolsError::InvalidDownloadUrl(reason);
+        let url = if self.download_url.contains("{ref}") {
+            if !is_valid_ref(&self.download_ref) {
+                return Err(invalid(format!("{:?} is not a branch or tag name", self.download_ref)));
+            }
+            self.download_url.replace("{ref}", &self.download_ref)
+        } else {
+            self.download_url.clone()
+        };
+
+        if !is_http_url(&url) {
+            return Err(invalid(format!("{} is not an absolute HTTP(S) URL", url)));
+        }
+        Ok(url)
+    }
+}
+
+/// Whether `url` is an `http` or `https` URL with a host and no whitespace or control characters
+fn is_http_url(url: &str) -> bool {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return false;
+    };
+    let host = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    matches!(scheme.to_ascii_lowercase().as_str(), "http" | "https")
+        && !host.is_empty()
+        && !host.starts_with(':')
+        && !url.chars().any(|c| c.is_whitespace() || c.is_control())
+}
+
+/// Whether `name` can be a git branch or tag, within the characters a URL path takes verbatim
+fn is_valid_ref(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '/'))
+        && !name.starts_with(['-', '.', '/'])
+        && !name.ends_with(['.', '/'])
+        && !name.ends_with(".lock")
+        && !name.contains("..")
+        && !name.contains("//")
+}
+
+impl Config {
+    /// Reads settings from a JSON file, using defaults for any missing field
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Config, Box<dyn Error>> {
+        let contents = fs::read_to_string(path)?;
+        let config: Config = serde_json::from_str(&contents)?;
+        config.scrape.validate()?;
+        config.database.resolved_download_url()?;
+        Ok(config)
+    }
+}
+
+/// Replaces the global settings used by the crate
+pub fn set_config(config: Config) {
+    *CONFIG.write().expect("Config lock poisoned") = config;
+}
+
+/// Returns a copy of the current global settings
+pub fn get_config() -> Config {
+    CONFIG.read().expect("Config lock poisoned").clone()
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use std::fs;
+    use crate::error::YahooSymbolsError;
+    use super::{
+        Config, DatabaseConfig, DEFAULT_DATABASE_PATH, DEFAULT_DOWNLOAD_TIMEOUT_SECS, DEFAULT_DOWNLOAD_URL,
+        DEFAULT_MAX_SEARCH_RESULTS,
+    };
+
+    #[test]
+    fn check_config_from_file() {
+        let path = std::env::temp_dir().join(format!("yahoo-finance-symbols-config-{}.json", std::process::id()));
+
+        fs::write(&path, r#"{ "database": { "pool_size": 4 }, "scrape": { "page_size": 500 } }"#).unwrap();
+        let config = Config::from_file(&path).unwrap();
+        assert_eq!(config.database.pool_size, 4);
+        assert_eq!(config.database.path.to_str(), Some(DEFAULT_DATABASE_PATH));
+        assert_eq!(config.database.download_timeout_secs, DEFAULT_DOWNLOAD_TIMEOUT_SECS);
+        assert!(config.database.auto_provision);
+        assert_eq!(config.scrape.page_size, 500);
+        assert_eq!(config.scrape.start_offset, 0);
+        assert_eq!(config.search.max_results, DEFAULT_MAX_SEARCH_RESULTS);
+
+        fs::write(&path, r#"{ "search": { "max_results": 0 } }"#).unwrap();
+        assert_eq!(Config::from_file(&path).unwrap().search.max_results, 0);
+
+        fs::write(&path, r#"{ "scrape": { "page_size": 0 } }"#).unwrap();
+        assert!(Config::from_file(&path).is_err());
+
+        fs::write(&path, r#"{ "database": { "download_ref": "v0.2.0" } }"#).unwrap();
+        let url = Config::from_file(&path).unwrap().database.resolved_download_url().unwrap();
+        assert_eq!(url, "https://github.com/Nnamdi-sys/yahoo-finance-symbols/raw/v0.2.0/rust/src/symbols.db");
+        fs::write(&path, r#"{ "database": { "download_ref": "../main" } }"#).unwrap();
+        assert!(Config::from_file(&path).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn check_resolved_download_url() {
+        let config = DatabaseConfig::default();
+        assert_eq!(config.resolved_download_url().unwrap(), DEFAULT_DOWNLOAD_URL);
+
+        for download_ref in ["release/0.2", "v0.1.4", "feature_x-1"] {
+            let config = DatabaseConfig { download_ref: download_ref.to_string(), ..DatabaseConfig::default() };
+            assert!(config.resolved_download_url().unwrap().contains(&format!("/raw/{}/", download_ref)));
+        }
+        for download_ref in ["", "-main", "v1..2", "main/", "a b", "main?x=1", "tag.lock", "a//b", "%2e%2e"] {
+            let config = DatabaseConfig { download_ref: download_ref.to_string(), ..DatabaseConfig::default() };
+            assert!(matches!(config.resolved_download_url(), Err(YahooSymbolsError::InvalidDownloadUrl(_))), "{:?}", download_ref);
+        }
+
+        // A URL of your own is used as is, but still checked
+        let hosted = DatabaseConfig { download_url: "https://example.com/symbols.db".to_string(), download_ref: String::new(), ..DatabaseConfig::default() };
+        assert_eq!(hosted.resolved_download_url().unwrap(), "https://example.com/symbols.db");
+        for download_url in ["symbols.db", "ftp://example.com/symbols.db", "file:///tmp/symbols.db"] {
+            let config = DatabaseConfig { download_url: download_url.to_string(), ..DatabaseConfig::default() };
+            assert!(config.resolved_download_url().is_err(), "{}", download_url);
+        }
+    }
+}
@@ -0,0 +1,97 @@
+/// Parses RFC 4180 CSV text into records of fields
+///
+/// Fields may be quoted with `"`, in which case they can contain commas, line breaks and
+/// doubled `""` quotes. Both `\n` and `\r\n` line endings are accepted and blank lines are skipped.
+#[cfg_attr(not(feature = "full"), allow(dead_code))]
+pub fn parse_csv(input: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' if field.is_empty() => in_quotes = true,
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                if !(record.len() == 1 && record[0].is_empty()) {
+                    records.push(std::mem::take(&mut record));
+                }
+                record.clear();
+            }
+            _ => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err("Unterminated quoted field".to_string());
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+/// Appends one RFC 4180 record to `out`, terminated by `\r\n`
+///
+/// Fields containing a comma, a quote or a line break are quoted, with quotes doubled; other
+/// fields are written as they are.
+pub fn write_csv_record<'a>(out: &mut String, fields: impl IntoIterator<Item = &'a str>) {
+    for (i, field) in fields.into_iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if field.contains([',', '"', '\r', '\n']) {
+            out.push('"');
+            out.push_str(&field.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(field);
+        }
+    }
+    out.push_str("\r\n");
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::{parse_csv, write_csv_record};
+
+    #[test]
+    fn check_parse_csv() {
+        let records = parse_csv("symbol,name\r\nAAPL,Apple Inc.\n\nT,\"AT&T, Inc.\"\nX,\"Say \"\"hi\"\"\"").unwrap();
+        assert_eq!(records, vec![
+            vec!["symbol", "name"],
+            vec!["AAPL", "Apple Inc."],
+            vec!["T", "AT&T, Inc."],
+            vec!["X", "Say \"hi\""],
+        ]);
+        assert!(parse_csv("A,\"unterminated").is_err());
+    }
+
+    #[test]
+    fn check_write_csv_record() {
+        let mut out = String::new();
+        write_csv_record(&mut out, ["T", "AT&T, Inc.", "Say \"hi\"", "two\nlines", ""]);
+        assert_eq!(out, "T,\"AT&T, Inc.\",\"Say \"\"hi\"\"\",\"two\nlines\",\r\n");
+        assert_eq!(parse_csv(&out).unwrap(), vec![vec!["T", "AT&T, Inc.", "Say \"hi\"", "two\nlines", ""]]);
+    }
+}
@@ -0,0 +1,1995 @@
+//! Handles to individual symbol databases.
+//!
+//! A [`SymbolDatabase`] owns its own connection pool, so several databases (for example a
+//! production copy and a test fixture, or one file per region) can be open at the same time.
+//! Every query method mirrors the free function of the same name in the crate root, which in
+//! turn delegates to the default handle returned by [`crate::default_database`].
+//!
+//! # Migrating from the free functions
+//!
+//! Code written against the free functions keeps working unchanged. To query a specific file
+//! instead of the configured default, open a handle and call the same function as a method:
+//!
+//! ```no_run
+//! use yahoo_finance_symbols::database::SymbolDatabase;
+//! use yahoo_finance_symbols::keys::{AssetClass, Category, Exchange};
+//! use std::error::Error;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn Error>> {
+//!     // Before: yahoo_finance_symbols::get_symbol("AAPL").await?
+//!     let us = SymbolDatabase::open("symbols_us.db")?;
+//!     let eu = SymbolDatabase::open("symbols_eu.db")?;
+//!
+//!     println!("{:?}", us.get_symbol("AAPL").await?);
+//!     println!("{:?}", eu.get_symbols(AssetClass::Stocks, Category::All, Exchange::Xetra).await?);
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Unlike the default handle, [`SymbolDatabase::open`] never downloads or scrapes a missing file.
+//! `update_database` and `import_symbols_from_csv` still operate on the default database only.
+
+use std::cmp;
+use std::collections::{BinaryHeap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use futures::future::try_join_all;
+use futures::stream::{self, Stream, StreamExt};
+#[cfg(feature = "polars")]
+use polars::prelude::*;
+#[cfg(feature = "arrow")]
+use crate::arrow::{symbols_to_record_batch, RecordBatch};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::serialize::OwnedData;
+use rusqlite::{params, params_from_iter, Connection, DatabaseName, InterruptHandle, OptionalExtension, Result, Row, ToSql};
+use tokio::sync::mpsc;
+use crate::config::get_config;
+use crate::error::YahooSymbolsError;
+use crate::filter::{SearchRanking, SymbolQuery};
+use crate::keys::{exchange_tier_sql, AssetClass, Category, Exchange, ParseKeyError, NA_CATEGORY};
+use crate::provenance::{read_build_info, read_meta, warn_on_version_mismatch, BuildInfo, SCRAPE_PENDING};
+use crate::schema::SchemaDescription;
+use crate::scraper::{create_symbols_table, decode_name};
+use crate::verify::{verify_connection, VerifyReport};
+use crate::{
+    export, fuzzy, import, json, schema, FilterOptions, SearchResults, SearchSplit, Symbol, SymbolLite,
+    LOOKUP_NAME_DISTANCE_DIVISOR, SUGGEST_MAX_LENGTH_DIFF,
+};
+
+
+/// Number of attempts made to create a connection pool before giving up
+pub const POOL_RETRY_ATTEMPTS: u32 = 3;
+/// Delay between attempts to create a connection pool
+pub const POOL_RETRY_DELAY: Duration = Duration::from_millis(100);
+/// Number of prepared statements each pooled connection keeps for reuse
+///
+/// Queries are prepared with `prepare_cached`, so repeated calls skip parsing and planning their
+/// SQL. The cache belongs to a connection: each connection of the pool prepares a statement the
+/// first time it runs it, and a reopened connection starts with an empty cache. Pools passed to
+/// `SymbolDatabase::from_pool` keep rusqlite's default capacity.
+pub const STATEMENT_CACHE_CAPACITY: usize = 64;
+/// Number of rows `query_symbols_df` holds as `Symbol`s at a time while building its DataFrame
+#[cfg(feature = "polars")]
+pub const QUERY_DF_CHUNK_ROWS: usize = 50_000;
+
+/// A symbols database with its own connection pool
+///
+/// Cloning is cheap and clones share the same pool, whose connections are reopened when the file
+/// is replaced (see `get_symbol`).
+#[derive(Debug, Clone)]
+pub struct SymbolDatabase {
+    pool: Pool<SqliteConnectionManager>,
+    path: Option<PathBuf>,
+    /// Identity of the file the pool was opened on, which its connections read until reopened
+    file: Option<FileId>,
+    /// Connection keeping a shared in-memory database alive while the pool's connections come and go
+    _memory_anchor: Option<Arc<Mutex<Connection>>>,
+}
+
+/// Device and inode of a file, which change when another file is renamed over its path
+///
+/// A reopened connection keeps the identity of the file it reads in its r2d2 extensions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileId(u64, u64);
+
+impl FileId {
+    #[cfg(unix)]
+    fn of(path: &Path) -> Option<FileId> {
+        use std::os::unix::fs::MetadataExt;
+        std::fs::metadata(path).ok().map(|meta| FileId(meta.dev(), meta.ino()))
+    }
+
+    /// Other platforms refuse to replace a file while it is open
+    #[cfg(not(unix))]
+    fn of(_path: &Path) -> Option<FileId> {
+        None
+    }
+}
+
+impl SymbolDatabase {
+    /// Opens an existing `symbols.db` file, pooling up to `pool_size` connections from the global config
+    ///
+    /// Creating the pool is retried up to `POOL_RETRY_ATTEMPTS` times, `POOL_RETRY_DELAY` apart,
+    /// which covers the file being briefly locked by another process that is initializing it.
+    pub fn open(path: impl AsRef<Path>) -> Result<SymbolDatabase, YahooSymbolsError> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Err(YahooSymbolsError::DatabaseNotFound(path.to_path_buf()));
+        }
+
+        let file = FileId::of(path);
+        let pool = with_retries(|| file_pool(path))?;
+        if let Ok(conn) = pool.get() {
+            warn_on_version_mismatch(&conn, path);
+        }
+
+        Ok(SymbolDatabase { pool, path: Some(path.to_path_buf()), file, _memory_anchor: None })
+    }
+
+    /// Wraps an existing connection pool
+    pub fn from_pool(pool: Pool<SqliteConnectionManager>) -> SymbolDatabase {
+        SymbolDatabase { pool, path: None, file: None, _memory_anchor: None }
+    }
+
+    /// Opens a read-only in-memory copy of the raw contents of a `symbols.db` file
+    ///
+    /// The data is served through a single pooled connection so only one copy is held in memory.
+    pub fn from_bytes(data: &[u8]) -> Result<SymbolDatabase, Box<dyn Error>> {
+        let data: Arc<[u8]> = Arc::from(data);
+        let manager = SqliteConnectionManager::memory().with_init(move |conn| {
+            conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+            deserialize_database(conn, &data)
+        });
+        let pool = Pool::builder().max_size(1).build(manager)?;
+
+        Ok(SymbolDatabase::from_pool(pool))
+    }
+
+    /// Creates an in-memory database holding `symbols`, shared by every pooled connection
+    ///
+    /// Unlike `:memory:`, which gives each connection a database of its own, the pool opens a
+    /// named `mode=memory&cache=shared` database, unique to this call, so that all its
+    /// connections see the same rows through the usual pool size. The database lives as long as
+    /// one connection to it is open; the handle and its clones hold one for that. The symbols are
+    /// stored as user rows (`source = "user"`). The shared cache locks whole tables, so the
+    /// database suits read-mostly use: a write while other connections read fails as locked
+    /// rather than waiting.
+    pub fn in_memory(symbols: &[Symbol]) -> Result<SymbolDatabase, Box<dyn Error>> {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        let uri = format!(
+            "file:yahoo-finance-symbols-{}-{}?mode=memory&cache=shared",
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        );
+
+        let mut anchor = Connection::open(&uri)?;
+        create_symbols_table(&anchor)?;
+        schema::migrate_connection(&mut anchor)?;
+        import::import_into(&anchor, symbols)?;
+        let pool = Pool::builder()
+            .max_size(get_config().database.pool_size)
+            .build(SqliteConnectionManager::file(&uri).with_init(init_connection))?;
+
+        Ok(SymbolDatabase { pool, path: None, file: None, _memory_anchor: Some(Arc::new(Mutex::new(anchor))) })
+    }
+
+    /// Path of the underlying file, or `None` for in-memory and externally built pools
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// The connection pool backing this database
+    ///
+    /// Connections checked out of it directly are not reopened when the file is replaced, and may
+    /// keep reading the old file until a query of this database checks them out.
+    pub fn pool(&self) -> &Pool<SqliteConnectionManager> {
+        &self.pool
+    }
+
+    /// An owned handle on the current connection pool, for moving into tasks
+    ///
+    /// The handle is a cheap clone of `pool`.
+    pub fn pool_handle(&self) -> Pool<SqliteConnectionManager> {
+        self.pool().clone()
+    }
+
+    /// Checks out a pooled connection to run a query on
+    ///
+    /// If another file was moved into place since the connection was opened, it is reopened on
+    /// that file first, along with the idle connections. Fails with `YahooSymbolsError::Checkout`
+    /// when no connection frees up within the pool's timeout.
+    pub(crate) fn connection(&self) -> std::result::Result<PoolConnection, YahooSymbolsError> {
+        let mut conn = checkout(&self.pool).map_err(|e| YahooSymbolsError::Checkout(e.to_string()))?;
+        if let Some(now) = self.path.as_deref().and_then(FileId::of) {
+            if self.opened_on(pooled(&mut conn)) != Some(now) {
+                self.reopen();
+                self.reopen_connection(pooled(&mut conn));
+            }
+        }
+        Ok(conn)
+    }
+
+    /// Fetches a symbol, taking its first stored listing if it is listed under several asset classes
+    ///
+    /// If the pooled connection is stale because the file was replaced underneath it, the
+    /// connection is reopened on the current file and the query retried once.
+    pub async fn get_symbol(&self, symbol: &str) -> Result<Symbol> {
+        self.query_with_reopen(|conn| {
+            let mut stmt = conn.prepare_cached("SELECT * FROM symbols WHERE symbol = ? ORDER BY rowid LIMIT 1")?;
+            stmt.query_row([symbol], symbol_from_row)
+        })
+    }
+
+    /// Fetches the first stored listing with the given ISIN, failing with `QueryReturnedNoRows` if none has it
+    pub async fn get_symbol_by_isin(&self, isin: &str) -> Result<Symbol> {
+        let isin = isin.trim().to_ascii_uppercase();
+        self.query_with_reopen(|conn| {
+            let mut stmt = conn.prepare_cached("SELECT * FROM symbols WHERE isin = ? ORDER BY rowid LIMIT 1")?;
+            stmt.query_row([&isin], symbol_from_row)
+        })
+    }
+
+    /// Fetches one symbol chosen at random, failing with `QueryReturnedNoRows` on an empty table
+    pub async fn get_random_symbol(&self) -> Result<Symbol> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare_cached("SELECT * FROM symbols ORDER BY RANDOM() LIMIT 1")?;
+        stmt.query_row([], symbol_from_row)
+    }
+
+    /// Fetches up to `n` distinct symbols chosen at random
+    pub async fn get_random_symbols(&self, n: usize) -> Result<Vec<Symbol>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare_cached("SELECT * FROM symbols ORDER BY RANDOM() LIMIT ?")?;
+
+        let rows = stmt.query_map([i64::try_from(n).unwrap_or(i64::MAX)], symbol_from_row)?;
+
+        rows.collect()
+    }
+
+    /// Checks whether a symbol exists without loading its details
+    pub async fn symbol_exists(&self, symbol: &str) -> Result<bool> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare_cached("SELECT 1 FROM symbols WHERE symbol = ? LIMIT 1")?;
+
+        stmt.exists([symbol])
+    }
+
+    /// Resolves a ticker, exact name or approximate name to the single best matching symbol
+    pub async fn lookup(&self, input: &str) -> Result<Option<Symbol>> {
+        let input = input.trim();
+        let Some(first) = input.chars().next() else {
+            return Ok(None);
+        };
+        let conn = self.connection()?;
+
+        let mut stmt = conn.prepare_cached("SELECT * FROM symbols WHERE symbol IN (?1, upper(?1)) ORDER BY symbol = ?1 DESC, rowid LIMIT 1")?;
+        if let Some(symbol) = stmt.query_row([input], symbol_from_row).optional()? {
+            return Ok(Some(symbol));
+        }
+
+        let mut stmt = conn.prepare_cached(
+            "SELECT * FROM symbols WHERE name = ? COLLATE NOCASE ORDER BY length(symbol), symbol LIMIT 1",
+        )?;
+        if let Some(symbol) = stmt.query_row([input], symbol_from_row).optional()? {
+            return Ok(Some(symbol));
+        }
+
+        let target = input.to_lowercase();
+        let length = target.chars().count();
+        let max_distance = (length / LOOKUP_NAME_DISTANCE_DIVISOR).max(1);
+        let mut stmt = conn.prepare_cached(
+            "SELECT * FROM symbols WHERE lower(substr(name, 1, 1)) = ? AND length(name) BETWEEN ? AND ?",
+        )?;
+        let rows = stmt.query_map(
+            params![
+                first.to_lowercase().to_string(),
+                length.saturating_sub(max_distance) as i64,
+                (length + max_distance) as i64
+            ],
+            symbol_from_row,
+        )?;
+
+        let mut best: Option<(usize, Symbol)> = None;
+        for row in rows {
+            let symbol = row?;
+            let distance = fuzzy::levenshtein(&target, &symbol.name.to_lowercase());
+            let better = match &best {
+                Some((best_distance, best_symbol)) => {
+                    (distance, symbol.symbol.len(), &symbol.symbol)
+                        < (*best_distance, best_symbol.symbol.len(), &best_symbol.symbol)
+                }
+                None => true,
+            };
+            if distance <= max_distance && better {
+                best = Some((distance, symbol));
+            }
+        }
+
+        Ok(best.map(|(_, symbol)| symbol))
+    }
+
+    /// Suggests up to `n` tickers closest to a likely mistyped symbol
+    pub async fn suggest_symbols(&self, symbol: &str, n: usize) -> Result<Vec<Symbol>> {
+        let target = symbol.trim().to_uppercase();
+        let first = match target.chars().next() {
+            Some(c) => c.to_string(),
+            None => return Ok(Vec::new()),
+        };
+        let length = target.chars().count();
+        let min_length = length.saturating_sub(SUGGEST_MAX_LENGTH_DIFF) as i64;
+        let max_length = (length + SUGGEST_MAX_LENGTH_DIFF) as i64;
+
+        let conn = self.connection()?;
+        let mut stmt = conn
+            .prepare_cached("SELECT * FROM symbols WHERE upper(substr(symbol, 1, 1)) = ? AND length(symbol) BETWEEN ? AND ?")?;
+
+        let rows = stmt.query_map(params![first, min_length, max_length], symbol_from_row)?;
+
+        let mut candidates = rows
+            .map(|row| row.map(|s| (fuzzy::levenshtein(&target, &s.symbol.to_uppercase()), s)))
+            .collect::<Result<Vec<(usize, Symbol)>>>()?;
+
+        candidates.sort_by(|(da, a), (db, b)| {
+            da.cmp(db)
+                .then(a.symbol.len().cmp(&b.symbol.len()))
+                .then(a.symbol.cmp(&b.symbol))
+        });
+
+        Ok(candidates.into_iter().take(n).map(|(_, s)| s).collect())
+    }
+
+    /// Fetches symbols that match the specified asset class, category, and exchange
+    ///
+    /// Like `get_symbol`, retries once on a reopened connection if the pooled connection is stale.
+    pub async fn get_symbols(&self, asset_class: AssetClass, category: Category, exchange: Exchange) -> Result<Vec<Symbol>> {
+        self.get_symbols_with_name(asset_class, category, exchange, None).await
+    }
+
+    /// Fetches only the tickers of the symbols that match the specified asset class, category, and exchange
+    pub async fn get_tickers(&self, asset_class: AssetClass, category: Category, exchange: Exchange) -> Result<Vec<String>> {
+        let (sql, values) =
+            select_sql("symbol", &SymbolQuery { asset_class, category, exchange, ..SymbolQuery::default() }).await;
+
+        self.query_with_reopen(|conn| {
+            let mut stmt = conn.prepare_cached(&sql)?;
+            let rows = stmt.query_map(params_from_iter(&values), |row| row.get(0))?;
+            rows.collect()
+        })
+    }
+
+    /// Fetches symbols that match the specified filters and, optionally, contain a substring in their name
+    pub async fn get_symbols_with_name(
+        &self,
+        asset_class: AssetClass,
+        category: Category,
+        exchange: Exchange,
+        name_contains: Option<&str>,
+    ) -> Result<Vec<Symbol>> {
+        let name_contains = name_contains.map(str::to_string);
+        self.query_symbols(SymbolQuery { asset_class, category, exchange, name_contains, order: None }).await
+    }
+
+    /// Fetches the symbols matching `query`, sorted in SQL when it sets an order
+    pub async fn query_symbols(&self, query: SymbolQuery) -> Result<Vec<Symbol>> {
+        let (sql, values) = symbols_sql(&query).await;
+
+        self.query_with_reopen(|conn| {
+            let mut stmt = conn.prepare_cached(&sql)?;
+            let rows = stmt.query_map(params_from_iter(&values), symbol_from_row)?;
+            rows.collect()
+        })
+    }
+
+    /// Fetches the symbols of `asset_class` that have a real sector category, skipping "N/A" and empty ones
+    pub async fn get_categorized_symbols(&self, asset_class: AssetClass) -> Result<Vec<Symbol>> {
+        let mut sql = format!("SELECT * FROM symbols WHERE NOT ({})", UNCATEGORIZED);
+        let mut values = vec![NA_CATEGORY.to_string()];
+        if asset_class != AssetClass::All {
+            let codes = asset_class.to_string_vec().await;
+            sql.push_str(&format!(" AND asset_class IN ({})", vec!["?"; codes.len()].join(",")));
+            values.extend(codes);
+        }
+
+        self.query_with_reopen(|conn| {
+            let mut stmt = conn.prepare_cached(&sql)?;
+            let rows = stmt.query_map(params_from_iter(&values), symbol_from_row)?;
+            rows.collect()
+        })
+    }
+
+    /// Counts the symbols whose category is "N/A", empty or missing
+    pub async fn get_uncategorized_count(&self) -> Result<i64> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare_cached(&format!("SELECT COUNT(*) FROM symbols WHERE {}", UNCATEGORIZED))?;
+        stmt.query_row([NA_CATEGORY], |row| row.get(0))
+    }
+
+    /// Fetches the symbols whose name, asset class or exchange is "N/A", empty or missing, in ticker order
+    pub async fn get_malformed_symbols(&self) -> Result<Vec<Symbol>> {
+        let conn = self.connection()?;
+        // Missing values read back as empty strings, so that every malformed row can be returned
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT symbol, coalesce(name, ''), coalesce(category, ''), coalesce(asset_class, ''), coalesce(exchange, '')
+             FROM symbols WHERE {} ORDER BY symbol, asset_class",
+            MALFORMED
+        ))?;
+
+        let rows = stmt.query_map([NA_CATEGORY], symbol_from_row)?;
+
+        rows.collect()
+    }
+
+    /// Counts the symbols whose name, asset class or exchange is "N/A", empty or missing
+    pub async fn count_malformed(&self) -> Result<i64> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare_cached(&format!("SELECT COUNT(*) FROM symbols WHERE {}", MALFORMED))?;
+        stmt.query_row([NA_CATEGORY], |row| row.get(0))
+    }
+
+    /// Fetches symbols whose exchange code starts with the given prefix, case-insensitively
+    pub async fn get_symbols_by_exchange_prefix(&self, prefix: &str) -> Result<Vec<Symbol>> {
+        let conn = self.connection()?;
+        let mut stmt = conn
+            .prepare_cached("SELECT * FROM symbols WHERE upper(substr(exchange, 1, length(?1))) = upper(?1)")?;
+
+        let rows = stmt.query_map([prefix], symbol_from_row)?;
+
+        rows.collect()
+    }
+
+    /// Fetches symbols whose ticker matches a case-sensitive `GLOB` pattern, in ticker order
+    pub async fn get_symbols_matching(&self, pattern: &str) -> Result<Vec<Symbol>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare_cached("SELECT * FROM symbols WHERE symbol GLOB ? ORDER BY symbol")?;
+
+        let rows = stmt.query_map([pattern], symbol_from_row)?;
+
+        rows.collect()
+    }
+
+    /// Fetches the symbols matching `query` in SQL, then keeps those for which `predicate` returns true
+    pub async fn get_symbols_filtered(&self, query: SymbolQuery, predicate: impl Fn(&Symbol) -> bool) -> Result<Vec<Symbol>> {
+        let mut symbols = self.query_symbols(query).await?;
+        symbols.retain(|symbol| predicate(symbol));
+        Ok(symbols)
+    }
+
+    /// Fetches every symbol
+    pub async fn get_all_symbols(&self) -> Result<Vec<Symbol>> {
+        self.get_symbols(AssetClass::All, Category::All, Exchange::All).await
+    }
+
+    /// Fetches the symbols for several filter combinations concurrently, one pooled connection per query
+    pub async fn get_symbols_many(&self, queries: Vec<(AssetClass, Category, Exchange)>) -> Result<Vec<Vec<Symbol>>> {
+        let tasks = queries.into_iter().map(|(asset_class, category, exchange)| {
+            let db = self.clone();
+            tokio::spawn(async move { db.get_symbols(asset_class, category, exchange).await })
+        });
+
+        let results = try_join_all(tasks).await.expect("Query task panicked");
+        results.into_iter().collect()
+    }
+
+    /// Whether a pooled connection can be checked out and answers `SELECT 1`, without touching the
+    /// symbols table, and no background scrape is still filling the file
+    ///
+    /// A file marked `scrape_pending` answers queries, but only from part of the symbols; see
+    /// `BuildInfo::scrape_pending`.
+    pub async fn is_ready(&self) -> bool {
+        match self.connection() {
+            Ok(conn) => {
+                conn.query_row("SELECT 1", [], |row| row.get::<_, i64>(0)).is_ok()
+                    && matches!(read_meta(&conn, SCRAPE_PENDING), Ok(None))
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Reads the schema version of the database; see the [`schema`](crate::schema) module
+    pub async fn get_schema_version(&self) -> Result<u32> {
+        let conn = self.connection()?;
+        schema::read_schema_version(&conn)
+    }
+
+    /// Describes the tables, columns, indexes and row counts of the database; see [`schema::describe_connection`]
+    pub async fn describe_database(&self) -> Result<SchemaDescription> {
+        self.query_with_reopen(schema::describe_connection)
+    }
+
+    /// Reads where the database came from and how it was built; see the [`provenance`](crate::provenance) module
+    pub async fn get_build_info(&self) -> Result<BuildInfo> {
+        let conn = self.connection()?;
+        read_build_info(&conn)
+    }
+
+    /// Upgrades the database to `schema::SCHEMA_VERSION`, returning the version it ends at
+    pub async fn migrate(&self) -> Result<u32> {
+        self.migrate_blocking()
+    }
+
+    /// Runs `migrate` on the calling thread, e.g. from `spawn_blocking`
+    pub(crate) fn migrate_blocking(&self) -> Result<u32> {
+        let mut conn = self.connection()?;
+        schema::migrate_connection(&mut conn)
+    }
+
+    /// Counts the symbols
+    pub async fn get_symbols_count(&self) -> Result<i64> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare_cached("SELECT COUNT(*) FROM symbols")?;
+        stmt.query_row([], |row| row.get(0))
+    }
+
+    /// Lists the distinct exchange codes
+    pub async fn get_distinct_exchanges(&self) -> Result<Vec<String>> {
+        let conn = self.connection()?;
+        distinct_values(&conn, DISTINCT_EXCHANGES)
+    }
+
+    /// Lists the distinct categories
+    pub async fn get_distinct_categories(&self) -> Result<Vec<String>> {
+        let conn = self.connection()?;
+        distinct_values(&conn, DISTINCT_CATEGORIES)
+    }
+
+    /// Lists the distinct asset classes
+    pub async fn get_distinct_asset_classes(&self) -> Result<Vec<String>> {
+        let conn = self.connection()?;
+        distinct_values(&conn, DISTINCT_ASSET_CLASSES)
+    }
+
+    /// Lists the names shared by at least `min_count` distinct tickers, with those tickers in order
+    pub async fn get_duplicate_names(&self, min_count: usize) -> Result<Vec<(String, Vec<String>)>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare_cached(DUPLICATE_NAMES)?;
+        let min_count = i64::try_from(min_count).unwrap_or(i64::MAX);
+        let rows = stmt.query_map([min_count], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+
+        let mut names: Vec<(String, Vec<String>)> = Vec::new();
+        for row in rows {
+            let (name, symbol) = row?;
+            match names.last_mut() {
+                Some((last, symbols)) if *last == name => symbols.push(symbol),
+                _ => names.push((name, vec![symbol])),
+            }
+        }
+        Ok(names)
+    }
+
+    /// Lists the symbols stored under more than one asset class, with those asset classes in order
+    pub async fn get_multi_class_symbols(&self) -> Result<Vec<(String, Vec<String>)>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare_cached(MULTI_CLASS_SYMBOLS)?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+
+        let mut symbols: Vec<(String, Vec<String>)> = Vec::new();
+        for row in rows {
+            let (symbol, asset_class) = row?;
+            match symbols.last_mut() {
+                Some((last, classes)) if *last == symbol => classes.push(asset_class),
+                _ => symbols.push((symbol, vec![asset_class])),
+            }
+        }
+        Ok(symbols)
+    }
+
+    /// Lists the distinct exchanges, categories and asset classes using a single connection
+    pub async fn get_filter_options(&self) -> Result<FilterOptions> {
+        let conn = self.connection()?;
+        Ok(FilterOptions {
+            exchanges: distinct_values(&conn, DISTINCT_EXCHANGES)?,
+            categories: distinct_values(&conn, DISTINCT_CATEGORIES)?,
+            asset_classes: distinct_values(&conn, DISTINCT_ASSET_CLASSES)?,
+        })
+    }
+
+    /// Lists the `n` categories with the most symbols, largest first, with their counts
+    pub async fn top_categories(&self, n: usize) -> Result<Vec<(String, i64)>> {
+        let conn = self.connection()?;
+        top_counts(&conn, TOP_CATEGORIES, n)
+    }
+
+    /// Lists the `n` exchanges with the most symbols, largest first, with their counts
+    pub async fn top_exchanges(&self, n: usize) -> Result<Vec<(String, i64)>> {
+        let conn = self.connection()?;
+        top_counts(&conn, TOP_EXCHANGES, n)
+    }
+
+    /// Counts the symbols per first character of their ticker, skipping empty tickers
+    pub async fn get_symbol_initial_distribution(&self) -> Result<HashMap<char, i64>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare_cached(INITIAL_DISTRIBUTION)?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))?;
+
+        let mut distribution = HashMap::new();
+        for row in rows {
+            let (initial, count) = row?;
+            if let Some(initial) = initial.chars().next() {
+                distribution.insert(initial, count);
+            }
+        }
+        Ok(distribution)
+    }
+
+    /// Fetches ticker symbols and names that closely match the query within an asset class
+    ///
+    /// Panics if `asset_class` is not one of: Equity, ETF, Mutual Fund, Index, Currency, Futures, Crypto, Options.
+    #[deprecated(note = "panics on an unknown asset class; use `try_search_symbols`, which returns an error instead")]
+    pub async fn search_symbols(&self, query: &str, asset_class: &str) -> Result<HashMap<String, String>> {
+        let asset_class = search_asset_class(asset_class)
+            .unwrap_or_else(|| panic!("Asset class must be one of: {}", SEARCH_ASSET_CLASSES));
+        self.search_in(query, asset_class).await
+    }
+
+    /// Fetches ticker symbols and names that closely match the query within an asset class, or in
+    /// every asset class if `asset_class` is `None`
+    ///
+    /// `asset_class` takes the names accepted by `search_symbols`; any other name fails with a
+    /// `ParseKeyError` instead of panicking.
+    pub async fn try_search_symbols(
+        &self,
+        query: &str,
+        asset_class: Option<&str>,
+    ) -> std::result::Result<HashMap<String, String>, Box<dyn Error>> {
+        let asset_class = match asset_class {
+            Some(name) => search_asset_class(name).ok_or_else(|| ParseKeyError(name.to_string()))?,
+            None => AssetClass::All,
+        };
+        Ok(self.search_in(query, asset_class).await?)
+    }
+
+    /// Fetches ticker symbols and names that closely match the query in every asset class
+    pub async fn search(&self, query: &str) -> Result<HashMap<String, String>> {
+        self.search_in(query, AssetClass::All).await
+    }
+
+    async fn search_in(&self, query: &str, asset_class: AssetClass) -> Result<HashMap<String, String>> {
+        #[cfg(feature = "history")]
+        crate::history::record_search(self, query);
+        let (sql, values) = symbols_sql(&SymbolQuery { asset_class, ..SymbolQuery::default() }).await;
+        let cap = get_config().search.max_results;
+        let original = query.to_string();
+        let query = query.to_lowercase();
+
+        self.run_interruptible(move |conn| {
+            let mut stmt = conn.prepare_cached(&sql)?;
+            let mut matches = TopMatches::new(cap);
+            for tc in stmt.query_map(params_from_iter(&values), symbol_from_row)? {
+                let tc = tc?;
+                if tc.symbol.to_lowercase().contains(&query) || tc.name.to_lowercase().contains(&query) {
+                    matches.push(tc, |tc| match_rank(&original, &tc.symbol, &tc.asset_class));
+                }
+            }
+            Ok(matches.into_vec().into_iter().map(|tc| (tc.symbol, tc.name)).collect())
+        })
+        .await
+    }
+
+    /// Fetches every row whose symbol or name matches the query within an asset class into one buffer
+    pub async fn search_refs(&self, query: &str, asset_class: AssetClass) -> Result<SearchResults> {
+        #[cfg(feature = "history")]
+        crate::history::record_search(self, query);
+        let (sql, values) = symbols_sql(&SymbolQuery { asset_class, ..SymbolQuery::default() }).await;
+        let cap = get_config().search.max_results;
+        let original = query.to_string();
+        let query = query.to_lowercase();
+
+        self.run_interruptible(move |conn| {
+            let mut stmt = conn.prepare_cached(&sql)?;
+            let mut rows = stmt.query(params_from_iter(&values))?;
+            let mut results = SearchResults::default();
+            // Only a capped search copies its matches out, to keep the best-ranked in table order
+            let mut capped = TopMatches::new(cap);
+            let mut lowered = String::new();
+            let mut index = 0;
+            while let Some(row) = rows.next()? {
+                let symbol = text_ref(row, 0)?;
+                let name = html_escape::decode_html_entities(text_ref(row, 1)?);
+                if contains_lowercase(symbol, &query, &mut lowered) || contains_lowercase(&name, &query, &mut lowered) {
+                    let fields = [symbol, &name, text_ref(row, 2)?, text_ref(row, 3)?, text_ref(row, 4)?];
+                    if cap == 0 {
+                        results.push(fields);
+                    } else {
+                        capped.push((index, fields.map(str::to_string)), |(_, fields)| match_rank(&original, &fields[0], &fields[3]));
+                    }
+                    index += 1;
+                }
+            }
+            let mut kept = capped.into_vec();
+            kept.sort_unstable_by_key(|(index, _)| *index);
+            for (_, fields) in &kept {
+                results.push(fields.each_ref().map(String::as_str));
+            }
+            Ok(results)
+        })
+        .await
+    }
+
+    /// Counts the rows whose symbol or name matches the query within an asset class
+    ///
+    /// The count runs in SQL on the stored text, so it can differ from the size of `search`'s
+    /// result; see the crate-level `search_count`.
+    pub async fn search_count(&self, query: &str, asset_class: AssetClass) -> Result<i64> {
+        #[cfg(feature = "history")]
+        crate::history::record_search(self, query);
+
+        let asset_classes = match asset_class {
+            AssetClass::All => Vec::new(),
+            _ => asset_class.to_string_vec().await,
+        };
+        let pattern = format!("%{}%", escape_like(query));
+
+        let mut sql = "SELECT COUNT(*) FROM symbols WHERE (name LIKE ? ESCAPE '\\' OR symbol LIKE ? ESCAPE '\\')".to_string();
+        if !asset_classes.is_empty() {
+            sql.push_str(&format!(
+                " AND asset_class IN ({})",
+                (0..asset_classes.len()).map(|_| "?").collect::<Vec<_>>().join(",")
+            ));
+        }
+
+        self.run_interruptible(move |conn| {
+            let mut values: Vec<&dyn ToSql> = vec![&pattern, &pattern];
+            values.extend(asset_classes.iter().map(|s| s as &dyn ToSql));
+            conn.prepare_cached(&sql)?.query_row(&*values, |row| row.get(0))
+        })
+        .await
+    }
+
+    /// Streams the first `SearchConfig::max_results` symbols whose symbol or name matches the query within an asset class, in table order
+    pub fn search_stream(&self, query: &str, asset_class: AssetClass) -> impl Stream<Item = Result<Symbol>> + Send + 'static {
+        #[cfg(feature = "history")]
+        crate::history::record_search(self, query);
+
+        let pool = self.pool_handle();
+        let pattern = format!("%{}%", escape_like(query));
+        let limit = sql_limit(get_config().search.max_results);
+        stream::once(async move {
+            let asset_classes = match asset_class {
+                AssetClass::All => Vec::new(),
+                _ => asset_class.to_string_vec().await,
+            };
+            let (tx, rx) = mpsc::channel(SEARCH_STREAM_BUFFER);
+            tokio::task::spawn_blocking(move || stream_matches(&pool, &pattern, &asset_classes, limit, &tx));
+            stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|item| (item, rx)) })
+        })
+        .flatten()
+    }
+
+    /// Fetches the first `SearchConfig::max_results` symbols matching a query within an asset class, exact ticker match first, then by ticker length and ticker
+    pub async fn search_sorted(&self, query: &str, asset_class: AssetClass) -> Result<Vec<Symbol>> {
+        #[cfg(feature = "history")]
+        crate::history::record_search(self, query);
+
+        let asset_classes = match asset_class {
+            AssetClass::All => Vec::new(),
+            _ => asset_class.to_string_vec().await,
+        };
+        self.search_ordered(query, asset_classes, "upper(symbol) = upper(?2) DESC, length(symbol), symbol, asset_class").await
+    }
+
+    /// Fetches the first `SearchConfig::max_results` symbols matching a query within an asset class, in the order of `ranking`
+    pub async fn search_ranked(&self, query: &str, asset_class: AssetClass, ranking: SearchRanking) -> Result<Vec<Symbol>> {
+        if ranking == SearchRanking::Lexical {
+            return self.search_sorted(query, asset_class).await;
+        }
+        #[cfg(feature = "history")]
+        crate::history::record_search(self, query);
+
+        let asset_classes = match asset_class {
+            AssetClass::All => Vec::new(),
+            _ => asset_class.to_string_vec().await,
+        };
+        let order = format!(
+            "upper(symbol) = upper(?2) DESC, substr(upper(symbol), 1, length(?2)) = upper(?2) DESC, {}, length(symbol), symbol, asset_class",
+            exchange_tier_sql()
+        );
+        self.search_ordered(query, asset_classes, &order).await
+    }
+
+    /// Fetches the first `SearchConfig::max_results` matches of `query` in `asset_classes` (all if empty), sorted by `order`
+    ///
+    /// `order` may refer to the query as `?2`.
+    async fn search_ordered(&self, query: &str, asset_classes: Vec<String>, order: &str) -> Result<Vec<Symbol>> {
+        let pattern = format!("%{}%", escape_like(query));
+        let query = query.to_string();
+        let limit = sql_limit(get_config().search.max_results);
+
+        let mut sql = "SELECT * FROM symbols WHERE (name LIKE ?1 ESCAPE '\\' OR symbol LIKE ?1 ESCAPE '\\')".to_string();
+        if !asset_classes.is_empty() {
+            sql.push_str(&format!(
+                " AND asset_class IN ({})",
+                (0..asset_classes.len()).map(|i| format!("?{}", i + 4)).collect::<Vec<_>>().join(",")
+            ));
+        }
+        sql.push_str(&format!(" ORDER BY {} LIMIT ?3", order));
+
+        self.run_interruptible(move |conn| {
+            let mut values: Vec<&dyn ToSql> = vec![&pattern, &query, &limit];
+            values.extend(asset_classes.iter().map(|s| s as &dyn ToSql));
+
+            let mut stmt = conn.prepare_cached(&sql)?;
+            let rows = stmt.query_map(&*values, symbol_from_row)?;
+            rows.collect()
+        })
+        .await
+    }
+
+    /// Fetches up to `limit` symbols whose ticker matches a query and, apart from those, up to `limit` whose name does
+    pub async fn search_split(&self, query: &str, asset_class: AssetClass, limit: usize) -> Result<SearchSplit> {
+        let asset_classes = match asset_class {
+            AssetClass::All => Vec::new(),
+            _ => asset_class.to_string_vec().await,
+        };
+        let pattern = format!("%{}%", escape_like(query));
+        let query = query.to_string();
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+
+        // ?1 is the pattern, ?2 the query, ?3 the limit, then the asset classes
+        let scoped = |condition: &str, order: &str| {
+            let mut sql = format!("SELECT * FROM symbols WHERE {}", condition);
+            if !asset_classes.is_empty() {
+                sql.push_str(&format!(
+                    " AND asset_class IN ({})",
+                    (0..asset_classes.len()).map(|i| format!("?{}", i + 4)).collect::<Vec<_>>().join(",")
+                ));
+            }
+            sql.push_str(&format!(" ORDER BY {} LIMIT ?3", order));
+            sql
+        };
+        let symbol_sql = scoped(
+            "symbol LIKE ?1 ESCAPE '\\'",
+            "upper(symbol) = upper(?2) DESC, length(symbol), symbol, asset_class",
+        );
+        let name_sql = scoped(
+            "name LIKE ?1 ESCAPE '\\' AND NOT symbol LIKE ?1 ESCAPE '\\'",
+            "substr(upper(name), 1, length(?2)) = upper(?2) DESC, name, symbol, asset_class",
+        );
+
+        self.run_interruptible(move |conn| {
+            let mut values: Vec<&dyn ToSql> = vec![&pattern, &query, &limit];
+            values.extend(asset_classes.iter().map(|s| s as &dyn ToSql));
+
+            let by_symbol = conn.prepare_cached(&symbol_sql)?.query_map(&*values, symbol_from_row)?.collect::<Result<_>>()?;
+            let by_name = conn.prepare_cached(&name_sql)?.query_map(&*values, symbol_from_row)?.collect::<Result<_>>()?;
+            Ok(SearchSplit { by_symbol, by_name })
+        })
+        .await
+    }
+
+    /// Fetches the next page of symbols matching a query, in ticker order, after the ticker `after_symbol`
+    pub async fn search_after(
+        &self,
+        query: &str,
+        asset_class: AssetClass,
+        after_symbol: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<Symbol>> {
+        let asset_classes = match asset_class {
+            AssetClass::All => Vec::new(),
+            _ => asset_class.to_string_vec().await,
+        };
+        let pattern = format!("%{}%", escape_like(query));
+        let after_symbol = after_symbol.map(str::to_string);
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+
+        // ?1 is the pattern, ?2 the cursor, ?3 the limit or asset class cursor, then the asset classes
+        let matching = |cursor: &str| {
+            let mut sql = format!("SELECT * FROM symbols WHERE (name LIKE ?1 ESCAPE '\\' OR symbol LIKE ?1 ESCAPE '\\') AND {}", cursor);
+            if !asset_classes.is_empty() {
+                sql.push_str(&format!(
+                    " AND asset_class IN ({})",
+                    (0..asset_classes.len()).map(|i| format!("?{}", i + 4)).collect::<Vec<_>>().join(",")
+                ));
+            }
+            sql
+        };
+        let page_sql = format!(
+            "{} ORDER BY symbol, asset_class LIMIT ?3",
+            matching(if after_symbol.is_some() { "symbol > ?2" } else { "?2 IS NULL" })
+        );
+        let rest_sql = format!("{} ORDER BY asset_class", matching("symbol = ?2 AND asset_class > ?3"));
+
+        self.run_interruptible(move |conn| {
+            let mut values: Vec<&dyn ToSql> = vec![&pattern, &after_symbol, &limit];
+            values.extend(asset_classes.iter().map(|s| s as &dyn ToSql));
+            let mut page: Vec<Symbol> = conn.prepare_cached(&page_sql)?.query_map(&*values, symbol_from_row)?.collect::<Result<_>>()?;
+
+            // Complete the listings of the last ticker, so that the next page can start after it
+            if let Some(last) = page.last().filter(|_| page.len() as i64 == limit) {
+                let (symbol, asset_class) = (last.symbol.clone(), last.asset_class.clone());
+                values[1] = &symbol;
+                values[2] = &asset_class;
+                let rest: Vec<Symbol> = conn.prepare_cached(&rest_sql)?.query_map(&*values, symbol_from_row)?.collect::<Result<_>>()?;
+                page.extend(rest);
+            }
+            Ok(page)
+        })
+        .await
+    }
+
+    /// Fetches the ticker and name of up to `limit` symbols matching a query, ranked for autocomplete
+    pub async fn search_lite(&self, query: &str, asset_class: AssetClass, limit: usize) -> Result<Vec<SymbolLite>> {
+        let asset_classes = match asset_class {
+            AssetClass::All => Vec::new(),
+            _ => asset_class.to_string_vec().await,
+        };
+        let pattern = format!("%{}%", escape_like(query));
+        let prefix = format!("{}%", escape_like(query));
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let query = query.to_string();
+
+        let mut sql = "SELECT symbol, name FROM symbols WHERE (name LIKE ?1 ESCAPE '\\' OR symbol LIKE ?1 ESCAPE '\\')".to_string();
+        if !asset_classes.is_empty() {
+            sql.push_str(&format!(
+                " AND asset_class IN ({})",
+                (0..asset_classes.len()).map(|i| format!("?{}", i + 5)).collect::<Vec<_>>().join(",")
+            ));
+        }
+        sql.push_str(" ORDER BY symbol = upper(?2) DESC, symbol LIKE ?3 ESCAPE '\\' DESC, length(symbol), symbol LIMIT ?4");
+
+        self.run_interruptible(move |conn| {
+            let mut values: Vec<&dyn ToSql> = vec![&pattern, &query, &prefix, &limit];
+            values.extend(asset_classes.iter().map(|s| s as &dyn ToSql));
+
+            let mut stmt = conn.prepare_cached(&sql)?;
+            let rows = stmt.query_map(&*values, |row| {
+                Ok(SymbolLite {
+                    symbol: row.get(0)?,
+                    name: decode_name(row.get(1)?),
+                })
+            })?;
+
+            rows.collect()
+        })
+        .await
+    }
+
+    /// Fetches the rows added by the last `update_database`, in table order
+    pub async fn get_new_symbols(&self) -> Result<Vec<Symbol>> {
+        let conn = self.connection()?;
+        let logged = conn
+            .prepare_cached("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'update_log'")?
+            .exists([])?;
+        if !logged {
+            return Ok(Vec::new());
+        }
+
+        let mut stmt = conn.prepare_cached(NEW_SYMBOLS)?;
+        let rows = stmt.query_map([], symbol_from_row)?;
+        rows.collect()
+    }
+
+    /// Fetches the rows added by the last `update_database` into a Polars DataFrame
+    #[cfg(feature = "polars")]
+    pub async fn get_new_symbols_df(&self) -> Result<DataFrame, Box<dyn Error>> {
+        let symbols = self.get_new_symbols().await?;
+
+        Ok(symbols_to_df(&symbols)?)
+    }
+
+    /// Fetches all symbols into a Polars DataFrame
+    #[cfg(feature = "polars")]
+    pub async fn get_symbols_df(&self) -> Result<DataFrame, Box<dyn Error>> {
+        let symbols = self.get_all_symbols().await?;
+
+        Ok(symbols_to_df(&symbols)?)
+    }
+
+    /// Fetches all symbols into a Polars DataFrame, reading `chunk_rows` rows at a time
+    #[cfg(feature = "polars")]
+    pub async fn get_symbols_df_chunked(&self, chunk_rows: usize) -> Result<DataFrame, Box<dyn Error>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare_cached("SELECT * FROM symbols")?;
+
+        let symbols_df = rows_to_df(stmt.query_map([], symbol_from_row)?, chunk_rows)?;
+
+        Ok(symbols_df)
+    }
+
+    /// Fetches the symbols matching `query` into a Polars DataFrame without collecting them into a `Vec` first
+    #[cfg(feature = "polars")]
+    pub async fn query_symbols_df(&self, query: SymbolQuery) -> Result<DataFrame, Box<dyn Error>> {
+        let (sql, values) = symbols_sql(&query).await;
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare_cached(&sql)?;
+
+        let symbols_df = rows_to_df(stmt.query_map(params_from_iter(&values), symbol_from_row)?, QUERY_DF_CHUNK_ROWS)?;
+
+        Ok(symbols_df)
+    }
+
+    /// Fetches all symbols into an Arrow record batch; see the `arrow` module
+    #[cfg(feature = "arrow")]
+    pub async fn get_symbols_arrow(&self) -> Result<RecordBatch, Box<dyn Error>> {
+        let symbols = self.get_all_symbols().await?;
+
+        symbols_to_record_batch(&symbols)
+    }
+
+    /// Stores user-supplied ISINs for the mapped tickers, returning how many rows were updated
+    pub async fn import_isins(&self, isins: HashMap<String, String>) -> std::result::Result<usize, Box<dyn Error>> {
+        let conn = self.connection()?;
+        import::set_isins(&conn, &isins)
+    }
+
+    /// Upserts user-provided symbols, returning how many rows were written
+    pub async fn import_symbols(&self, symbols: Vec<Symbol>) -> Result<usize> {
+        let conn = self.connection()?;
+        import::import_into(&conn, &symbols)
+    }
+
+    /// Rebuilds the database file with `VACUUM`, reclaiming pages freed by updates
+    ///
+    /// `VACUUM` needs exclusive access, so run it when no other queries are in flight.
+    pub async fn compact_database(&self) -> Result<()> {
+        let conn = self.connection()?;
+        conn.execute_batch("VACUUM")
+    }
+
+    /// Writes a VACUUMed, indexed copy of the database and its checksum file for redistribution
+    pub async fn export_database(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let conn = self.connection()?;
+        export::export_connection(&conn, path)
+    }
+
+    /// Writes every symbol to `path` as a JSON array, returning how many were written
+    pub async fn dump_json(&self, path: &Path) -> Result<usize, Box<dyn Error>> {
+        let conn = self.connection()?;
+        json::dump_connection(&conn, path)
+    }
+
+    /// Replaces the symbols table with the JSON array at `path`, returning how many symbols were loaded
+    pub async fn load_json(&self, path: &Path) -> Result<usize, Box<dyn Error>> {
+        let conn = self.connection()?;
+        json::load_connection(&conn, path)
+    }
+
+    /// Checks that the database is present, non-empty and complete
+    pub async fn verify_database(&self) -> Result<VerifyReport> {
+        let conn = self.connection()?;
+        verify_connection(&conn)
+    }
+
+    /// Runs `query` on a pooled connection, reopening the connection and running it again if it
+    /// turns out to be stale
+    ///
+    /// The idle connections are reopened too, as they read the same stale file.
+    fn query_with_reopen<T>(&self, query: impl Fn(&Connection) -> Result<T>) -> Result<T> {
+        let mut conn = self.connection()?;
+        match query(&conn) {
+            Err(e) if is_stale_connection(&e) && self.reopen_connection(pooled(&mut conn)) => {
+                self.reopen();
+                query(&conn)
+            }
+            result => result,
+        }
+    }
+
+    /// Runs `query` on a blocking thread through `query_with_reopen`
+    ///
+    /// Dropping the returned future interrupts the statement in progress rather than waiting for
+    /// it, so the connection goes back to the pool as soon as SQLite notices, and the thread
+    /// dropping the future never blocks on the query.
+    async fn run_interruptible<T: Send + 'static>(
+        &self,
+        query: impl Fn(&Connection) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let db = self.clone();
+        let interrupt = Arc::new(QueryInterrupt::default());
+        let _guard = InterruptOnDrop(Arc::clone(&interrupt));
+
+        tokio::task::spawn_blocking(move || db.query_with_reopen(|conn| interrupt.run(conn, &query)))
+            .await
+            .expect("Query task panicked")
+    }
+
+    /// Reopens the idle connections of the pool on the file now at the database path
+    ///
+    /// The connections replaced close right away, so they stop holding the old file open. Those
+    /// checked out meanwhile are reopened the next time a query checks them out. In-memory and
+    /// externally built pools have no file to reopen.
+    pub(crate) fn reopen(&self) {
+        if self.path.is_none() {
+            return;
+        }
+        // Holding the idle connections until all are reopened keeps each from being taken twice
+        let mut idle = Vec::new();
+        while let Some(mut conn) = self.pool.try_get() {
+            self.reopen_connection(&mut conn);
+            idle.push(conn);
+        }
+    }
+
+    /// Replaces `conn` with a new connection to the file now at the database path, returning
+    /// whether that was possible
+    fn reopen_connection(&self, conn: &mut r2d2::PooledConnection<SqliteConnectionManager>) -> bool {
+        let Some(path) = &self.path else {
+            return false;
+        };
+        let file = FileId::of(path);
+        match open_connection(path) {
+            Ok(fresh) => {
+                **conn = fresh;
+                if let Some(file) = file {
+                    r2d2::PooledConnection::extensions_mut(conn).insert(file);
+                }
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Identity of the file `conn` reads: the one it was last reopened on, or else the pool's
+    fn opened_on(&self, conn: &r2d2::PooledConnection<SqliteConnectionManager>) -> Option<FileId> {
+        r2d2::PooledConnection::extensions(conn).get::<FileId>().copied().or(self.file)
+    }
+}
+
+/// Asset class names taken by `search_symbols` and `try_search_symbols`
+const SEARCH_ASSET_CLASSES: &str = "Equity, ETF, Mutual Fund, Index, Currency, Futures, Crypto, Options";
+
+/// The asset class a name of `SEARCH_ASSET_CLASSES` stands for
+fn search_asset_class(name: &str) -> Option<AssetClass> {
+    match name {
+        "Equity" => Some(AssetClass::Stocks),
+        "ETF" => Some(AssetClass::ETFs),
+        "Mutual Fund" => Some(AssetClass::MutualFunds),
+        "Index" => Some(AssetClass::Indices),
+        "Currency" => Some(AssetClass::Currencies),
+        "Futures" => Some(AssetClass::Futures),
+        "Crypto" => Some(AssetClass::Cryptocurrencies),
+        "Options" => Some(AssetClass::Options),
+        _ => None,
+    }
+}
+
+const DISTINCT_EXCHANGES: &str = "SELECT DISTINCT exchange FROM symbols";
+const DISTINCT_CATEGORIES: &str = "SELECT DISTINCT category FROM symbols";
+const DISTINCT_ASSET_CLASSES: &str = "SELECT DISTINCT asset_class FROM symbols";
+const MULTI_CLASS_SYMBOLS: &str = "SELECT DISTINCT symbol, asset_class FROM symbols WHERE symbol IN
+     (SELECT symbol FROM symbols GROUP BY symbol HAVING COUNT(DISTINCT asset_class) > 1)
+     ORDER BY symbol, asset_class";
+/// Distinct `(name, symbol)` pairs of the names shared by at least `?` tickers
+const DUPLICATE_NAMES: &str = "SELECT DISTINCT name, symbol FROM symbols WHERE name IN
+     (SELECT name FROM symbols WHERE name <> '' GROUP BY name HAVING COUNT(DISTINCT symbol) >= ?)
+     ORDER BY name, symbol";
+
+/// Matches rows without a real category, with `?` bound to `NA_CATEGORY`
+const UNCATEGORIZED: &str = "category IS NULL OR category = '' OR category = ?";
+
+/// Matches rows with a name, asset class or exchange that is missing, blank or `?1`, bound to `NA_CATEGORY`
+const MALFORMED: &str = "coalesce(trim(name), '') IN ('', ?1) OR coalesce(trim(asset_class), '') IN ('', ?1)
+     OR coalesce(trim(exchange), '') IN ('', ?1)";
+
+const TOP_CATEGORIES: &str =
+    "SELECT category, COUNT(*) FROM symbols GROUP BY category ORDER BY COUNT(*) DESC, category LIMIT ?";
+const NEW_SYMBOLS: &str = "SELECT s.* FROM symbols s
+     JOIN update_log l ON l.symbol = s.symbol AND l.asset_class IS s.asset_class
+     ORDER BY s.rowid";
+const INITIAL_DISTRIBUTION: &str =
+    "SELECT substr(symbol, 1, 1), COUNT(*) FROM symbols WHERE symbol <> '' GROUP BY substr(symbol, 1, 1)";
+const TOP_EXCHANGES: &str =
+    "SELECT exchange, COUNT(*) FROM symbols GROUP BY exchange ORDER BY COUNT(*) DESC, exchange LIMIT ?";
+
+/// Rows `search_stream` reads ahead of the consumer before the scan pauses
+pub const SEARCH_STREAM_BUFFER: usize = 256;
+
+/// Sends up to `limit` rows matching `pattern` to `tx`, or the error that stopped the scan
+fn stream_matches(pool: &Pool<SqliteConnectionManager>, pattern: &str, asset_classes: &[String], limit: i64, tx: &mpsc::Sender<Result<Symbol>>) {
+    if let Err(e) = send_matches(pool, pattern, asset_classes, limit, tx) {
+        let _ = tx.blocking_send(Err(e));
+    }
+}
+
+fn send_matches(pool: &Pool<SqliteConnectionManager>, pattern: &str, asset_classes: &[String], limit: i64, tx: &mpsc::Sender<Result<Symbol>>) -> Result<()> {
+    let conn = checkout(pool).map_err(|e| YahooSymbolsError::Checkout(e.to_string()))?;
+
+    let mut sql = "SELECT symbol, name, category, asset_class, exchange FROM symbols WHERE (name LIKE ? ESCAPE '\\' OR symbol LIKE ? ESCAPE '\\')".to_string();
+    let mut values: Vec<&dyn ToSql> = vec![&pattern, &pattern];
+    if !asset_classes.is_empty() {
+        sql.push_str(&format!(
+            " AND asset_class IN ({})",
+            (0..asset_classes.len()).map(|_| "?").collect::<Vec<_>>().join(",")
+        ));
+        values.extend(asset_classes.iter().map(|s| s as &dyn ToSql));
+    }
+    sql.push_str(" LIMIT ?");
+    values.push(&limit);
+
+    let mut stmt = conn.prepare_cached(&sql)?;
+    for row in stmt.query_map(&*values, symbol_from_row)? {
+        // Blocks while the buffer is full, and fails once the stream has been dropped
+        if tx.blocking_send(row).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// `SearchConfig::max_results` as an SQL `LIMIT`, where -1 means no limit
+fn sql_limit(cap: usize) -> i64 {
+    if cap == 0 { -1 } else { i64::try_from(cap).unwrap_or(i64::MAX) }
+}
+
+/// Position of a match in the order of `search_sorted`: exact ticker, ticker length, ticker, asset class
+type MatchRank = (bool, usize, String, String);
+
+fn match_rank(query: &str, symbol: &str, asset_class: &str) -> MatchRank {
+    (!symbol.eq_ignore_ascii_case(query), symbol.chars().count(), symbol.to_string(), asset_class.to_string())
+}
+
+/// Keeps the `cap` best-ranked items pushed to it, or every item when `cap` is 0
+///
+/// Memory stays bounded by the cap however many items are pushed.
+struct TopMatches<T> {
+    cap: usize,
+    ranked: BinaryHeap<Ranked<T>>,
+    all: Vec<T>,
+}
+
+/// An item ordered by its rank alone, so that the heap keeps the worst on top
+struct Ranked<T>(MatchRank, T);
+
+impl<T> PartialEq for Ranked<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T> Eq for Ranked<T> {}
+
+impl<T> PartialOrd for Ranked<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Ranked<T> {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+impl<T> TopMatches<T> {
+    fn new(cap: usize) -> TopMatches<T> {
+        TopMatches { cap, ranked: BinaryHeap::new(), all: Vec::new() }
+    }
+
+    /// Adds `item`, ranked by `rank`, which is only called when the cap applies
+    fn push(&mut self, item: T, rank: impl FnOnce(&T) -> MatchRank) {
+        if self.cap == 0 {
+            self.all.push(item);
+            return;
+        }
+        let rank = rank(&item);
+        if self.ranked.len() < self.cap {
+            self.ranked.push(Ranked(rank, item));
+        } else if self.ranked.peek().is_some_and(|worst| rank < worst.0) {
+            self.ranked.pop();
+            self.ranked.push(Ranked(rank, item));
+        }
+    }
+
+    /// The kept items, best-ranked first when capped, otherwise in the order they were pushed
+    fn into_vec(self) -> Vec<T> {
+        if self.cap == 0 {
+            return self.all;
+        }
+        self.ranked.into_sorted_vec().into_iter().map(|Ranked(_, item)| item).collect()
+    }
+}
+
+fn top_counts(conn: &Connection, sql: &str, n: usize) -> Result<Vec<(String, i64)>> {
+    let limit = i64::try_from(n).unwrap_or(i64::MAX);
+    let mut stmt = conn.prepare_cached(sql)?;
+    let rows = stmt.query_map([limit], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+fn distinct_values(conn: &Connection, sql: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare_cached(sql)?;
+
+    let rows = stmt.query_map([], |row| row.get(0))?;
+
+    rows.collect()
+}
+
+/// Lets a query running on a blocking thread be interrupted from the async side
+#[derive(Default)]
+struct QueryInterrupt(Mutex<InterruptState>);
+
+#[derive(Default)]
+struct InterruptState {
+    interrupted: bool,
+    /// Handle of the connection running the query, while it runs
+    handle: Option<InterruptHandle>,
+}
+
+impl QueryInterrupt {
+    /// Runs `query` on `conn`, failing with `SQLITE_INTERRUPT` if `interrupt` is called first or meanwhile
+    fn run<T>(&self, conn: &Connection, query: impl FnOnce(&Connection) -> Result<T>) -> Result<T> {
+        {
+            let mut state = self.0.lock().expect("Query interrupt lock poisoned");
+            if state.interrupted {
+                return Err(rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_INTERRUPT), None));
+            }
+            state.handle = Some(conn.get_interrupt_handle());
+        }
+        let result = query(conn);
+        // Cleared before the connection returns to the pool, so a late interrupt cannot hit its next query
+        self.0.lock().expect("Query interrupt lock poisoned").handle = None;
+
+        result
+    }
+
+    /// Interrupts the running query, or the one about to start; the lock is never held while a query runs
+    fn interrupt(&self) {
+        let mut state = self.0.lock().expect("Query interrupt lock poisoned");
+        state.interrupted = true;
+        if let Some(handle) = &state.handle {
+            handle.interrupt();
+        }
+    }
+}
+
+/// Calls `QueryInterrupt::interrupt` when the future owning it is dropped
+struct InterruptOnDrop(Arc<QueryInterrupt>);
+
+impl Drop for InterruptOnDrop {
+    fn drop(&mut self) {
+        self.0.interrupt();
+    }
+}
+
+/// Escapes the `LIKE` wildcards `%` and `_` in user input, so that it matches literally
+///
+/// The resulting pattern must be used with `ESCAPE '\'`.
+pub(crate) fn escape_like(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for c in input.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Builds the `SELECT` statement for `query` along with its positional parameters
+pub(crate) async fn symbols_sql(query: &SymbolQuery) -> (String, Vec<String>) {
+    select_sql("*", query).await
+}
+
+/// Builds a statement selecting `columns` of the rows matching `query`, along with its positional parameters
+/// `count` comma-separated `?` placeholders
+fn placeholders_for(count: usize) -> String {
+    vec!["?"; count].join(",")
+}
+
+async fn select_sql(columns: &str, query: &SymbolQuery) -> (String, Vec<String>) {
+    // The `All` variants match every row, so they add no clause rather than listing every value
+    let mut filters: Vec<(String, Vec<String>)> = Vec::new();
+    if query.asset_class != AssetClass::All {
+        let codes = query.asset_class.to_string_vec().await;
+        filters.push((format!("asset_class IN ({})", placeholders_for(codes.len())), codes));
+    }
+    // Asset classes without a category dimension treat any category filter as Category::All
+    if query.asset_class.has_categories() && query.category != Category::All {
+        let codes = query.category.to_string_vec().await;
+        filters.push((query.asset_class.category_condition(codes.len()), codes));
+    }
+    if query.exchange != Exchange::All {
+        let codes = query.exchange.to_string_vec().await;
+        filters.push((format!("exchange IN ({})", placeholders_for(codes.len())), codes));
+    }
+
+    // Prepare a dynamic number of placeholders and values based on the provided filters
+    let (mut placeholders, mut values): (Vec<String>, Vec<String>) = (Vec::new(), Vec::new());
+    for (condition, codes) in filters {
+        placeholders.push(condition);
+        values.extend(codes);
+    }
+    if let Some(name) = &query.name_contains {
+        placeholders.push("name LIKE ? ESCAPE '\\'".to_string());
+        values.push(format!("%{}%", escape_like(name)));
+    }
+
+    let sql = if placeholders.is_empty() {
+        format!("SELECT {} FROM symbols{}", columns, query.order_clause())
+    } else {
+        format!("SELECT {} FROM symbols WHERE {}{}", columns, placeholders.join(" AND "), query.order_clause())
+    };
+
+    (sql, values)
+}
+
+/// A connection checked out of a pool, timed when the `metrics` feature is enabled
+#[cfg(feature = "metrics")]
+type PoolConnection = crate::metrics::TimedConnection;
+#[cfg(not(feature = "metrics"))]
+type PoolConnection = r2d2::PooledConnection<SqliteConnectionManager>;
+
+/// Checks out a connection from `pool` for a query
+fn checkout(pool: &Pool<SqliteConnectionManager>) -> std::result::Result<PoolConnection, r2d2::Error> {
+    #[cfg(feature = "metrics")]
+    return crate::metrics::TimedConnection::get(pool);
+    #[cfg(not(feature = "metrics"))]
+    pool.get()
+}
+
+/// The r2d2 connection under `conn`, whose extensions hold the file it was reopened on
+fn pooled(conn: &mut PoolConnection) -> &mut r2d2::PooledConnection<SqliteConnectionManager> {
+    #[cfg(feature = "metrics")]
+    return conn.pooled_mut();
+    #[cfg(not(feature = "metrics"))]
+    conn
+}
+
+/// Opens a connection on the file at `path` as the pools of `file_pool` do
+fn open_connection(path: &Path) -> Result<Connection> {
+    let mut conn = Connection::open(path)?;
+    init_connection(&mut conn)?;
+    Ok(conn)
+}
+
+/// Builds a pool of up to `pool_size` connections from the global config on the file at `path`
+fn file_pool(path: &Path) -> std::result::Result<Pool<SqliteConnectionManager>, r2d2::Error> {
+    Pool::builder()
+        .max_size(get_config().database.pool_size)
+        .build(SqliteConnectionManager::file(path).with_init(init_connection))
+}
+
+/// Sets up a new pooled connection
+fn init_connection(conn: &mut Connection) -> Result<()> {
+    conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+    Ok(())
+}
+
+/// Whether `error` comes from a connection whose file was replaced after it was opened
+///
+/// A connection still reading an old file that was overwritten reports the table as missing or
+/// fails with an I/O error.
+fn is_stale_connection(error: &rusqlite::Error) -> bool {
+    match error {
+        rusqlite::Error::SqliteFailure(e, message) => {
+            e.code == rusqlite::ErrorCode::SystemIoFailure
+                || message.as_deref().is_some_and(|message| message.starts_with("no such table"))
+        }
+        _ => false,
+    }
+}
+
+/// Runs `build` until it succeeds, at most `POOL_RETRY_ATTEMPTS` times
+fn with_retries<T, E: fmt::Display>(mut build: impl FnMut() -> Result<T, E>) -> Result<T, YahooSymbolsError> {
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        match build() {
+            Ok(value) => return Ok(value),
+            Err(e) if attempts >= POOL_RETRY_ATTEMPTS => {
+                return Err(YahooSymbolsError::Pool { attempts, reason: e.to_string() });
+            }
+            Err(_) => std::thread::sleep(POOL_RETRY_DELAY),
+        }
+    }
+}
+
+/// Borrows a text column of `row`, failing like `Row::get::<String>` on other types
+fn text_ref<'a>(row: &'a Row, idx: usize) -> Result<&'a str> {
+    let value = row.get_ref(idx)?;
+    value.as_str().map_err(|_| {
+        let name = row.as_ref().column_name(idx).unwrap_or_default().to_string();
+        rusqlite::Error::InvalidColumnType(idx, name, value.data_type())
+    })
+}
+
+/// Whether `haystack.to_lowercase()` contains `lowered_needle`, reusing `buffer` for ASCII text
+fn contains_lowercase(haystack: &str, lowered_needle: &str, buffer: &mut String) -> bool {
+    if !haystack.is_ascii() {
+        return haystack.to_lowercase().contains(lowered_needle);
+    }
+    buffer.clear();
+    buffer.push_str(haystack);
+    buffer.make_ascii_lowercase();
+    buffer.contains(lowered_needle)
+}
+
+/// Maps a `SELECT *` row of the `symbols` table to a `Symbol`
+pub(crate) fn symbol_from_row(row: &Row) -> Result<Symbol> {
+    Ok(Symbol {
+        symbol: row.get(0)?,
+        name: decode_name(row.get(1)?),
+        category: row.get(2)?,
+        asset_class: row.get(3)?,
+        exchange: row.get(4)?,
+    })
+}
+
+/// Builds a Polars DataFrame from `rows`, converting `chunk_rows` of them at a time
+///
+/// Only one chunk is held as `Symbol`s at once; each is converted with `symbols_to_df` and stacked
+/// onto the result, so the repetitive columns are held as strings one chunk at a time. The string
+/// cache is held for the whole build, which gives every chunk the same categorical codes. A
+/// `chunk_rows` of 0 is treated as 1.
+#[cfg(feature = "polars")]
+fn rows_to_df(mut rows: impl Iterator<Item = Result<Symbol>>, chunk_rows: usize) -> Result<DataFrame, Box<dyn Error>> {
+    let _cache = StringCacheHolder::hold();
+    let chunk_rows = chunk_rows.max(1);
+    let mut symbols_df = symbols_to_df(&[])?;
+    let mut chunk: Vec<Symbol> = Vec::with_capacity(chunk_rows);
+    loop {
+        chunk.clear();
+        for row in rows.by_ref().take(chunk_rows) {
+            chunk.push(row?);
+        }
+        if chunk.is_empty() {
+            break;
+        }
+        symbols_df.vstack_mut(&symbols_to_df(&chunk)?)?;
+    }
+
+    Ok(symbols_df)
+}
+
+/// Builds a Polars DataFrame with one column per `Symbol` field
+///
+/// `symbol` and `name` are strings; `category`, `asset_class` and `exchange` are categorical.
+#[cfg(feature = "polars")]
+fn symbols_to_df(symbols: &[Symbol]) -> PolarsResult<DataFrame> {
+    with_categoricals(symbols_to_string_df(symbols)?)
+}
+
+/// Builds a Polars DataFrame with one string column per `Symbol` field
+///
+/// The five column vectors are filled in a single pass over `symbols`.
+#[cfg(feature = "polars")]
+fn symbols_to_string_df(symbols: &[Symbol]) -> PolarsResult<DataFrame> {
+    let mut symbol = Vec::with_capacity(symbols.len());
+    let mut name = Vec::with_capacity(symbols.len());
+    let mut category = Vec::with_capacity(symbols.len());
+    let mut asset_class = Vec::with_capacity(symbols.len());
+    let mut exchange = Vec::with_capacity(symbols.len());
+    for s in symbols {
+        symbol.push(s.symbol.as_str());
+        name.push(s.name.as_str());
+        category.push(s.category.as_str());
+        asset_class.push(s.asset_class.as_str());
+        exchange.push(s.exchange.as_str());
+    }
+
+    let symbols_series: Vec<Series> = vec![
+        Series::new("symbol", symbol),
+        Series::new("name", name),
+        Series::new("category", category),
+        Series::new("asset_class", asset_class),
+        Series::new("exchange", exchange),
+    ];
+
+    DataFrame::new(symbols_series)
+}
+
+/// Casts `category`, `asset_class` and `exchange` to `Categorical`
+///
+/// Each holds only a few hundred distinct values across all rows, so storing a 4-byte code per row
+/// instead of the string cuts their memory and speeds up group-bys on them. The casts run under a
+/// `StringCacheHolder`, so the codes come from Polars' global string cache: they agree with those
+/// of every other frame built while the cache is held.
+#[cfg(feature = "polars")]
+fn with_categoricals(mut df: DataFrame) -> PolarsResult<DataFrame> {
+    let _cache = StringCacheHolder::hold();
+    for column in ["category", "asset_class", "exchange"] {
+        df.try_apply(column, |s| s.cast(&DataType::Categorical(None, CategoricalOrdering::Physical)))?;
+    }
+    Ok(df)
+}
+
+fn deserialize_database(conn: &mut Connection, data: &[u8]) -> Result<()> {
+    // SAFETY: sqlite3_deserialize takes ownership of a buffer allocated with sqlite3_malloc64,
+    // so the bytes are copied into such a buffer and handed over through OwnedData.
+    let owned = unsafe {
+        let ptr = rusqlite::ffi::sqlite3_malloc64(data.len() as u64) as *mut u8;
+        let ptr = NonNull::new(ptr).ok_or_else(|| rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_NOMEM), None,
+        ))?;
+        std::ptr::copy_nonoverlapping(data.as_ptr(), ptr.as_ptr(), data.len());
+        OwnedData::from_raw_nonnull(ptr, data.len())
+    };
+    conn.deserialize(DatabaseName::Main, owned, true)
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use r2d2::Pool;
+    use r2d2_sqlite::SqliteConnectionManager;
+    use rusqlite::{Connection, DatabaseName};
+    use crate::keys::{AssetClass, Category, Exchange};
+    use crate::scraper::create_symbols_table;
+    #[cfg(feature = "download")]
+    use crate::scraper::{insert_document, Ticker};
+    use crate::error::YahooSymbolsError;
+    use crate::Symbol;
+    use super::{deserialize_database, with_retries, SymbolDatabase, POOL_RETRY_ATTEMPTS};
+
+    #[tokio::test]
+    async fn check_duplicate_names() {
+        let symbol = |ticker: &str, name: &str, asset_class: &str| Symbol {
+            symbol: ticker.to_string(),
+            name: name.to_string(),
+            asset_class: asset_class.to_string(),
+            ..Symbol::new()
+        };
+        let db = SymbolDatabase::in_memory(&[
+            symbol("BRK-A", "Berkshire Hathaway Inc.", "Stocks"),
+            symbol("BRK-B", "Berkshire Hathaway Inc.", "Stocks"),
+            symbol("BRK.B", "Berkshire Hathaway Inc.", "Stocks"),
+            symbol("QQQ", "Invesco QQQ Trust", "ETF"),
+            // One ticker under two asset classes is not a duplicate
+            symbol("QQQ", "Invesco QQQ Trust", "Mutual Fund"),
+            symbol("NONAME1", "", "Stocks"),
+            symbol("NONAME2", "", "Stocks"),
+        ])
+        .unwrap();
+
+        let berkshire = ("Berkshire Hathaway Inc.".to_string(), vec!["BRK-A".to_string(), "BRK-B".to_string(), "BRK.B".to_string()]);
+        assert_eq!(db.get_duplicate_names(2).await.unwrap(), vec![berkshire.clone()]);
+        assert_eq!(db.get_duplicate_names(3).await.unwrap(), vec![berkshire]);
+        assert!(db.get_duplicate_names(4).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn check_search_count_differences() {
+        let symbol = |ticker: &str, name: &str, asset_class: &str| Symbol {
+            symbol: ticker.to_string(),
+            name: name.to_string(),
+            asset_class: asset_class.to_string(),
+            ..Symbol::new()
+        };
+        let db = SymbolDatabase::in_memory(&[
+            symbol("DUP", "Duplicate Corp", "Stocks"),
+            symbol("DUP", "Duplicate Corp", "ETF"),
+            symbol("ATT", "AT&amp;T Inc.", "Stocks"),
+            symbol("ECO", "Éco Société", "ETF"),
+        ])
+        .unwrap();
+        let search_len = |query: &'static str| {
+            let db = db.clone();
+            async move { db.search(query).await.unwrap().len() as i64 }
+        };
+
+        // Each listing of a ticker is counted, while the search yields each ticker once
+        assert_eq!(db.search_count("dup", AssetClass::All).await.unwrap(), 2);
+        assert_eq!(search_len("dup").await, 1);
+        assert_eq!(db.search_count("dup", AssetClass::ETFs).await.unwrap(), 1);
+
+        // Names are counted as stored, with their HTML entities, and searched decoded
+        assert_eq!(db.search_count("&amp;", AssetClass::All).await.unwrap(), 1);
+        assert_eq!(search_len("&amp;").await, 0);
+        assert_eq!(db.search_count("AT&T", AssetClass::All).await.unwrap(), 0);
+        assert_eq!(search_len("AT&T").await, 1);
+
+        // `LIKE` only folds ASCII letters
+        assert_eq!(db.search_count("SOCIÉTÉ", AssetClass::All).await.unwrap(), 0);
+        assert_eq!(search_len("SOCIÉTÉ").await, 1);
+        assert_eq!(db.search_count("ÉCO", AssetClass::All).await.unwrap(), search_len("ÉCO").await);
+    }
+
+    #[tokio::test]
+    async fn check_checkout_failure() {
+        let pool = Pool::builder()
+            .max_size(1)
+            .connection_timeout(Duration::from_millis(50))
+            .build(SqliteConnectionManager::memory())
+            .unwrap();
+        let db = SymbolDatabase::from_pool(pool);
+        let _held = db.pool().get().unwrap();
+
+        // Every way a query gets its connection fails instead of panicking
+        let error = db.get_symbols_count().await.unwrap_err();
+        assert!(error.to_string().starts_with("Failed to get a connection from the pool"));
+        assert!(db.get_symbol("AAPL").await.is_err());
+        assert!(!db.is_ready().await);
+        let error = db.import_isins(Default::default()).await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<YahooSymbolsError>(), Some(YahooSymbolsError::Checkout(_))));
+    }
+
+    #[tokio::test]
+    async fn check_in_memory_shared() {
+        let symbol = |ticker: &str, name: &str| Symbol {
+            symbol: ticker.to_string(),
+            name: name.to_string(),
+            category: "N/A".to_string(),
+            asset_class: "Stocks".to_string(),
+            exchange: "NYQ".to_string(),
+        };
+        let db = SymbolDatabase::in_memory(&[symbol("ACME", "Acme Corp"), symbol("INIT", "Initech")]).unwrap();
+
+        // Two connections held at once both see the seeded rows
+        let pool = db.pool();
+        let (first, second) = (pool.get().unwrap(), pool.get().unwrap());
+        let count = |conn: &Connection| conn.query_row("SELECT COUNT(*) FROM symbols", [], |row| row.get::<_, i64>(0)).unwrap();
+        assert_eq!((count(&first), count(&second)), (2, 2));
+        first.execute("INSERT INTO symbols (symbol, name, category, asset_class, exchange) VALUES ('NEW', 'New Co', 'N/A', 'Stocks', 'NYQ')", []).unwrap();
+        assert_eq!(count(&second), 3);
+        drop((first, second));
+
+        assert_eq!(db.get_symbol("INIT").await.unwrap().name, "Initech");
+        assert_eq!(db.clone().get_symbols_count().await.unwrap(), 3);
+        // Each call creates a database of its own
+        assert_eq!(SymbolDatabase::in_memory(&[]).unwrap().get_symbols_count().await.unwrap(), 0);
+    }
+
+    #[test]
+    fn check_deserialize_database() {
+        let source = Connection::open_in_memory().unwrap();
+        source.execute_batch(
+            "CREATE TABLE symbols (symbol TEXT PRIMARY KEY, name TEXT, category TEXT, asset_class TEXT, exchange TEXT);
+             INSERT INTO symbols VALUES ('AAPL', 'Apple Inc.', 'Technology', 'Stocks', 'NMS');",
+        ).unwrap();
+        let data = source.serialize(DatabaseName::Main).unwrap();
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        deserialize_database(&mut conn, &data).unwrap();
+        let name: String = conn.query_row("SELECT name FROM symbols WHERE symbol = 'AAPL'", [], |row| row.get(0)).unwrap();
+        assert_eq!(name, "Apple Inc.");
+        assert!(conn.execute("DELETE FROM symbols", []).is_err());
+    }
+
+    #[tokio::test]
+    async fn check_multiple_databases() {
+        let dir = std::env::temp_dir();
+        let us_path = dir.join(format!("yahoo-finance-symbols-us-{}.db", std::process::id()));
+        let eu_path = dir.join(format!("yahoo-finance-symbols-eu-{}.db", std::process::id()));
+        for (path, row) in [
+            (&us_path, "('AAPL', 'Apple Inc.', 'Technology', 'Stocks', 'NMS')"),
+            (&eu_path, "('SAP.DE', 'SAP SE', 'Technology', 'Stocks', 'GER')"),
+        ] {
+            let _ = std::fs::remove_file(path);
+            let conn = Connection::open(path).unwrap();
+            create_symbols_table(&conn).unwrap();
+            conn.execute(&format!("INSERT INTO symbols (symbol, name, category, asset_class, exchange) VALUES {}", row), []).unwrap();
+        }
+
+        let us = SymbolDatabase::open(&us_path).unwrap();
+        let eu = SymbolDatabase::open(&eu_path).unwrap();
+        assert_eq!(us.path(), Some(us_path.as_path()));
+
+        assert_eq!(us.get_symbol("AAPL").await.unwrap().name, "Apple Inc.");
+        assert!(eu.get_symbol("AAPL").await.is_err());
+        assert!(eu.symbol_exists("SAP.DE").await.unwrap());
+        assert_eq!(us.get_symbols_count().await.unwrap(), 1);
+
+        let all = eu.get_symbols(AssetClass::All, Category::All, Exchange::All).await.unwrap();
+        assert_eq!(all.len(), 1);
+        assert_eq!(eu.get_symbols(AssetClass::Stocks, Category::Technology, Exchange::Xetra).await.unwrap().len(), 1);
+        assert!(eu.get_symbols(AssetClass::Stocks, Category::All, Exchange::NASDAQ).await.unwrap().is_empty());
+
+        assert!(matches!(
+            SymbolDatabase::open(dir.join("yahoo-finance-symbols-missing.db")),
+            Err(YahooSymbolsError::DatabaseNotFound(_))
+        ));
+
+        drop((us, eu));
+        std::fs::remove_file(&us_path).unwrap();
+        std::fs::remove_file(&eu_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_compact_database() {
+        let path = std::env::temp_dir().join(format!("yahoo-finance-symbols-compact-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        create_symbols_table(&Connection::open(&path).unwrap()).unwrap();
+
+        let db = SymbolDatabase::open(&path).unwrap();
+        let file_size = || std::fs::metadata(&path).unwrap().len();
+        let mut sizes = Vec::new();
+        for cycle in 0..5 {
+            {
+                let conn = db.pool().get().unwrap();
+                let tx = conn.unchecked_transaction().unwrap();
+                for i in 0..2_000 {
+                    tx.execute(
+                        "INSERT INTO symbols (symbol, name, category, asset_class, exchange) VALUES (?, 'Padding name for a test row', 'N/A', 'Stocks', 'PNK')",
+                        [format!("T{}-{}", cycle, i)],
+                    ).unwrap();
+                }
+                tx.execute("DELETE FROM symbols", []).unwrap();
+                tx.commit().unwrap();
+            }
+            let before = file_size();
+            db.compact_database().await.unwrap();
+            assert!(file_size() < before);
+            sizes.push(file_size());
+        }
+        assert!(sizes.iter().all(|&size| size <= sizes[0]));
+
+        drop(db);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_reopen_stale_connection() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("yahoo-finance-symbols-stale-{}.db", std::process::id()));
+        let update = dir.join(format!("yahoo-finance-symbols-stale-update-{}.db", std::process::id()));
+        for (file, name) in [(&path, "Apple Inc."), (&update, "Apple Inc. (updated)")] {
+            let _ = std::fs::remove_file(file);
+            let conn = Connection::open(file).unwrap();
+            create_symbols_table(&conn).unwrap();
+            conn.execute(
+                "INSERT INTO symbols (symbol, name, category, asset_class, exchange) VALUES ('AAPL', ?, 'Technology', 'Stocks', 'NMS')",
+                [name],
+            ).unwrap();
+        }
+
+        let db = SymbolDatabase::open(&path).unwrap();
+        assert_eq!(db.get_symbol("AAPL").await.unwrap().name, "Apple Inc.");
+
+        // The old file is overwritten before the update is moved into place, so the connections
+        // still open on it find an empty database
+        std::fs::File::create(&path).unwrap();
+        std::fs::rename(&update, &path).unwrap();
+        assert_eq!(db.get_symbol("AAPL").await.unwrap().name, "Apple Inc. (updated)");
+        assert_eq!(db.get_symbols(AssetClass::Stocks, Category::All, Exchange::All).await.unwrap().len(), 1);
+
+        drop(db);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_reopen_renamed_file() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("yahoo-finance-symbols-renamed-{}.db", std::process::id()));
+        let update = dir.join(format!("yahoo-finance-symbols-renamed-update-{}.db", std::process::id()));
+        for (file, name) in [(&path, "Apple Inc."), (&update, "Apple Inc. (updated)")] {
+            let _ = std::fs::remove_file(file);
+            let conn = Connection::open(file).unwrap();
+            create_symbols_table(&conn).unwrap();
+            conn.execute(
+                "INSERT INTO symbols (symbol, name, category, asset_class, exchange) VALUES ('AAPL', ?, 'Technology', 'Stocks', 'NMS')",
+                [name],
+            ).unwrap();
+        }
+
+        let db = SymbolDatabase::open(&path).unwrap();
+        let pool = db.pool();
+        assert_eq!(db.get_symbol("AAPL").await.unwrap().name, "Apple Inc.");
+
+        // The old file stays readable through its open connections, so only its replacement on
+        // disk tells that they are out of date
+        std::fs::rename(&update, &path).unwrap();
+        assert_eq!(db.get_symbol("AAPL").await.unwrap().name, "Apple Inc. (updated)");
+
+        // The same pool is kept, and its idle connections were reopened along with the one used
+        assert!(std::ptr::eq(pool, db.pool()));
+        let conns = (0..pool.max_size()).map(|_| pool.get().unwrap()).collect::<Vec<_>>();
+        for conn in &conns {
+            let name: String = conn.query_row("SELECT name FROM symbols", [], |row| row.get(0)).unwrap();
+            assert_eq!(name, "Apple Inc. (updated)");
+        }
+        drop(conns);
+        assert_eq!(pool.state().connections, pool.max_size());
+
+        drop(db);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_search_cancel_releases_connection() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::mpsc;
+
+        // The first statement step reports that the search is running, then holds it until the
+        // future has been dropped
+        let (started_tx, started_rx) = tokio::sync::oneshot::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let hook = Arc::new(Mutex::new(Some((started_tx, release_rx))));
+        let manager = SqliteConnectionManager::file("symbols.db").with_init(move |conn| {
+            let hook = Arc::clone(&hook);
+            let held = AtomicBool::new(false);
+            conn.progress_handler(1, Some(move || {
+                if !held.swap(true, Ordering::SeqCst) {
+                    if let Some((started, release)) = hook.lock().unwrap().take() {
+                        let _ = started.send(());
+                        let _ = release.recv_timeout(Duration::from_secs(10));
+                    }
+                }
+                false
+            }));
+            Ok(())
+        });
+        let db = SymbolDatabase::from_pool(Pool::builder().max_size(1).build(manager).unwrap());
+
+        // The only pooled connection is checked out by the search until it is released
+        let search = db.try_search_symbols("a", Some("Equity"));
+        tokio::select! {
+            _ = search => panic!("the search finished while held"),
+            started = started_rx => started.unwrap(),
+        }
+        release_tx.send(()).unwrap();
+        let conn = db.pool().get_timeout(Duration::from_secs(10)).unwrap();
+        drop(conn);
+
+        assert!(db.search_count("Apple", AssetClass::Stocks).await.unwrap() > 0);
+    }
+
+    #[cfg(feature = "download")]
+    #[tokio::test]
+    async fn check_decoded_names() {
+        let path = std::env::temp_dir().join(format!("yahoo-finance-symbols-entities-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        {
+            let conn = Connection::open(&path).unwrap();
+            create_symbols_table(&conn).unwrap();
+            // Written by an older scraper that stored names undecoded, next to an already clean row
+            conn.execute_batch(
+                "INSERT INTO symbols (symbol, name, category, asset_class, exchange) VALUES
+                     ('T', 'AT&amp;T Inc.', 'Communication Services', 'Stocks', 'NYQ'),
+                     ('T.MX', 'AT&T Inc.', 'Communication Services', 'Stocks', 'MEX');",
+            ).unwrap();
+            let scraped = Ticker {
+                symbol: "T.BA".to_string(),
+                name: "AT&amp;T Inc.".to_string(),
+                category: "Communication Services".to_string(),
+                asset_class: "Stocks".to_string(),
+                exchange: "BUE".to_string(),
+            };
+            insert_document(&conn, &scraped).unwrap();
+            let stored: String = conn.query_row("SELECT name FROM symbols WHERE symbol = 'T.BA'", [], |row| row.get(0)).unwrap();
+            assert_eq!(stored, "AT&T Inc.");
+        }
+
+        let db = SymbolDatabase::open(&path).unwrap();
+        for symbol in ["T", "T.MX", "T.BA"] {
+            assert_eq!(db.get_symbol(symbol).await.unwrap().name, "AT&T Inc.");
+        }
+        let all = db.get_symbols(AssetClass::Stocks, Category::All, Exchange::All).await.unwrap();
+        assert!(all.iter().all(|s| s.name == "AT&T Inc."));
+        assert_eq!(db.search_lite("T", AssetClass::All, 1).await.unwrap()[0].name, "AT&T Inc.");
+
+        drop(db);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_multi_class_symbols() {
+        let manager = SqliteConnectionManager::memory();
+        let db = SymbolDatabase::from_pool(Pool::builder().max_size(1).build(manager).unwrap());
+        assert!(SymbolDatabase::open("symbols.db").unwrap().get_multi_class_symbols().await.unwrap().is_empty());
+
+        // A table keyed on more than the ticker, as a custom pool may provide
+        db.pool().get().unwrap().execute_batch(
+            "CREATE TABLE symbols (symbol TEXT, name TEXT, category TEXT, asset_class TEXT, exchange TEXT);
+             INSERT INTO symbols VALUES
+                 ('QQQ', 'Invesco QQQ Trust', 'N/A', 'ETF', 'NMS'),
+                 ('QQQ', 'Invesco QQQ Trust', 'N/A', 'Mutual Fund', 'NAS'),
+                 ('QQQ', 'Invesco QQQ Trust', 'N/A', 'ETF', 'PCX'),
+                 ('AAPL', 'Apple Inc.', 'Technology', 'Stocks', 'NMS');",
+        ).unwrap();
+        let multi = db.get_multi_class_symbols().await.unwrap();
+        assert_eq!(multi, vec![("QQQ".to_string(), vec!["ETF".to_string(), "Mutual Fund".to_string()])]);
+    }
+
+    #[test]
+    fn check_pool_retries() {
+        let mut calls = 0;
+        let value = with_retries(|| {
+            calls += 1;
+            if calls == 1 { Err("database is locked") } else { Ok(calls) }
+        });
+        assert_eq!(value, Ok(2));
+
+        let failed: Result<(), _> = with_retries(|| Err("database is locked"));
+        assert_eq!(failed, Err(YahooSymbolsError::Pool { attempts: POOL_RETRY_ATTEMPTS, reason: "database is locked".to_string() }));
+    }
+}
@@ -0,0 +1,111 @@
+use std::path::Path;
+use rusqlite::{Connection, OpenFlags, Result};
+
+
+/// Lists the symbols whose name differs between two `symbols.db` files
+///
+/// Both files are opened read-only and directly, without going through the global connection
+/// pool, so any two snapshots (e.g. last week's and today's) can be compared. Symbols present in
+/// only one of the files are not reported.
+///
+/// # Arguments
+///
+/// * `old` - path of the earlier database
+/// * `new` - path of the later database
+///
+/// # Returns
+///
+/// * `Vec<(String, String, String)>` - `(symbol, old_name, new_name)` for every renamed symbol, ordered by symbol
+///
+/// # Example
+///
+/// ```no_run
+/// use yahoo_finance_symbols::diff_databases;
+/// use std::error::Error;
+/// use std::path::Path;
+///
+/// fn main() -> Result<(), Box<dyn Error>> {
+///     for (symbol, old_name, new_name) in diff_databases(Path::new("last_week.db"), Path::new("symbols.db"))? {
+///         println!("{}: {} -> {}", symbol, old_name, new_name);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub fn diff_databases(old: &Path, new: &Path) -> Result<Vec<(String, String, String)>> {
+    let conn = Connection::open_with_flags(old, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)?;
+    conn.execute("ATTACH DATABASE ? AS new", [new.to_string_lossy()])?;
+
+    let mut stmt = conn.prepare(
+        "SELECT o.symbol, o.name, n.name FROM main.symbols o
+         JOIN new.symbols n ON n.symbol = o.symbol AND n.asset_class IS o.asset_class
+         WHERE o.name IS NOT n.name
+         ORDER BY o.symbol",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get(0)?,
+            row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+            row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+        ))
+    })?;
+
+    rows.collect()
+}
+
+/// Replaces the `update_log` table of `conn` with the rows that are not in the database at `previous`
+///
+/// `update_log` holds the `(symbol, asset_class)` key of every row added by the last update.
+/// Without a previous database, every row counts as added. Returns the number of rows logged.
+pub(crate) fn record_added_symbols(conn: &Connection, previous: Option<&Path>) -> Result<usize> {
+    conn.execute_batch(
+        "DROP TABLE IF EXISTS update_log;
+         CREATE TABLE update_log (symbol TEXT, asset_class TEXT);",
+    )?;
+
+    let Some(previous) = previous else {
+        return conn.execute("INSERT INTO update_log SELECT symbol, asset_class FROM symbols", []);
+    };
+    conn.execute("ATTACH DATABASE ? AS previous", [previous.to_string_lossy()])?;
+    let added = conn.execute(
+        "INSERT INTO update_log
+         SELECT symbol, asset_class FROM main.symbols EXCEPT SELECT symbol, asset_class FROM previous.symbols",
+        [],
+    );
+    conn.execute("DETACH DATABASE previous", [])?;
+    added
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use rusqlite::Connection;
+    use crate::scraper::create_symbols_table;
+    use super::diff_databases;
+
+    #[test]
+    fn check_diff_databases() {
+        let dir = std::env::temp_dir();
+        let old_path = dir.join(format!("yahoo-finance-symbols-diff-old-{}.db", std::process::id()));
+        let new_path = dir.join(format!("yahoo-finance-symbols-diff-new-{}.db", std::process::id()));
+        for (path, rows) in [
+            (&old_path, "('FB', 'Facebook, Inc.'), ('AAPL', 'Apple Inc.'), ('TWTR', 'Twitter, Inc.')"),
+            (&new_path, "('FB', 'Meta Platforms, Inc.'), ('AAPL', 'Apple Inc.'), ('NVDA', 'NVIDIA Corporation')"),
+        ] {
+            let _ = std::fs::remove_file(path);
+            let conn = Connection::open(path).unwrap();
+            create_symbols_table(&conn).unwrap();
+            conn.execute(&format!("INSERT INTO symbols (symbol, name) VALUES {}", rows), []).unwrap();
+        }
+
+        let changes = diff_databases(&old_path, &new_path).unwrap();
+        assert_eq!(changes, vec![
+            ("FB".to_string(), "Facebook, Inc.".to_string(), "Meta Platforms, Inc.".to_string()),
+        ]);
+        assert!(diff_databases(&old_path, &old_path).unwrap().is_empty());
+        assert!(diff_databases(&dir.join("yahoo-finance-symbols-diff-missing.db"), &new_path).is_err());
+
+        std::fs::remove_file(&old_path).unwrap();
+        std::fs::remove_file(&new_path).unwrap();
+    }
+}
@@ -0,0 +1,89 @@
+use std::fmt;
+use std::error::Error;
+use std::path::PathBuf;
+
+
+/// Errors raised by this crate that callers may want to match on
+///
+/// Functions opening a database return them directly. Functions returning `Box<dyn Error>` return
+/// them boxed; use `downcast_ref::<YahooSymbolsError>()` to inspect them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum YahooSymbolsError {
+    /// The first lookup pages answered without a single parseable row, which means Yahoo
+    /// Finance most likely changed the layout of its lookup page and the scraper needs updating
+    ScrapeLayoutChanged {
+        /// Number of lookup queries that answered with no rows
+        queries: usize,
+    },
+    /// The connection pool could not be created, even after retrying
+    Pool {
+        /// Number of attempts made
+        attempts: u32,
+        /// Error reported by the last attempt
+        reason: String,
+    },
+    /// No connection could be checked out of an existing pool, e.g. because every connection
+    /// stayed busy for the pool's connection timeout
+    Checkout(String),
+    /// `SymbolDatabase::open` was given a path with no database file
+    DatabaseNotFound(PathBuf),
+    /// The default database file is missing and `DatabaseConfig::auto_provision` is off
+    ProvisioningDisabled(PathBuf),
+    /// The default database could not be upgraded to the current schema version
+    Migration(String),
+    /// A missing database file could neither be downloaded nor scraped
+    Provisioning(String),
+    /// The file passed to `use_existing_database` is not a symbols database
+    InvalidSchema(String),
+    /// The default database was already initialized, so it cannot be pointed at another file
+    AlreadyInitialized,
+    /// `DatabaseConfig::download_url` or `DatabaseConfig::download_ref` does not make a valid
+    /// HTTP(S) URL
+    InvalidDownloadUrl(String),
+}
+
+impl fmt::Display for YahooSymbolsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            YahooSymbolsError::ScrapeLayoutChanged { queries } => write!(
+                f,
+                "No symbols could be parsed from the first {} Yahoo Finance lookup pages; the page layout has likely changed",
+                queries
+            ),
+            YahooSymbolsError::Pool { attempts, reason } => write!(
+                f,
+                "Failed to create database connection pool after {} attempts: {}",
+                attempts, reason
+            ),
+            YahooSymbolsError::Checkout(reason) => write!(f, "Failed to get a connection from the pool: {}", reason),
+            YahooSymbolsError::DatabaseNotFound(path) => write!(f, "Database file not found: {}", path.display()),
+            YahooSymbolsError::ProvisioningDisabled(path) => write!(
+                f,
+                "Database file not found: {}, and automatic provisioning is disabled; download it with \
+                 `refresh_database()` or scrape it with `scraper::save_symbols` first",
+                path.display()
+            ),
+            YahooSymbolsError::Migration(reason) => write!(f, "Failed to migrate the database schema: {}", reason),
+            YahooSymbolsError::Provisioning(reason) => write!(f, "Failed to provision the database: {}", reason),
+            YahooSymbolsError::InvalidSchema(reason) => write!(f, "Not a symbols database: {}", reason),
+            YahooSymbolsError::AlreadyInitialized => write!(f, "Database has already been initialized"),
+            YahooSymbolsError::InvalidDownloadUrl(reason) => write!(f, "Invalid database download URL: {}", reason),
+        }
+    }
+}
+
+impl Error for YahooSymbolsError {}
+
+/// Lets functions returning `rusqlite::Result` propagate errors from opening the database or
+/// checking out one of its connections
+///
+/// The error becomes a `SQLITE_CANTOPEN` failure carrying this error's message; use
+/// `default_database` or `SymbolDatabase::open` directly to get the typed error.
+impl From<YahooSymbolsError> for rusqlite::Error {
+    fn from(error: YahooSymbolsError) -> Self {
+        rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CANTOPEN),
+            Some(error.to_string()),
+        )
+    }
+}
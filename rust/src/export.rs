@@ -0,0 +1,131 @@
+//! Producing a canonical copy of the database for redistribution.
+//!
+//! [`export_connection`] (exposed as `export_database`) writes two files:
+//!
+//! * `<path>` - a VACUUMed SQLite copy of the `symbols` table, migrated to the current schema
+//!   version (so with its filter indexes), and a `meta` table holding `schema_version`,
+//!   `last_updated` (seconds since the Unix epoch), `download_url` and the build details of the
+//!   source database (see [`provenance`](crate::provenance)). Local search history and scrape
+//!   checkpoints are left out.
+//! * `<path>.sha256` - the SHA-256 of that file in `sha256sum` format (`<hex>  <file name>`).
+//!
+//! Host both side by side: the `.db` file is what `DatabaseConfig::download_url` points at, and
+//! the download path fetches `<download_url>.sha256` when present, rejecting a download whose
+//! checksum does not match.
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use rusqlite::Connection;
+use crate::config::get_config;
+use crate::provenance::{now_secs, write_meta};
+use crate::schema::migrate_connection;
+use crate::sha256::sha256_hex;
+
+
+/// Extension appended to the exported file name for its checksum file
+pub const CHECKSUM_EXTENSION: &str = "sha256";
+
+/// Path of the checksum file written next to an exported database
+pub fn checksum_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(CHECKSUM_EXTENSION);
+    path.with_file_name(name)
+}
+
+/// Reads the hex digest from the contents of a checksum file, if it holds one
+#[cfg(feature = "download")]
+pub(crate) fn parse_checksum(contents: &str) -> Option<String> {
+    let digest = contents.split_whitespace().next()?;
+    (digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit())).then(|| digest.to_lowercase())
+}
+
+/// Checks the SHA-256 of the file at `path` against `expected`
+#[cfg(feature = "download")]
+pub(crate) fn verify_checksum(path: &Path, expected: &str) -> Result<(), Box<dyn Error>> {
+    let actual = sha256_hex(&fs::read(path)?);
+    if actual != expected {
+        return Err(format!("Checksum mismatch for {}: expected {}, got {}", path.display(), expected, actual).into());
+    }
+    Ok(())
+}
+
+/// Exports the database behind `conn` to `path`, replacing any existing file, plus its checksum file
+pub fn export_connection(conn: &Connection, path: &Path) -> Result<(), Box<dyn Error>> {
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    conn.execute("VACUUM INTO ?", [path.to_string_lossy()])?;
+
+    {
+        let mut copy = Connection::open(path)?;
+        copy.execute_batch("DROP TABLE IF EXISTS search_history; DROP TABLE IF EXISTS scrape_checkpoint;")?;
+        migrate_connection(&mut copy)?;
+
+        write_meta(&copy, "last_updated", &now_secs().to_string())?;
+        write_meta(&copy, "download_url", &get_config().database.resolved_download_url()?)?;
+        // Reclaim the pages of the dropped tables
+        copy.execute_batch("VACUUM")?;
+    }
+
+    let digest = sha256_hex(&fs::read(path)?);
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    fs::write(checksum_path(path), format!("{}  {}\n", digest, file_name))?;
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use std::fs;
+    use rusqlite::Connection;
+    use crate::schema::{read_schema_version, SCHEMA_VERSION};
+    use crate::scraper::create_symbols_table;
+    use super::{checksum_path, export_connection};
+    #[cfg(feature = "download")]
+    use super::{parse_checksum, verify_checksum};
+
+    #[test]
+    fn check_export_database() {
+        let path = std::env::temp_dir().join(format!("yahoo-finance-symbols-export-{}.db", std::process::id()));
+        let conn = Connection::open_in_memory().unwrap();
+        create_symbols_table(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO symbols (symbol, name, category, asset_class, exchange) VALUES ('AAPL', 'Apple Inc.', 'Technology', 'Stocks', 'NMS');
+             CREATE TABLE search_history (id INTEGER PRIMARY KEY, query TEXT);",
+        ).unwrap();
+
+        export_connection(&conn, &path).unwrap();
+        // Exporting again replaces the previous files
+        export_connection(&conn, &path).unwrap();
+
+        let copy = Connection::open(&path).unwrap();
+        let name: String = copy.query_row("SELECT name FROM symbols WHERE symbol = 'AAPL'", [], |row| row.get(0)).unwrap();
+        assert_eq!(name, "Apple Inc.");
+        let tables: i64 = copy.query_row("SELECT COUNT(*) FROM sqlite_master WHERE name = 'search_history'", [], |row| row.get(0)).unwrap();
+        assert_eq!(tables, 0);
+        let indexes: i64 = copy.query_row("SELECT COUNT(*) FROM sqlite_master WHERE type = 'index' AND tbl_name = 'symbols' AND sql IS NOT NULL", [], |row| row.get(0)).unwrap();
+        assert!(indexes >= 3);
+        let last_updated: String = copy.query_row("SELECT value FROM meta WHERE key = 'last_updated'", [], |row| row.get(0)).unwrap();
+        assert!(last_updated.parse::<u64>().unwrap() > 0);
+        assert_eq!(read_schema_version(&copy).unwrap(), SCHEMA_VERSION);
+        drop(copy);
+
+        let checksum = fs::read_to_string(checksum_path(&path)).unwrap();
+        assert!(checksum.ends_with(&format!("  {}\n", path.file_name().unwrap().to_string_lossy())));
+        // Downloads are checked against the published file
+        #[cfg(feature = "download")]
+        {
+            let digest = parse_checksum(&checksum).unwrap();
+            verify_checksum(&path, &digest).unwrap();
+            assert!(verify_checksum(&path, &"0".repeat(64)).is_err());
+            assert_eq!(parse_checksum("<html>Not Found</html>"), None);
+        }
+
+        fs::remove_file(checksum_path(&path)).unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+}
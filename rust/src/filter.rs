@@ -0,0 +1,250 @@
+//! Filtering query results beyond what the SQL filters express.
+//!
+//! A query runs in two stages. The categorical [`SymbolQuery`] filters (asset class, category,
+//! exchange and name substring) become a `WHERE` clause, so SQLite narrows the rows first and
+//! only the survivors are loaded. A predicate, either a plain closure or a [`SymbolFilter`]
+//! built from combinators, then runs over those rows in Rust. Keep as much of the selection as
+//! possible in the `SymbolQuery` so the predicate sees fewer rows. Sorting with
+//! [`SymbolQuery::order_by`] also happens in SQL, and the predicate keeps that order.
+//!
+//! ```
+//! use yahoo_finance_symbols::filter::{SymbolFilter, SymbolQuery};
+//! use yahoo_finance_symbols::get_symbols_filtered;
+//! use yahoo_finance_symbols::keys::{AssetClass, Exchange};
+//! use std::error::Error;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn Error>> {
+//!     let query = SymbolQuery { asset_class: AssetClass::Stocks, exchange: Exchange::NASDAQ, ..SymbolQuery::default() };
+//!     let short = SymbolFilter::new(|s| s.name.len() < 10).and(SymbolFilter::new(|s| !s.symbol.contains('.')));
+//!
+//!     let symbols = get_symbols_filtered(query, |s| short.matches(s)).await?;
+//!     println!("{:?}", symbols);
+//!     Ok(())
+//! }
+//! ```
+
+#[cfg(feature = "polars")]
+use std::error::Error;
+use std::fmt;
+use std::ops::Not;
+use std::sync::Arc;
+#[cfg(feature = "polars")]
+use polars::prelude::DataFrame;
+use crate::database::symbols_sql;
+use crate::keys::{AssetClass, Category, Exchange};
+use crate::Symbol;
+
+
+/// The filters that run in SQL, as taken by `get_symbols_with_name`
+///
+/// Defaults to every symbol: all asset classes, categories and exchanges, with no name filter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolQuery {
+    pub asset_class: AssetClass,
+    /// Only applied to asset classes with categories (see `AssetClass::has_categories`)
+    pub category: Category,
+    pub exchange: Exchange,
+    /// Substring the name must contain, matched case-insensitively
+    pub name_contains: Option<String>,
+    /// Column and direction to sort by; rows come back in database order when `None`
+    pub order: Option<(Column, SortDir)>,
+}
+
+impl SymbolQuery {
+    /// Sorts the results by `column` in direction `dir`, replacing any earlier order
+    pub fn order_by(mut self, column: Column, dir: SortDir) -> SymbolQuery {
+        self.order = Some((column, dir));
+        self
+    }
+
+    /// The `SELECT` statement this query runs, with `?` placeholders, and the values bound to them in order
+    ///
+    /// Nothing is executed. See `explain_query` for the common case without a name filter or order.
+    pub async fn sql(&self) -> (String, Vec<String>) {
+        symbols_sql(self).await
+    }
+
+    /// Fetches the matching symbols from the default database straight into a Polars DataFrame
+    ///
+    /// The frame has the same columns as `get_symbols_df`. Rows are converted in chunks of
+    /// `database::QUERY_DF_CHUNK_ROWS` rather than collected into a `Vec<Symbol>` first. Use
+    /// `SymbolDatabase::query_symbols_df` to query another database.
+    ///
+    /// ```
+    /// use yahoo_finance_symbols::filter::{Column, SortDir, SymbolQuery};
+    /// use yahoo_finance_symbols::keys::{AssetClass, Category, Exchange};
+    /// use std::error::Error;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn Error>> {
+    ///     let query = SymbolQuery {
+    ///         asset_class: AssetClass::Stocks,
+    ///         category: Category::Technology,
+    ///         exchange: Exchange::NASDAQ,
+    ///         ..SymbolQuery::default()
+    ///     };
+    ///     let tech_df = query.order_by(Column::Symbol, SortDir::Asc).fetch_df().await?;
+    ///     assert!(tech_df.height() > 0);
+    ///     println!("{:?}", tech_df);
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "polars")]
+    pub async fn fetch_df(self) -> Result<DataFrame, Box<dyn Error>> {
+        crate::default_database().await?.query_symbols_df(self).await
+    }
+
+    /// The `ORDER BY` clause for this query, empty when no order is set
+    pub(crate) fn order_clause(&self) -> String {
+        match self.order {
+            Some((column, dir)) => format!(" ORDER BY {} {}", column.as_sql(), dir.as_sql()),
+            None => String::new(),
+        }
+    }
+}
+
+impl Default for SymbolQuery {
+    fn default() -> Self {
+        SymbolQuery {
+            asset_class: AssetClass::All,
+            category: Category::All,
+            exchange: Exchange::All,
+            name_contains: None,
+            order: None,
+        }
+    }
+}
+
+/// A column of the `symbols` table to sort by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Symbol,
+    Name,
+    Category,
+    AssetClass,
+    Exchange,
+}
+
+impl Column {
+    fn as_sql(self) -> &'static str {
+        match self {
+            Column::Symbol => "symbol",
+            Column::Name => "name",
+            Column::Category => "category",
+            Column::AssetClass => "asset_class",
+            Column::Exchange => "exchange",
+        }
+    }
+}
+
+/// Sort direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortDir {
+    Asc,
+    Desc,
+}
+
+impl SortDir {
+    fn as_sql(self) -> &'static str {
+        match self {
+            SortDir::Asc => "ASC",
+            SortDir::Desc => "DESC",
+        }
+    }
+}
+
+/// Order of the matches of `search_ranked`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchRanking {
+    /// The order of `search_sorted`: exact ticker first, then shorter tickers, then alphabetically
+    #[default]
+    Lexical,
+    /// Exact ticker first, then tickers starting with the query, then by the tier of the exchange
+    /// (see `keys::exchange_tier`), then shorter tickers, then alphabetically
+    Popularity,
+}
+
+/// A composable predicate over symbols
+///
+/// Wraps a closure so that predicates can be stored, cloned and combined with [`and`](Self::and),
+/// [`or`](Self::or) and `!`.
+#[derive(Clone)]
+pub struct SymbolFilter(Arc<dyn Fn(&Symbol) -> bool + Send + Sync>);
+
+impl SymbolFilter {
+    pub fn new(predicate: impl Fn(&Symbol) -> bool + Send + Sync + 'static) -> SymbolFilter {
+        SymbolFilter(Arc::new(predicate))
+    }
+
+    /// Whether `symbol` passes this filter
+    pub fn matches(&self, symbol: &Symbol) -> bool {
+        (self.0)(symbol)
+    }
+
+    /// Passes symbols that pass both filters
+    pub fn and(self, other: SymbolFilter) -> SymbolFilter {
+        SymbolFilter::new(move |s| self.matches(s) && other.matches(s))
+    }
+
+    /// Passes symbols that pass either filter
+    pub fn or(self, other: SymbolFilter) -> SymbolFilter {
+        SymbolFilter::new(move |s| self.matches(s) || other.matches(s))
+    }
+}
+
+impl Not for SymbolFilter {
+    type Output = SymbolFilter;
+
+    /// Passes symbols that this filter rejects
+    fn not(self) -> SymbolFilter {
+        SymbolFilter::new(move |s| !self.matches(s))
+    }
+}
+
+impl fmt::Debug for SymbolFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SymbolFilter(..)")
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use crate::keys::{AssetClass, Category, Exchange};
+    use crate::Symbol;
+    use super::{Column, SortDir, SymbolFilter, SymbolQuery};
+
+    #[test]
+    fn check_symbol_filter_combinators() {
+        let symbol = |ticker: &str, name: &str| Symbol { symbol: ticker.to_string(), name: name.to_string(), ..Symbol::new() };
+        let short_name = SymbolFilter::new(|s| s.name.len() < 10);
+        let has_dot = SymbolFilter::new(|s| s.symbol.contains('.'));
+        let filter = short_name.clone().and(!has_dot.clone());
+
+        assert!(filter.matches(&symbol("AAPL", "Apple")));
+        assert!(!filter.matches(&symbol("APC.F", "Apple")));
+        assert!(!filter.matches(&symbol("MSFT", "Microsoft Corporation")));
+        assert!(short_name.or(has_dot).matches(&symbol("MSF.BR", "Microsoft Corporation")));
+    }
+
+    #[tokio::test]
+    async fn check_query_sql() {
+        let query = SymbolQuery {
+            asset_class: AssetClass::Stocks,
+            category: Category::Technology,
+            exchange: Exchange::NASDAQ,
+            name_contains: Some("micro".to_string()),
+            order: None,
+        };
+        let (sql, values) = query.order_by(Column::Name, SortDir::Desc).sql().await;
+        assert_eq!(
+            sql,
+            "SELECT * FROM symbols WHERE asset_class IN (?) AND category IN (?) AND exchange IN (?) AND name LIKE ? ESCAPE '\\' ORDER BY name DESC"
+        );
+        assert_eq!(values, ["Stocks", "Technology", "NMS", "%micro%"]);
+
+        assert_eq!(SymbolQuery::default().sql().await, ("SELECT * FROM symbols".to_string(), Vec::new()));
+    }
+}
@@ -0,0 +1,4000 @@
+//! The connection pool and every query, search, import and export function, on the default
+//! database. Built with the `full` feature and re-exported from the crate root.
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::future::Future;
+use futures::future;
+use futures::stream::{self, Stream, StreamExt};
+use std::path::{Path, PathBuf};
+#[cfg(feature = "polars")]
+use polars::prelude::*;
+use crate::scraper::create_symbols_table;
+use crate::scraper::ScrapeResult;
+#[cfg(feature = "download")]
+use crate::scraper::download_file_with_progress;
+use rusqlite::{Connection, Result};
+use crate::keys::Category;
+use crate::config::{get_config, DatabaseConfig};
+use crate::database::SymbolDatabase;
+use crate::filter::{SearchRanking, SymbolQuery};
+use crate::source::{save_symbols_from, SymbolSource};
+#[cfg(feature = "download")]
+use crate::source::YahooSource;
+pub use crate::diff::diff_databases;
+#[cfg(feature = "download")]
+pub use crate::validate::{validate_symbols, validate_symbols_with_config};
+pub use crate::warm::{search_warmed, warm_subset};
+use crate::provenance::BuildInfo;
+use crate::schema::SchemaDescription;
+use crate::verify::VerifyReport;
+/// Row count above which the database is considered complete; see [`verify::MIN_TOTAL_SYMBOLS`](crate::verify::MIN_TOTAL_SYMBOLS)
+pub use crate::verify::MIN_TOTAL_SYMBOLS as EXPECTED_MIN_SYMBOLS;
+/// Per asset class row counts below which a sector is considered incompletely scraped; see
+/// [`verify::MIN_ASSET_CLASS_SYMBOLS`](crate::verify::MIN_ASSET_CLASS_SYMBOLS)
+pub use crate::verify::MIN_ASSET_CLASS_SYMBOLS as EXPECTED_MIN_ASSET_CLASS_SYMBOLS;
+use tokio::sync::OnceCell;
+pub use crate::import::{import_isins, import_isins_from_csv, import_symbols, import_symbols_from_csv};
+use crate::import::read_user_symbols;
+use crate::keys::{AssetClass, Exchange};
+use crate::{diff, import, schema};
+#[cfg(feature = "download")]
+use crate::{export, provenance, scraper};
+use crate::{SearchResults, Symbol, SymbolLite, YahooSymbolsError};
+
+
+pub(crate) static DEFAULT_DATABASE: OnceCell<SymbolDatabase> = OnceCell::const_new();
+
+async fn initialize_database() -> Result<SymbolDatabase, YahooSymbolsError> {
+    open_or_provision(&get_config().database).await
+}
+
+/// Opens the database at `config.path`, downloading or scraping it first if the file is missing
+/// and `config.auto_provision` allows it, and upgrades it to the current schema version
+///
+/// Without the `download` feature nothing is fetched, so a missing file fails with
+/// `YahooSymbolsError::DatabaseNotFound`.
+async fn open_or_provision(config: &DatabaseConfig) -> Result<SymbolDatabase, YahooSymbolsError> {
+    #[cfg(feature = "download")]
+    {
+        if !config.path.exists() && !config.auto_provision {
+            return Err(YahooSymbolsError::ProvisioningDisabled(config.path.clone()));
+        }
+        if config.serve_while_scraping {
+            provision_while_scraping(config, scraper::LOOKUP_URL).await?;
+        } else {
+            ensure_database(config).await?;
+        }
+    }
+
+    // Building the pool may retry with sleeps, and a migration may copy the whole table
+    let path = config.path.clone();
+    run_blocking(move || {
+        let db = SymbolDatabase::open(&path)?;
+        db.migrate_blocking().map_err(|e| YahooSymbolsError::Migration(e.to_string()))?;
+        Ok(db)
+    })
+    .await
+}
+
+/// Runs `work` on Tokio's blocking thread pool and waits for it without blocking the runtime
+///
+/// Unlike `block_in_place`, this works on current-thread runtimes too. A panic in `work` is
+/// resumed in the caller.
+pub(crate) async fn run_blocking<T: Send + 'static>(work: impl FnOnce() -> T + Send + 'static) -> T {
+    match tokio::task::spawn_blocking(work).await {
+        Ok(value) => value,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+/// `scrape_pending` value of a file whose scrape stopped because the lookup page layout changed
+#[cfg(feature = "download")]
+const LAYOUT_CHANGED: &str = "layout_changed";
+
+/// Provisions the database like `ensure_database`, but scrapes in a background task
+///
+/// When the download fails, the file is created with an empty, current-schema `symbols` table
+/// and marked with the `scrape_pending` meta entry, so that it can be opened right away; the
+/// scrape then fills it lookup by lookup and clears the mark once every lookup completed. Failed
+/// lookups keep their place in the scrape checkpoint, so the mark stays until they succeed too.
+/// A marked file found on disk, left by a process that stopped before its scrape finished or
+/// whose lookups failed, has the scrape resumed from its checkpoint, unless the scrape stopped
+/// because the lookup page layout changed: retrying cannot help then, so the file stays marked
+/// until it is replaced, e.g. by `refresh_database`. Returns the scraping task, if one was started.
+#[cfg(feature = "download")]
+async fn provision_while_scraping(
+    config: &DatabaseConfig,
+    lookup_url: &str,
+) -> Result<Option<tokio::task::JoinHandle<()>>, YahooSymbolsError> {
+    let db_path = &config.path;
+    let provisioning = |e: rusqlite::Error| YahooSymbolsError::Provisioning(e.to_string());
+
+    if db_path.exists() {
+        let conn = Connection::open(db_path).map_err(provisioning)?;
+        match provenance::read_meta(&conn, provenance::SCRAPE_PENDING).map_err(provisioning)?.as_deref() {
+            None => return Ok(None),
+            Some(LAYOUT_CHANGED) => {
+                log::warn!(
+                    "Not resuming the scrape of {}: the Yahoo Finance lookup page layout changed, so it only holds part of the symbols",
+                    db_path.display()
+                );
+                return Ok(None);
+            }
+            Some(_) => log::warn!("Resuming the interrupted scrape of {} in the background", db_path.display()),
+        }
+    } else {
+        let Err(download_error) = download_database(config).await else {
+            return Ok(None);
+        };
+        report_download_failure(&config.resolved_download_url().unwrap_or_else(|_| config.download_url.clone()), &download_error);
+
+        let mut conn = Connection::open(db_path).map_err(provisioning)?;
+        create_symbols_table(&conn).map_err(provisioning)?;
+        schema::migrate_connection(&mut conn).map_err(provisioning)?;
+        provenance::write_meta(&conn, provenance::SCRAPE_PENDING, "1").map_err(provisioning)?;
+    }
+
+    let db_path = db_path.clone();
+    let lookup_url = lookup_url.to_string();
+    let scrape_config = get_config().scrape;
+    Ok(Some(tokio::spawn(async move {
+        let scrape = scraper::scrape_to_database(&db_path, &lookup_url, &scrape_config, true).await;
+        let finished = match scrape {
+            Ok(result) if !result.failed_sectors.is_empty() => Err(format!("lookups of {} failed", result.failed_sectors.join(", "))),
+            Ok(result) => Connection::open(&db_path)
+                .and_then(|conn| provenance::delete_meta(&conn, provenance::SCRAPE_PENDING))
+                .map(|()| result)
+                .map_err(|e| e.to_string()),
+            Err(e) => {
+                if let Some(YahooSymbolsError::ScrapeLayoutChanged { .. }) = e.downcast_ref::<YahooSymbolsError>() {
+                    let marked = Connection::open(&db_path).and_then(|conn| provenance::write_meta(&conn, provenance::SCRAPE_PENDING, LAYOUT_CHANGED));
+                    if let Err(mark) = marked {
+                        log::warn!("Error marking {} as stopped: {}", db_path.display(), mark);
+                    }
+                    log::warn!("Background scrape of {} stopped: {}; it is not resumed", db_path.display(), e);
+                    return;
+                }
+                Err(e.to_string())
+            }
+        };
+        match finished {
+            Ok(result) => log::info!("Background scrape of {} completed: {} symbols", db_path.display(), result.total_inserted),
+            Err(e) => log::warn!("Background scrape of {} failed: {}; it resumes on the next start", db_path.display(), e),
+        }
+    })))
+}
+
+/// How `ensure_database` made the database file available
+#[cfg(feature = "download")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatabaseStatus {
+    /// The file was already there; nothing was fetched
+    Existing,
+    /// The file was downloaded from `DatabaseConfig::download_url`
+    Downloaded,
+    /// The download failed, so the file was scraped from Yahoo Finance
+    Scraped,
+}
+
+/// Makes sure the database file at `config.path` exists, downloading or scraping it if needed
+///
+/// This is the provisioning step the first query runs, as a call of its own, so that the
+/// potentially slow download happens when the application chooses, e.g. behind a progress
+/// indicator (see `DatabaseConfig::download_progress`) or during deployment. An existing file is
+/// left untouched. Otherwise the file is downloaded from `config.download_url` and checked
+/// against its published checksum; if that fails, the symbols are scraped from Yahoo Finance
+/// instead, which takes several minutes.
+///
+/// It runs regardless of `config.auto_provision`, which only governs the implicit provisioning
+/// of the first query. Once the file exists, the default database opens it without fetching
+/// anything, so calling this before the first query with auto-provisioning turned off gives full
+/// control over the database lifecycle.
+///
+/// # Arguments
+///
+/// * `config` - database location and download settings, usually `get_config().database`
+///
+/// # Returns
+///
+/// * `DatabaseStatus` - whether the file already existed, was downloaded or was scraped
+///
+/// # Errors
+///
+/// Returns `YahooSymbolsError::Provisioning` if both the download and the scrape failed.
+///
+/// # Example
+///
+/// ```no_run
+/// use yahoo_finance_symbols::config::get_config;
+/// use yahoo_finance_symbols::{ensure_database, get_symbol, DatabaseStatus};
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     println!("Preparing the symbols database...");
+///     match ensure_database(&get_config().database).await? {
+///         DatabaseStatus::Existing => println!("Using the existing database"),
+///         DatabaseStatus::Downloaded => println!("Downloaded the database"),
+///         DatabaseStatus::Scraped => println!("Scraped the database from Yahoo Finance"),
+///     }
+///     println!("{:?}", get_symbol("AAPL").await?);
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "download")]
+pub async fn ensure_database(config: &DatabaseConfig) -> Result<DatabaseStatus, YahooSymbolsError> {
+    ensure_database_from(config, scraper::LOOKUP_URL).await
+}
+
+/// Provisions the database like `ensure_database`, scraping the lookup pages at `lookup_url`
+#[cfg(feature = "download")]
+async fn ensure_database_from(config: &DatabaseConfig, lookup_url: &str) -> Result<DatabaseStatus, YahooSymbolsError> {
+    let db_path = &config.path;
+    if db_path.exists() {
+        return Ok(DatabaseStatus::Existing);
+    }
+
+    let Err(download_error) = download_database(config).await else {
+        return Ok(DatabaseStatus::Downloaded);
+    };
+
+    report_download_failure(&config.resolved_download_url().unwrap_or_else(|_| config.download_url.clone()), &download_error);
+    let scrape = scraper::scrape_to_database(db_path, lookup_url, &get_config().scrape, false)
+        .await
+        .map_err(|e| e.to_string());
+    match scrape {
+        Ok(_) => Ok(DatabaseStatus::Scraped),
+        Err(scrape_error) => {
+            // A partially scraped file would be taken for a complete one next time
+            let _ = std::fs::remove_file(db_path);
+            Err(YahooSymbolsError::Provisioning(format!(
+                "download failed ({}), and scraping failed ({})",
+                download_error, scrape_error
+            )))
+        }
+    }
+}
+
+/// Downloads the database to `config.path` and checks it against its published checksum
+#[cfg(feature = "download")]
+async fn download_database(config: &DatabaseConfig) -> Result<(), String> {
+    let url = config.resolved_download_url().map_err(|e| e.to_string())?;
+    let url = url.as_str();
+    let timeout = config.download_timeout();
+    // Only the message is kept, so that the error is not held across the awaits below
+    let download = download_file_with_progress(url, &config.path, timeout, config.download_progress.as_ref())
+        .await
+        .map_err(|e| e.to_string());
+    match download {
+        Ok(()) => verify_download(url, &config.path).await,
+        Err(e) => Err(e),
+    }
+}
+
+/// Logs that provisioning falls back to scraping, as a warning through the `log` crate
+///
+/// Nothing is written to stdout, so that the output of a CLI built on this crate stays clean;
+/// the message only shows up if the application installs a logger.
+#[cfg(feature = "download")]
+fn report_download_failure(url: &str, error: &str) {
+    if log::Level::Warn <= log::max_level() {
+        report_download_failure_to(log::logger(), url, error);
+    }
+}
+
+/// Logs that provisioning falls back to scraping to `logger`
+#[cfg(feature = "download")]
+fn report_download_failure_to(logger: &dyn log::Log, url: &str, error: &str) {
+    logger.log(
+        &log::Record::builder()
+            .level(log::Level::Warn)
+            .target(module_path!())
+            .args(format_args!("Unable to download database from: {} ({}). Scraping symbols now from Yahoo Finance", url, error))
+            .build(),
+    );
+}
+
+/// Checks a downloaded database against the checksum published next to it, removing it on mismatch
+///
+/// Hosts that do not publish a `.sha256` file are trusted as before.
+#[cfg(feature = "download")]
+async fn verify_download(url: &str, db_path: &Path) -> Result<(), String> {
+    let Some(expected) = scraper::fetch_checksum(url).await else {
+        return Ok(());
+    };
+    // Hashing the whole file takes a while, so it is kept off the runtime's threads
+    let db_path = db_path.to_path_buf();
+    run_blocking(move || {
+        export::verify_checksum(&db_path, &expected).map_err(|e| {
+            let _ = std::fs::remove_file(&db_path);
+            e.to_string()
+        })
+    })
+    .await
+}
+
+/// Returns the database used by the free query functions, opening it on first use
+///
+/// The default database is the file at `config::DatabaseConfig::path`, downloaded (or scraped,
+/// if the download fails) when it does not exist yet, unless `load_from_bytes` was called first.
+/// Built without the `download` feature, nothing is fetched and a missing file is reported as
+/// `YahooSymbolsError::DatabaseNotFound`.
+/// Files written by older releases are upgraded to the current layout on opening; see the
+/// [`schema`] module. See the [`database`](crate::database) module for opening other databases alongside it.
+///
+/// # Explicit provisioning
+///
+/// The download is about 28 MB and the scrape fallback takes several minutes, which is surprising
+/// as the side effect of a first `get_symbol`. To make it an explicit step, turn off
+/// `DatabaseConfig::auto_provision` and fetch the file yourself, e.g. during deployment or on
+/// startup, before the first query:
+///
+#[cfg_attr(feature = "download", doc = "```no_run")]
+#[cfg_attr(not(feature = "download"), doc = "```ignore")]
+/// use yahoo_finance_symbols::config::{get_config, set_config};
+/// use yahoo_finance_symbols::{get_symbol, refresh_database};
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let mut config = get_config();
+///     config.database.auto_provision = false;
+///     set_config(config);
+///
+///     // Downloads the hosted database, or does nothing if the local copy is current
+///     refresh_database().await?;
+///     println!("{:?}", get_symbol("AAPL").await?);
+///     Ok(())
+/// }
+/// ```
+///
+/// `ensure_database` downloads the file, falling back to scraping, and reports which it did;
+/// `scraper::save_symbols` builds the file by scraping only.
+///
+/// # Partial availability while scraping
+///
+/// The download is the default source and usually completes within seconds. When it fails and
+/// `DatabaseConfig::serve_while_scraping` is on (it is off by default), the first query does not
+/// wait for the scrape: it creates an empty database, starts scraping into it in a background
+/// task and answers right away. Each lookup (a search term within a lookup sector, see
+/// `scraper::LOOKUP_SECTORS`) is written in one transaction as it completes, so until the scrape
+/// finishes queries see a consistent but growing subset of the symbols: a lookup that misses
+/// now may succeed later, and counts increase. Until then `is_ready` returns `false` and
+/// `get_build_info` reports `scrape_pending` and no `built_at` time. If the process exits first,
+/// or some lookups fail, the next start resumes the scrape in the background where it stopped;
+/// a scrape that stopped because the lookup page layout changed is not resumed. With the setting
+/// off, the first query waits for the complete scrape, as `ensure_database` always does.
+///
+/// # Runtime
+///
+/// The free functions need a Tokio runtime, of either flavor: `#[tokio::main]` and
+/// `#[tokio::main(flavor = "current_thread")]` both work. The blocking parts of the first query,
+/// i.e. opening the connection pool, upgrading an older file and verifying the checksum of a
+/// download, run on Tokio's blocking thread pool through `spawn_blocking`, so they never stall the
+/// runtime's own threads, and other tasks keep running meanwhile. On a current-thread runtime, a
+/// background scrape (see above) only makes progress while the application awaits something.
+///
+/// # Errors
+///
+/// Returns `YahooSymbolsError::ProvisioningDisabled` if the file is missing and
+/// `DatabaseConfig::auto_provision` is off; nothing is downloaded or scraped then.
+///
+/// Returns `YahooSymbolsError::Provisioning` if the file is missing and could neither be
+/// downloaded nor scraped.
+///
+/// Returns `YahooSymbolsError::Pool` if the connection pool still cannot be created after
+/// `database::POOL_RETRY_ATTEMPTS` attempts, and `YahooSymbolsError::Migration` if an older file
+/// cannot be upgraded, e.g. because it is read-only. The free query functions report these as a
+/// `rusqlite::Error` with the same message.
+///
+/// The error type is `YahooSymbolsError` rather than the `rusqlite::Error` of earlier versions, so
+/// that these cases can be told apart; a caller propagating it with `?` into `rusqlite::Result`
+/// still compiles through the `From` conversion.
+///
+/// # Example
+///
+/// ```
+/// use yahoo_finance_symbols::default_database;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let db = default_database().await?;
+///     println!("{:?}", db.get_symbol("AAPL").await?);
+///     Ok(())
+/// }
+/// ```
+pub async fn default_database() -> Result<&'static SymbolDatabase, YahooSymbolsError> {
+    DEFAULT_DATABASE.get_or_try_init(initialize_database).await
+}
+
+/// Reports whether the default database is open and answering queries
+///
+/// Meant for readiness probes such as a `/healthz` handler. Unlike the query functions it never
+/// opens, downloads or scrapes the database: it returns `false` until the first query (or
+/// `default_database`) has finished initializing it, and then runs a trivial `SELECT 1` on a pooled
+/// connection rather than scanning the table. It also returns `false` while a background scrape
+/// (see `DatabaseConfig::serve_while_scraping`) is still filling the file, which queries answer
+/// from in the meantime. It never panics.
+///
+/// # Returns
+///
+/// * `bool` - `true` if the database is initialized and a connection works
+///
+/// # Example
+///
+/// ```
+/// use yahoo_finance_symbols::{default_database, is_ready};
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     default_database().await?;
+///     assert!(is_ready().await);
+///     Ok(())
+/// }
+/// ```
+pub async fn is_ready() -> bool {
+    database_ready(&DEFAULT_DATABASE).await
+}
+
+async fn database_ready(cell: &OnceCell<SymbolDatabase>) -> bool {
+    match cell.get() {
+        Some(db) => db.is_ready().await,
+        None => false,
+    }
+}
+
+/// Initializes the database from the raw contents of a `symbols.db` file held in memory
+///
+/// Nothing is written to disk, which suits read-only hosts. Subsequent query calls use this
+/// in-memory database instead of downloading or opening `symbols.db`. The database is opened
+/// read-only, so `update_database` and other write operations do not affect it, and it is served
+/// through a single pooled connection to keep only one copy of the data in memory.
+///
+/// Must be called before any other query function, otherwise an error is returned.
+///
+/// # Arguments
+///
+/// * `data` - contents of a `symbols.db` SQLite file
+///
+/// # Example
+///
+/// ```no_run
+/// use yahoo_finance_symbols::{get_symbol, load_from_bytes};
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let data = std::fs::read("symbols.db")?;
+///     load_from_bytes(&data)?;
+///     println!("{:?}", get_symbol("AAPL").await?);
+///     Ok(())
+/// }
+/// ```
+pub fn load_from_bytes(data: &[u8]) -> Result<(), Box<dyn Error>> {
+    load_from_bytes_in(&DEFAULT_DATABASE, data)
+}
+
+fn load_from_bytes_in(cell: &OnceCell<SymbolDatabase>, data: &[u8]) -> Result<(), Box<dyn Error>> {
+    cell.set(SymbolDatabase::from_bytes(data)?)
+        .map_err(|_| "Database has already been initialized")?;
+
+    Ok(())
+}
+
+/// Streams the hosted database into `writer`, returning the number of bytes written
+///
+/// Fetches the same file as the first query, from `DatabaseConfig::resolved_download_url`, but
+/// leaves storing it to the caller, e.g. for caching it in object storage: the bytes go to any
+/// `tokio::io::AsyncWrite`, such as a `Vec<u8>` or a `tokio::fs::File`, as they arrive. Nothing
+/// is checked against the published `.sha256`, opened or installed as the default database;
+/// `load_from_bytes` serves a downloaded buffer directly. `DatabaseConfig::download_progress`, if
+/// set, receives the bytes written so far and the total size, and `download_timeout_secs` applies.
+///
+/// # Arguments
+///
+/// * `writer` - destination of the file contents
+///
+/// # Returns
+///
+/// * `u64` - size of the downloaded file, in bytes
+///
+/// # Errors
+///
+/// Fails if the download URL is invalid, the request fails or times out, the server answers with
+/// an error status, or the connection closes before the announced size was received. `writer` may
+/// hold part of the file then.
+///
+/// # Example
+///
+/// ```no_run
+/// use yahoo_finance_symbols::{download_database_to, get_symbol, load_from_bytes};
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let mut data = Vec::new();
+///     let size = download_database_to(&mut data).await?;
+///     println!("Downloaded {} bytes", size);
+///     load_from_bytes(&data)?;
+///     println!("{:?}", get_symbol("AAPL").await?);
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "download")]
+pub async fn download_database_to(mut writer: impl tokio::io::AsyncWrite + Unpin) -> Result<u64, Box<dyn Error>> {
+    let config = get_config().database;
+    let url = config.resolved_download_url()?;
+    scraper::download_to_writer(&url, &mut writer, config.download_timeout(), config.download_progress.as_ref()).await
+}
+
+/// Initializes the database as an in-memory database holding `symbols`
+///
+/// Nothing is read from or written to disk, which suits tests and hosts that bring their own
+/// symbol list. Unlike `load_from_bytes`, every connection of the pool (see
+/// `DatabaseConfig::pool_size`) reads the same shared in-memory database, so concurrent queries
+/// do not queue on a single connection; see `SymbolDatabase::in_memory`. The data lives until
+/// the process exits.
+///
+/// Must be called before any other query function, otherwise an error is returned.
+///
+/// # Arguments
+///
+/// * `symbols` - symbols to serve, stored as user rows
+///
+/// # Example
+///
+/// ```
+/// use yahoo_finance_symbols::{get_symbol, init_in_memory, Symbol};
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     init_in_memory(vec![Symbol {
+///         symbol: "ACME".to_string(),
+///         name: "Acme Corp".to_string(),
+///         category: "Industrials".to_string(),
+///         asset_class: "Stocks".to_string(),
+///         exchange: "NYQ".to_string(),
+///     }])?;
+///     assert_eq!(get_symbol("ACME").await?.name, "Acme Corp");
+///     Ok(())
+/// }
+/// ```
+pub fn init_in_memory(symbols: Vec<Symbol>) -> Result<(), Box<dyn Error>> {
+    DEFAULT_DATABASE
+        .set(SymbolDatabase::in_memory(&symbols)?)
+        .map_err(|_| YahooSymbolsError::AlreadyInitialized)?;
+
+    Ok(())
+}
+
+/// Makes a `symbols.db` file built outside this crate the database of all query functions
+///
+/// This is the explicit counterpart to auto-provisioning, for applications that build and manage
+/// the SQLite file themselves, e.g. with `import_symbols` or their own tooling: nothing is
+/// downloaded or scraped, and `DatabaseConfig::path` is ignored. The file must hold a `symbols`
+/// table with at least the `symbol`, `name`, `category`, `asset_class` and `exchange` columns; an
+/// older layout is upgraded in place to the current schema version, as the default file would be.
+///
+/// Must be called before any other query function, otherwise an error is returned.
+///
+/// # Arguments
+///
+/// * `path` - path of the existing SQLite file
+///
+/// # Errors
+///
+/// Returns `YahooSymbolsError::DatabaseNotFound` if there is no file at `path`,
+/// `YahooSymbolsError::InvalidSchema` if it is no SQLite database or lacks the columns above,
+/// `YahooSymbolsError::Migration` if it cannot be upgraded, and
+/// `YahooSymbolsError::AlreadyInitialized` if the default database is already in use.
+///
+/// # Example
+///
+/// ```no_run
+/// use yahoo_finance_symbols::{get_symbol, use_existing_database};
+/// use std::error::Error;
+/// use std::path::Path;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     use_existing_database(Path::new("/var/lib/myapp/symbols.db")).await?;
+///     println!("{:?}", get_symbol("AAPL").await?);
+///     Ok(())
+/// }
+/// ```
+pub async fn use_existing_database(path: &Path) -> Result<(), YahooSymbolsError> {
+    use_existing_database_in(&DEFAULT_DATABASE, path).await
+}
+
+async fn use_existing_database_in(cell: &OnceCell<SymbolDatabase>, path: &Path) -> Result<(), YahooSymbolsError> {
+    if cell.initialized() {
+        return Err(YahooSymbolsError::AlreadyInitialized);
+    }
+    let db = SymbolDatabase::open(path)?;
+
+    let invalid = |e: rusqlite::Error| YahooSymbolsError::InvalidSchema(e.to_string());
+    let conn = Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(invalid)?;
+    let missing = schema::missing_columns(&conn).map_err(invalid)?;
+    if !missing.is_empty() {
+        return Err(YahooSymbolsError::InvalidSchema(format!(
+            "{} has no symbols table with the columns {}",
+            path.display(),
+            missing.join(", ")
+        )));
+    }
+    drop(conn);
+
+    db.migrate().await.map_err(|e| YahooSymbolsError::Migration(e.to_string()))?;
+    cell.set(db).map_err(|_| YahooSymbolsError::AlreadyInitialized)
+}
+
+
+/// Re-downloads the database from `DatabaseConfig::download_url` if the hosted copy changed
+///
+/// The branch or tag downloaded from is `DatabaseConfig::download_ref`, `main` by default.
+///
+/// The request is conditional: the `ETag` and `Last-Modified` headers of the previous download,
+/// saved next to the database as `<path>.etag`, are sent back as `If-None-Match` and
+/// `If-Modified-Since`, and a `304 Not Modified` answer skips the transfer. This keeps frequent
+/// refresh policies from fetching the full file when nothing changed. A changed file is checked
+/// against the published `.sha256`, when there is one, before it replaces the current database.
+///
+/// The new copy replaces the database as in `update_database_atomic`: it is downloaded next to
+/// the file, renamed over it, and the connections of the default database are reopened, so the
+/// next query reads it. Queries already running finish on the old file. User-imported symbols and
+/// ISINs are carried over, and the symbols the new copy adds are recorded as for `update_database`.
+///
+/// # Returns
+///
+/// * `bool` - whether a new copy was downloaded
+///
+/// # Example
+///
+/// ```no_run
+/// use yahoo_finance_symbols::refresh_database;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     if refresh_database().await? {
+///         println!("Downloaded a newer symbols database");
+///     }
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "download")]
+pub async fn refresh_database() -> Result<bool, Box<dyn Error>> {
+    refresh_database_in(DEFAULT_DATABASE.get(), &get_config().database).await
+}
+
+#[cfg(feature = "download")]
+async fn refresh_database_in(db: Option<&SymbolDatabase>, config: &DatabaseConfig) -> Result<bool, Box<dyn Error>> {
+    let url = config.resolved_download_url()?;
+    let timeout = config.download_timeout();
+    let partial = sibling_path(&config.path, ".download");
+
+    let downloaded = scraper::download_if_modified_to(&url, &config.path, &partial, timeout, config.download_progress.as_ref()).await?;
+    let Some(validators) = downloaded else {
+        return Ok(false);
+    };
+    replace_database(db, &config.path, |staging| async move { Ok(tokio::fs::rename(&partial, staging).await?) }).await?;
+    validators.write(&config.path)?;
+
+    Ok(true)
+}
+
+/// Re-scrapes the database from Yahoo Finance, keeping any user-imported symbols
+///
+/// Dropping the returned future stops the scrape promptly. The database then holds the user rows
+/// plus whatever was scraped before cancellation; run the update again to complete it.
+///
+/// A completed update records the rows that were not in the previous database, which
+/// `get_new_symbols` and `get_new_symbols_df` return until the next update.
+///
+/// # Returns
+///
+/// * `ScrapeResult` - how many symbols were scraped, per lookup sector, how long it took and
+///   which sectors had lookups that failed
+///
+/// # Example
+///
+/// ```no_run
+/// use yahoo_finance_symbols::update_database;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let result = update_database().await?;
+///     println!("Scraped {} symbols in {:?}", result.total_inserted, result.duration);
+///     if !result.failed_sectors.is_empty() {
+///         println!("Incomplete sectors: {:?}", result.failed_sectors);
+///     }
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "download")]
+pub async fn update_database() -> Result<ScrapeResult, Box<dyn Error>> {
+    update_database_from(&YahooSource::default()).await
+}
+
+/// Re-scrapes the database from Yahoo Finance without interrupting reads, keeping any user-imported symbols
+///
+/// `update_database` moves the current file aside while it scrapes, so queries running meanwhile
+/// fail or see a partial database. This instead scrapes into `<path>.updating` next to the
+/// database, then renames it over the old file, an atomic replacement on the same filesystem, and
+/// reopens the connections of the default database. Every query therefore reads either the
+/// old or the new database, never a missing or half-built one, which makes it suitable for a
+/// periodic refresh in a long-running service. Queries already running finish on the old file.
+///
+/// Dropping the returned future stops the scrape and leaves the current database untouched; the
+/// next update discards the unfinished `<path>.updating`. The refresh needs room for a second copy
+/// of the database while it runs. New symbols are recorded as for `update_database`.
+///
+/// # Returns
+///
+/// * `ScrapeResult` - how many symbols were scraped, per lookup sector, how long it took and
+///   which sectors had lookups that failed
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use yahoo_finance_symbols::update_database_atomic;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     tokio::spawn(async {
+///         loop {
+///             tokio::time::sleep(Duration::from_secs(24 * 60 * 60)).await;
+///             if let Err(e) = update_database_atomic().await {
+///                 eprintln!("Symbol refresh failed: {}", e);
+///             }
+///         }
+///     });
+///     // ... serve queries meanwhile
+/// }
+/// ```
+#[cfg(feature = "download")]
+pub async fn update_database_atomic() -> Result<ScrapeResult, Box<dyn Error>> {
+    let db_path = get_config().database.path;
+
+    let source = YahooSource::default();
+    let result = replace_database(DEFAULT_DATABASE.get(), &db_path, |path| async move { save_symbols_from(&path, &source).await }).await?;
+
+    log::info!("Database replaced successfully: {} symbols scraped in {:?}.", result.total_inserted, result.duration);
+
+    Ok(result)
+}
+
+/// Re-scrapes only the lookup terms starting with the given letters, updating the database in place
+///
+/// A full `update_database` runs every single-letter, digit and two-letter lookup term and takes
+/// minutes. New listings, such as IPOs, tend to be few and concentrated, so when you know where
+/// they appeared this re-runs only the terms starting with one of `letters`, in every lookup
+/// sector, which takes seconds: `'A'` runs "A" and "AA" to "AZ", a digit its single term. Case is
+/// ignored. The rows returned are upserted into the default database: new tickers are inserted,
+/// and the name, category and exchange of the Yahoo rows already stored are updated. User-imported
+/// rows are left as they are.
+///
+/// Changes outside the given prefixes are not detected, and nothing is deleted: symbols delisted
+/// since the last full update stay until the next one. The file is written in place, one lookup
+/// per transaction, so queries keep working meanwhile. `get_new_symbols` and `get_build_info`
+/// keep describing the last full update.
+///
+/// # Arguments
+///
+/// * `letters` - first characters of the lookup terms to re-run, ASCII letters or digits
+///
+/// # Returns
+///
+/// * `ScrapeResult` - how many symbols were new, per lookup sector, how long it took and which
+///   sectors had lookups that failed
+///
+/// # Errors
+///
+/// Fails if `letters` holds anything but ASCII letters and digits, or if the default database has
+/// no file, e.g. after `init_in_memory`.
+///
+/// # Example
+///
+/// ```no_run
+/// use yahoo_finance_symbols::update_recent;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let result = update_recent(&['Q', 'X']).await?;
+///     println!("{} new symbols in {:?}", result.total_inserted, result.duration);
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "download")]
+pub async fn update_recent(letters: &[char]) -> Result<ScrapeResult, Box<dyn Error>> {
+    let db = default_database().await?;
+    let db_path = db.path().ok_or("update_recent needs a database file, not an in-memory database")?;
+    let mut conn = Connection::open(db_path)?;
+
+    let result = scraper::scrape_prefixes_into(&mut conn, scraper::LOOKUP_URL, &get_config().scrape, letters).await?;
+
+    log::info!("Database refreshed for prefixes {:?}: {} new symbols in {:?}.", letters, result.total_inserted, result.duration);
+
+    Ok(result)
+}
+
+/// Rebuilds the database from an alternative symbol source, keeping any user-imported symbols
+///
+/// `update_database` is this with the default `source::YahooSource`. The rows are written by
+/// `SymbolSource::save_to`, and the added rows are recorded in the same way.
+///
+/// # Returns
+///
+/// * `ScrapeResult` - how many symbols were written, per lookup sector for Yahoo Finance or under
+///   the source name otherwise, how long it took and which sectors had lookups that failed
+///
+/// # Arguments
+///
+/// * `source` - where to fetch the symbols from
+///
+/// # Example
+///
+#[cfg_attr(feature = "download", doc = "```no_run")]
+#[cfg_attr(not(feature = "download"), doc = "```ignore")]
+/// use yahoo_finance_symbols::source::YahooSource;
+/// use yahoo_finance_symbols::update_database_from;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     update_database_from(&YahooSource::default()).await?;
+///     Ok(())
+/// }
+/// ```
+pub async fn update_database_from(source: &dyn SymbolSource) -> Result<ScrapeResult, Box<dyn Error>> {
+    let db_path = get_config().database.path;
+
+    let result = rebuild_database(&db_path, |path| async move { save_symbols_from(&path, source).await }).await?;
+
+    log::info!("Database updated successfully: {} symbols scraped in {:?}.", result.total_inserted, result.duration);
+
+    Ok(result)
+}
+
+/// Replaces the database at `db_path` with the output of `rebuild`, re-applying user rows and ISINs afterwards
+///
+/// The old file is kept as `<db_path>.previous` until the rebuild completes, so that the rows it
+/// lacked can be written to the `update_log` table. If the rebuild fails, the partial file is
+/// removed and the old one renamed back into place.
+pub(crate) async fn rebuild_database<F, Fut, T>(db_path: &Path, rebuild: F) -> Result<T, Box<dyn Error>>
+where
+    F: FnOnce(PathBuf) -> Fut,
+    Fut: Future<Output = Result<T, Box<dyn Error>>>,
+{
+    let previous_path = sibling_path(db_path, ".previous");
+    let previous = db_path.exists();
+
+    if previous {
+        tokio::fs::rename(db_path, &previous_path).await?;
+    }
+
+    let rebuilt = match rebuild_into(previous.then_some(previous_path.as_path()), db_path, rebuild).await {
+        Ok(rebuilt) => rebuilt,
+        Err(e) if previous => {
+            if let Err(restore) = restore_previous(db_path, &previous_path).await {
+                log::warn!("Error restoring {} from {}: {}", db_path.display(), previous_path.display(), restore);
+            }
+            return Err(e);
+        }
+        Err(e) => return Err(e),
+    };
+
+    if previous {
+        tokio::fs::remove_file(&previous_path).await?;
+    }
+
+    Ok(rebuilt)
+}
+
+/// Puts the database kept at `previous_path` back at `db_path`, over what a failed rebuild left there
+async fn restore_previous(db_path: &Path, previous_path: &Path) -> std::io::Result<()> {
+    match tokio::fs::remove_file(db_path).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    tokio::fs::rename(previous_path, db_path).await
+}
+
+/// Builds a replacement for the database at `db_path` in `<db_path>.updating` with `rebuild`, then
+/// renames it over `db_path` and reopens the connections of `db`
+///
+/// Until the rename, `db_path` is left as it was, so readers never see the rebuild in progress.
+#[cfg(feature = "download")]
+async fn replace_database<F, Fut, T>(db: Option<&SymbolDatabase>, db_path: &Path, rebuild: F) -> Result<T, Box<dyn Error>>
+where
+    F: FnOnce(PathBuf) -> Fut,
+    Fut: Future<Output = Result<T, Box<dyn Error>>>,
+{
+    let staging_path = sibling_path(db_path, ".updating");
+    // Left behind by a cancelled update
+    if staging_path.exists() {
+        tokio::fs::remove_file(&staging_path).await?;
+    }
+
+    let rebuilt = rebuild_into(db_path.exists().then_some(db_path), &staging_path, rebuild).await?;
+
+    tokio::fs::rename(&staging_path, db_path).await?;
+    if let Some(db) = db {
+        db.reopen();
+    }
+
+    Ok(rebuilt)
+}
+
+/// Writes the output of `rebuild` to `target`, carrying over the user rows and ISINs of `previous`
+///
+/// The rows `previous` lacked are written to the `update_log` table of `target`.
+async fn rebuild_into<F, Fut, T>(previous: Option<&Path>, target: &Path, rebuild: F) -> Result<T, Box<dyn Error>>
+where
+    F: FnOnce(PathBuf) -> Fut,
+    Fut: Future<Output = Result<T, Box<dyn Error>>>,
+{
+    let mut user_symbols = Vec::new();
+    let mut isins = HashMap::new();
+    if let Some(previous) = previous {
+        let conn = Connection::open(previous)?;
+        user_symbols = read_user_symbols(&conn)?;
+        isins = import::read_isins(&conn)?;
+    }
+
+    // User rows are restored before rebuilding so that a cancelled rebuild cannot lose them
+    if !user_symbols.is_empty() {
+        let conn = Connection::open(target)?;
+        create_symbols_table(&conn)?;
+        import::import_into(&conn, &user_symbols)?;
+    }
+
+    let rebuilt = rebuild(target.to_path_buf()).await?;
+
+    let conn = Connection::open(target)?;
+    // Again, in case `rebuild` replaced the file, as a download does
+    if !user_symbols.is_empty() {
+        import::import_into(&conn, &user_symbols)?;
+    }
+    if !isins.is_empty() {
+        import::set_isins(&conn, &isins)?;
+    }
+    diff::record_added_symbols(&conn, previous)?;
+
+    Ok(rebuilt)
+}
+
+/// `path` with `suffix` appended to its file name
+pub(crate) fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut sibling = path.as_os_str().to_owned();
+    sibling.push(suffix);
+    PathBuf::from(sibling)
+}
+
+/// Fetches a symbol from the database
+///
+/// A ticker Yahoo Finance lists under several asset classes is stored once per asset class; this
+/// returns the listing stored first. Use `get_symbols` with an asset class to pick a listing.
+///
+/// # Arguments
+///
+/// * `symbol` - Symbol string
+///
+/// # Returns
+///
+/// * `Symbol` - Symbol struct
+///
+/// # Example
+///
+/// ```
+/// use std::error::Error;
+/// use yahoo_finance_symbols::get_symbol;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let result = get_symbol("AAPL").await?;
+///     println!("{:?}", result);
+///     Ok(())
+/// }
+/// ```
+pub async fn get_symbol(symbol: &str) -> Result<Symbol> {
+    default_database().await?.get_symbol(symbol).await
+}
+
+/// Fetches the symbol with the given ISIN
+///
+/// Yahoo Finance does not provide ISINs: the `isin` column is NULL for every row until a
+/// ticker-to-ISIN mapping is loaded with `import_isins` or `import_isins_from_csv`. The ISIN is
+/// matched ignoring case, and the listing stored first is returned if several have it.
+///
+/// # Arguments
+///
+/// * `isin` - ISIN, e.g. `US0378331005`
+///
+/// # Returns
+///
+/// * `Symbol` - Symbol struct, or a `QueryReturnedNoRows` error if no symbol has this ISIN
+///
+/// # Example
+///
+/// ```no_run
+/// use std::error::Error;
+/// use yahoo_finance_symbols::get_symbol_by_isin;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let result = get_symbol_by_isin("US0378331005").await?;
+///     println!("{:?}", result);
+///     Ok(())
+/// }
+/// ```
+pub async fn get_symbol_by_isin(isin: &str) -> Result<Symbol> {
+    default_database().await?.get_symbol_by_isin(isin).await
+}
+
+/// Checks whether a symbol exists in the database without loading its details
+///
+/// # Arguments
+///
+/// * `symbol` - Symbol string
+///
+/// # Returns
+///
+/// * `bool` - true if the symbol is in the database
+///
+/// # Example
+///
+/// ```
+/// use std::error::Error;
+/// use yahoo_finance_symbols::symbol_exists;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     assert!(symbol_exists("AAPL").await?);
+///     Ok(())
+/// }
+/// ```
+pub async fn symbol_exists(symbol: &str) -> Result<bool> {
+    default_database().await?.symbol_exists(symbol).await
+}
+
+/// Fetches one symbol chosen at random, e.g. for a "discover a ticker" feature
+///
+/// SQLite picks the row with `ORDER BY RANDOM() LIMIT 1`, so only one symbol is loaded. The result
+/// is not deterministic: every call may return a different symbol and the choice cannot be seeded.
+///
+/// # Returns
+///
+/// * `Symbol` - a symbol from the database
+///
+/// # Errors
+///
+/// Fails with `rusqlite::Error::QueryReturnedNoRows` if the database holds no symbols.
+///
+/// # Example
+///
+/// ```
+/// use std::error::Error;
+/// use yahoo_finance_symbols::get_random_symbol;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let symbol = get_random_symbol().await?;
+///     println!("{} ({})", symbol.symbol, symbol.name);
+///     Ok(())
+/// }
+/// ```
+pub async fn get_random_symbol() -> Result<Symbol> {
+    default_database().await?.get_random_symbol().await
+}
+
+/// Fetches up to `n` distinct symbols chosen at random
+///
+/// The bulk counterpart of `get_random_symbol`, just as non-deterministic. Fewer than `n` symbols
+/// are returned only when the database holds fewer.
+///
+/// # Arguments
+///
+/// * `n` - Number of symbols to sample
+///
+/// # Returns
+///
+/// * `Vec<Symbol>` - the sampled symbols, in random order
+///
+/// # Example
+///
+/// ```
+/// use std::error::Error;
+/// use yahoo_finance_symbols::get_random_symbols;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     for symbol in get_random_symbols(5).await? {
+///         println!("{}", symbol.symbol);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub async fn get_random_symbols(n: usize) -> Result<Vec<Symbol>> {
+    default_database().await?.get_random_symbols(n).await
+}
+
+/// Largest share of a name's characters `lookup` lets differ for an approximate match, as 1 in N
+pub const LOOKUP_NAME_DISTANCE_DIVISOR: usize = 3;
+
+/// Resolves a ticker or a company name to the single best matching symbol
+///
+/// Resolution stops at the first step that finds anything:
+///
+/// 1. **Ticker** - `input` (trimmed) equals a symbol exactly, as typed or upper-cased.
+/// 2. **Exact name** - `input` equals a name, ignoring ASCII case. When several listings share
+///    the name, the shortest symbol wins, then the alphabetically first, which normally picks
+///    the primary listing (`AAPL` over `APC.F` for "Apple Inc.").
+/// 3. **Approximate name** - the name with the smallest Levenshtein distance to `input`,
+///    ignoring case, among names that start with the same letter. The distance may be at most
+///    the input's length divided by `LOOKUP_NAME_DISTANCE_DIVISOR` (rounded down, at least 1);
+///    ties are broken as for exact names.
+///
+/// Use `search_symbols` or `search_lite` when several candidates should be shown instead.
+///
+/// # Arguments
+///
+/// * `input` - ticker symbol or company name
+///
+/// # Returns
+///
+/// * `Option<Symbol>` - the best match, or `None` if no step matched
+///
+/// # Example
+///
+/// ```
+/// use std::error::Error;
+/// use yahoo_finance_symbols::lookup;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let symbol = lookup("Apple Inc.").await?;
+///     assert_eq!(symbol.map(|s| s.symbol), Some("AAPL".to_string()));
+///     Ok(())
+/// }
+/// ```
+pub async fn lookup(input: &str) -> Result<Option<Symbol>> {
+    default_database().await?.lookup(input).await
+}
+
+/// Maximum difference in length between a mistyped ticker and the candidates considered by `suggest_symbols`
+pub const SUGGEST_MAX_LENGTH_DIFF: usize = 2;
+
+/// Suggests the tickers closest to a likely mistyped symbol, by Levenshtein distance
+///
+/// Comparing against every ticker in the database is slow, so candidates are pruned first:
+/// only tickers starting with the same character (case-insensitive) and whose length is within
+/// `SUGGEST_MAX_LENGTH_DIFF` of the input are scored. Results are ordered by distance, then by
+/// ticker length, then alphabetically.
+///
+/// # Arguments
+///
+/// * `symbol` - possibly mistyped symbol string
+/// * `n` - maximum number of suggestions
+///
+/// # Returns
+///
+/// * `Vec<Symbol>` - up to `n` closest symbols
+///
+/// # Example
+///
+/// ```
+/// use std::error::Error;
+/// use yahoo_finance_symbols::suggest_symbols;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let suggestions = suggest_symbols("APPL", 5).await?;
+///     println!("{:?}", suggestions);
+///     Ok(())
+/// }
+/// ```
+pub async fn suggest_symbols(symbol: &str, n: usize) -> Result<Vec<Symbol>> {
+    default_database().await?.suggest_symbols(symbol, n).await
+}
+
+/// Fetches symbols that match the specified asset class, category, and exchange from the database
+///
+/// # Arguments
+///
+/// * `asset_class` - Asset class enum
+/// * `category` - Category enum, only applied to asset classes with categories (see `AssetClass::has_categories`)
+/// * `exchange` - Exchange enum
+///
+/// # Returns
+///
+/// * `Vec<Symbol>` - Vector of symbols
+///
+/// # Example
+///
+/// ```
+/// use std::error::Error;
+/// use yahoo_finance_symbols::keys::{AssetClass, Category, Exchange};
+/// use yahoo_finance_symbols::get_symbols;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let result = get_symbols(AssetClass::Stocks, Category::Technology, Exchange::NASDAQ).await?;
+///     println!("{:?}", result);
+///     let result = get_symbols(AssetClass::ETFs, Category::All, Exchange::All).await?;
+///     println!("{:?}", result);
+///     let result = get_symbols(AssetClass::Futures, Category::All, Exchange::All).await?;
+///     println!("{:?}", result);
+///     let result = get_symbols(AssetClass::Indices, Category::All, Exchange::All).await?;
+///     println!("{:?}", result);
+///     let result = get_symbols(AssetClass::MutualFunds, Category::All, Exchange::All).await?;
+///     println!("{:?}", result);
+///     let result = get_symbols(AssetClass::Cryptocurrencies, Category::All, Exchange::All).await?;
+///     println!("{:?}", result);
+///     let result = get_symbols(AssetClass::Currencies, Category::All, Exchange::All).await?;
+///     println!("{:?}", result);
+///     let result = get_symbols(AssetClass::Options, Category::All, Exchange::OPRA).await?;
+///     println!("{:?}", result);
+///     Ok(())
+/// }
+/// ```
+pub async fn get_symbols(asset_class: AssetClass, category: Category, exchange: Exchange) -> Result<Vec<Symbol>> {
+    default_database().await?.get_symbols(asset_class, category, exchange).await
+}
+
+/// Fetches only the ticker symbols that match the specified asset class, category, and exchange
+///
+/// Filters exactly like `get_symbols`, but selects the `symbol` column alone, so no `Symbol`
+/// structs are built. Handy for feeding price scripts or other command-line tools. A ticker
+/// listed in several asset classes appears once per matching listing.
+///
+/// # Arguments
+///
+/// * `asset_class` - Asset class enum
+/// * `category` - Category enum, only applied to asset classes with categories (see `AssetClass::has_categories`)
+/// * `exchange` - Exchange enum
+///
+/// # Returns
+///
+/// * `Vec<String>` - the ticker symbols, in table order
+///
+/// # Example
+///
+/// Print every NASDAQ stock ticker, one per line:
+///
+/// ```
+/// use std::error::Error;
+/// use yahoo_finance_symbols::keys::{AssetClass, Category, Exchange};
+/// use yahoo_finance_symbols::get_tickers;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let tickers = get_tickers(AssetClass::Stocks, Category::All, Exchange::NASDAQ).await?;
+///     println!("{}", tickers.join("\n"));
+///     Ok(())
+/// }
+/// ```
+pub async fn get_tickers(asset_class: AssetClass, category: Category, exchange: Exchange) -> Result<Vec<String>> {
+    default_database().await?.get_tickers(asset_class, category, exchange).await
+}
+
+/// Fetches symbols that match the specified filters and whose name contains a substring
+///
+/// Works like `get_symbols`, with an extra case-insensitive `LIKE` filter on the name applied in
+/// the same SQL query, so no Rust-side filtering is needed. Passing `None` for `name_contains` is
+/// the same as calling `get_symbols`.
+///
+/// # Arguments
+///
+/// * `asset_class` - Asset class enum
+/// * `category` - Category enum, only applied to asset classes with categories (see `AssetClass::has_categories`)
+/// * `exchange` - Exchange enum
+/// * `name_contains` - substring the name must contain, if any
+///
+/// # Returns
+///
+/// * `Vec<Symbol>` - Vector of symbols
+///
+/// # Example
+///
+/// ```
+/// use std::error::Error;
+/// use yahoo_finance_symbols::keys::{AssetClass, Category, Exchange};
+/// use yahoo_finance_symbols::get_symbols_with_name;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let banks = get_symbols_with_name(AssetClass::Stocks, Category::All, Exchange::NASDAQ, Some("bank")).await?;
+///     println!("{:?}", banks);
+///     Ok(())
+/// }
+/// ```
+pub async fn get_symbols_with_name(
+    asset_class: AssetClass,
+    category: Category,
+    exchange: Exchange,
+    name_contains: Option<&str>,
+) -> Result<Vec<Symbol>> {
+    default_database().await?.get_symbols_with_name(asset_class, category, exchange, name_contains).await
+}
+
+/// Fetches the symbols of an asset class that have a real sector category
+///
+/// The scraper stores `keys::NA_CATEGORY` ("N/A") for every symbol Yahoo Finance gives no sector,
+/// which covers every asset class but stocks and some stocks too. Those rows, and any with an
+/// empty or missing category, would skew per-category analytics, so they are left out here. Only
+/// `AssetClass::Stocks` (or `AssetClass::All`) returns anything for the Yahoo Finance data.
+///
+/// # Arguments
+///
+/// * `asset_class` - AssetClass enum
+///
+/// # Returns
+///
+/// * `Vec<Symbol>` - Vector of symbols with a real category
+///
+/// # Example
+///
+/// ```
+/// use std::error::Error;
+/// use yahoo_finance_symbols::get_categorized_symbols;
+/// use yahoo_finance_symbols::keys::AssetClass;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let result = get_categorized_symbols(AssetClass::Stocks).await?;
+///     println!("{}", result.len());
+///     Ok(())
+/// }
+/// ```
+pub async fn get_categorized_symbols(asset_class: AssetClass) -> Result<Vec<Symbol>> {
+    default_database().await?.get_categorized_symbols(asset_class).await
+}
+
+/// Lists the names shared by several tickers, for auditing the data
+///
+/// Distinct tickers with an identical name are usually dual listings or share classes
+/// (`BRK-A`, `BRK-B`), but unusually large groups point at scraping or data errors. Rows with an
+/// empty name are left out, and a ticker listed under several asset classes counts once.
+///
+/// # Arguments
+///
+/// * `min_count` - smallest number of distinct tickers a name must be shared by
+///
+/// # Returns
+///
+/// * `Vec<(String, Vec<String>)>` - names in order, each with its tickers in order
+///
+/// # Example
+///
+/// ```
+/// use std::error::Error;
+/// use yahoo_finance_symbols::get_duplicate_names;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     for (name, symbols) in get_duplicate_names(10).await? {
+///         println!("{} ({}): {}", name, symbols.len(), symbols.join(", "));
+///     }
+///     Ok(())
+/// }
+/// ```
+pub async fn get_duplicate_names(min_count: usize) -> Result<Vec<(String, Vec<String>)>> {
+    default_database().await?.get_duplicate_names(min_count).await
+}
+
+/// Lists the tickers stored under more than one asset class
+///
+/// Yahoo Finance lists some tickers under several asset classes. The `symbols` table is keyed on
+/// `(symbol, asset_class)`, so every listing is stored as its own row and a ticker is ambiguous
+/// when it has more than one. `get_symbol` returns the first stored listing of such a ticker.
+///
+/// # Returns
+///
+/// * `Vec<(String, Vec<String>)>` - tickers in order, each with its asset classes in order
+///
+/// # Example
+///
+/// ```
+/// use std::error::Error;
+/// use yahoo_finance_symbols::get_multi_class_symbols;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     for (symbol, asset_classes) in get_multi_class_symbols().await? {
+///         println!("{}: {}", symbol, asset_classes.join(", "));
+///     }
+///     Ok(())
+/// }
+/// ```
+pub async fn get_multi_class_symbols() -> Result<Vec<(String, Vec<String>)>> {
+    default_database().await?.get_multi_class_symbols().await
+}
+
+/// Counts the symbols without a real category: "N/A", empty or missing
+///
+/// Together with `get_categorized_symbols(AssetClass::All)` this covers every row.
+///
+/// # Returns
+///
+/// * `i64` - number of uncategorized symbols
+///
+/// # Example
+///
+/// ```
+/// use std::error::Error;
+/// use yahoo_finance_symbols::get_uncategorized_count;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     println!("{} symbols have no category", get_uncategorized_count().await?);
+///     Ok(())
+/// }
+/// ```
+pub async fn get_uncategorized_count() -> Result<i64> {
+    default_database().await?.get_uncategorized_count().await
+}
+
+/// Fetches the symbols with incomplete data, for cleaning up the database or reporting scraping gaps
+///
+/// A row is malformed when its name, asset class or exchange is "N/A", blank or missing, as
+/// happens when a lookup page leaves a cell empty. Missing values are returned as empty strings.
+/// Unlike the category, which many instruments legitimately lack, these fields are always set
+/// for a well-parsed row. Use `count_malformed` for the count alone.
+///
+/// # Returns
+///
+/// * `Vec<Symbol>` - malformed symbols, in ticker order
+///
+/// # Example
+///
+/// ```
+/// use std::error::Error;
+/// use yahoo_finance_symbols::get_malformed_symbols;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     for symbol in get_malformed_symbols().await?.iter().take(10) {
+///         println!("{:?}", symbol);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub async fn get_malformed_symbols() -> Result<Vec<Symbol>> {
+    default_database().await?.get_malformed_symbols().await
+}
+
+/// Counts the symbols whose name, asset class or exchange is "N/A", blank or missing
+///
+/// These are the rows `get_malformed_symbols` returns.
+///
+/// # Returns
+///
+/// * `i64` - number of malformed symbols
+///
+/// # Example
+///
+/// ```
+/// use std::error::Error;
+/// use yahoo_finance_symbols::count_malformed;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     println!("{} symbols have incomplete data", count_malformed().await?);
+///     Ok(())
+/// }
+/// ```
+pub async fn count_malformed() -> Result<i64> {
+    default_database().await?.count_malformed().await
+}
+
+/// Fetches symbols whose exchange code starts with the given prefix
+///
+/// Exchange codes are Yahoo Finance's three-letter codes (see `get_distinct_exchanges`); the
+/// match is case-insensitive. Codes of related venues do not always share a prefix (the NASDAQ
+/// tiers are NMS, NGM and NCM), so use `Exchange::NASDAQAllTiers` with `get_symbols` for those.
+///
+/// # Arguments
+///
+/// * `prefix` - start of the exchange code, e.g. "N"
+///
+/// # Returns
+///
+/// * `Vec<Symbol>` - Vector of symbols
+///
+/// # Example
+///
+/// ```
+/// use std::error::Error;
+/// use yahoo_finance_symbols::get_symbols_by_exchange_prefix;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let result = get_symbols_by_exchange_prefix("NY").await?;
+///     println!("{}", result.len());
+///     Ok(())
+/// }
+/// ```
+pub async fn get_symbols_by_exchange_prefix(prefix: &str) -> Result<Vec<Symbol>> {
+    default_database().await?.get_symbols_by_exchange_prefix(prefix).await
+}
+
+/// Fetches symbols whose ticker matches a wildcard pattern
+///
+/// The pattern uses SQLite `GLOB` syntax and is case-sensitive, which suits Yahoo Finance's
+/// upper-case tickers:
+///
+/// * `*` matches any run of characters, including none
+/// * `?` matches exactly one character
+/// * `[...]` matches one of the listed characters or ranges (`[AB]`, `[0-9]`), and `[^...]` one
+///   character that is not listed
+///
+/// Every other character matches itself, so `"BRK*"` returns both Berkshire Hathaway classes
+/// along with every other ticker starting with BRK, `"BRK-?"` only the share classes, and `"^*"`
+/// the indices. To match a literal `*`, `?` or `[`, wrap it in brackets (`[*]`), or build the
+/// pattern from user input with `escape_glob`, e.g. `format!("{}*", escape_glob(input))`. Patterns that
+/// start with literal characters use the ticker index. This matches tickers only; use
+/// `search_symbols` to search names.
+///
+/// # Arguments
+///
+/// * `pattern` - `GLOB` pattern matched against the whole ticker
+///
+/// # Returns
+///
+/// * `Vec<Symbol>` - matching symbols, sorted by ticker
+///
+/// # Example
+///
+/// ```
+/// use yahoo_finance_symbols::get_symbols_matching;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     for symbol in get_symbols_matching("BRK-?").await? {
+///         println!("{} {}", symbol.symbol, symbol.name);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub async fn get_symbols_matching(pattern: &str) -> Result<Vec<Symbol>> {
+    default_database().await?.get_symbols_matching(pattern).await
+}
+
+/// Fetches the symbols matching a `SymbolQuery`
+///
+/// Equivalent to `get_symbols_with_name` with the query's filters, except that setting an order
+/// with `SymbolQuery::order_by` makes SQLite sort the rows before they are returned.
+///
+/// # Arguments
+///
+/// * `query` - filters and optional sort order
+///
+/// # Returns
+///
+/// * `Vec<Symbol>` - matching symbols, sorted if the query sets an order
+///
+/// # Example
+///
+/// ```
+/// use std::error::Error;
+/// use yahoo_finance_symbols::filter::{Column, SortDir, SymbolQuery};
+/// use yahoo_finance_symbols::keys::{AssetClass, Exchange};
+/// use yahoo_finance_symbols::query_symbols;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let query = SymbolQuery { asset_class: AssetClass::ETFs, exchange: Exchange::NYSEArca, ..SymbolQuery::default() }
+///         .order_by(Column::Name, SortDir::Asc);
+///     for symbol in query_symbols(query).await?.iter().take(10) {
+///         println!("{} {}", symbol.name, symbol.symbol);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub async fn query_symbols(query: SymbolQuery) -> Result<Vec<Symbol>> {
+    default_database().await?.query_symbols(query).await
+}
+
+/// Returns the SQL `get_symbols` runs for a filter combination, without running it
+///
+/// `All` filters add no clause, and asset classes without categories ignore the category
+/// filter, so the result shows exactly which conditions apply. Placeholders are left as `?`; use
+/// `SymbolQuery::sql` to also get the values bound to them, or to include a name filter or order.
+///
+/// # Arguments
+///
+/// * `asset_class` - Asset class enum
+/// * `category` - Category enum
+/// * `exchange` - Exchange enum
+///
+/// # Returns
+///
+/// * `String` - the `SELECT` statement with `?` placeholders
+///
+/// # Example
+///
+/// ```
+/// use yahoo_finance_symbols::explain_query;
+/// use yahoo_finance_symbols::keys::{AssetClass, Category, Exchange};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let sql = explain_query(AssetClass::ETFs, Category::Technology, Exchange::NYSEArca).await;
+///     assert_eq!(sql, "SELECT * FROM symbols WHERE asset_class IN (?) AND exchange IN (?)");
+/// }
+/// ```
+pub async fn explain_query(asset_class: AssetClass, category: Category, exchange: Exchange) -> String {
+    let query = SymbolQuery { asset_class, category, exchange, ..SymbolQuery::default() };
+    query.sql().await.0
+}
+
+/// Fetches symbols matching SQL filters and a custom predicate
+///
+/// The categorical filters in `query` run in SQL first, as in `get_symbols_with_name`, so only
+/// matching rows are loaded; `predicate` then runs in Rust over those rows and keeps the ones it
+/// returns `true` for. This allows conditions SQL cannot express without writing raw SQL. See
+/// the [`filter`](crate::filter) module for composing predicates with `SymbolFilter`.
+///
+/// # Arguments
+///
+/// * `query` - asset class, category, exchange and name filters applied in SQL
+/// * `predicate` - condition each remaining symbol must satisfy
+///
+/// # Returns
+///
+/// * `Vec<Symbol>` - symbols passing both stages
+///
+/// # Example
+///
+/// ```
+/// use std::error::Error;
+/// use yahoo_finance_symbols::filter::SymbolQuery;
+/// use yahoo_finance_symbols::get_symbols_filtered;
+/// use yahoo_finance_symbols::keys::AssetClass;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let query = SymbolQuery { asset_class: AssetClass::Stocks, ..SymbolQuery::default() };
+///     let symbols = get_symbols_filtered(query, |s| s.name.len() < 10 && !s.symbol.contains('.')).await?;
+///     println!("{:?}", symbols);
+///     Ok(())
+/// }
+/// ```
+pub async fn get_symbols_filtered(query: SymbolQuery, predicate: impl Fn(&Symbol) -> bool) -> Result<Vec<Symbol>> {
+    default_database().await?.get_symbols_filtered(query, predicate).await
+}
+
+/// Fetches every symbol in the database
+///
+/// Shorthand for `get_symbols(AssetClass::All, Category::All, Exchange::All)`.
+///
+/// # Returns
+///
+/// * `Vec<Symbol>` - Vector of all symbols
+///
+/// # Example
+///
+/// ```
+/// use std::error::Error;
+/// use yahoo_finance_symbols::get_all_symbols;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let result = get_all_symbols().await?;
+///     println!("{}", result.len());
+///     Ok(())
+/// }
+/// ```
+pub async fn get_all_symbols() -> Result<Vec<Symbol>> {
+    default_database().await?.get_all_symbols().await
+}
+
+/// Fetches the symbols for several filter combinations concurrently
+///
+/// Each query runs on its own task with a separate pooled connection, so independent
+/// filters are served in parallel instead of one after another.
+///
+/// # Arguments
+///
+/// * `queries` - asset class, category and exchange filters, one tuple per query
+///
+/// # Returns
+///
+/// * `Vec<Vec<Symbol>>` - symbols for each query, in the same order as `queries`
+///
+/// # Example
+///
+/// ```
+/// use std::error::Error;
+/// use yahoo_finance_symbols::keys::{AssetClass, Category, Exchange};
+/// use yahoo_finance_symbols::get_symbols_many;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let results = get_symbols_many(vec![
+///         (AssetClass::Stocks, Category::Technology, Exchange::NASDAQ),
+///         (AssetClass::ETFs, Category::All, Exchange::NYSEArca),
+///     ]).await?;
+///     println!("{:?}", results);
+///     Ok(())
+/// }
+/// ```
+pub async fn get_symbols_many(queries: Vec<(AssetClass, Category, Exchange)>) -> Result<Vec<Vec<Symbol>>> {
+    default_database().await?.get_symbols_many(queries).await
+}
+
+pub async fn get_symbols_count() -> Result<i64> {
+    default_database().await?.get_symbols_count().await
+}
+
+/// Reads where the default database came from and how it was built
+///
+/// `save_symbols` and `update_database` record the symbol source and scrape settings, and
+/// `export_database` the URL the copy is published at, in the file's `meta` table, so a
+/// downloaded or redistributed database describes itself. See the [`provenance`] module for
+/// every recorded entry. Files that predate this, or were assembled by hand, report `None` for
+/// what they lack.
+///
+/// The version of this crate that built the file is recorded too: `BuildInfo::version_mismatch`
+/// tells whether it differs from the running one, which opening the database also logs as a
+/// warning. An old download read by a newer release is a common cause of schema skew.
+///
+/// # Returns
+///
+/// * `BuildInfo` - recorded provenance of the database
+///
+/// # Example
+///
+/// ```
+/// use std::error::Error;
+/// use yahoo_finance_symbols::get_build_info;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let info = get_build_info().await?;
+///     println!("Built from {:?} at {:?}, published at {:?}", info.source, info.built_at, info.download_url);
+///     Ok(())
+/// }
+/// ```
+pub async fn get_build_info() -> Result<BuildInfo> {
+    default_database().await?.get_build_info().await
+}
+
+/// Reads the schema version of the default database
+///
+/// The default database is migrated when it is opened, so this is `schema::SCHEMA_VERSION`
+/// unless the file was written by a newer release. See the [`schema`] module for the history.
+///
+/// # Returns
+///
+/// * `u32` - schema version
+///
+/// # Example
+///
+/// ```
+/// use std::error::Error;
+/// use yahoo_finance_symbols::get_schema_version;
+/// use yahoo_finance_symbols::schema::SCHEMA_VERSION;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     assert!(get_schema_version().await? >= SCHEMA_VERSION);
+///     Ok(())
+/// }
+/// ```
+pub async fn get_schema_version() -> Result<u32> {
+    default_database().await?.get_schema_version().await
+}
+
+/// Describes the layout and size of the default database
+///
+/// Lists every table with its columns (declared type, `NOT NULL`, default and position in the
+/// primary key, from `PRAGMA table_info`), its indexes (from `PRAGMA index_list`) and its row
+/// count. The description serializes with serde, and `SchemaDescription::to_json` renders it for
+/// data dictionaries or for checking that a migration took effect.
+///
+/// # Returns
+///
+/// * `SchemaDescription` - tables of the database, by name
+///
+/// # Example
+///
+/// ```
+/// use std::error::Error;
+/// use yahoo_finance_symbols::describe_database;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let description = describe_database().await?;
+///     for table in &description.tables {
+///         println!("{}: {} rows, {} columns", table.name, table.row_count, table.columns.len());
+///     }
+///     println!("{}", description.to_json());
+///     Ok(())
+/// }
+/// ```
+pub async fn describe_database() -> Result<SchemaDescription> {
+    default_database().await?.describe_database().await
+}
+
+/// Upgrades the default database to `schema::SCHEMA_VERSION` step by step
+///
+/// Opening the default database already does this, so calling it is only needed after the file
+/// was replaced underneath a running process, e.g. by an older copy. Each step runs in its own
+/// transaction and migrating an up-to-date database does nothing.
+///
+/// # Returns
+///
+/// * `u32` - schema version after migrating
+///
+/// # Example
+///
+/// ```no_run
+/// use std::error::Error;
+/// use yahoo_finance_symbols::migrate;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     println!("Schema version {}", migrate().await?);
+///     Ok(())
+/// }
+/// ```
+pub async fn migrate() -> Result<u32> {
+    default_database().await?.migrate().await
+}
+
+pub async fn get_distinct_exchanges() -> Result<Vec<String>> {
+    default_database().await?.get_distinct_exchanges().await
+}
+
+pub async fn get_distinct_categories() -> Result<Vec<String>> {
+    default_database().await?.get_distinct_categories().await
+}
+
+pub async fn get_distinct_asset_classes() -> Result<Vec<String>> {
+    default_database().await?.get_distinct_asset_classes().await
+}
+
+
+/// The distinct values of each filter column, for populating filter dropdowns
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilterOptions {
+    pub exchanges: Vec<String>,
+    pub categories: Vec<String>,
+    pub asset_classes: Vec<String>,
+}
+
+/// Fetches the distinct exchanges, categories and asset classes in one call
+///
+/// Returns the same lists as `get_distinct_exchanges`, `get_distinct_categories` and
+/// `get_distinct_asset_classes`, but checks out a single pooled connection for all three.
+///
+/// # Returns
+///
+/// * `FilterOptions` - distinct values of each filter column
+///
+/// # Example
+///
+/// ```
+/// use yahoo_finance_symbols::get_filter_options;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let options = get_filter_options().await?;
+///     println!("{:?}", options.exchanges);
+///     Ok(())
+/// }
+/// ```
+pub async fn get_filter_options() -> Result<FilterOptions> {
+    default_database().await?.get_filter_options().await
+}
+
+/// Streams the symbols whose symbol or name matches the query, as the table is scanned
+///
+/// Matches the same rows as `search_count`, in table order, but yields each one as soon as it is
+/// read so the first results can be shown before a broad query finishes scanning.
+///
+/// # Backpressure
+///
+/// The scan runs on a blocking thread and reads at most `database::SEARCH_STREAM_BUFFER` rows
+/// ahead of the consumer; it pauses while the buffer is full, so a slow consumer never causes
+/// the whole result set to be buffered. Dropping the stream stops the scan.
+///
+/// # Connections
+///
+/// The scan holds one pooled connection from the first poll until the last row has been read or
+/// the stream is dropped. Keep the pool size in mind when running many streams at once, and drop
+/// streams that are no longer read.
+///
+/// # Arguments
+///
+/// * `query` - ticker symbol or name query
+/// * `asset_class` - Asset class enum
+///
+/// # Example
+///
+/// ```
+/// use futures::StreamExt;
+/// use yahoo_finance_symbols::keys::AssetClass;
+/// use yahoo_finance_symbols::search_stream;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let mut results = Box::pin(search_stream("Apple", AssetClass::Stocks));
+///     while let Some(symbol) = results.next().await {
+///         println!("{:?}", symbol?);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub fn search_stream(query: &str, asset_class: AssetClass) -> impl Stream<Item = Result<Symbol>> + Send + 'static {
+    let query = query.to_string();
+    stream::once(default_database()).flat_map(move |db| match db {
+        Ok(db) => db.search_stream(&query, asset_class).left_stream(),
+        Err(e) => stream::once(future::ready(Err(e.into()))).right_stream(),
+    })
+}
+
+/// Fetches the categories with the most symbols
+///
+/// # Arguments
+///
+/// * `n` - maximum number of categories returned
+///
+/// # Returns
+///
+/// * `Vec<(String, i64)>` - category names and symbol counts, largest first
+///
+/// # Example
+///
+/// ```
+/// use yahoo_finance_symbols::top_categories;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     for (category, count) in top_categories(5).await? {
+///         println!("{}: {}", category, count);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub async fn top_categories(n: usize) -> Result<Vec<(String, i64)>> {
+    default_database().await?.top_categories(n).await
+}
+
+/// Fetches the exchanges with the most symbols
+///
+/// # Arguments
+///
+/// * `n` - maximum number of exchanges returned
+///
+/// # Returns
+///
+/// * `Vec<(String, i64)>` - exchange codes and symbol counts, largest first
+///
+/// # Example
+///
+/// ```
+/// use yahoo_finance_symbols::top_exchanges;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     for (exchange, count) in top_exchanges(5).await? {
+///         println!("{}: {}", exchange, count);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub async fn top_exchanges(n: usize) -> Result<Vec<(String, i64)>> {
+    default_database().await?.top_exchanges(n).await
+}
+
+/// Counts the symbols per first character of their ticker, to sanity-check a scrape
+///
+/// The scraper looks up every letter, digit and two-letter term, and Yahoo Finance caps how many
+/// results a lookup returns. A letter with suspiciously few symbols compared with an earlier
+/// database, or with its neighbours, hints that its lookups were truncated. Tickers are counted
+/// as stored, so lower-case or punctuation initials (e.g. `^` for indices) get their own entries;
+/// rows with an empty ticker are left out.
+///
+/// # Returns
+///
+/// * `HashMap<char, i64>` - number of symbols per initial character
+///
+/// # Example
+///
+/// ```
+/// use std::error::Error;
+/// use yahoo_finance_symbols::get_symbol_initial_distribution;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let distribution = get_symbol_initial_distribution().await?;
+///     for letter in 'A'..='Z' {
+///         println!("{}: {}", letter, distribution.get(&letter).unwrap_or(&0));
+///     }
+///     Ok(())
+/// }
+/// ```
+pub async fn get_symbol_initial_distribution() -> Result<HashMap<char, i64>> {
+    default_database().await?.get_symbol_initial_distribution().await
+}
+
+/// Fetches ticker symbols that closely match the specified query and asset class
+///
+/// # Cancellation
+///
+/// The search is cancel-safe, which suits type-ahead boxes that abandon the previous search on
+/// every keystroke. The query runs on a blocking thread, so the future never blocks the async
+/// runtime. Dropping it interrupts the query in SQLite rather than waiting for the scan to finish.
+/// The pooled connection then returns to the pool within milliseconds, and the drop itself never
+/// blocks. `search_count` and `search_lite` behave the same way.
+///
+/// # Result size
+///
+/// A short query can match most of the database, so at most `config::SearchConfig::max_results`
+/// matches are kept (`config::DEFAULT_MAX_SEARCH_RESULTS`, 1000, by default): the best-ranked in
+/// the order of `search_sorted`, which puts the exact ticker first. Raise the cap through
+/// `config::set_config`, or set it to 0 to return every match; the scan still reads every row,
+/// but only the kept matches are held in memory.
+///
+/// # Arguments
+///
+/// * `query` - ticker symbol query
+/// * `asset_class` - asset class (Equity, ETF, Mutual Fund, Index, Currency, Futures, Crypto, Options)
+///
+/// # Returns
+///
+/// * `HashMap<String, String>` - dictionary of ticker symbols and names
+///
+/// # Panics
+///
+/// Panics if `asset_class` is not one of the names listed above. Use `try_search_symbols`
+/// instead, which reports an unknown asset class as an error, or `search` to search every asset
+/// class.
+///
+/// # Example
+///
+/// ```
+/// #![allow(deprecated)]
+/// use yahoo_finance_symbols::search_symbols;
+/// use std::error::Error;
+/// 
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let symbols = search_symbols("Apple", "Equity").await?;
+///     println!("{:?}", symbols);
+///     Ok(())
+/// }
+/// ```
+#[deprecated(note = "panics on an unknown asset class; use `try_search_symbols`, which returns an error instead")]
+#[allow(deprecated)]
+pub async fn search_symbols(query: &str, asset_class: &str) -> Result<HashMap<String, String>> {
+    default_database().await?.search_symbols(query, asset_class).await
+}
+
+/// Fetches ticker symbols that closely match the specified query, in one asset class or in all of them
+///
+/// Searches like `search_symbols`, with the same matching, result cap and cancellation, but
+/// never panics: `None` searches every asset class, as `search` does, and an asset class name
+/// other than those of `search_symbols` fails with a `keys::ParseKeyError`.
+///
+/// # Arguments
+///
+/// * `query` - ticker symbol query
+/// * `asset_class` - asset class (Equity, ETF, Mutual Fund, Index, Currency, Futures, Crypto,
+///   Options), or `None` for every asset class
+///
+/// # Returns
+///
+/// * `HashMap<String, String>` - dictionary of ticker symbols and names
+///
+/// # Example
+///
+/// ```
+/// use yahoo_finance_symbols::try_search_symbols;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let equities = try_search_symbols("Apple", Some("Equity")).await?;
+///     let everything = try_search_symbols("Apple", None).await?;
+///     println!("{:?} {:?}", equities, everything);
+///     assert!(try_search_symbols("Apple", Some("Stocks")).await.is_err());
+///     Ok(())
+/// }
+/// ```
+pub async fn try_search_symbols(query: &str, asset_class: Option<&str>) -> std::result::Result<HashMap<String, String>, Box<dyn Error>> {
+    default_database().await?.try_search_symbols(query, asset_class).await
+}
+
+/// Fetches ticker symbols that closely match the specified query in every asset class
+///
+/// The default for search boxes that are not restricted to one asset class: equivalent to
+/// `try_search_symbols(query, None)`, which covers the asset classes not listed in
+/// `search_symbols` as well, and it never panics. Matching, the result cap and cancellation
+/// behave as in `search_symbols`.
+///
+/// # Arguments
+///
+/// * `query` - ticker symbol or name query
+///
+/// # Returns
+///
+/// * `HashMap<String, String>` - dictionary of ticker symbols and names
+///
+/// # Example
+///
+/// ```
+/// use yahoo_finance_symbols::search;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let symbols = search("Apple").await?;
+///     println!("{:?}", symbols);
+///     Ok(())
+/// }
+/// ```
+pub async fn search(query: &str) -> Result<HashMap<String, String>> {
+    default_database().await?.search(query).await
+}
+
+/// Counts ticker symbols whose symbol or name matches the specified query within an asset class
+///
+/// The match is a case-insensitive substring match, like `search_symbols`, but the counting
+/// happens in SQL so no rows are loaded. `%` and `_` in the query match themselves, not any
+/// characters. It counts the same rows as `search_sorted` and `search_stream`, which can differ
+/// from the size of the `search_symbols` result in three ways:
+///
+/// * a ticker listed under several asset classes counts once per listing, while `search_symbols`
+///   returns it once, so `AssetClass::All` and `AssetClass::Common` can count more;
+/// * names are matched as stored, so a query containing `&` may count an HTML entity in the
+///   name such as `&amp;` instead of the decoded character `search_symbols` matches on;
+/// * SQLite's `LIKE` only ignores the case of ASCII letters, so `"SOCIÉTÉ"` does not count
+///   "Société", which `search_symbols` matches.
+///
+/// `search_symbols` is also capped by `SearchConfig::max_results`, which this count is not.
+/// Dropping the future interrupts the count, as described for `search_symbols`.
+///
+/// # Arguments
+///
+/// * `query` - ticker symbol or name query
+/// * `asset_class` - Asset class enum
+///
+/// # Returns
+///
+/// * `i64` - number of matching symbols
+///
+/// # Example
+///
+/// ```
+/// use yahoo_finance_symbols::keys::AssetClass;
+/// use yahoo_finance_symbols::search_count;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let count = search_count("Apple", AssetClass::Stocks).await?;
+///     println!("{}", count);
+///     Ok(())
+/// }
+/// ```
+pub async fn search_count(query: &str, asset_class: AssetClass) -> Result<i64> {
+    default_database().await?.search_count(query, asset_class).await
+}
+
+/// Fetches the symbols matching a query as a list in a fixed order
+///
+/// Matches the same rows as `search_count` (a case-insensitive substring of the ticker or the
+/// name), returning full `Symbol`s instead of the unordered `HashMap` of `search_symbols`.
+/// The order only depends on the matching rows, so repeated calls, UIs and snapshot tests see the
+/// same list:
+///
+/// 1. the ticker equal to the query, ignoring case, if any;
+/// 2. then shorter tickers before longer ones;
+/// 3. then tickers of equal length alphabetically (byte order, so `BRK-A` sorts before `BRKA`);
+/// 4. and the listings of one ticker in several asset classes by asset class name.
+///
+/// Only the first `config::SearchConfig::max_results` symbols in that order are returned, as
+/// described for `search_symbols`; page through the rest with `search_after`. Dropping the
+/// future interrupts the search, as described for `search_symbols`.
+///
+/// # Arguments
+///
+/// * `query` - ticker symbol or name query
+/// * `asset_class` - Asset class enum
+///
+/// # Returns
+///
+/// * `Vec<Symbol>` - the matching symbols, in the order above
+///
+/// # Example
+///
+/// ```
+/// use yahoo_finance_symbols::keys::AssetClass;
+/// use yahoo_finance_symbols::search_sorted;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let symbols = search_sorted("Apple", AssetClass::Stocks).await?;
+///     for symbol in symbols.iter().take(10) {
+///         println!("{}: {}", symbol.symbol, symbol.name);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub async fn search_sorted(query: &str, asset_class: AssetClass) -> Result<Vec<Symbol>> {
+    default_database().await?.search_sorted(query, asset_class).await
+}
+
+/// Fetches the symbols matching a query, ranked lexically or by an estimate of popularity
+///
+/// `SearchRanking::Lexical` is the order of `search_sorted`. `SearchRanking::Popularity`
+/// approximates how widely followed a symbol is without any volume data, so that a prefix such as
+/// "AAP" surfaces Apple (`AAPL`, NASDAQ) above same-length microcaps such as `AAPI` (OTC):
+///
+/// 1. the ticker equal to the query, ignoring case, if any;
+/// 2. then tickers starting with the query before those that only match elsewhere or by name;
+/// 3. then by the tier of the listing exchange (`keys::exchange_tier`): NASDAQ and the NYSE,
+///    then the other US venues, then the main foreign primary exchanges, then the rest, and OTC
+///    markets last;
+/// 4. then shorter tickers before longer ones, alphabetically, and by asset class.
+///
+/// The heuristic is lexical and static; it knows nothing of market capitalisation or recent
+/// trading. Matching, the result cap and cancellation behave as in `search_symbols`.
+///
+/// # Arguments
+///
+/// * `query` - ticker symbol or name query
+/// * `asset_class` - Asset class enum
+/// * `ranking` - order of the results
+///
+/// # Returns
+///
+/// * `Vec<Symbol>` - the matching symbols, best first
+///
+/// # Example
+///
+/// ```
+/// use yahoo_finance_symbols::filter::SearchRanking;
+/// use yahoo_finance_symbols::keys::AssetClass;
+/// use yahoo_finance_symbols::search_ranked;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let symbols = search_ranked("AAP", AssetClass::All, SearchRanking::Popularity).await?;
+///     for symbol in symbols.iter().take(5) {
+///         println!("{}: {} ({})", symbol.symbol, symbol.name, symbol.exchange);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub async fn search_ranked(query: &str, asset_class: AssetClass, ranking: SearchRanking) -> Result<Vec<Symbol>> {
+    default_database().await?.search_ranked(query, asset_class, ranking).await
+}
+
+/// Fetches one page of the symbols matching a query, for paging through large result sets
+///
+/// Matches the same rows as `search_count`, ordered by ticker (byte order) and then by asset
+/// class. `None` starts from the first matching ticker; passing the ticker of the last symbol of
+/// a page as `after_symbol` returns the next page, i.e. the matching tickers greater than it.
+/// The cursor is a ticker rather than an offset, so each page is found with the index on `symbol`
+/// and rows added or removed in between do not shift later pages.
+///
+/// A ticker listed in several asset classes is never split across pages: when the last ticker of
+/// a page has more listings than fit within `limit`, they are all returned, so a page can be
+/// slightly longer than `limit`. A page shorter than `limit` is the last one.
+///
+/// # Arguments
+///
+/// * `query` - ticker symbol or name query
+/// * `asset_class` - Asset class enum
+/// * `after_symbol` - ticker of the last symbol of the previous page, or `None` for the first page
+/// * `limit` - number of symbols per page
+///
+/// # Returns
+///
+/// * `Vec<Symbol>` - the page, in the order above
+///
+/// # Example
+///
+/// ```
+/// use yahoo_finance_symbols::keys::AssetClass;
+/// use yahoo_finance_symbols::search_after;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let mut after = None;
+///     loop {
+///         let page = search_after("Apple", AssetClass::Stocks, after.as_deref(), 100).await?;
+///         for symbol in &page {
+///             println!("{}: {}", symbol.symbol, symbol.name);
+///         }
+///         if page.len() < 100 {
+///             break;
+///         }
+///         after = page.last().map(|symbol| symbol.symbol.clone());
+///     }
+///     Ok(())
+/// }
+/// ```
+pub async fn search_after(query: &str, asset_class: AssetClass, after_symbol: Option<&str>, limit: usize) -> Result<Vec<Symbol>> {
+    default_database().await?.search_after(query, asset_class, after_symbol, limit).await
+}
+
+/// Matches of a search grouped by where the query was found, as returned by `search_split`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchSplit {
+    /// Symbols whose ticker contains the query
+    pub by_symbol: Vec<Symbol>,
+    /// Symbols whose name contains the query but whose ticker does not
+    pub by_name: Vec<Symbol>,
+}
+
+/// Fetches the symbols matching a query in two groups: ticker matches and name matches
+///
+/// For search interfaces that list "Symbol matches" above "Name matches" instead of one blended
+/// list. Each group is its own SQL query, capped at `limit` rows. `by_symbol` holds the tickers
+/// containing the query, case-insensitively, in the order of `search_sorted`: the exact ticker
+/// first, then shorter tickers, then alphabetically. `by_name` holds the symbols whose name
+/// contains the query but whose ticker does not, names starting with the query first, then by
+/// name. A row matching on both therefore only appears in `by_symbol`, even when that group is
+/// cut short by `limit`. `SearchConfig::max_results` does not apply.
+///
+/// # Arguments
+///
+/// * `query` - text to look for in tickers and names
+/// * `asset_class` - Asset class enum
+/// * `limit` - most symbols returned in each group
+///
+/// # Returns
+///
+/// * `SearchSplit` - the ticker matches and the name matches
+///
+/// # Example
+///
+/// ```
+/// use yahoo_finance_symbols::keys::AssetClass;
+/// use yahoo_finance_symbols::search_split;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let matches = search_split("AAPL", AssetClass::All, 10).await?;
+///     println!("Symbol matches:");
+///     for symbol in &matches.by_symbol {
+///         println!("  {}: {}", symbol.symbol, symbol.name);
+///     }
+///     println!("Name matches:");
+///     for symbol in &matches.by_name {
+///         println!("  {}: {}", symbol.symbol, symbol.name);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub async fn search_split(query: &str, asset_class: AssetClass, limit: usize) -> Result<SearchSplit> {
+    default_database().await?.search_split(query, asset_class, limit).await
+}
+
+/// Fetches the symbols matching a query into one buffer, without allocating per row
+///
+/// Matches the same rows as `search`, restricted to `asset_class`, but returns every matching
+/// row, so a ticker listed in several asset classes appears once per listing. Rows keep table
+/// order. As for `search`, at most `config::SearchConfig::max_results` rows are kept, the
+/// best-ranked ones. Where `search` allocates the strings of every row it scans and of every match it keeps,
+/// this reads candidates straight from SQLite and copies only the matches, into the single text
+/// buffer of `SearchResults`. That makes a difference when a short query matches tens of
+/// thousands of rows.
+///
+/// The `SymbolRef`s it yields borrow from the returned `SearchResults` and cannot outlive it;
+/// convert the ones to keep with `SymbolRef::to_symbol`. Dropping the future interrupts the
+/// search, as described for `search_symbols`.
+///
+/// # Arguments
+///
+/// * `query` - ticker symbol or name query
+/// * `asset_class` - Asset class enum
+///
+/// # Returns
+///
+/// * `SearchResults` - the matching rows
+///
+/// # Example
+///
+/// ```
+/// use yahoo_finance_symbols::keys::AssetClass;
+/// use yahoo_finance_symbols::search_refs;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let results = search_refs("Bank", AssetClass::Stocks).await?;
+///     let on_nyse = results.iter().filter(|symbol| symbol.exchange == "NYQ").count();
+///     println!("{} of {} matches trade on the NYSE", on_nyse, results.len());
+///     Ok(())
+/// }
+/// ```
+pub async fn search_refs(query: &str, asset_class: AssetClass) -> Result<SearchResults> {
+    default_database().await?.search_refs(query, asset_class).await
+}
+
+/// Fetches the ticker and name of symbols matching a query, for autocomplete
+///
+/// Matches the same rows as `search_count` (a case-insensitive substring of the ticker or the
+/// name), but only the `symbol` and `name` columns are read. Results are ranked for completion:
+/// an exact ticker match first, then tickers starting with the query, then the rest, shorter
+/// tickers before longer ones. Like `search_symbols`, it is cancel-safe, so a type-ahead box can
+/// drop the search for the previous keystroke.
+///
+/// # Arguments
+///
+/// * `query` - ticker symbol or name query
+/// * `asset_class` - Asset class enum
+/// * `limit` - maximum number of results
+///
+/// # Returns
+///
+/// * `Vec<SymbolLite>` - up to `limit` matching tickers and names
+///
+/// # Example
+///
+/// ```
+/// use yahoo_finance_symbols::keys::AssetClass;
+/// use yahoo_finance_symbols::search_lite;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let completions = search_lite("AAP", AssetClass::Stocks, 10).await?;
+///     println!("{:?}", completions);
+///     Ok(())
+/// }
+/// ```
+pub async fn search_lite(query: &str, asset_class: AssetClass, limit: usize) -> Result<Vec<SymbolLite>> {
+    default_database().await?.search_lite(query, asset_class, limit).await
+}
+
+/// Fetches the symbols added by the last database update
+///
+/// `update_database` and `update_database_from` compare the rebuilt database with the one it
+/// replaces and record the rows (`(symbol, asset_class)` pairs) that were not there before, e.g.
+/// new listings. They stay recorded until the next update. A database that was built by the first
+/// update on a machine counts every row as added, and one never updated here (such as the
+/// downloaded copy) has no record, so this returns no rows for it.
+///
+/// # Returns
+///
+/// * `Vec<Symbol>` - the added symbols, in table order
+///
+/// # Example
+///
+/// ```
+/// use yahoo_finance_symbols::get_new_symbols;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     for symbol in get_new_symbols().await? {
+///         println!("New listing: {} ({})", symbol.symbol, symbol.name);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub async fn get_new_symbols() -> Result<Vec<Symbol>> {
+    default_database().await?.get_new_symbols().await
+}
+
+/// Fetches the symbols added by the last database update into a Polars DataFrame
+///
+/// The rows of `get_new_symbols`, with the columns and dtypes of `get_symbols_df`.
+///
+/// # Returns
+///
+/// * `DataFrame` - Polars DataFrame of the added symbols
+///
+/// # Example
+///
+/// ```
+/// use yahoo_finance_symbols::get_new_symbols_df;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let new_listings = get_new_symbols_df().await?;
+///     println!("{}", new_listings);
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "polars")]
+pub async fn get_new_symbols_df() -> Result<DataFrame, Box<dyn Error>> {
+    default_database().await?.get_new_symbols_df().await
+}
+
+/// Fetches all Symbols into a Polars DataFrame
+///
+/// `symbol` and `name` are `String` columns. `category`, `asset_class` and `exchange` are
+/// `Categorical`: each holds only a few hundred distinct values across all rows, so storing them
+/// as codes into a shared dictionary saves most of their memory and speeds up group-bys. The
+/// dictionary is Polars' global string cache, which is only kept while someone holds it: to join
+/// or stack the categorical columns of two DataFrames, build both under one
+/// `polars::prelude::StringCacheHolder`, or enable the cache for the whole process with
+/// `polars::enable_string_cache()`. Cast them back with `.cast(&DataType::String)` where plain
+/// strings are needed.
+/// 
+/// # Returns
+/// 
+/// * `DataFrame` - Polars DataFrame of all Yahoo Finance Symbols
+/// 
+/// # Example
+/// 
+/// ```
+/// use yahoo_finance_symbols::get_symbols_df;
+/// use std::error::Error;
+/// 
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let symbols_df = get_symbols_df().await?;
+///     println!("{:?}", symbols_df);
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "polars")]
+pub async fn get_symbols_df() -> Result<DataFrame, Box<dyn Error>> {
+    default_database().await?.get_symbols_df().await
+}
+
+/// Fetches all Symbols into a Polars DataFrame, reading the table in chunks to bound peak memory
+///
+/// Rows are streamed from a single query and only `chunk_rows` of them are held as `Symbol`s at a
+/// time; each chunk is converted to a small DataFrame and stacked onto the result. The resulting
+/// DataFrame has the same rows as `get_symbols_df`, but is made of several memory chunks. This is
+/// slightly slower than `get_symbols_df` but avoids holding the full `Vec<Symbol>` next to the frame.
+///
+/// # Arguments
+///
+/// * `chunk_rows` - number of rows read per chunk (a value of 0 is treated as 1)
+///
+/// # Returns
+///
+/// * `DataFrame` - Polars DataFrame of all Yahoo Finance Symbols
+///
+/// # Example
+///
+/// ```
+/// use yahoo_finance_symbols::get_symbols_df_chunked;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let symbols_df = get_symbols_df_chunked(50_000).await?;
+///     println!("{:?}", symbols_df);
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "polars")]
+pub async fn get_symbols_df_chunked(chunk_rows: usize) -> Result<DataFrame, Box<dyn Error>> {
+    default_database().await?.get_symbols_df_chunked(chunk_rows).await
+}
+
+/// Fetches all Symbols into an Arrow `RecordBatch`
+///
+/// For handing the symbol universe to Arrow-based systems, such as DataFusion or PyArrow through
+/// the Arrow C data interface, without going through Polars. `symbol` and `name` are `Utf8`
+/// columns; `category`, `asset_class` and `exchange` are dictionary-encoded. The columns are laid
+/// out as `arrow::symbols_schema()` describes, which is also the schema of the batch; see the
+/// [`arrow`](crate::arrow) module.
+///
+/// # Returns
+///
+/// * `RecordBatch` - one row per symbol, with the five columns of `Symbol`
+///
+/// # Example
+///
+/// ```
+/// use yahoo_finance_symbols::arrow::arrow_array::Array;
+/// use yahoo_finance_symbols::get_symbols_arrow;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let batch = get_symbols_arrow().await?;
+///     for (field, array) in batch.schema().fields().iter().zip(batch.columns()) {
+///         println!("{}: {} rows of {:?}", field.name(), array.len(), field.data_type());
+///     }
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "arrow")]
+pub async fn get_symbols_arrow() -> Result<crate::arrow::RecordBatch, Box<dyn Error>> {
+    default_database().await?.get_symbols_arrow().await
+}
+
+/// Reclaims the space left behind by deleted or replaced rows
+///
+/// Runs SQLite's `VACUUM`, which rewrites the database file without its free pages. Call it after
+/// updates or imports to keep the file lean, e.g. before redistributing it. `VACUUM` requires
+/// exclusive access to the database, so run it when no other queries are in flight.
+///
+/// # Example
+///
+/// ```no_run
+/// use yahoo_finance_symbols::{compact_database, import_symbols_from_csv};
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     import_symbols_from_csv("my_symbols.csv").await?;
+///     compact_database().await?;
+///     Ok(())
+/// }
+/// ```
+pub async fn compact_database() -> Result<()> {
+    default_database().await?.compact_database().await
+}
+
+/// Exports a clean copy of the database for others to download
+///
+/// Writes a VACUUMed copy with the filter indexes and a `meta` table holding `last_updated` to
+/// `path`, and its SHA-256 to `<path>.sha256`; see the [`export`] module for the file formats.
+/// Hosting both files and pointing `DatabaseConfig::download_url` at the copy gives exactly what
+/// the first-run download expects, including checksum verification.
+///
+/// # Arguments
+///
+/// * `path` - where to write the copy; an existing file is replaced
+///
+/// # Example
+///
+/// ```no_run
+/// use yahoo_finance_symbols::export_database;
+/// use std::error::Error;
+/// use std::path::Path;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     export_database(Path::new("dist/symbols.db")).await?;
+///     Ok(())
+/// }
+/// ```
+pub async fn export_database(path: &Path) -> Result<(), Box<dyn Error>> {
+    default_database().await?.export_database(path).await
+}
+
+/// Dumps the whole symbols table to a JSON file
+///
+/// The file is a single array of `Symbol` objects, readable by any JSON tool and reloadable with
+/// `load_json`, which makes it a backup independent of the SQLite file format.
+///
+/// # Arguments
+///
+/// * `path` - where to write the JSON; an existing file is replaced
+///
+/// # Returns
+///
+/// * `usize` - number of symbols written
+///
+/// # Example
+///
+/// ```no_run
+/// use yahoo_finance_symbols::dump_json;
+/// use std::error::Error;
+/// use std::path::Path;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let count = dump_json(Path::new("symbols.json")).await?;
+///     println!("Backed up {} symbols", count);
+///     Ok(())
+/// }
+/// ```
+pub async fn dump_json(path: &Path) -> Result<usize, Box<dyn Error>> {
+    default_database().await?.dump_json(path).await
+}
+
+/// Rebuilds the symbols table from a JSON file written by `dump_json`
+///
+/// Every existing row is replaced by the contents of the file, in a single transaction. The file
+/// is parsed first, so a malformed file leaves the database untouched. Restored rows get the
+/// default `source`, since `Symbol` does not record whether a row was user-imported.
+///
+/// # Arguments
+///
+/// * `path` - JSON array of `Symbol` objects
+///
+/// # Returns
+///
+/// * `usize` - number of symbols loaded
+///
+/// # Example
+///
+/// ```no_run
+/// use yahoo_finance_symbols::load_json;
+/// use std::error::Error;
+/// use std::path::Path;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let count = load_json(Path::new("symbols.json")).await?;
+///     println!("Restored {} symbols", count);
+///     Ok(())
+/// }
+/// ```
+pub async fn load_json(path: &Path) -> Result<usize, Box<dyn Error>> {
+    default_database().await?.load_json(path).await
+}
+
+/// Checks that the symbols database is present, non-empty and complete
+///
+/// # Returns
+///
+/// * `VerifyReport` - pass/fail status with total and per asset class counts, plus any anomalies found
+///
+/// The thresholds used are documented in [`verify::MIN_TOTAL_SYMBOLS`](crate::verify::MIN_TOTAL_SYMBOLS) and [`verify::MIN_ASSET_CLASS_SYMBOLS`](crate::verify::MIN_ASSET_CLASS_SYMBOLS).
+///
+/// # Example
+///
+/// ```
+/// use yahoo_finance_symbols::verify_database;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let report = verify_database().await?;
+///     println!("{:?}", report);
+///     assert!(report.passed);
+///     Ok(())
+/// }
+/// ```
+pub async fn verify_database() -> Result<VerifyReport> {
+    default_database().await?.verify_database().await
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use crate::keys::{exchange_market_hours, exchange_tier, AssetClass, Category, Exchange, MarketTime, ParseKeyError, SymbolKind, DEFAULT_EXCHANGE_TIER, NA_CATEGORY};
+    use futures::StreamExt;
+    use std::collections::{HashMap, HashSet};
+    use std::path::PathBuf;
+    #[cfg(feature = "download")]
+    use std::sync::atomic::{AtomicBool, Ordering};
+    #[cfg(feature = "download")]
+    use std::sync::{Arc, Mutex};
+    #[cfg(feature = "download")]
+    use std::time::Duration;
+    #[cfg(feature = "download")]
+    use crate::test_util::{bytes_response, html_response, serve_with};
+    use rusqlite::Connection;
+    use crate::import::import_into;
+    use crate::scraper::create_symbols_table;
+    use tokio::sync::OnceCell;
+    use crate::config::{get_config, DatabaseConfig, DEFAULT_MAX_SEARCH_RESULTS};
+    use crate::database::{SymbolDatabase, POOL_RETRY_ATTEMPTS};
+    use crate::schema::SCHEMA_VERSION;
+    #[cfg(feature = "polars")]
+    use crate::{get_all_symbols, get_symbols_df, get_symbols_df_chunked};
+    #[cfg(feature = "arrow")]
+    use crate::{arrow::symbols_schema, get_symbols_arrow};
+    use crate::filter::{Column, SearchRanking, SortDir, SymbolFilter, SymbolQuery};
+    #[cfg(feature = "download")]
+    use crate::{ensure_database, run_blocking, DatabaseStatus};
+    #[cfg(feature = "download")]
+    use super::{
+        ensure_database_from, provision_while_scraping, refresh_database_in, replace_database, report_download_failure,
+        report_download_failure_to,
+    };
+    use super::{database_ready, load_from_bytes_in, open_or_provision, rebuild_database, use_existing_database_in};
+    use crate::{
+        default_database, describe_database, escape_glob,
+        get_categorized_symbols, get_distinct_asset_classes, get_distinct_categories, get_distinct_exchanges,
+        get_filter_options, get_random_symbol, get_random_symbols, get_schema_version,
+        get_symbol, get_symbol_initial_distribution, get_symbols, get_symbols_by_exchange_prefix,
+        get_symbols_count, get_symbols_filtered, get_symbols_many, get_symbols_matching,
+        get_symbols_with_name, get_tickers, get_uncategorized_count, is_ready, lookup,
+        migrate, query_symbols, search, search_after, search_count,
+        search_lite, search_ranked, search_refs, search_sorted, search_split, search_stream, suggest_symbols,
+        symbol_exists, symbols_to_csv_string, top_categories, top_exchanges, try_search_symbols,
+        verify_database, zip_prices, EXPECTED_MIN_ASSET_CLASS_SYMBOLS,
+        EXPECTED_MIN_SYMBOLS, Symbol, YahooSymbolsError,
+    };
+
+    #[tokio::test]
+    async fn check_symbols_count() {
+        let symbols_count = get_symbols_count().await.unwrap();
+        println!("{}", symbols_count);
+
+        #[cfg(feature = "polars")]
+        println!("{:?}", get_symbols_df().await.unwrap());
+
+        assert!(symbols_count >= EXPECTED_MIN_SYMBOLS);
+    }
+
+    #[cfg(feature = "arrow")]
+    #[tokio::test]
+    async fn check_get_symbols_arrow() {
+        let batch = get_symbols_arrow().await.unwrap();
+        assert_eq!(batch.num_rows() as i64, get_symbols_count().await.unwrap());
+
+        let schema = batch.schema();
+        assert_eq!(schema, symbols_schema());
+        let columns: Vec<&str> = schema.fields().iter().map(|field| field.name().as_str()).collect();
+        assert_eq!(columns, ["symbol", "name", "category", "asset_class", "exchange"]);
+    }
+
+    #[tokio::test]
+    async fn check_describe_database() {
+        let description = describe_database().await.unwrap();
+        let symbols = description.table("symbols").unwrap();
+        assert_eq!(symbols.row_count, get_symbols_count().await.unwrap());
+        assert!(symbols.columns.iter().any(|c| c.name == "isin"));
+        assert_eq!(description.schema_version, SCHEMA_VERSION);
+    }
+
+    #[tokio::test]
+    async fn check_verify_database() {
+        let report = verify_database().await.unwrap();
+        assert!(report.passed);
+        assert!(report.total_count >= EXPECTED_MIN_SYMBOLS);
+        for (asset_class, min_count) in EXPECTED_MIN_ASSET_CLASS_SYMBOLS {
+            assert!(report.asset_class_counts[*asset_class] >= *min_count);
+        }
+    }
+
+    #[tokio::test]
+    async fn check_search_count() {
+        let count = search_count("Apple", AssetClass::Stocks).await.unwrap();
+        let symbols = try_search_symbols("Apple", Some("Equity")).await.unwrap();
+        assert!(count > 0);
+        assert_eq!(count as usize, symbols.len());
+    }
+
+    #[tokio::test]
+    async fn check_category_filters() {
+        let technology = get_symbols(AssetClass::Stocks, Category::Technology, Exchange::All).await.unwrap();
+        assert!(!technology.is_empty());
+        assert!(technology.iter().all(|s| s.category == "Technology"));
+
+        let etfs = get_symbols(AssetClass::ETFs, Category::All, Exchange::All).await.unwrap();
+        let etfs_technology = get_symbols(AssetClass::ETFs, Category::Technology, Exchange::All).await.unwrap();
+        assert!(!etfs.is_empty());
+        assert_eq!(etfs.len(), etfs_technology.len());
+
+        let futures = get_symbols(AssetClass::Futures, Category::Energy, Exchange::All).await.unwrap();
+        assert!(futures.iter().all(|s| s.asset_class == "Future"));
+        assert!(!futures.is_empty());
+
+        // Under `All` the category applies to stocks and does not drop the uncategorized asset classes
+        assert!(AssetClass::All.has_categories());
+        let all_technology = get_symbols(AssetClass::All, Category::Technology, Exchange::NYSEArca).await.unwrap();
+        assert!(all_technology.iter().any(|s| s.asset_class == "ETF"));
+        let all_technology = get_symbols(AssetClass::All, Category::Technology, Exchange::NASDAQ).await.unwrap();
+        let stocks: Vec<&Symbol> = all_technology.iter().filter(|s| s.asset_class == "Stocks").collect();
+        assert!(!stocks.is_empty());
+        assert!(stocks.iter().all(|s| s.category == "Technology"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn check_get_symbols_many() {
+        let results = get_symbols_many(vec![
+            (AssetClass::Stocks, Category::Technology, Exchange::NASDAQ),
+            (AssetClass::ETFs, Category::All, Exchange::NYSEArca),
+            (AssetClass::Futures, Category::All, Exchange::All),
+        ]).await.unwrap();
+
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|r| !r.is_empty()));
+        assert!(results[0].iter().all(|s| s.asset_class == "Stocks" && s.category == "Technology" && s.exchange == "NMS"));
+        assert!(results[1].iter().all(|s| s.asset_class == "ETF" && s.exchange == "PCX"));
+        assert!(results[2].iter().all(|s| s.asset_class == "Future"));
+    }
+
+    #[tokio::test]
+    async fn check_symbol_enums() {
+        let symbol = get_symbol("AAPL").await.unwrap();
+        assert_eq!(symbol.asset_class_enum(), Some(AssetClass::Stocks));
+        assert_eq!(symbol.exchange_enum(), Some(Exchange::NASDAQ));
+
+        let stocks = get_symbols(symbol.asset_class_enum().unwrap(), Category::Technology, symbol.exchange_enum().unwrap()).await.unwrap();
+        assert!(stocks.iter().any(|s| s.symbol == "AAPL"));
+
+        let unknown = Symbol { asset_class: "Unknown".to_string(), exchange: "???".to_string(), ..Symbol::new() };
+        assert_eq!(unknown.asset_class_enum(), None);
+        assert_eq!(unknown.exchange_enum(), None);
+    }
+
+    #[tokio::test]
+    async fn check_common_asset_classes() {
+        assert_eq!(AssetClass::Common.to_string_vec().await, vec!["Stocks", "ETF", "Mutual Fund", "Index"]);
+        assert!(AssetClass::Common.has_categories());
+
+        assert!(search_count("=F", AssetClass::Futures).await.unwrap() > 0);
+        assert_eq!(search_count("=F", AssetClass::Common).await.unwrap(), 0);
+        let futures = get_tickers(AssetClass::Futures, Category::All, Exchange::All).await.unwrap();
+        let common: HashSet<String> = get_tickers(AssetClass::Common, Category::All, Exchange::All).await.unwrap().into_iter().collect();
+        assert!(common.contains("AAPL") && common.contains("SPY"));
+        assert!(futures.iter().filter(|ticker| ticker.ends_with("=F")).all(|ticker| !common.contains(ticker)));
+
+        // A category only filters the stocks of `Common`, not its uncategorized classes
+        let technology = get_symbols(AssetClass::Common, Category::Technology, Exchange::NASDAQ).await.unwrap();
+        let classes: HashSet<&str> = technology.iter().map(|s| s.asset_class.as_str()).collect();
+        assert!(["Stocks", "ETF", "Mutual Fund", "Index"].iter().all(|class| classes.contains(class)), "{:?}", classes);
+        assert!(technology.iter().filter(|s| s.asset_class == "Stocks").all(|s| s.category == "Technology"));
+    }
+
+    #[test]
+    fn check_symbols_to_csv_string() {
+        let symbol = |symbol: &str, name: &str| Symbol {
+            symbol: symbol.to_string(),
+            name: name.to_string(),
+            category: "N/A".to_string(),
+            asset_class: "Stocks".to_string(),
+            exchange: "NYQ".to_string(),
+        };
+        let symbols = [symbol("T", "AT&T, Inc."), symbol("Q", "The \"Quoted\" Company"), symbol("AAPL", "Apple Inc.")];
+        let csv = symbols_to_csv_string(&symbols);
+        assert_eq!(
+            csv.lines().collect::<Vec<_>>(),
+            vec![
+                "symbol,name,category,asset_class,exchange",
+                "T,\"AT&T, Inc.\",N/A,Stocks,NYQ",
+                "Q,\"The \"\"Quoted\"\" Company\",N/A,Stocks,NYQ",
+                "AAPL,Apple Inc.,N/A,Stocks,NYQ",
+            ]
+        );
+        assert_eq!(csv.matches("\r\n").count(), 4);
+
+        let parsed = crate::import::symbols_from_csv(&csv).unwrap();
+        let names: Vec<&str> = parsed.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["AT&T, Inc.", "The \"Quoted\" Company", "Apple Inc."]);
+        assert_eq!(symbols_to_csv_string(&[]), "symbol,name,category,asset_class,exchange\r\n");
+    }
+
+    #[test]
+    fn check_symbol_kind() {
+        let kind = |symbol: &str, asset_class: &str| {
+            Symbol { symbol: symbol.to_string(), asset_class: asset_class.to_string(), ..Symbol::new() }.kind()
+        };
+        assert_eq!(kind("AAPL", "Stocks"), SymbolKind::Equity);
+        assert_eq!(kind("SPY", "ETF"), SymbolKind::Etf);
+        assert_eq!(kind("VFIAX", "Mutual Fund"), SymbolKind::MutualFund);
+        assert_eq!(kind("^GSPC", "Index"), SymbolKind::Index);
+        assert_eq!(kind("EURUSD=X", "Currency"), SymbolKind::Currency);
+        assert_eq!(kind("ES=F", "Future"), SymbolKind::Future);
+        assert_eq!(kind("ZCZ24.CBT", "Future"), SymbolKind::Future);
+        assert_eq!(kind("BTC-USD", "CRYPTOCURRENCY"), SymbolKind::Crypto);
+        assert_eq!(kind("AAPL240119C00150000", "Option"), SymbolKind::Option);
+        assert_eq!(kind("SPX240315P04500000", ""), SymbolKind::Option);
+        assert_eq!(kind("XYZ", "Warrant"), SymbolKind::Unknown);
+
+        // Ticker conventions win over the tag of the lookup page
+        assert_eq!(kind("USDJPY=X", "Stocks"), SymbolKind::Currency);
+        assert_eq!(kind("GC=F", "ETF"), SymbolKind::Future);
+        assert_eq!(kind("^SMH", "ETF"), SymbolKind::Index);
+        // But not look-alikes
+        assert_eq!(kind("USDP", "Stocks"), SymbolKind::Equity);
+        assert_eq!(kind("HASH-USD.AS", "Stocks"), SymbolKind::Equity);
+        assert_eq!(kind("C00150000", "Stocks"), SymbolKind::Equity);
+    }
+
+    #[test]
+    fn check_exchange_display_name() {
+        let on = |exchange: &str| Symbol { exchange: exchange.to_string(), ..Symbol::new() };
+        assert_eq!(on("NMS").exchange_display_name(), "NASDAQ");
+        assert_eq!(on("NYQ").exchange_display_name(), "NYSE");
+        assert_eq!(on("PCX").exchange_display_name(), "NYSE Arca");
+        assert_eq!(on("LSE").exchange_display_name(), "London Stock Exchange");
+        assert_eq!(on("XYZ").exchange_display_name(), "XYZ");
+    }
+
+    #[test]
+    fn check_exchange_market_hours() {
+        let nyse = exchange_market_hours("NYQ").unwrap();
+        assert_eq!((nyse.open, nyse.close, nyse.timezone), (MarketTime::new(9, 30), MarketTime::new(16, 0), "America/New_York"));
+        assert_eq!(exchange_market_hours("NMS").unwrap(), nyse);
+        assert!(nyse.is_open_at(MarketTime::new(9, 30)));
+        assert!(!nyse.is_open_at(MarketTime::new(9, 29)));
+        assert!(!nyse.is_open_at(MarketTime::new(16, 0)));
+
+        let lse = exchange_market_hours("LSE").unwrap();
+        assert_eq!((lse.open, lse.close, lse.timezone), (MarketTime::new(8, 0), MarketTime::new(16, 30), "Europe/London"));
+        assert_eq!(lse.close.to_string(), "16:30");
+        assert!(lse.is_open_at(MarketTime::new(12, 0)));
+
+        assert!(exchange_market_hours("CCC").is_none());
+        assert!(exchange_market_hours("XYZ").is_none());
+    }
+
+    #[cfg(feature = "polars")]
+    #[tokio::test]
+    async fn check_symbols_df_chunked() {
+        // Categoricals only compare equal when built under the same string cache
+        let _cache = polars::prelude::StringCacheHolder::hold();
+        let symbols_df = get_symbols_df().await.unwrap();
+        let chunked_df = get_symbols_df_chunked(100_000).await.unwrap();
+        assert_eq!(symbols_df.shape(), chunked_df.shape());
+        assert!(symbols_df.equals(&chunked_df));
+    }
+
+    #[cfg(feature = "polars")]
+    #[tokio::test]
+    async fn check_symbols_df_dtypes() {
+        use polars::prelude::{DataType, StringCacheHolder};
+
+        // Held across both builds so that their categorical codes agree
+        let _cache = StringCacheHolder::hold();
+        let symbols_df = get_symbols_df().await.unwrap();
+        let chunked_df = get_symbols_df_chunked(100_000).await.unwrap();
+        for df in [&symbols_df, &chunked_df] {
+            let dtypes: Vec<DataType> = df.dtypes();
+            assert_eq!(dtypes[0], DataType::String);
+            assert_eq!(dtypes[1], DataType::String);
+            assert!(dtypes[2..].iter().all(|dtype| matches!(dtype, DataType::Categorical(..))), "{:?}", dtypes);
+        }
+
+        let symbols = get_all_symbols().await.unwrap();
+        for (column, expected) in [
+            ("category", symbols.iter().map(|s| s.category.as_str()).collect::<Vec<_>>()),
+            ("asset_class", symbols.iter().map(|s| s.asset_class.as_str()).collect()),
+            ("exchange", symbols.iter().map(|s| s.exchange.as_str()).collect()),
+        ] {
+            for df in [&symbols_df, &chunked_df] {
+                let values = df.column(column).unwrap().cast(&DataType::String).unwrap();
+                let values: Vec<&str> = values.str().unwrap().into_no_null_iter().collect();
+                assert_eq!(values, expected, "{}", column);
+            }
+        }
+
+        let stacked = symbols_df.vstack(&chunked_df).unwrap();
+        assert_eq!(stacked.height(), 2 * symbols.len());
+    }
+
+    #[tokio::test]
+    async fn check_categorized_symbols() {
+        let categorized = get_categorized_symbols(AssetClass::All).await.unwrap();
+        assert!(!categorized.is_empty());
+        assert!(categorized.iter().all(|s| !s.category.is_empty() && s.category != NA_CATEGORY));
+
+        let stocks = get_categorized_symbols(AssetClass::Stocks).await.unwrap();
+        assert_eq!(stocks.len(), categorized.len());
+        assert!(stocks.iter().any(|s| s.symbol == "AAPL"));
+        assert!(get_categorized_symbols(AssetClass::ETFs).await.unwrap().is_empty());
+
+        let uncategorized = get_uncategorized_count().await.unwrap();
+        assert!(uncategorized > 0);
+        assert_eq!(uncategorized + categorized.len() as i64, get_symbols_count().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn check_symbol_exists() {
+        assert!(symbol_exists("AAPL").await.unwrap());
+        assert!(!symbol_exists("NOT-A-REAL-TICKER").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn check_random_symbol() {
+        let symbol = get_random_symbol().await.unwrap();
+        assert!(!symbol.symbol.is_empty());
+        assert_eq!(get_symbol(&symbol.symbol).await.unwrap().symbol, symbol.symbol);
+
+        let sample = get_random_symbols(10).await.unwrap();
+        assert_eq!(sample.len(), 10);
+        let distinct: std::collections::HashSet<&str> = sample.iter().map(|s| s.symbol.as_str()).collect();
+        assert_eq!(distinct.len(), 10);
+    }
+
+    #[tokio::test]
+    async fn check_suggest_symbols() {
+        let suggestions = suggest_symbols("APPL", 5).await.unwrap();
+        assert_eq!(suggestions.len(), 5);
+        assert!(suggestions.iter().any(|s| s.symbol == "AAPL"));
+
+        let suggestions = suggest_symbols("aapl", 1).await.unwrap();
+        assert_eq!(suggestions[0].symbol, "AAPL");
+        assert!(suggest_symbols("", 5).await.unwrap().is_empty());
+    }
+
+    #[cfg(feature = "polars")]
+    #[tokio::test]
+    async fn check_new_symbols_df() {
+        let db_path = std::env::temp_dir().join(format!("yahoo-finance-symbols-new-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db_path);
+
+        let fake_scrape = |rows: &'static str| {
+            move |path: PathBuf| async move {
+                let conn = Connection::open(&path)?;
+                create_symbols_table(&conn)?;
+                conn.execute(&format!("INSERT INTO symbols (symbol, name, category, asset_class, exchange) VALUES {}", rows), [])?;
+                Ok(())
+            }
+        };
+
+        rebuild_database(&db_path, fake_scrape("('AAPL', 'Apple Inc.', 'Technology', 'Stocks', 'NMS')")).await.unwrap();
+        assert_eq!(SymbolDatabase::open(&db_path).unwrap().get_new_symbols().await.unwrap().len(), 1);
+
+        rebuild_database(
+            &db_path,
+            fake_scrape(
+                "('AAPL', 'Apple Inc.', 'Technology', 'Stocks', 'NMS'), ('NVDA', 'NVIDIA Corporation', 'Technology', 'Stocks', 'NMS'),
+                 ('AAPL', 'Apple Inc.', 'N/A', 'ETF', 'NMS')",
+            ),
+        )
+        .await
+        .unwrap();
+        let db = SymbolDatabase::open(&db_path).unwrap();
+        let new_symbols = db.get_new_symbols_df().await.unwrap();
+        assert_eq!(new_symbols.height(), 2);
+        let symbols: Vec<Option<&str>> = new_symbols.column("symbol").unwrap().str().unwrap().into_iter().collect();
+        assert_eq!(symbols, vec![Some("NVDA"), Some("AAPL")]);
+        assert!(matches!(new_symbols.column("asset_class").unwrap().dtype(), polars::prelude::DataType::Categorical(..)));
+        assert!(!std::path::Path::new(&format!("{}.previous", db_path.display())).exists());
+
+        drop(db);
+        std::fs::remove_file(&db_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_update_preserves_user_symbols() {
+        let db_path = std::env::temp_dir().join(format!("yahoo-finance-symbols-update-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db_path);
+
+        let fake_scrape = |path: PathBuf| {
+            async move {
+                let conn = Connection::open(&path)?;
+                create_symbols_table(&conn)?;
+                conn.execute("INSERT OR IGNORE INTO symbols (symbol, name, category, asset_class, exchange) VALUES ('AAPL', 'Apple Inc.', 'Technology', 'Stocks', 'NMS')", [])?;
+                Ok(())
+            }
+        };
+
+        rebuild_database(&db_path, fake_scrape).await.unwrap();
+        {
+            let conn = Connection::open(&db_path).unwrap();
+            let user = Symbol { symbol: "MYCO".to_string(), name: "My Company".to_string(), ..Symbol::new() };
+            import_into(&conn, &[user]).unwrap();
+            crate::import::set_isins(&conn, &HashMap::from([("AAPL".to_string(), "US0378331005".to_string())])).unwrap();
+        }
+
+        rebuild_database(&db_path, fake_scrape).await.unwrap();
+        let conn = Connection::open(&db_path).unwrap();
+        assert_eq!(crate::import::read_isins(&conn).unwrap()["AAPL"], "US0378331005");
+        let rows: Vec<(String, String)> = conn
+            .prepare("SELECT symbol, source FROM symbols ORDER BY symbol").unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap()
+            .collect::<rusqlite::Result<_>>().unwrap();
+        assert_eq!(rows, vec![
+            ("AAPL".to_string(), "yahoo".to_string()),
+            ("MYCO".to_string(), "user".to_string()),
+        ]);
+
+        drop(conn);
+        std::fs::remove_file(&db_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_failed_rebuild_keeps_database() {
+        let db_path = std::env::temp_dir().join(format!("yahoo-finance-symbols-failed-rebuild-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db_path);
+        {
+            let conn = Connection::open(&db_path).unwrap();
+            create_symbols_table(&conn).unwrap();
+            conn.execute("INSERT INTO symbols (symbol, name, category, asset_class, exchange) VALUES ('AAPL', 'Apple Inc.', 'Technology', 'Stocks', 'NMS')", []).unwrap();
+        }
+
+        // The scrape gets as far as creating its table before failing
+        let error = rebuild_database(&db_path, |path: PathBuf| async move {
+            let conn = Connection::open(&path)?;
+            create_symbols_table(&conn)?;
+            conn.execute("INSERT INTO symbols (symbol, name, category, asset_class, exchange) VALUES ('MSFT', 'Microsoft Corporation', 'Technology', 'Stocks', 'NMS')", [])?;
+            Err::<(), Box<dyn std::error::Error>>("Yahoo Finance is unreachable".into())
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(error.to_string(), "Yahoo Finance is unreachable");
+
+        let conn = Connection::open(&db_path).unwrap();
+        let symbols: Vec<String> = conn
+            .prepare("SELECT symbol FROM symbols").unwrap()
+            .query_map([], |row| row.get(0)).unwrap()
+            .collect::<rusqlite::Result<_>>().unwrap();
+        assert_eq!(symbols, vec!["AAPL".to_string()]);
+        assert!(!crate::sibling_path(&db_path, ".previous").exists());
+
+        drop(conn);
+        std::fs::remove_file(&db_path).unwrap();
+    }
+
+    #[cfg(feature = "download")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn check_update_database_atomic() {
+        let db_path = std::env::temp_dir().join(format!("yahoo-finance-symbols-atomic-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db_path);
+
+        let fake_scrape = |name: &'static str| {
+            move |path: PathBuf| async move {
+                // Slow enough for the readers to run while the replacement is built
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                let conn = Connection::open(&path)?;
+                create_symbols_table(&conn)?;
+                conn.execute("INSERT OR IGNORE INTO symbols (symbol, name, category, asset_class, exchange) VALUES ('AAPL', ?, 'Technology', 'Stocks', 'NMS')", [name])?;
+                Ok(())
+            }
+        };
+        rebuild_database(&db_path, fake_scrape("Apple Inc.")).await.unwrap();
+        let user = Symbol { symbol: "MYCO".to_string(), name: "My Company".to_string(), ..Symbol::new() };
+        import_into(&Connection::open(&db_path).unwrap(), &[user]).unwrap();
+
+        let db = SymbolDatabase::open(&db_path).unwrap();
+        let updated = Arc::new(AtomicBool::new(false));
+        let reader = {
+            let (db, updated) = (db.clone(), Arc::clone(&updated));
+            tokio::spawn(async move {
+                let mut reads = 0;
+                while !updated.load(Ordering::SeqCst) {
+                    let name = db.get_symbol("AAPL").await.unwrap().name;
+                    assert!(name == "Apple Inc." || name == "Apple Inc. (new)");
+                    assert_eq!(db.get_symbols_count().await.unwrap(), 2);
+                    reads += 1;
+                    // Leaves a single worker thread free to run the timer of the rebuild
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+                }
+                reads
+            })
+        };
+
+        replace_database(Some(&db), &db_path, fake_scrape("Apple Inc. (new)")).await.unwrap();
+        updated.store(true, Ordering::SeqCst);
+        assert!(reader.await.unwrap() > 0);
+
+        assert_eq!(db.get_symbol("AAPL").await.unwrap().name, "Apple Inc. (new)");
+        assert_eq!(db.get_symbol("MYCO").await.unwrap().name, "My Company");
+        assert!(!std::path::Path::new(&format!("{}.updating", db_path.display())).exists());
+
+        drop(db);
+        std::fs::remove_file(&db_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_symbol_by_isin() {
+        let listing = |asset_class: &str| Symbol {
+            symbol: "SAP".to_string(),
+            name: "SAP SE".to_string(),
+            asset_class: asset_class.to_string(),
+            ..Symbol::new()
+        };
+        let db = SymbolDatabase::in_memory(&[listing("Stocks"), listing("ETF")]).unwrap();
+        assert!(matches!(db.get_symbol_by_isin("DE0007164600").await, Err(rusqlite::Error::QueryReturnedNoRows)));
+
+        let isins = HashMap::from([("SAP".to_string(), " de0007164600 ".to_string()), ("NOPE".to_string(), "US0378331005".to_string())]);
+        assert_eq!(db.import_isins(isins).await.unwrap(), 2);
+        let sap = db.get_symbol_by_isin("de0007164600").await.unwrap();
+        assert_eq!((sap.symbol.as_str(), sap.asset_class.as_str()), ("SAP", "Stocks"));
+
+        // A bad check digit rejects the whole mapping
+        let isins = HashMap::from([("SAP".to_string(), "DE0007164601".to_string())]);
+        assert!(db.import_isins(isins).await.unwrap_err().to_string().contains("DE0007164601"));
+        assert_eq!(db.get_symbol_by_isin("DE0007164600").await.unwrap().symbol, "SAP");
+    }
+
+    #[tokio::test]
+    async fn check_exchange_prefix_and_groups() {
+        let symbols = get_symbols_by_exchange_prefix("ny").await.unwrap();
+        assert!(!symbols.is_empty());
+        assert!(symbols.iter().all(|s| s.exchange.starts_with("NY")));
+
+        let nasdaq = get_symbols(AssetClass::Stocks, Category::All, Exchange::NASDAQAllTiers).await.unwrap();
+        for code in ["NMS", "NGM", "NCM"] {
+            assert!(nasdaq.iter().any(|s| s.exchange == code));
+        }
+        assert!(nasdaq.iter().all(|s| ["NMS", "NGM", "NCM"].contains(&s.exchange.as_str())));
+    }
+
+    #[tokio::test]
+    async fn check_symbols_with_name() {
+        let banks = get_symbols_with_name(AssetClass::Stocks, Category::All, Exchange::NASDAQ, Some("bank")).await.unwrap();
+        assert!(!banks.is_empty());
+        assert!(banks.iter().all(|s| s.asset_class == "Stocks" && s.exchange == "NMS"));
+        assert!(banks.iter().all(|s| s.name.to_lowercase().contains("bank")));
+
+        let stocks = get_symbols(AssetClass::Stocks, Category::All, Exchange::NASDAQ).await.unwrap();
+        let expected = stocks.iter().filter(|s| s.name.to_lowercase().contains("bank")).count();
+        assert_eq!(banks.len(), expected);
+    }
+
+    #[test]
+    fn check_zip_prices() {
+        let symbol = |ticker: &str| Symbol { symbol: ticker.to_string(), ..Symbol::new() };
+        let prices = HashMap::from([("AAPL".to_string(), 189.84), ("MSFT".to_string(), 415.5)]);
+
+        let priced = zip_prices(vec![symbol("MSFT"), symbol("NOPE"), symbol("AAPL")], &prices);
+        let priced: Vec<(&str, Option<f64>)> = priced.iter().map(|(s, p)| (s.symbol.as_str(), *p)).collect();
+        assert_eq!(priced, vec![("MSFT", Some(415.5)), ("NOPE", None), ("AAPL", Some(189.84))]);
+        assert!(zip_prices(Vec::new(), &prices).is_empty());
+    }
+
+    #[tokio::test]
+    async fn check_search_lite() {
+        let completions = search_lite("aapl", AssetClass::Stocks, 5).await.unwrap();
+        assert_eq!(completions.len(), 5);
+        assert_eq!(completions[0].symbol, "AAPL");
+        assert!(completions.iter().all(|s| s.symbol.to_uppercase().contains("AAPL") || s.name.to_uppercase().contains("AAPL")));
+
+        let all = search_lite("Apple", AssetClass::Stocks, usize::MAX).await.unwrap();
+        assert_eq!(all.len() as i64, search_count("Apple", AssetClass::Stocks).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn check_search_sorted() {
+        let symbols = search_sorted("msft", AssetClass::All).await.unwrap();
+        assert!(symbols.len() > 3);
+        assert_eq!(symbols.len() as i64, search_count("msft", AssetClass::All).await.unwrap());
+        assert_eq!(symbols[0].symbol, "MSFT");
+        assert!(symbols[1..].windows(2).all(|w| {
+            let key = |s: &Symbol| (s.symbol.len(), s.symbol.clone(), s.asset_class.clone());
+            key(&w[0]) <= key(&w[1])
+        }));
+        let tickers = |symbols: &[Symbol]| symbols.iter().map(|s| (s.symbol.clone(), s.asset_class.clone())).collect::<Vec<_>>();
+        assert_eq!(tickers(&search_sorted("msft", AssetClass::All).await.unwrap()), tickers(&symbols));
+    }
+
+    #[tokio::test]
+    async fn check_search_after() {
+        let mut pages = Vec::new();
+        let mut after: Option<String> = None;
+        loop {
+            let page = search_after("msft", AssetClass::All, after.as_deref(), 2).await.unwrap();
+            let last = page.len() < 2;
+            after = page.last().map(|s| s.symbol.clone());
+            pages.extend(page);
+            if last {
+                break;
+            }
+        }
+        assert_eq!(pages.len() as i64, search_count("msft", AssetClass::All).await.unwrap());
+        let keys: Vec<_> = pages.iter().map(|s| (s.symbol.clone(), s.asset_class.clone())).collect();
+        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+
+        let etfs = search_after("msft", AssetClass::ETFs, None, 1000).await.unwrap();
+        assert!(etfs.iter().all(|s| s.asset_class == "ETF"));
+        let last = pages.last().unwrap().symbol.clone();
+        assert!(search_after("msft", AssetClass::All, Some(&last), 10).await.unwrap().is_empty());
+        assert!(search_after("msft", AssetClass::All, None, 0).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn check_search_refs() {
+        let results = search_refs("Apple", AssetClass::Stocks).await.unwrap();
+        assert!(!results.is_empty());
+        let matches = try_search_symbols("Apple", Some("Equity")).await.unwrap();
+        assert!(results.iter().all(|symbol| matches[symbol.symbol] == symbol.name));
+        let unique: HashSet<&str> = results.iter().map(|symbol| symbol.symbol).collect();
+        assert_eq!(unique.len(), matches.len());
+
+        let aapl = results.iter().find(|symbol| symbol.symbol == "AAPL").unwrap();
+        assert_eq!(aapl.asset_class, "Stocks");
+        assert_eq!(aapl.to_symbol().name, get_symbol("AAPL").await.unwrap().name);
+        assert!(results.get(results.len()).is_none());
+        assert!(search_refs("no such symbol anywhere", AssetClass::All).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn check_tickers() {
+        let symbols = get_symbols(AssetClass::Stocks, Category::All, Exchange::NASDAQ).await.unwrap();
+        let tickers = get_tickers(AssetClass::Stocks, Category::All, Exchange::NASDAQ).await.unwrap();
+        assert!(!tickers.is_empty());
+        assert_eq!(tickers, symbols.into_iter().map(|s| s.symbol).collect::<Vec<_>>());
+        assert!(tickers.iter().any(|t| t == "AAPL"));
+    }
+
+    #[tokio::test]
+    async fn check_filter_options() {
+        let options = get_filter_options().await.unwrap();
+        assert_eq!(options.exchanges, get_distinct_exchanges().await.unwrap());
+        assert_eq!(options.categories, get_distinct_categories().await.unwrap());
+        assert_eq!(options.asset_classes, get_distinct_asset_classes().await.unwrap());
+        assert!(options.asset_classes.iter().any(|a| a == "Stocks"));
+    }
+
+    #[tokio::test]
+    async fn check_top_counts() {
+        let exchanges = top_exchanges(5).await.unwrap();
+        assert_eq!(exchanges.len(), 5);
+        assert!(exchanges.windows(2).all(|w| w[0].1 >= w[1].1));
+        let total: i64 = top_exchanges(usize::MAX).await.unwrap().iter().map(|(_, count)| count).sum();
+        assert_eq!(total, get_symbols_count().await.unwrap());
+
+        let categories = top_categories(3).await.unwrap();
+        assert_eq!(categories.len(), 3);
+        // Every asset class other than stocks is stored as "N/A", so it dominates
+        assert_eq!(categories[0].0, "N/A");
+        assert!(top_categories(0).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn check_symbol_initial_distribution() {
+        let distribution = get_symbol_initial_distribution().await.unwrap();
+        assert_eq!(distribution.values().sum::<i64>(), get_symbols_count().await.unwrap());
+        assert!(('A'..='Z').all(|letter| distribution.get(&letter).is_some_and(|count| *count > 0)));
+        assert!(distribution[&'^'] > 0);
+    }
+
+    #[tokio::test]
+    async fn check_search_stream() {
+        let symbols: Vec<Symbol> = search_stream("Apple", AssetClass::Stocks).map(|s| s.unwrap()).collect().await;
+        assert_eq!(symbols.len() as i64, search_count("Apple", AssetClass::Stocks).await.unwrap());
+        assert!(symbols.iter().any(|s| s.symbol == "AAPL"));
+        assert!(symbols.iter().all(|s| s.asset_class == "Stocks"));
+
+        // Stopping early drops the scan instead of reading every match
+        let first: Vec<_> = search_stream("a", AssetClass::All).take(3).collect().await;
+        assert_eq!(first.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn check_lookup() {
+        let resolve = |input: &'static str| async move { lookup(input).await.unwrap().map(|s| s.symbol) };
+        assert_eq!(resolve("AAPL").await.as_deref(), Some("AAPL"));
+        assert_eq!(resolve(" msft ").await.as_deref(), Some("MSFT"));
+        // "Apple Inc." is also listed as APC.F, APC.DE, ... but the primary listing wins
+        assert_eq!(resolve("Apple Inc.").await.as_deref(), Some("AAPL"));
+        assert_eq!(resolve("microsoft corporation").await.as_deref(), Some("MSFT"));
+        assert_eq!(resolve("Microsoft Corporaton").await.as_deref(), Some("MSFT"));
+        // As close to "Tesla Inc" (TL0.F) as to "Tesla, Inc." (TSLA); the shorter symbol wins
+        assert_eq!(resolve("Tesla, Inc").await.as_deref(), Some("TSLA"));
+        assert_eq!(resolve("Qzxqzx Wvvwvw Holdings").await, None);
+        assert_eq!(resolve("   ").await, None);
+    }
+
+    #[tokio::test]
+    async fn check_search_all_classes() {
+        let everything = search("Apple").await.unwrap();
+        for asset_class in ["Equity", "ETF", "Mutual Fund"] {
+            let matches = try_search_symbols("Apple", Some(asset_class)).await.unwrap();
+            assert!(!matches.is_empty());
+            assert!(matches.iter().all(|(symbol, name)| everything.get(symbol) == Some(name)));
+        }
+        assert_eq!(everything.len() as i64, search_count("Apple", AssetClass::All).await.unwrap());
+        assert_eq!(try_search_symbols("Apple", None).await.unwrap(), everything);
+
+        // An unknown asset class is an error rather than a panic
+        let error = try_search_symbols("Apple", Some("Stocks")).await.unwrap_err();
+        assert!(error.downcast_ref::<ParseKeyError>().is_some(), "{}", error);
+        #[allow(deprecated)]
+        let equities = crate::search_symbols("Apple", "Equity").await.unwrap();
+        assert_eq!(equities, try_search_symbols("Apple", Some("Equity")).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn check_search_split() {
+        // "AAPL" is in the ticker of Apple and in the names of leveraged funds such as AAPU
+        let split = search_split("aapl", AssetClass::All, 100).await.unwrap();
+        assert_eq!(split.by_symbol[0].symbol, "AAPL");
+        assert!(split.by_symbol.iter().all(|s| s.symbol.to_uppercase().contains("AAPL")));
+        assert!(split.by_name.iter().any(|s| s.symbol == "AAPU"));
+        assert!(split.by_name.iter().all(|s| s.name.to_uppercase().contains("AAPL") && !s.symbol.to_uppercase().contains("AAPL")));
+        let by_symbol: HashSet<_> = split.by_symbol.iter().map(|s| (&s.symbol, &s.asset_class)).collect();
+        assert!(split.by_name.iter().all(|s| !by_symbol.contains(&(&s.symbol, &s.asset_class))));
+        assert!(!split.by_symbol.is_empty() && !split.by_name.is_empty());
+
+        // Together the groups are every match
+        assert_eq!((split.by_symbol.len() + split.by_name.len()) as i64, search_count("aapl", AssetClass::All).await.unwrap());
+
+        let capped = search_split("aapl", AssetClass::ETFs, 2).await.unwrap();
+        assert!(capped.by_symbol.len() <= 2 && capped.by_name.len() == 2);
+        assert!(capped.by_symbol.iter().chain(&capped.by_name).all(|s| s.asset_class == "ETF"));
+    }
+
+    #[tokio::test]
+    async fn check_search_ranked() {
+        let position = |symbols: &[Symbol], ticker: &str| symbols.iter().position(|s| s.symbol == ticker).unwrap();
+
+        let lexical = search_ranked("AAP", AssetClass::All, SearchRanking::Lexical).await.unwrap();
+        let sorted = search_sorted("AAP", AssetClass::All).await.unwrap();
+        assert!(lexical.iter().map(|s| &s.symbol).eq(sorted.iter().map(|s| &s.symbol)));
+        assert!(position(&lexical, "AAPI") < position(&lexical, "AAPL"));
+
+        // The NASDAQ listing outranks the OTC one of the same length, and the exact match stays first
+        let popular = search_ranked("AAP", AssetClass::All, SearchRanking::Popularity).await.unwrap();
+        assert_eq!(popular[0].symbol, "AAP");
+        assert!(position(&popular, "AAPL") < position(&popular, "AAPI"));
+        assert_eq!(popular.len(), lexical.len());
+        assert_eq!(exchange_tier("NMS"), 0);
+        assert_eq!(exchange_tier("PNK"), 4);
+        assert_eq!(exchange_tier("XYZ"), DEFAULT_EXCHANGE_TIER);
+
+        // Tickers starting with the query come before matches on the name only
+        let amazon = search_ranked("amzn", AssetClass::All, SearchRanking::Popularity).await.unwrap();
+        assert_eq!(amazon[0].symbol, "AMZN");
+        let first_name_match = amazon.iter().position(|s| !s.symbol.to_uppercase().starts_with("AMZN")).unwrap_or(amazon.len());
+        assert!(amazon[first_name_match..].iter().all(|s| !s.symbol.to_uppercase().starts_with("AMZN")));
+    }
+
+    #[tokio::test]
+    async fn check_search_result_cap() {
+        assert!(search_count("a", AssetClass::All).await.unwrap() > DEFAULT_MAX_SEARCH_RESULTS as i64);
+
+        let sorted = search_sorted("a", AssetClass::All).await.unwrap();
+        assert_eq!(sorted.len(), DEFAULT_MAX_SEARCH_RESULTS);
+        assert_eq!(sorted[0].symbol, "A");
+        assert!(sorted.windows(2).all(|pair| pair[0].symbol.len() <= pair[1].symbol.len() || pair[0].symbol == "A"));
+
+        let matches = search("a").await.unwrap();
+        assert!(matches.len() <= DEFAULT_MAX_SEARCH_RESULTS);
+        assert_eq!(matches.len(), sorted.iter().map(|s| s.symbol.as_str()).collect::<HashSet<_>>().len());
+        assert!(sorted.iter().all(|s| matches.contains_key(&s.symbol)));
+
+        assert!(try_search_symbols("a", Some("Equity")).await.unwrap().len() <= DEFAULT_MAX_SEARCH_RESULTS);
+        let refs = search_refs("a", AssetClass::All).await.unwrap();
+        assert_eq!(refs.len(), DEFAULT_MAX_SEARCH_RESULTS);
+        assert!(refs.iter().any(|s| s.symbol == "A"));
+        assert_eq!(search_stream("a", AssetClass::All).count().await, DEFAULT_MAX_SEARCH_RESULTS);
+    }
+
+    #[tokio::test]
+    async fn check_special_characters() {
+        for query in ["%", "_", "^", "5%", "\\"] {
+            let sorted = search_sorted(query, AssetClass::All).await.unwrap();
+            let count = search_count(query, AssetClass::All).await.unwrap();
+            assert_eq!(sorted.len() as i64, count.min(DEFAULT_MAX_SEARCH_RESULTS as i64), "{}", query);
+            let lowered = query.to_lowercase();
+            assert!(sorted.iter().all(|s| s.symbol.to_lowercase().contains(&lowered) || s.name.to_lowercase().contains(&lowered)), "{}", query);
+            let matches = search(query).await.unwrap();
+            assert_eq!(sorted.iter().map(|s| s.symbol.as_str()).collect::<HashSet<_>>().len(), matches.len(), "{}", query);
+        }
+        assert!(search_count("%", AssetClass::All).await.unwrap() < get_symbols_count().await.unwrap());
+        assert_eq!(search_lite("^GSPC", AssetClass::Indices, 1).await.unwrap()[0].symbol, "^GSPC");
+
+        let named = get_symbols_with_name(AssetClass::All, Category::All, Exchange::All, Some("_")).await.unwrap();
+        assert!(named.iter().all(|s| s.name.contains('_')));
+
+        let indices = get_symbols_matching(&format!("{}*", escape_glob("^GS"))).await.unwrap();
+        assert!(indices.iter().any(|s| s.symbol == "^GSPC"));
+        assert!(get_symbols_matching(&escape_glob("BRK-?")).await.unwrap().is_empty());
+        assert_eq!(escape_glob("A*B?[C]"), "A[*]B[?][[]C]");
+    }
+
+    #[tokio::test]
+    async fn check_symbols_matching() {
+        let brk = get_symbols_matching("BRK*").await.unwrap();
+        let tickers: Vec<&str> = brk.iter().map(|s| s.symbol.as_str()).collect();
+        assert!(tickers.contains(&"BRK-A") && tickers.contains(&"BRK-B"));
+        assert!(tickers.iter().all(|t| t.starts_with("BRK")));
+        assert!(tickers.windows(2).all(|pair| pair[0] < pair[1]));
+        let classes = get_symbols_matching("BRK-?").await.unwrap();
+        assert_eq!(classes.iter().map(|s| s.symbol.as_str()).collect::<Vec<_>>(), ["BRK-A", "BRK-B"]);
+
+        let indices = get_symbols_matching("^*").await.unwrap();
+        assert!(indices.iter().any(|s| s.symbol == "^GSPC"));
+        assert!(indices.iter().all(|s| s.symbol.starts_with('^')));
+        assert!(indices.iter().filter(|s| s.asset_class == "Index").count() * 10 > indices.len() * 9);
+
+        // GLOB is case-sensitive
+        assert!(get_symbols_matching("brk*").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn check_query_symbols_order() {
+        let query = SymbolQuery { asset_class: AssetClass::Stocks, exchange: Exchange::NASDAQ, ..SymbolQuery::default() };
+        let by_name = query_symbols(query.clone().order_by(Column::Name, SortDir::Asc)).await.unwrap();
+        assert!(by_name.len() > 1);
+        assert!(by_name.windows(2).all(|pair| pair[0].name <= pair[1].name));
+
+        let by_symbol = query_symbols(query.clone().order_by(Column::Symbol, SortDir::Desc)).await.unwrap();
+        assert_eq!(by_symbol.len(), by_name.len());
+        assert!(by_symbol.windows(2).all(|pair| pair[0].symbol >= pair[1].symbol));
+
+        let filtered = get_symbols_filtered(query.order_by(Column::Exchange, SortDir::Asc), |s| s.name.contains("Bank")).await.unwrap();
+        assert!(filtered.windows(2).all(|pair| pair[0].exchange <= pair[1].exchange));
+    }
+
+    #[tokio::test]
+    async fn check_symbols_filtered() {
+        let query = SymbolQuery { asset_class: AssetClass::Stocks, exchange: Exchange::NASDAQ, ..SymbolQuery::default() };
+        let short_undotted = |s: &Symbol| s.name.chars().count() < 10 && !s.symbol.contains('.');
+        let symbols = get_symbols_filtered(query.clone(), short_undotted).await.unwrap();
+
+        let expected: Vec<String> = get_symbols(AssetClass::Stocks, Category::All, Exchange::NASDAQ).await.unwrap()
+            .into_iter()
+            .filter(short_undotted)
+            .map(|s| s.symbol)
+            .collect();
+        assert!(!expected.is_empty());
+        assert_eq!(symbols.iter().map(|s| s.symbol.clone()).collect::<Vec<_>>(), expected);
+
+        let apple = SymbolQuery { name_contains: Some("apple".to_string()), ..query };
+        let filter = SymbolFilter::new(|s| s.symbol.starts_with("AAP"));
+        let symbols = get_symbols_filtered(apple, |s| filter.matches(s)).await.unwrap();
+        assert!(symbols.iter().any(|s| s.symbol == "AAPL"));
+        assert!(symbols.iter().all(|s| s.symbol.starts_with("AAP") && s.name.to_lowercase().contains("apple")));
+    }
+
+    #[tokio::test]
+    async fn check_is_ready() {
+        let cell = OnceCell::new();
+        assert!(!database_ready(&cell).await);
+        cell.set(SymbolDatabase::open("symbols.db").unwrap()).unwrap();
+        assert!(database_ready(&cell).await);
+
+        default_database().await.unwrap();
+        assert!(is_ready().await);
+    }
+
+    #[tokio::test]
+    async fn check_default_database() {
+        let db: &'static SymbolDatabase = default_database().await.unwrap();
+        assert!(std::ptr::eq(db, default_database().await.unwrap()));
+        assert_eq!(db.path(), Some(get_config().database.path.as_path()));
+        assert!(db.get_symbol("AAPL").await.is_ok());
+
+        // The free query functions return the opening error as a rusqlite::Error with its message
+        let error = YahooSymbolsError::Pool { attempts: POOL_RETRY_ATTEMPTS, reason: "database is locked".to_string() };
+        let converted = rusqlite::Error::from(error.clone());
+        assert_eq!(converted.sqlite_error_code(), Some(rusqlite::ErrorCode::CannotOpen));
+        assert_eq!(converted.to_string(), error.to_string());
+    }
+
+    #[cfg(feature = "download")]
+    #[tokio::test]
+    async fn check_auto_provision_disabled() {
+        let db_path = std::env::temp_dir().join(format!("yahoo-finance-symbols-provision-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db_path);
+
+        // An unreachable URL, so that a wrongly attempted download fails fast instead of scraping
+        let config = DatabaseConfig {
+            path: db_path.clone(),
+            download_url: "http://127.0.0.1:9/symbols.db".to_string(),
+            auto_provision: false,
+            ..DatabaseConfig::default()
+        };
+        let err = open_or_provision(&config).await.unwrap_err();
+        assert_eq!(err, YahooSymbolsError::ProvisioningDisabled(db_path.clone()));
+        assert!(err.to_string().contains("refresh_database"));
+        assert!(!db_path.exists());
+
+        // An existing file opens as usual
+        let config = DatabaseConfig { path: PathBuf::from("symbols.db"), ..config };
+        assert!(open_or_provision(&config).await.unwrap().get_symbol("AAPL").await.is_ok());
+    }
+
+    #[cfg(not(feature = "download"))]
+    #[tokio::test]
+    async fn check_missing_database_without_download() {
+        let db_path = std::env::temp_dir().join(format!("yahoo-finance-symbols-no-download-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db_path);
+
+        // Nothing can be fetched, whatever `auto_provision` says
+        let config = DatabaseConfig { path: db_path.clone(), ..DatabaseConfig::default() };
+        let err = open_or_provision(&config).await.unwrap_err();
+        assert_eq!(err, YahooSymbolsError::DatabaseNotFound(db_path.clone()));
+        assert!(!db_path.exists());
+
+        let config = DatabaseConfig { path: PathBuf::from("symbols.db"), ..config };
+        assert!(open_or_provision(&config).await.unwrap().get_symbol("AAPL").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn check_malformed_symbols() {
+        let db_path = std::env::temp_dir().join(format!("yahoo-finance-symbols-malformed-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db_path);
+        Connection::open(&db_path).unwrap().execute_batch(
+            "CREATE TABLE symbols (symbol TEXT PRIMARY KEY, name TEXT, category TEXT, asset_class TEXT, exchange TEXT);
+             INSERT INTO symbols VALUES ('ACME', 'Acme Corp', 'N/A', 'Stocks', 'NYQ');
+             INSERT INTO symbols VALUES ('NOEX', 'No Exchange Inc.', 'Industrials', 'Stocks', '');
+             INSERT INTO symbols VALUES ('NONAME', 'N/A', 'N/A', 'ETF', 'PCX');
+             INSERT INTO symbols VALUES ('BLANK', '  ', 'N/A', 'ETF', 'PCX');
+             INSERT INTO symbols VALUES ('NOCLASS', 'No Class Ltd', 'N/A', NULL, 'LSE');",
+        ).unwrap();
+
+        // A missing category alone is not malformed
+        let db = SymbolDatabase::open(&db_path).unwrap();
+        let malformed = db.get_malformed_symbols().await.unwrap();
+        assert_eq!(malformed.iter().map(|s| s.symbol.as_str()).collect::<Vec<_>>(), ["BLANK", "NOCLASS", "NOEX", "NONAME"]);
+        assert_eq!(malformed[1].asset_class, "");
+        assert_eq!(db.count_malformed().await.unwrap(), 4);
+
+        drop(db);
+        std::fs::remove_file(&db_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_use_existing_database() {
+        let dir = std::env::temp_dir();
+        let fixture_path = dir.join(format!("yahoo-finance-symbols-existing-{}.db", std::process::id()));
+        let invalid_path = dir.join(format!("yahoo-finance-symbols-existing-invalid-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&fixture_path);
+        let _ = std::fs::remove_file(&invalid_path);
+        Connection::open(&fixture_path).unwrap().execute_batch(
+            "CREATE TABLE symbols (symbol TEXT PRIMARY KEY, name TEXT, category TEXT, asset_class TEXT, exchange TEXT);
+             INSERT INTO symbols VALUES ('ACME', 'Acme Corp', 'Industrials', 'Stocks', 'NYQ');",
+        ).unwrap();
+        Connection::open(&invalid_path).unwrap().execute_batch("CREATE TABLE symbols (symbol TEXT, name TEXT)").unwrap();
+
+        let cell = OnceCell::new();
+        let missing = dir.join("yahoo-finance-symbols-no-such-file.db");
+        assert!(matches!(use_existing_database_in(&cell, &missing).await, Err(YahooSymbolsError::DatabaseNotFound(_))));
+        let error = use_existing_database_in(&cell, &invalid_path).await.unwrap_err();
+        assert!(matches!(&error, YahooSymbolsError::InvalidSchema(reason) if reason.contains("category, asset_class, exchange")));
+        assert!(cell.get().is_none());
+
+        use_existing_database_in(&cell, &fixture_path).await.unwrap();
+        let db = cell.get().unwrap();
+        assert_eq!(db.path(), Some(fixture_path.as_path()));
+        assert_eq!(db.get_symbol("ACME").await.unwrap().name, "Acme Corp");
+        assert_eq!(db.get_symbols_count().await.unwrap(), 1);
+        assert_eq!(db.get_build_info().await.unwrap().schema_version, crate::schema::SCHEMA_VERSION);
+        assert_eq!(use_existing_database_in(&cell, &fixture_path).await.unwrap_err(), YahooSymbolsError::AlreadyInitialized);
+
+        std::fs::remove_file(&fixture_path).unwrap();
+        std::fs::remove_file(&invalid_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_load_from_bytes() {
+        let fixture_path = std::env::temp_dir().join(format!("yahoo-finance-symbols-bytes-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&fixture_path);
+        Connection::open(&fixture_path).unwrap().execute_batch(
+            "CREATE TABLE symbols (symbol TEXT PRIMARY KEY, name TEXT, category TEXT, asset_class TEXT, exchange TEXT);
+             INSERT INTO symbols VALUES ('ACME', 'Acme Corp', 'Industrials', 'Stocks', 'NYQ');
+             INSERT INTO symbols VALUES ('ACMX', 'Acme ETF', '', 'ETFs', 'PCX');",
+        ).unwrap();
+        let data = std::fs::read(&fixture_path).unwrap();
+        std::fs::remove_file(&fixture_path).unwrap();
+
+        let cell = OnceCell::new();
+        load_from_bytes_in(&cell, &data).unwrap();
+        let db = cell.get().unwrap();
+        assert_eq!(db.path(), None);
+        assert_eq!(db.get_symbol("ACME").await.unwrap().name, "Acme Corp");
+        assert_eq!(db.get_symbols_count().await.unwrap(), 2);
+        assert!(load_from_bytes_in(&cell, &data).is_err());
+    }
+
+    #[cfg(feature = "download")]
+    #[tokio::test]
+    async fn check_ensure_database() {
+        let unreachable = "http://127.0.0.1:9/symbols.db".to_string();
+        let existing = DatabaseConfig { path: PathBuf::from("symbols.db"), download_url: unreachable.clone(), ..DatabaseConfig::default() };
+        assert_eq!(ensure_database(&existing).await.unwrap(), DatabaseStatus::Existing);
+
+        let dir = std::env::temp_dir();
+        let db_path = dir.join(format!("yahoo-finance-symbols-ensure-{}.db", std::process::id()));
+        let fixture_path = dir.join(format!("yahoo-finance-symbols-ensure-fixture-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db_path);
+        let _ = std::fs::remove_file(&fixture_path);
+        {
+            let conn = Connection::open(&fixture_path).unwrap();
+            create_symbols_table(&conn).unwrap();
+            conn.execute("INSERT INTO symbols (symbol, name, category, asset_class, exchange) VALUES ('AAPL', 'Apple Inc.', 'Technology', 'Stocks', 'NMS')", []).unwrap();
+        }
+        let fixture = std::fs::read(&fixture_path).unwrap();
+        std::fs::remove_file(&fixture_path).unwrap();
+
+        // Serves the fixture, without a checksum file
+        let url = serve_with(move |request| {
+            if request.contains(".sha256") {
+                bytes_response("404 Not Found", &[], b"")
+            } else {
+                bytes_response("200 OK", &[], &fixture)
+            }
+        })
+        .await;
+        let config = DatabaseConfig { path: db_path.clone(), download_url: format!("{}symbols.db", url), ..DatabaseConfig::default() };
+        assert_eq!(ensure_database(&config).await.unwrap(), DatabaseStatus::Downloaded);
+        assert_eq!(SymbolDatabase::open(&db_path).unwrap().get_symbol("AAPL").await.unwrap().name, "Apple Inc.");
+        std::fs::remove_file(&db_path).unwrap();
+
+        // Without a download, the lookup pages are scraped
+        let lookup_url = serve_with(|_| {
+            html_response(
+                r#"<table><tbody><tr><td><a data-symbol="MSFT">MSFT</a></td><td>Microsoft Corporation</td><td>1</td>
+                <td><a>Technology</a></td><td>Stocks</td><td>NMS</td></tr></tbody></table>"#,
+            )
+        })
+        .await;
+        let config = DatabaseConfig { download_url: unreachable, ..config };
+        assert_eq!(ensure_database_from(&config, &lookup_url).await.unwrap(), DatabaseStatus::Scraped);
+        assert_eq!(SymbolDatabase::open(&db_path).unwrap().get_symbols_count().await.unwrap(), 1);
+        std::fs::remove_file(&db_path).unwrap();
+
+        // Neither works
+        let broken_url = serve_with(|_| html_response("<html><body>Moved</body></html>")).await;
+        let err = ensure_database_from(&config, &broken_url).await.unwrap_err();
+        assert!(matches!(err, YahooSymbolsError::Provisioning(_)), "{}", err);
+        assert!(!db_path.exists());
+    }
+
+    #[cfg(feature = "download")]
+    #[tokio::test(flavor = "current_thread")]
+    async fn check_provision_current_thread() {
+        // A version 1 file, so that opening it runs a migration too
+        let dir = std::env::temp_dir();
+        let db_path = dir.join(format!("yahoo-finance-symbols-current-thread-{}.db", std::process::id()));
+        let fixture_path = dir.join(format!("yahoo-finance-symbols-current-thread-fixture-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db_path);
+        let _ = std::fs::remove_file(&fixture_path);
+        Connection::open(&fixture_path).unwrap().execute_batch(
+            "CREATE TABLE symbols (symbol TEXT PRIMARY KEY, name TEXT, category TEXT, asset_class TEXT, exchange TEXT);
+             INSERT INTO symbols VALUES ('AAPL', 'Apple Inc.', 'Technology', 'Stocks', 'NMS');",
+        ).unwrap();
+        let fixture = std::fs::read(&fixture_path).unwrap();
+        std::fs::remove_file(&fixture_path).unwrap();
+        let checksum = crate::sha256::sha256_hex(&fixture);
+
+        // Serves the fixture and its checksum from the same single-threaded runtime
+        let url = serve_with(move |request| {
+            let body = if request.contains(".sha256") { checksum.as_bytes() } else { &fixture };
+            bytes_response("200 OK", &[], body)
+        })
+        .await;
+
+        let config = DatabaseConfig { path: db_path.clone(), download_url: format!("{}symbols.db", url), serve_while_scraping: false, ..DatabaseConfig::default() };
+        let db = tokio::time::timeout(Duration::from_secs(30), open_or_provision(&config)).await.unwrap().unwrap();
+        assert_eq!(db.get_schema_version().await.unwrap(), SCHEMA_VERSION);
+        assert_eq!(db.get_symbol("AAPL").await.unwrap().name, "Apple Inc.");
+
+        // The blocking pool runs alongside the runtime's only thread
+        let worker = run_blocking(|| std::thread::current().id()).await;
+        assert_ne!(worker, std::thread::current().id());
+
+        drop(db);
+        std::fs::remove_file(&db_path).unwrap();
+    }
+
+    #[cfg(feature = "download")]
+    #[tokio::test]
+    async fn check_refresh_database() {
+        let dir = std::env::temp_dir();
+        let db_path = dir.join(format!("yahoo-finance-symbols-refresh-{}.db", std::process::id()));
+        let fixture_path = dir.join(format!("yahoo-finance-symbols-refresh-fixture-{}.db", std::process::id()));
+        for (path, name) in [(&db_path, "Apple (old)"), (&fixture_path, "Apple Inc.")] {
+            let _ = std::fs::remove_file(path);
+            let conn = Connection::open(path).unwrap();
+            create_symbols_table(&conn).unwrap();
+            conn.execute(
+                "INSERT INTO symbols (symbol, name, category, asset_class, exchange) VALUES ('AAPL', ?, 'Technology', 'Stocks', 'NMS')",
+                [name],
+            ).unwrap();
+        }
+        import_into(&Connection::open(&db_path).unwrap(), &[Symbol { symbol: "MINE".to_string(), ..Symbol::new() }]).unwrap();
+        let fixture = std::fs::read(&fixture_path).unwrap();
+        std::fs::remove_file(&fixture_path).unwrap();
+
+        // Serves the fixture with an ETag, and answers a request carrying it with 304 Not Modified
+        let url = serve_with(move |request| {
+            let request = request.to_lowercase();
+            if request.contains(".sha256") {
+                bytes_response("404 Not Found", &[], b"")
+            } else if request.contains("if-none-match: \"v1\"") {
+                bytes_response("304 Not Modified", &["ETag: \"v1\""], b"")
+            } else {
+                bytes_response("200 OK", &["ETag: \"v1\""], &fixture)
+            }
+        })
+        .await;
+
+        let config = DatabaseConfig { path: db_path.clone(), download_url: format!("{}symbols.db", url), ..DatabaseConfig::default() };
+        let db = SymbolDatabase::open(&db_path).unwrap();
+        assert_eq!(db.get_symbol("AAPL").await.unwrap().name, "Apple (old)");
+
+        // The connections are reopened, so the same handle reads the new copy, which keeps the user row
+        assert!(refresh_database_in(Some(&db), &config).await.unwrap());
+        assert_eq!(db.get_symbol("AAPL").await.unwrap().name, "Apple Inc.");
+        assert!(db.get_symbol("MINE").await.is_ok());
+        assert!(!refresh_database_in(Some(&db), &config).await.unwrap());
+        assert_eq!(db.get_symbol("AAPL").await.unwrap().name, "Apple Inc.");
+
+        drop(db);
+        std::fs::remove_file(crate::sibling_path(&db_path, ".etag")).unwrap();
+        std::fs::remove_file(&db_path).unwrap();
+    }
+
+    #[cfg(feature = "download")]
+    #[tokio::test]
+    async fn check_serve_while_scraping() {
+        let db_path = std::env::temp_dir().join(format!("yahoo-finance-symbols-partial-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db_path);
+        let lookup_url = serve_with(|_| {
+            html_response(
+                r#"<table><tbody><tr><td><a data-symbol="MSFT">MSFT</a></td><td>Microsoft Corporation</td><td>1</td>
+                <td><a>Technology</a></td><td>Stocks</td><td>NMS</td></tr></tbody></table>"#,
+            )
+        })
+        .await;
+        let config = DatabaseConfig { path: db_path.clone(), download_url: "http://127.0.0.1:9/symbols.db".to_string(), ..DatabaseConfig::default() };
+
+        // The file answers queries as soon as the scrape has started
+        let scrape = provision_while_scraping(&config, &lookup_url).await.unwrap().unwrap();
+        let db = SymbolDatabase::open(&db_path).unwrap();
+        assert!(db.get_symbols_count().await.unwrap() <= 1);
+        scrape.await.unwrap();
+        assert_eq!(db.get_symbol("MSFT").await.unwrap().name, "Microsoft Corporation");
+        let build_info = db.get_build_info().await.unwrap();
+        assert!(build_info.built_at.is_some() && !build_info.scrape_pending);
+        assert!(db.is_ready().await);
+        assert!(provision_while_scraping(&config, &lookup_url).await.unwrap().is_none());
+
+        // A scrape that did not finish is resumed
+        crate::provenance::write_meta(&Connection::open(&db_path).unwrap(), "scrape_pending", "1").unwrap();
+        assert!(!db.is_ready().await);
+        provision_while_scraping(&config, &lookup_url).await.unwrap().unwrap().await.unwrap();
+        assert!(provision_while_scraping(&config, &lookup_url).await.unwrap().is_none());
+        assert_eq!(db.get_symbols_count().await.unwrap(), 1);
+
+        // A scrape that stopped on a changed page layout stays marked but is not resumed
+        let empty_url = serve_with(|_| html_response("<table><tbody></tbody></table>")).await;
+        let stopped_path = std::env::temp_dir().join(format!("yahoo-finance-symbols-stopped-scrape-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&stopped_path);
+        let stopped_config = DatabaseConfig { path: stopped_path.clone(), ..config.clone() };
+        provision_while_scraping(&stopped_config, &empty_url).await.unwrap().unwrap().await.unwrap();
+        let stopped = SymbolDatabase::open(&stopped_path).unwrap();
+        assert!(stopped.get_build_info().await.unwrap().scrape_pending);
+        assert!(!stopped.is_ready().await);
+        assert!(provision_while_scraping(&stopped_config, &empty_url).await.unwrap().is_none());
+
+        drop((db, stopped));
+        std::fs::remove_file(&db_path).unwrap();
+        std::fs::remove_file(&stopped_path).unwrap();
+    }
+
+    #[cfg(feature = "download")]
+    #[test]
+    fn check_download_failure_is_logged() {
+        const URL: &str = "http://127.0.0.1:9/symbols.db";
+        // The child process started below reports through the global logger, of which it has none
+        if std::env::var_os("YAHOO_FINANCE_SYMBOLS_REPORT_CHILD").is_some() {
+            report_download_failure(URL, "connection refused");
+            return;
+        }
+
+        struct Capture(Mutex<Vec<(log::Level, String)>>);
+        impl log::Log for Capture {
+            fn enabled(&self, _: &log::Metadata) -> bool {
+                true
+            }
+            fn log(&self, record: &log::Record) {
+                self.0.lock().unwrap().push((record.level(), record.args().to_string()));
+            }
+            fn flush(&self) {}
+        }
+        let capture = Capture(Mutex::new(Vec::new()));
+        report_download_failure_to(&capture, URL, "connection refused");
+        let records = capture.0.lock().unwrap();
+        assert!(records.iter().any(|(level, message)| {
+            *level == log::Level::Warn && message.contains(URL) && message.contains("connection refused")
+        }));
+
+        // Run as the only test of a child process, whose stdout can be read back
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", "full::tests::check_download_failure_is_logged", "--nocapture"])
+            .env("YAHOO_FINANCE_SYMBOLS_REPORT_CHILD", "1")
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success() && stdout.contains("1 passed"), "{}", stdout);
+        assert!(!stdout.contains(URL), "{}", stdout);
+    }
+
+    #[tokio::test]
+    async fn check_schema_version() {
+        assert_eq!(get_schema_version().await.unwrap(), SCHEMA_VERSION);
+        assert_eq!(migrate().await.unwrap(), SCHEMA_VERSION);
+    }
+}
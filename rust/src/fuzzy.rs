@@ -0,0 +1,33 @@
+/// Levenshtein edit distance between two strings, counted in characters
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current: Vec<usize> = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::levenshtein;
+
+    #[test]
+    fn check_levenshtein() {
+        assert_eq!(levenshtein("AAPL", "AAPL"), 0);
+        assert_eq!(levenshtein("APPL", "AAPL"), 1);
+        assert_eq!(levenshtein("MSFT", "MSF"), 1);
+        assert_eq!(levenshtein("", "GOOG"), 4);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+    }
+}
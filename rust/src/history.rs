@@ -0,0 +1,179 @@
+//! Opt-in persistence of search queries for analytics.
+//!
+//! Nothing is recorded unless the crate is built with the `history` feature **and**
+//! [`enable_search_history`] has been called at runtime. Only the query text and a
+//! timestamp are stored, never the results. Records live in a `search_history` table
+//! inside the local symbols database.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use rusqlite::{params, Connection, Result};
+use serde::{Deserialize, Serialize};
+use crate::database::SymbolDatabase;
+
+
+static HISTORY_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// A single recorded search query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchRecord {
+    pub query: String,
+    /// Seconds since the Unix epoch
+    pub searched_at: i64,
+}
+
+/// Turns search history recording on or off for the current process (off by default)
+///
+/// Turning it on creates the `search_history` table in the default database, if that is already
+/// open; otherwise the first recorded query creates it.
+pub fn enable_search_history(enabled: bool) {
+    HISTORY_ENABLED.store(enabled, Ordering::Relaxed);
+    if let (true, Some(db)) = (enabled, crate::DEFAULT_DATABASE.get()) {
+        let created = db.connection().map_err(|e| e.to_string()).and_then(|conn| create_history_table(&conn).map_err(|e| e.to_string()));
+        if let Err(e) = created {
+            log::warn!("Failed to create the search history table: {}", e);
+        }
+    }
+}
+
+/// Returns whether search queries are currently being recorded
+pub fn is_search_history_enabled() -> bool {
+    HISTORY_ENABLED.load(Ordering::Relaxed)
+}
+
+fn create_history_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS search_history (
+             id INTEGER PRIMARY KEY AUTOINCREMENT,
+             query TEXT NOT NULL,
+             searched_at INTEGER NOT NULL
+         )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Records a query in the background so the search path never waits on the write
+pub(crate) fn record_search(db: &SymbolDatabase, query: &str) {
+    if is_search_history_enabled() {
+        record_query(db, query);
+    }
+}
+
+/// Writes `query` to the history on Tokio's blocking thread pool, or right away outside a runtime
+fn record_query(db: &SymbolDatabase, query: &str) {
+    let db = db.clone();
+    let query = query.to_string();
+    let searched_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+
+    let record = move || {
+        let result = db.connection().map_err(|e| e.to_string()).and_then(|conn| {
+            create_history_table(&conn)
+                .and_then(|_| conn.execute(
+                    "INSERT INTO search_history (query, searched_at) VALUES (?, ?)",
+                    params![query, searched_at],
+                ))
+                .map_err(|e| e.to_string())
+        });
+        if let Err(e) = result {
+            log::warn!("Failed to record search history: {}", e);
+        }
+    };
+
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => drop(handle.spawn_blocking(record)),
+        Err(_) => record(),
+    }
+}
+
+/// Fetches the most recent recorded search queries, newest first
+///
+/// # Arguments
+///
+/// * `limit` - maximum number of records to return
+///
+/// # Returns
+///
+/// * `Vec<SearchRecord>` - recorded queries with their timestamps
+///
+/// # Example
+///
+/// ```
+/// use yahoo_finance_symbols::{enable_search_history, get_search_history, try_search_symbols};
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     enable_search_history(true);
+///     try_search_symbols("Apple", Some("Equity")).await?;
+///     let history = get_search_history(10).await?;
+///     println!("{:?}", history);
+///     Ok(())
+/// }
+/// ```
+pub async fn get_search_history(limit: usize) -> Result<Vec<SearchRecord>> {
+    let conn = crate::default_database().await?.connection()?;
+    // Reading never writes, so that read-only files work; nothing was recorded without the table
+    let has_history: bool = conn
+        .prepare("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'search_history'")?
+        .exists([])?;
+    if !has_history {
+        return Ok(Vec::new());
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT query, searched_at FROM search_history ORDER BY searched_at DESC, id DESC LIMIT ?",
+    )?;
+    let rows = stmt.query_map([limit as i64], |row| {
+        Ok(SearchRecord {
+            query: row.get(0)?,
+            searched_at: row.get(1)?,
+        })
+    })?;
+
+    rows.collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use std::time::Duration;
+    use r2d2::Pool;
+    use r2d2_sqlite::SqliteConnectionManager;
+    use crate::keys::AssetClass;
+    use crate::database::SymbolDatabase;
+    use crate::search_count;
+    use super::{enable_search_history, get_search_history, record_query};
+
+    #[tokio::test]
+    async fn check_search_history() {
+        enable_search_history(true);
+        search_count("History Test Query", AssetClass::Stocks).await.unwrap();
+
+        let mut recorded = false;
+        for _ in 0..20 {
+            let history = get_search_history(50).await.unwrap();
+            if history.iter().any(|r| r.query == "History Test Query") {
+                recorded = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        enable_search_history(false);
+        assert!(recorded);
+    }
+
+    #[test]
+    fn check_record_without_runtime() {
+        let pool = Pool::builder().max_size(1).build(SqliteConnectionManager::memory()).unwrap();
+        record_query(&SymbolDatabase::from_pool(pool.clone()), "No Runtime Query");
+
+        let conn = pool.get().unwrap();
+        let recorded: String = conn.query_row("SELECT query FROM search_history", [], |row| row.get(0)).unwrap();
+        assert_eq!(recorded, "No Runtime Query");
+    }
+}
@@ -0,0 +1,400 @@
+//! Importing custom symbols that Yahoo Finance does not list.
+//!
+//! Imported rows are stored in the `symbols` table alongside scraped ones, with the
+//! `source` column set to [`USER_SOURCE`] instead of [`YAHOO_SOURCE`].
+//!
+//! `update_database` rebuilds the file from a fresh scrape, so before removing the old file it
+//! reads out every user row and re-imports them into the new file before scraping starts. The
+//! scraper skips symbols that are already present, so custom data always wins over Yahoo rows,
+//! and an interrupted update never loses user rows.
+//!
+//! Yahoo Finance does not publish ISINs, so the `isin` column is NULL unless a ticker-to-ISIN
+//! mapping is loaded with [`import_isins`] or [`import_isins_from_csv`]. `update_database`
+//! carries the loaded ISINs over to the rebuilt file in the same way as user rows.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::error::Error;
+use rusqlite::{params, Connection, Result};
+use crate::csv::parse_csv;
+use crate::Symbol;
+
+
+/// `source` value of rows scraped from Yahoo Finance
+pub const YAHOO_SOURCE: &str = "yahoo";
+/// `source` value of rows imported by the user
+pub const USER_SOURCE: &str = "user";
+
+fn has_source_column(conn: &Connection) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info('symbols') WHERE name = 'source'",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// Adds the `source` column to databases created before it existed, marking existing rows as Yahoo rows
+pub fn ensure_source_column(conn: &Connection) -> Result<()> {
+    if !has_source_column(conn)? {
+        conn.execute(
+            &format!("ALTER TABLE symbols ADD COLUMN source TEXT NOT NULL DEFAULT '{}'", YAHOO_SOURCE),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+/// Adds the nullable `isin` column and its index to databases created before they existed
+pub fn ensure_isin_column(conn: &Connection) -> Result<()> {
+    let has_column = conn.prepare("SELECT 1 FROM pragma_table_info('symbols') WHERE name = 'isin'")?.exists([])?;
+    if !has_column {
+        conn.execute("ALTER TABLE symbols ADD COLUMN isin TEXT", [])?;
+    }
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_symbols_isin ON symbols (isin)", [])?;
+    Ok(())
+}
+
+/// Whether `isin` is a well-formed ISIN: a two-letter country code, nine upper-case letters or
+/// digits and a check digit that matches them
+// `u32::is_multiple_of` needs Rust 1.87, newer than the toolchains this crate supports
+#[allow(clippy::manual_is_multiple_of)]
+pub fn is_valid_isin(isin: &str) -> bool {
+    let bytes = isin.as_bytes();
+    let well_formed = bytes.len() == 12
+        && bytes[..2].iter().all(u8::is_ascii_uppercase)
+        && bytes[2..11].iter().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+        && bytes[11].is_ascii_digit();
+    if !well_formed {
+        return false;
+    }
+
+    // Letters stand for two digits, A = 10 to Z = 35, and the digits then pass the Luhn check
+    let digits: Vec<u32> = bytes
+        .iter()
+        .flat_map(|b| match (*b as char).to_digit(36) {
+            Some(value) if value >= 10 => vec![value / 10, value % 10],
+            value => vec![value.unwrap_or(0)],
+        })
+        .collect();
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, digit)| if i % 2 == 1 { digit * 2 / 10 + digit * 2 % 10 } else { *digit })
+        .sum();
+    sum % 10 == 0
+}
+
+/// Sets the ISIN of every listing of the mapped tickers, returning how many rows were updated
+///
+/// ISINs are trimmed and upper-cased, and the whole mapping is rejected if any of them is not a
+/// valid ISIN. Tickers that are not in the database are ignored.
+pub fn set_isins(conn: &Connection, isins: &HashMap<String, String>) -> std::result::Result<usize, Box<dyn Error>> {
+    let mut normalized = Vec::with_capacity(isins.len());
+    for (symbol, isin) in isins {
+        let isin = isin.trim().to_ascii_uppercase();
+        if !is_valid_isin(&isin) {
+            return Err(format!("Invalid ISIN for {}: {}", symbol, isin).into());
+        }
+        normalized.push((symbol.trim(), isin));
+    }
+    ensure_isin_column(conn)?;
+
+    let tx = conn.unchecked_transaction()?;
+    let mut count = 0;
+    {
+        let mut stmt = tx.prepare("UPDATE symbols SET isin = ? WHERE symbol = ?")?;
+        for (symbol, isin) in &normalized {
+            count += stmt.execute(params![isin, symbol])?;
+        }
+    }
+    tx.commit()?;
+
+    Ok(count)
+}
+
+/// Reads the ISIN of every ticker that has one, or nothing if the database predates the `isin` column
+pub fn read_isins(conn: &Connection) -> Result<HashMap<String, String>> {
+    let has_column = conn.prepare("SELECT 1 FROM pragma_table_info('symbols') WHERE name = 'isin'")?.exists([])?;
+    if !has_column {
+        return Ok(HashMap::new());
+    }
+
+    let mut stmt = conn.prepare("SELECT symbol, isin FROM symbols WHERE isin IS NOT NULL ORDER BY rowid")?;
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+    let mut isins = HashMap::new();
+    for row in rows {
+        let (symbol, isin) = row?;
+        isins.entry(symbol).or_insert(isin);
+    }
+    Ok(isins)
+}
+
+/// Reads every user-imported row, or nothing if the database predates the `source` column
+pub fn read_user_symbols(conn: &Connection) -> Result<Vec<Symbol>> {
+    if !has_source_column(conn)? {
+        return Ok(Vec::new());
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT symbol, name, category, asset_class, exchange FROM symbols WHERE source = ?",
+    )?;
+    let rows = stmt.query_map([USER_SOURCE], |row| {
+        Ok(Symbol {
+            symbol: row.get(0)?,
+            name: row.get(1)?,
+            category: row.get(2)?,
+            asset_class: row.get(3)?,
+            exchange: row.get(4)?,
+        })
+    })?;
+
+    rows.collect()
+}
+
+/// Inserts or replaces the given symbols as user rows, returning how many were written
+pub fn import_into(conn: &Connection, symbols: &[Symbol]) -> Result<usize> {
+    ensure_source_column(conn)?;
+
+    let tx = conn.unchecked_transaction()?;
+    let mut count = 0;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT OR REPLACE INTO symbols (symbol, name, category, asset_class, exchange, source) VALUES (?, ?, ?, ?, ?, ?)",
+        )?;
+        for symbol in symbols {
+            count += stmt.execute(params![
+                &symbol.symbol,
+                &symbol.name,
+                &symbol.category,
+                &symbol.asset_class,
+                &symbol.exchange,
+                USER_SOURCE
+            ])?;
+        }
+    }
+    tx.commit()?;
+
+    Ok(count)
+}
+
+/// Reads symbols from CSV text with a `symbol,name,category,asset_class,exchange` header
+///
+/// Columns may appear in any order and extra columns are ignored.
+pub fn symbols_from_csv(input: &str) -> Result<Vec<Symbol>, Box<dyn Error>> {
+    let mut records = parse_csv(input)?.into_iter();
+    let header = records.next().ok_or("CSV file is empty")?;
+
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(name))
+            .ok_or(format!("CSV header is missing the `{}` column", name))
+    };
+    let (symbol, name, category, asset_class, exchange) =
+        (column("symbol")?, column("name")?, column("category")?, column("asset_class")?, column("exchange")?);
+
+    let field = |record: &[String], index: usize| record.get(index).map(|f| f.trim().to_string()).unwrap_or_default();
+
+    let symbols = records
+        .map(|record| Symbol {
+            symbol: field(&record, symbol),
+            name: field(&record, name),
+            category: field(&record, category),
+            asset_class: field(&record, asset_class),
+            exchange: field(&record, exchange),
+        })
+        .filter(|s| !s.symbol.is_empty())
+        .collect();
+
+    Ok(symbols)
+}
+
+/// Upserts user-provided symbols into the database
+///
+/// Rows are written with `source = "user"`; an existing row with the same key, the symbol and
+/// asset class, is replaced.
+///
+/// # Arguments
+///
+/// * `symbols` - symbols to import
+///
+/// # Returns
+///
+/// * `usize` - number of rows written
+///
+/// # Example
+///
+/// ```no_run
+/// use yahoo_finance_symbols::{import_symbols, Symbol};
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let symbol = Symbol {
+///         symbol: "MYCO".to_string(),
+///         name: "My Private Company".to_string(),
+///         category: "N/A".to_string(),
+///         asset_class: "Stocks".to_string(),
+///         exchange: "PNK".to_string(),
+///     };
+///     let count = import_symbols(vec![symbol]).await?;
+///     println!("{}", count);
+///     Ok(())
+/// }
+/// ```
+pub async fn import_symbols(symbols: Vec<Symbol>) -> Result<usize> {
+    crate::default_database().await?.import_symbols(symbols).await
+}
+
+/// Upserts user-provided symbols from a CSV file into the database
+///
+/// The file needs a header row naming the `symbol`, `name`, `category`, `asset_class` and
+/// `exchange` columns. Rows are written with `source = "user"`.
+///
+/// # Arguments
+///
+/// * `path` - path of the CSV file
+///
+/// # Returns
+///
+/// * `usize` - number of rows written
+pub async fn import_symbols_from_csv(path: impl AsRef<Path>) -> Result<usize, Box<dyn Error>> {
+    let contents = fs::read_to_string(path)?;
+    let symbols = symbols_from_csv(&contents)?;
+    Ok(import_symbols(symbols).await?)
+}
+
+/// Reads a ticker-to-ISIN mapping from CSV text with a `symbol,isin` header
+///
+/// Columns may appear in any order and extra columns are ignored; rows without an ISIN are skipped.
+pub fn isins_from_csv(input: &str) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let mut records = parse_csv(input)?.into_iter();
+    let header = records.next().ok_or("CSV file is empty")?;
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(name))
+            .ok_or(format!("CSV header is missing the `{}` column", name))
+    };
+    let (symbol, isin) = (column("symbol")?, column("isin")?);
+
+    let field = |record: &[String], index: usize| record.get(index).map(|f| f.trim().to_string()).unwrap_or_default();
+    Ok(records
+        .map(|record| (field(&record, symbol), field(&record, isin)))
+        .filter(|(symbol, isin)| !symbol.is_empty() && !isin.is_empty())
+        .collect())
+}
+
+/// Stores user-supplied ISINs for the symbols in the database
+///
+/// Yahoo Finance does not provide ISINs, so the `isin` column is NULL until a mapping is loaded.
+/// Every listing of a mapped ticker gets its ISIN, which `get_symbol_by_isin` then finds. The
+/// mapping is kept across `update_database`; importing a listing again with `import_symbols`
+/// replaces the row and clears its ISIN, so load the mapping after the symbols.
+///
+/// # Arguments
+///
+/// * `isins` - ISIN of each ticker, e.g. `"SAP.DE"` to `"DE0007164600"`
+///
+/// # Returns
+///
+/// * `usize` - number of rows updated
+///
+/// # Errors
+///
+/// Fails without writing anything if any ISIN is malformed or has a wrong check digit.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::collections::HashMap;
+/// use yahoo_finance_symbols::{get_symbol_by_isin, import_isins};
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let isins = HashMap::from([("AAPL".to_string(), "US0378331005".to_string())]);
+///     import_isins(isins).await?;
+///     println!("{:?}", get_symbol_by_isin("US0378331005").await?);
+///     Ok(())
+/// }
+/// ```
+pub async fn import_isins(isins: HashMap<String, String>) -> Result<usize, Box<dyn Error>> {
+    crate::default_database().await?.import_isins(isins).await
+}
+
+/// Stores the ISINs of a CSV file with a `symbol,isin` header, as `import_isins` does
+///
+/// # Arguments
+///
+/// * `path` - path of the CSV file
+///
+/// # Returns
+///
+/// * `usize` - number of rows updated
+pub async fn import_isins_from_csv(path: impl AsRef<Path>) -> Result<usize, Box<dyn Error>> {
+    let contents = fs::read_to_string(path)?;
+    import_isins(isins_from_csv(&contents)?).await
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use rusqlite::Connection;
+    use super::{import_into, is_valid_isin, isins_from_csv, read_isins, set_isins, symbols_from_csv, USER_SOURCE, YAHOO_SOURCE};
+
+    #[test]
+    fn check_import_symbols() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE symbols (symbol TEXT PRIMARY KEY, name TEXT, category TEXT, asset_class TEXT, exchange TEXT);
+             INSERT INTO symbols VALUES ('AAPL', 'Apple Inc.', 'Technology', 'Stocks', 'NMS');",
+        ).unwrap();
+
+        let symbols = symbols_from_csv(
+            "exchange,symbol,name,category,asset_class\nPNK,MYCO,\"My Company, Ltd\",N/A,Stocks\nPNK,OTHR,Other,N/A,Stocks\n",
+        ).unwrap();
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(symbols[0].name, "My Company, Ltd");
+
+        assert_eq!(import_into(&conn, &symbols).unwrap(), 2);
+        assert_eq!(import_into(&conn, &symbols[..1]).unwrap(), 1);
+
+        let source: String = conn.query_row("SELECT source FROM symbols WHERE symbol = 'MYCO'", [], |row| row.get(0)).unwrap();
+        assert_eq!(source, USER_SOURCE);
+        let source: String = conn.query_row("SELECT source FROM symbols WHERE symbol = 'AAPL'", [], |row| row.get(0)).unwrap();
+        assert_eq!(source, YAHOO_SOURCE);
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM symbols", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 3);
+
+        assert!(symbols_from_csv("symbol,name\nAAPL,Apple").is_err());
+    }
+
+    #[test]
+    fn check_isins() {
+        for valid in ["US0378331005", "US5949181045", "DE0007164600", "GB0002634946", "AU0000XVGZA3"] {
+            assert!(is_valid_isin(valid), "{}", valid);
+        }
+        for invalid in ["US0378331006", "US037833100", "us0378331005", "1S0378331005", "US03783310A5"] {
+            assert!(!is_valid_isin(invalid), "{}", invalid);
+        }
+
+        // A database written before the column existed
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE symbols (symbol TEXT, name TEXT, category TEXT, asset_class TEXT, exchange TEXT);
+             INSERT INTO symbols VALUES ('AAPL', 'Apple Inc.', 'Technology', 'Stocks', 'NMS');",
+        ).unwrap();
+        assert!(read_isins(&conn).unwrap().is_empty());
+
+        let isins = isins_from_csv("isin,symbol\nUS0378331005,AAPL\n,MSFT\n").unwrap();
+        assert_eq!(isins.len(), 1);
+        assert_eq!(set_isins(&conn, &isins).unwrap(), 1);
+        assert_eq!(read_isins(&conn).unwrap(), isins);
+        assert!(isins_from_csv("symbol\nAAPL").is_err());
+    }
+}
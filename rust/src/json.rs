@@ -0,0 +1,96 @@
+//! Human-readable backup of the `symbols` table as a single JSON array of [`Symbol`] objects.
+//!
+//! Only the `Symbol` fields are written, so a restored table tags every row with the default
+//! `source`; re-import user rows with `import_symbols` if that distinction matters.
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use rusqlite::{params, Connection};
+use crate::database::symbol_from_row;
+use crate::scraper::create_symbols_table;
+use crate::Symbol;
+
+
+/// Writes every symbol behind `conn` to `path` as a JSON array, returning how many were written
+pub fn dump_connection(conn: &Connection, path: &Path) -> Result<usize, Box<dyn Error>> {
+    let mut stmt = conn.prepare("SELECT symbol, name, category, asset_class, exchange FROM symbols ORDER BY symbol")?;
+    let symbols = stmt.query_map([], symbol_from_row)?.collect::<rusqlite::Result<Vec<Symbol>>>()?;
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut writer, &symbols)?;
+    writer.flush()?;
+
+    Ok(symbols.len())
+}
+
+/// Replaces the `symbols` table behind `conn` with the JSON array at `path`, returning how many
+/// symbols were loaded
+///
+/// The file is parsed before the table is touched, and the swap happens in one transaction, so a
+/// malformed file leaves the existing rows in place.
+pub fn load_connection(conn: &Connection, path: &Path) -> Result<usize, Box<dyn Error>> {
+    let symbols: Vec<Symbol> = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+
+    let tx = conn.unchecked_transaction()?;
+    create_symbols_table(&tx)?;
+    tx.execute("DELETE FROM symbols", [])?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT OR REPLACE INTO symbols (symbol, name, category, asset_class, exchange) VALUES (?, ?, ?, ?, ?)",
+        )?;
+        for symbol in &symbols {
+            stmt.execute(params![&symbol.symbol, &symbol.name, &symbol.category, &symbol.asset_class, &symbol.exchange])?;
+        }
+    }
+    tx.commit()?;
+
+    Ok(symbols.len())
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use std::fs;
+    use rusqlite::Connection;
+    use crate::scraper::create_symbols_table;
+    use super::{dump_connection, load_connection};
+
+    fn rows(conn: &Connection) -> Vec<(String, String, String, String, String)> {
+        conn.prepare("SELECT symbol, name, category, asset_class, exchange FROM symbols ORDER BY symbol").unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?))).unwrap()
+            .collect::<rusqlite::Result<_>>().unwrap()
+    }
+
+    #[test]
+    fn check_json_round_trip() {
+        let path = std::env::temp_dir().join(format!("yahoo-finance-symbols-json-{}.json", std::process::id()));
+        let original = Connection::open_in_memory().unwrap();
+        create_symbols_table(&original).unwrap();
+        original.execute_batch(
+            "INSERT INTO symbols (symbol, name, category, asset_class, exchange) VALUES
+                 ('AAPL', 'Apple Inc.', 'Technology', 'Stocks', 'NMS'),
+                 ('SPY', 'SPDR S&P 500 ETF \"Trust\"', 'N/A', 'ETF', 'PCX'),
+                 ('^GSPC', 'S&P 500', 'N/A', 'Index', 'SNP');",
+        ).unwrap();
+
+        assert_eq!(dump_connection(&original, &path).unwrap(), 3);
+        let dumped: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(dumped[0]["symbol"], "AAPL");
+
+        // Loading replaces whatever the target table held before
+        let restored = Connection::open_in_memory().unwrap();
+        create_symbols_table(&restored).unwrap();
+        restored.execute("INSERT INTO symbols (symbol, name) VALUES ('OLD', 'Stale row')", []).unwrap();
+        assert_eq!(load_connection(&restored, &path).unwrap(), 3);
+        assert_eq!(rows(&restored), rows(&original));
+
+        fs::write(&path, "[{\"symbol\": \"AAPL\"").unwrap();
+        assert!(load_connection(&restored, &path).is_err());
+        assert_eq!(rows(&restored).len(), 3);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
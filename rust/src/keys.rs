@@ -1,3 +1,21 @@
+use std::fmt;
+use std::error::Error;
+use std::str::FromStr;
+
+
+/// Error returned when a stored string does not match any known key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseKeyError(pub String);
+
+impl fmt::Display for ParseKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown key: {}", self.0)
+    }
+}
+
+impl Error for ParseKeyError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AssetClass {
     Stocks,
     ETFs,
@@ -6,9 +24,21 @@ pub enum AssetClass {
     Futures,
     Currencies,
     Cryptocurrencies,
-    All
+    /// Equity and index option contracts, quoted on the OPRA feed (exchange `OPR`)
+    Options,
+    /// Every stored asset class
+    All,
+    /// The curated set most symbol searches want: stocks, ETFs, mutual funds and indices
+    ///
+    /// Leaves out futures, currencies, cryptocurrencies and options, whose many look-alike
+    /// tickers (`ES=F`, `EURUSD=X`, `BTC-USD`, `AAPL240119C00150000`) tend to crowd out the
+    /// instruments a search UI is after. See `COMMON_ASSET_CLASSES`.
+    Common,
 }
 
+/// The asset classes `AssetClass::Common` stands for
+pub const COMMON_ASSET_CLASSES: [AssetClass; 4] = [AssetClass::Stocks, AssetClass::ETFs, AssetClass::MutualFunds, AssetClass::Indices];
+
 impl AssetClass {
     pub async fn to_string_vec(&self) -> Vec<String> {
         match self {
@@ -19,11 +49,141 @@ impl AssetClass {
             AssetClass::Futures => vec!["Future".to_string()],
             AssetClass::Currencies => vec!["Currency".to_string()],
             AssetClass::Cryptocurrencies => vec!["CRYPTOCURRENCY".to_string()],
+            AssetClass::Options => vec!["Option".to_string()],
+            #[cfg(feature = "full")]
             AssetClass::All => crate::get_distinct_asset_classes().await.unwrap(),
+            #[cfg(not(feature = "full"))]
+            AssetClass::All => crate::lite::distinct_values("asset_class").unwrap_or_default(),
+            AssetClass::Common => {
+                let mut classes = Vec::new();
+                for asset_class in COMMON_ASSET_CLASSES {
+                    classes.extend(Box::pin(asset_class.to_string_vec()).await);
+                }
+                classes
+            }
+        }
+    }
+
+    /// Whether Yahoo Finance assigns a sector category to some symbols of this asset class.
+    ///
+    /// Only stocks carry a category; every other asset class is stored as "N/A",
+    /// so category filters are ignored for them. `All` and `Common` span stocks as well as
+    /// uncategorized classes, so they apply the category to their stocks only; see
+    /// [`AssetClass::category_condition`].
+    pub fn has_categories(&self) -> bool {
+        matches!(self, AssetClass::Stocks | AssetClass::Common | AssetClass::All)
+    }
+
+    /// SQL condition keeping the rows of this asset class in one of `codes` categories, with one
+    /// `?` placeholder per code
+    ///
+    /// For `All` and `Common` the condition only applies to stock rows and keeps the rest.
+    pub(crate) fn category_condition(&self, codes: usize) -> String {
+        let placeholders = vec!["?"; codes].join(",");
+        if *self == AssetClass::Stocks {
+            format!("category IN ({})", placeholders)
+        } else {
+            format!("(asset_class IS NOT 'Stocks' OR category IN ({}))", placeholders)
+        }
+    }
+}
+
+impl FromStr for AssetClass {
+    type Err = ParseKeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Stocks" => Ok(AssetClass::Stocks),
+            "ETF" => Ok(AssetClass::ETFs),
+            "Mutual Fund" => Ok(AssetClass::MutualFunds),
+            "Index" => Ok(AssetClass::Indices),
+            "Future" => Ok(AssetClass::Futures),
+            "Currency" => Ok(AssetClass::Currencies),
+            "CRYPTOCURRENCY" => Ok(AssetClass::Cryptocurrencies),
+            "Option" => Ok(AssetClass::Options),
+            _ => Err(ParseKeyError(s.to_string())),
         }
     }
 }
 
+/// What kind of instrument a symbol quotes, as classified by `SymbolKind::classify`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SymbolKind {
+    Equity,
+    Etf,
+    MutualFund,
+    Index,
+    Currency,
+    Future,
+    Crypto,
+    Option,
+    Unknown,
+}
+
+impl SymbolKind {
+    /// Classifies a ticker from its stored asset class and the conventions of Yahoo Finance tickers
+    ///
+    /// Yahoo Finance derives some tickers from the kind of instrument, and those conventions win
+    /// over the asset class a lookup page tagged the row with:
+    ///
+    /// * `=X` suffix (`EURUSD=X`): `Currency`
+    /// * `=F` suffix (`ES=F`): `Future`
+    /// * `^` prefix (`^GSPC`, and the `^SMH`-style indicative values of ETFs): `Index`
+    /// * an OCC contract code, i.e. a root followed by the expiry as `YYMMDD`, `C` or `P` and
+    ///   the strike in eight digits (`AAPL240119C00150000`): `Option`
+    ///
+    /// Any other ticker takes the kind of its asset class, and `Unknown` if that is not one of
+    /// the stored asset classes (see `AssetClass`).
+    pub fn classify(symbol: &str, asset_class: &str) -> SymbolKind {
+        if symbol.ends_with("=X") {
+            return SymbolKind::Currency;
+        }
+        if symbol.ends_with("=F") {
+            return SymbolKind::Future;
+        }
+        if symbol.starts_with('^') {
+            return SymbolKind::Index;
+        }
+        if is_option_contract(symbol) {
+            return SymbolKind::Option;
+        }
+
+        match asset_class.parse() {
+            Ok(AssetClass::Stocks) => SymbolKind::Equity,
+            Ok(AssetClass::ETFs) => SymbolKind::Etf,
+            Ok(AssetClass::MutualFunds) => SymbolKind::MutualFund,
+            Ok(AssetClass::Indices) => SymbolKind::Index,
+            Ok(AssetClass::Futures) => SymbolKind::Future,
+            Ok(AssetClass::Currencies) => SymbolKind::Currency,
+            Ok(AssetClass::Cryptocurrencies) => SymbolKind::Crypto,
+            Ok(AssetClass::Options) => SymbolKind::Option,
+            Ok(AssetClass::All | AssetClass::Common) | Err(_) => SymbolKind::Unknown,
+        }
+    }
+}
+
+/// Whether `symbol` is an OCC option code: a root, `YYMMDD`, `C` or `P`, and an eight-digit strike
+fn is_option_contract(symbol: &str) -> bool {
+    let bytes = symbol.as_bytes();
+    if bytes.len() <= 15 {
+        return false;
+    }
+    let (root, contract) = bytes.split_at(bytes.len() - 15);
+    root.iter().all(u8::is_ascii_alphanumeric)
+        && contract[..6].iter().all(u8::is_ascii_digit)
+        && matches!(contract[6], b'C' | b'P')
+        && contract[7..].iter().all(u8::is_ascii_digit)
+}
+
+
+/// Category stored for symbols Yahoo Finance gives no sector, including every non-stock symbol
+pub const NA_CATEGORY: &str = "N/A";
+
+/// Sector categories, as assigned by Yahoo Finance.
+///
+/// Categories are only populated for `AssetClass::Stocks`; ETFs, mutual funds, indices,
+/// futures, currencies, cryptocurrencies and options are all stored with the "N/A" category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Category {
     ConsumerCyclical,
     CommunicationServices,
@@ -59,18 +219,50 @@ impl Category {
             Category::Healthcare => vec!["Healthcare".to_string()],
             Category::Energy => vec!["Energy".to_string()],
             Category::Industrials => vec!["Industrials".to_string()],
-            Category::NA => vec!["N/A".to_string()],
+            Category::NA => vec![NA_CATEGORY.to_string()],
             Category::Services => vec!["Services".to_string()],
             Category::Financial => vec!["Financial".to_string()],
             Category::IndustrialGoods => vec!["Industrial Goods".to_string()],
             Category::ConsumerGoods => vec!["Consumer Goods".to_string()],
             Category::Conglomerates => vec!["Conglomerates".to_string()],
+            #[cfg(feature = "full")]
             Category::All => crate::get_distinct_categories().await.unwrap(),
+            #[cfg(not(feature = "full"))]
+            Category::All => crate::lite::distinct_values("category").unwrap_or_default(),
         }
     }
 }
 
+impl FromStr for Category {
+    type Err = ParseKeyError;
 
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Consumer Cyclical" => Ok(Category::ConsumerCyclical),
+            "Communication Services" => Ok(Category::CommunicationServices),
+            "Financial Services" => Ok(Category::FinancialServices),
+            "Real Estate" => Ok(Category::RealEstate),
+            "Basic Materials" => Ok(Category::BasicMaterials),
+            "Utilities" => Ok(Category::Utilities),
+            "Technology" => Ok(Category::Technology),
+            "Consumer Defensive" => Ok(Category::ConsumerDefensive),
+            "Healthcare" => Ok(Category::Healthcare),
+            "Energy" => Ok(Category::Energy),
+            "Industrials" => Ok(Category::Industrials),
+            NA_CATEGORY => Ok(Category::NA),
+            "Services" => Ok(Category::Services),
+            "Financial" => Ok(Category::Financial),
+            "Industrial Goods" => Ok(Category::IndustrialGoods),
+            "Consumer Goods" => Ok(Category::ConsumerGoods),
+            "Conglomerates" => Ok(Category::Conglomerates),
+            _ => Err(ParseKeyError(s.to_string())),
+        }
+    }
+}
+
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Exchange {
     NewYorkStockExchange,     // NYQ
     NASDAQ,                   // NMS
@@ -180,6 +372,9 @@ pub enum Exchange {
     CaracasStockExchange,     // CCS
     OPI,                    // OPI
     Euronext,                 // ENX
+    OPRA,                     // OPR
+    /// All three NASDAQ listing tiers: Global Select (NMS), Global Market (NGM) and Capital Market (NCM)
+    NASDAQAllTiers,
     All,
 }
 
@@ -295,7 +490,335 @@ impl Exchange {
             Exchange::CaracasStockExchange => vec!["CCS".to_string()],
             Exchange::OPI => vec!["OPI".to_string()],
             Exchange::Euronext => vec!["ENX".to_string()],
+            Exchange::OPRA => vec!["OPR".to_string()],
+            Exchange::NASDAQAllTiers => vec!["NMS".to_string(), "NGM".to_string(), "NCM".to_string()],
+            #[cfg(feature = "full")]
             Exchange::All => crate::get_distinct_exchanges().await.unwrap(),
+            #[cfg(not(feature = "full"))]
+            Exchange::All => crate::lite::distinct_values("exchange").unwrap_or_default(),
         }
     }
-}
\ No newline at end of file
+}
+
+/// Human-readable names of the most common Yahoo Finance exchange codes
+const EXCHANGE_DISPLAY_NAMES: &[(&str, &str)] = &[
+    ("NMS", "NASDAQ"),
+    ("NGM", "NASDAQ Global Market"),
+    ("NCM", "NASDAQ Capital Market"),
+    ("NAS", "NASDAQ"),
+    ("NYQ", "NYSE"),
+    ("NYS", "NYSE"),
+    ("PCX", "NYSE Arca"),
+    ("ASE", "NYSE American"),
+    ("NIM", "NYSE American"),
+    ("PNK", "OTC Markets"),
+    ("OBB", "OTC Bulletin Board"),
+    ("OPR", "OPRA"),
+    ("CBO", "Cboe"),
+    ("CME", "CME"),
+    ("CBT", "CBOT"),
+    ("NYM", "NYMEX"),
+    ("CMX", "COMEX"),
+    ("NYB", "ICE Futures US"),
+    ("SNP", "S&P Indices"),
+    ("DJI", "Dow Jones Indices"),
+    ("CCY", "Currencies"),
+    ("CCC", "Cryptocurrencies"),
+    ("TOR", "Toronto Stock Exchange"),
+    ("VAN", "TSX Venture Exchange"),
+    ("NEO", "Cboe Canada"),
+    ("LSE", "London Stock Exchange"),
+    ("GER", "Xetra"),
+    ("FRA", "Frankfurt Stock Exchange"),
+    ("PAR", "Euronext Paris"),
+    ("AMS", "Euronext Amsterdam"),
+    ("BRU", "Euronext Brussels"),
+    ("LIS", "Euronext Lisbon"),
+    ("MIL", "Borsa Italiana"),
+    ("ZRH", "SIX Swiss Exchange"),
+    ("STO", "Nasdaq Stockholm"),
+    ("CPH", "Nasdaq Copenhagen"),
+    ("HEL", "Nasdaq Helsinki"),
+    ("OSL", "Oslo Stock Exchange"),
+    ("JPX", "Tokyo Stock Exchange"),
+    ("HKG", "Hong Kong Stock Exchange"),
+    ("SHH", "Shanghai Stock Exchange"),
+    ("SHZ", "Shenzhen Stock Exchange"),
+    ("KSC", "Korea Exchange"),
+    ("TAI", "Taiwan Stock Exchange"),
+    ("BSE", "Bombay Stock Exchange"),
+    ("ASX", "Australian Securities Exchange"),
+    ("SAO", "B3"),
+    ("MEX", "Mexican Stock Exchange"),
+];
+
+/// Human-readable name of a Yahoo Finance exchange code, or `None` if it is not in the table
+pub fn exchange_display_name(code: &str) -> Option<&'static str> {
+    EXCHANGE_DISPLAY_NAMES.iter().find(|(c, _)| *c == code).map(|(_, name)| *name)
+}
+
+/// Popularity tiers of exchange codes, lower first: the primary US listings, the other US
+/// venues, the main foreign primary exchanges and, last, the OTC markets
+const EXCHANGE_TIERS: &[(&str, u8)] = &[
+    ("NMS", 0),
+    ("NYQ", 0),
+    ("NGM", 1),
+    ("NCM", 1),
+    ("NAS", 1),
+    ("PCX", 1),
+    ("ASE", 1),
+    ("NIM", 1),
+    ("BTS", 1),
+    ("LSE", 2),
+    ("TOR", 2),
+    ("GER", 2),
+    ("PAR", 2),
+    ("AMS", 2),
+    ("ZRH", 2),
+    ("MIL", 2),
+    ("JPX", 2),
+    ("HKG", 2),
+    ("SHH", 2),
+    ("SHZ", 2),
+    ("KSC", 2),
+    ("TAI", 2),
+    ("ASX", 2),
+    ("PNK", 4),
+    ("OBB", 4),
+    ("OEM", 4),
+    ("OQB", 4),
+    ("OQX", 4),
+];
+
+/// Tier of the exchange codes missing from the tier table, e.g. secondary German listings
+pub const DEFAULT_EXCHANGE_TIER: u8 = 3;
+
+/// Popularity tier of a Yahoo Finance exchange code, from 0 for NASDAQ and the NYSE to 4 for OTC markets
+///
+/// Without trading volumes, where a symbol is listed approximates how widely it is followed:
+/// 1 covers the other US venues (NASDAQ Global and Capital Markets, NYSE Arca and American,
+/// Cboe BZX), 2 the largest foreign primary exchanges, and codes not in the table get
+/// `DEFAULT_EXCHANGE_TIER`.
+pub fn exchange_tier(code: &str) -> u8 {
+    EXCHANGE_TIERS.iter().find(|(c, _)| *c == code).map_or(DEFAULT_EXCHANGE_TIER, |(_, tier)| *tier)
+}
+
+/// `exchange_tier` of the `exchange` column, as an SQL expression
+#[cfg(feature = "full")]
+pub(crate) fn exchange_tier_sql() -> String {
+    let cases: String = EXCHANGE_TIERS.iter().map(|(code, tier)| format!(" WHEN '{}' THEN {}", code, tier)).collect();
+    format!("CASE exchange{} ELSE {} END", cases, DEFAULT_EXCHANGE_TIER)
+}
+
+/// A time of day on an exchange's local clock
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MarketTime {
+    pub hour: u8,
+    pub minute: u8,
+}
+
+impl MarketTime {
+    pub const fn new(hour: u8, minute: u8) -> MarketTime {
+        MarketTime { hour, minute }
+    }
+}
+
+impl fmt::Display for MarketTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour, self.minute)
+    }
+}
+
+/// Regular trading session of an exchange, on weekdays, in the exchange's local time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MarketHours {
+    /// Start of continuous trading
+    pub open: MarketTime,
+    /// End of continuous trading
+    pub close: MarketTime,
+    /// IANA time zone the times are given in, e.g. `America/New_York`
+    pub timezone: &'static str,
+}
+
+impl MarketHours {
+    const fn new(timezone: &'static str, open: (u8, u8), close: (u8, u8)) -> MarketHours {
+        MarketHours { open: MarketTime::new(open.0, open.1), close: MarketTime::new(close.0, close.1), timezone }
+    }
+
+    /// Whether the local time `time` falls within the session, from the open up to but excluding the close
+    pub fn is_open_at(&self, time: MarketTime) -> bool {
+        self.open <= time && time < self.close
+    }
+}
+
+/// Regular sessions of the major exchanges, by Yahoo Finance exchange code
+const EXCHANGE_MARKET_HOURS: &[(&str, MarketHours)] = &[
+    ("NMS", MarketHours::new("America/New_York", (9, 30), (16, 0))),
+    ("NGM", MarketHours::new("America/New_York", (9, 30), (16, 0))),
+    ("NCM", MarketHours::new("America/New_York", (9, 30), (16, 0))),
+    ("NAS", MarketHours::new("America/New_York", (9, 30), (16, 0))),
+    ("NYQ", MarketHours::new("America/New_York", (9, 30), (16, 0))),
+    ("NYS", MarketHours::new("America/New_York", (9, 30), (16, 0))),
+    ("PCX", MarketHours::new("America/New_York", (9, 30), (16, 0))),
+    ("ASE", MarketHours::new("America/New_York", (9, 30), (16, 0))),
+    ("NIM", MarketHours::new("America/New_York", (9, 30), (16, 0))),
+    ("TOR", MarketHours::new("America/Toronto", (9, 30), (16, 0))),
+    ("VAN", MarketHours::new("America/Toronto", (9, 30), (16, 0))),
+    ("NEO", MarketHours::new("America/Toronto", (9, 30), (16, 0))),
+    ("MEX", MarketHours::new("America/Mexico_City", (8, 30), (15, 0))),
+    ("LSE", MarketHours::new("Europe/London", (8, 0), (16, 30))),
+    ("IOB", MarketHours::new("Europe/London", (8, 0), (16, 30))),
+    ("GER", MarketHours::new("Europe/Berlin", (9, 0), (17, 30))),
+    ("PAR", MarketHours::new("Europe/Paris", (9, 0), (17, 30))),
+    ("AMS", MarketHours::new("Europe/Amsterdam", (9, 0), (17, 30))),
+    ("BRU", MarketHours::new("Europe/Brussels", (9, 0), (17, 30))),
+    ("LIS", MarketHours::new("Europe/Lisbon", (8, 0), (16, 30))),
+    ("MIL", MarketHours::new("Europe/Rome", (9, 0), (17, 30))),
+    ("ZRH", MarketHours::new("Europe/Zurich", (9, 0), (17, 30))),
+    ("STO", MarketHours::new("Europe/Stockholm", (9, 0), (17, 30))),
+    ("CPH", MarketHours::new("Europe/Copenhagen", (9, 0), (17, 0))),
+    ("HEL", MarketHours::new("Europe/Helsinki", (10, 0), (18, 30))),
+    ("OSL", MarketHours::new("Europe/Oslo", (9, 0), (16, 20))),
+    ("JNB", MarketHours::new("Africa/Johannesburg", (9, 0), (17, 0))),
+    ("JPX", MarketHours::new("Asia/Tokyo", (9, 0), (15, 30))),
+    ("HKG", MarketHours::new("Asia/Hong_Kong", (9, 30), (16, 0))),
+    ("SHH", MarketHours::new("Asia/Shanghai", (9, 30), (15, 0))),
+    ("SHZ", MarketHours::new("Asia/Shanghai", (9, 30), (15, 0))),
+    ("KSC", MarketHours::new("Asia/Seoul", (9, 0), (15, 30))),
+    ("TAI", MarketHours::new("Asia/Taipei", (9, 0), (13, 30))),
+    ("SES", MarketHours::new("Asia/Singapore", (9, 0), (17, 0))),
+    ("BSE", MarketHours::new("Asia/Kolkata", (9, 15), (15, 30))),
+    ("ASX", MarketHours::new("Australia/Sydney", (10, 0), (16, 0))),
+    ("NZE", MarketHours::new("Pacific/Auckland", (10, 0), (16, 45))),
+];
+
+/// Regular trading session of a Yahoo Finance exchange code, or `None` if it is not in the table
+///
+/// Covers the stock exchanges of North America, Western Europe and the largest markets of Asia
+/// Pacific, plus Johannesburg; futures, options, OTC, index, currency and crypto codes have no
+/// fixed session and return `None`, as do unknown codes. The times are those of the continuous
+/// session on weekdays: lunch breaks (Tokyo, Hong Kong, Shanghai, Shenzhen), auctions,
+/// extended hours and holidays are not modelled. Convert the current time to `timezone` before
+/// calling `MarketHours::is_open_at`.
+pub fn exchange_market_hours(exchange: &str) -> Option<MarketHours> {
+    EXCHANGE_MARKET_HOURS.iter().find(|(code, _)| *code == exchange).map(|(_, hours)| *hours)
+}
+
+impl FromStr for Exchange {
+    type Err = ParseKeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "NYQ" => Ok(Exchange::NewYorkStockExchange),
+            "NMS" => Ok(Exchange::NASDAQ),
+            "STO" => Ok(Exchange::StockholmStockExchange),
+            "DJI" => Ok(Exchange::DowJonesIndices),
+            "NCM" => Ok(Exchange::NasdaqCapitalMarket),
+            "NGM" => Ok(Exchange::NasdaqGlobalMarket),
+            "CCY" => Ok(Exchange::Currencies),
+            "CCC" => Ok(Exchange::Crytpocurrencies),
+            "PCX" => Ok(Exchange::NYSEArca),
+            "NIM" => Ok(Exchange::NYSEAmerican),
+            "NYM" => Ok(Exchange::NewYorkMercantileExchange),
+            "CMX" => Ok(Exchange::COMEX),
+            "CBT" => Ok(Exchange::ChicagoBoardofTrade),
+            "CME" => Ok(Exchange::ChicagoMercantileExchange),
+            "PNK" => Ok(Exchange::PinkOpenMarket),
+            "TOR" => Ok(Exchange::TorontoStockExchange),
+            "ASE" => Ok(Exchange::NYSEAmericanOptions),
+            "NYB" => Ok(Exchange::NewYorkBoardofTrade),
+            "SNP" => Ok(Exchange::SNPIndices),
+            "WCB" => Ok(Exchange::WestCoastBoardofTrade),
+            "BTS" => Ok(Exchange::BTS),
+            "CXI" => Ok(Exchange::CurrencyExchangeInternational),
+            "NAS" => Ok(Exchange::NASDAQStockMarket),
+            "NSI" => Ok(Exchange::NagoyaStockExchange),
+            "LSE" => Ok(Exchange::LondonStockExchange),
+            "GER" => Ok(Exchange::Xetra),
+            "BER" => Ok(Exchange::BerlinStockExchange),
+            "DUS" => Ok(Exchange::DusseldorfStockExchange),
+            "PAR" => Ok(Exchange::EuronextParis),
+            "NYS" => Ok(Exchange::NewYorkStockExchangeARCA),
+            "IOB" => Ok(Exchange::LondonIOB),
+            "ZRH" => Ok(Exchange::SIXSwissExchange),
+            "BUE" => Ok(Exchange::BuenosAiresStockExchange),
+            "BSE" => Ok(Exchange::BombayStockExchange),
+            "ASX" => Ok(Exchange::AustralianSecuritiesExchange),
+            "VAN" => Ok(Exchange::VancouverStockExchange),
+            "AMS" => Ok(Exchange::AmsterdamStockExchange),
+            "JPX" => Ok(Exchange::JapanExchangeGroup),
+            "CNQ" => Ok(Exchange::CanadianNationalStockExchange),
+            "FRA" => Ok(Exchange::FrankfurtStockExchange),
+            "MUN" => Ok(Exchange::MunichStockExchange),
+            "IST" => Ok(Exchange::IstanbulStockExchange),
+            "MEX" => Ok(Exchange::MexicanStockExchange),
+            "MIL" => Ok(Exchange::MilanStockExchange),
+            "NZE" => Ok(Exchange::NewZealandStockExchange),
+            "SAO" => Ok(Exchange::SaoPauloStockExchange),
+            "KSC" => Ok(Exchange::KoreaStockExchange),
+            "FGI" => Ok(Exchange::FukuokaStockExchange),
+            "HKG" => Ok(Exchange::HongKongStockExchange),
+            "SET" => Ok(Exchange::StockExchangeofThailand),
+            "SES" => Ok(Exchange::SingaporeExchangeSecurities),
+            "SHH" => Ok(Exchange::ShanghaiStockExchange),
+            "EBS" => Ok(Exchange::SwissElectronicBourse),
+            "OSL" => Ok(Exchange::OsloStockExchange),
+            "TLV" => Ok(Exchange::TelAvivStockExchange),
+            "KOE" => Ok(Exchange::KoreaExchange),
+            "CPH" => Ok(Exchange::CopenhagenStockExchange),
+            "STU" => Ok(Exchange::StuttgartStockExchange),
+            "KLS" => Ok(Exchange::BursaMalaysia),
+            "HAM" => Ok(Exchange::HamburgStockExchange),
+            "VIE" => Ok(Exchange::ViennaStockExchange),
+            "PRA" => Ok(Exchange::PragueStockExchange),
+            "HAN" => Ok(Exchange::HanoiStockExchange),
+            "JNB" => Ok(Exchange::JohannesburgStockExchange),
+            "DXE" => Ok(Exchange::CboeDXE),
+            "MSC" => Ok(Exchange::MoscowExchange),
+            "CXA" => Ok(Exchange::CboeAustralia),
+            "SHZ" => Ok(Exchange::ShenzhenStockExchange),
+            "VSE" => Ok(Exchange::VietnamStockExchange),
+            "WSE" => Ok(Exchange::WarsawStockExchange),
+            "ICE" => Ok(Exchange::IntercontinentalExchange),
+            "RIS" => Ok(Exchange::RigaStockExchange),
+            "CXE" => Ok(Exchange::ZagrebStockExchange),
+            "JKT" => Ok(Exchange::JakartaStockExchange),
+            "TWO" => Ok(Exchange::TaiwanOTCExchange),
+            "OSA" => Ok(Exchange::OsakaStockExchange),
+            "AQS" => Ok(Exchange::AquisStockExchange),
+            "TAI" => Ok(Exchange::TaiwanStockExchange),
+            "DOH" => Ok(Exchange::QatarStockExchange),
+            "HEL" => Ok(Exchange::HelsinkiStockExchange),
+            "TSI" => Ok(Exchange::TallinnStockExchange),
+            "MCE" => Ok(Exchange::MoldovaStockExchange),
+            "NEO" => Ok(Exchange::NEOExchange),
+            "BRU" => Ok(Exchange::EuronextBrussels),
+            "LIT" => Ok(Exchange::VilniusStockExchange),
+            "BUD" => Ok(Exchange::BudapestStockExchange),
+            "LIS" => Ok(Exchange::EuronextLisbon),
+            "SGO" => Ok(Exchange::SantiagoStockExchange),
+            "FSI" => Ok(Exchange::FSI),
+            "ISE" => Ok(Exchange::IrishStockExchange),
+            "ATH" => Ok(Exchange::AthensStockExchange),
+            "SAU" => Ok(Exchange::SaudiStockExchange),
+            "TLO" => Ok(Exchange::TrinidadandTobagoStockExchange),
+            "CBO" => Ok(Exchange::CboeBXE),
+            "BVC" => Ok(Exchange::BVPBratislavaStockExchange),
+            "TAL" => Ok(Exchange::TAL),
+            "KUW" => Ok(Exchange::BoursaKuwait),
+            "CAI" => Ok(Exchange::EgyptianExchange),
+            "CSE" => Ok(Exchange::ColomboStockExchange),
+            "DFM" => Ok(Exchange::DubaiFinancialMarket),
+            "PHS" => Ok(Exchange::PhilippineStockExchange),
+            "FKA" => Ok(Exchange::KazakhstanStockExchange),
+            "OBB" => Ok(Exchange::OTCBulletinBoard),
+            "YHD" => Ok(Exchange::YHD),
+            "SAP" => Ok(Exchange::SAP),
+            "CCS" => Ok(Exchange::CaracasStockExchange),
+            "OPI" => Ok(Exchange::OPI),
+            "ENX" => Ok(Exchange::Euronext),
+            "OPR" => Ok(Exchange::OPRA),
+            _ => Err(ParseKeyError(s.to_string())),
+        }
+    }
+}
@@ -1,63 +1,65 @@
+#[cfg(not(any(feature = "full", feature = "lite")))]
+compile_error!("Enable the `full` feature, which is on by default but must be listed when default features are disabled, or the `lite` feature");
+
 pub mod keys;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "full")]
+pub mod config;
+mod csv;
+#[cfg(feature = "full")]
+pub mod database;
+#[cfg(feature = "full")]
+mod diff;
+pub mod error;
+#[cfg(feature = "full")]
+pub mod export;
+#[cfg(feature = "full")]
+pub mod filter;
+#[cfg(feature = "full")]
+mod full;
+#[cfg(feature = "full")]
+pub mod fuzzy;
+#[cfg(feature = "full")]
+pub mod import;
+#[cfg(feature = "full")]
+mod json;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "full")]
+pub mod provenance;
+#[cfg(feature = "full")]
+pub mod schema;
+#[cfg(feature = "full")]
 pub mod scraper;
+#[cfg(feature = "full")]
+mod sha256;
+#[cfg(feature = "full")]
+pub mod source;
+#[cfg(all(test, feature = "download"))]
+mod test_util;
+#[cfg(feature = "download")]
+pub mod validate;
+#[cfg(feature = "full")]
+pub mod verify;
+#[cfg(feature = "full")]
+pub mod warm;
+#[cfg(feature = "history")]
+pub mod history;
+#[cfg(feature = "lite")]
+pub mod lite;
 
-use r2d2::Pool;
-use std::error::Error;
-use std::path::PathBuf;
-use polars::prelude::*;
-use scraper::{download_file, save_symbols};
 use std::collections::HashMap;
-use rusqlite::{Result, ToSql};
 use serde::{Deserialize, Serialize};
-use r2d2_sqlite::SqliteConnectionManager;
-use keys::{AssetClass, Category, Exchange};
-use tokio::sync::OnceCell;
-
-
-static DATABASE_POOL: OnceCell<Pool<SqliteConnectionManager>> = OnceCell::const_new();
-
-async fn initialize_database() -> Result<Pool<SqliteConnectionManager>> {
-    let db_file = "symbols.db";
-    let db_path = PathBuf::from(db_file);
-
-    if !db_path.exists() {
-        let url = "https://github.com/Nnamdi-sys/yahoo-finance-symbols/raw/main/rust/src/symbols.db";
-        if download_file(url, &db_path).await.is_err() {
-            println!("Unable to download database from: {}. Scraping symbols now from Yahoo Finance", url);
-            save_symbols(&db_path).await.expect("Failed to Get Symbols Database");
-        }
-    }
-
-    let manager = SqliteConnectionManager::file(db_file);
-    let pool = Pool::new(manager).expect("Failed to create database connection pool");
-
-    Ok(pool)
-}
-
-async fn get_database_pool() -> Result<&'static Pool<SqliteConnectionManager>> {
-    if DATABASE_POOL.get().is_none() {
-        let pool = initialize_database().await?;
-        DATABASE_POOL.set(pool).unwrap();
-    }
-    Ok(DATABASE_POOL.get().unwrap())
-}
-
-
-
-pub async fn update_database() -> Result<(), Box<dyn Error>> {
-    let db_file = "symbols.db";
-    let db_path = PathBuf::from(db_file);
-
-    if db_path.exists() {
-        tokio::fs::remove_file(&db_path).await?;
-    }
-
-    save_symbols(&db_path).await?;
+use keys::{AssetClass, Exchange, SymbolKind};
+pub use error::YahooSymbolsError;
+#[cfg(feature = "history")]
+pub use history::{enable_search_history, get_search_history, is_search_history_enabled, SearchRecord};
+#[cfg(all(feature = "lite", not(feature = "full")))]
+pub use lite::{get_symbol, get_symbols};
+#[cfg(feature = "full")]
+pub use full::*;
 
-    println!("Database updated successfully.");
-
-    Ok(())
-}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Symbol {
@@ -69,6 +71,12 @@ pub struct Symbol {
 }
 
 
+impl Default for Symbol {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Symbol {
     pub fn new() -> Symbol {
         Symbol {
@@ -79,277 +87,238 @@ impl Symbol {
             exchange: String::new(),
         }
     }
-}
 
-/// Fetches a symbol from the database
-///
-/// # Arguments
-///
-/// * `symbol` - Symbol string
-///
-/// # Returns
-///
-/// * `Symbol` - Symbol struct
-///
-/// # Example
-///
-/// ```
-/// use std::error::Error;
-/// use yahoo_finance_symbols::get_symbol;
-///
-/// #[tokio::main]
-/// async fn main() -> Result<(), Box<dyn Error>> {
-///     let result = get_symbol("AAPL").await?;
-///     println!("{:?}", result);
-///     Ok(())
-/// }
-/// ```
-pub async fn get_symbol(symbol: &str) -> Result<Symbol> {
-    let pool = get_database_pool().await?;
-    let conn = pool.get().expect("Failed to get connection from pool");
-    let mut stmt = conn.prepare("SELECT * FROM symbols WHERE symbol = ?")
-        .expect("Failed to prepare statement");
+    /// Parses the stored asset class into the typed `AssetClass`, or `None` if it is not recognised
+    pub fn asset_class_enum(&self) -> Option<AssetClass> {
+        self.asset_class.parse().ok()
+    }
 
-    let symbol_row = stmt.query_row(&[symbol], |row| {
-        Ok(Symbol {
-            symbol: row.get(0)?,
-            name: row.get(1)?,
-            category: row.get(2)?,
-            asset_class: row.get(3)?,
-            exchange: row.get(4)?,
-        })
-    });
+    /// Parses the stored exchange code into the typed `Exchange`, or `None` if it is not recognised
+    pub fn exchange_enum(&self) -> Option<Exchange> {
+        self.exchange.parse().ok()
+    }
+
+    /// Classifies the instrument from its asset class and ticker conventions; see `SymbolKind::classify`
+    pub fn kind(&self) -> SymbolKind {
+        SymbolKind::classify(&self.symbol, &self.asset_class)
+    }
 
-    symbol_row
+    /// Human-readable name of the exchange, such as "NASDAQ" for "NMS", or the raw code if it is not known
+    pub fn exchange_display_name(&self) -> String {
+        keys::exchange_display_name(&self.exchange).unwrap_or(&self.exchange).to_string()
+    }
 }
 
-/// Fetches symbols that match the specified asset class, category, and exchange from the database
-///
-/// # Arguments
-///
-/// * `asset_class` - Asset class enum
-/// * `category` - Category enum
-/// * `exchange` - Exchange enum
+/// The ticker and name of a symbol, for lookups that need nothing else
 ///
-/// # Returns
-///
-/// * `Vec<Symbol>` - Vector of symbols
-///
-/// # Example
-///
-/// ```
-/// use std::error::Error;
-/// use yahoo_finance_symbols::keys::{AssetClass, Category, Exchange};
-/// use yahoo_finance_symbols::get_symbols;
-///
-/// #[tokio::main]
-/// async fn main() -> Result<(), Box<dyn Error>> {
-///     let result = get_symbols(AssetClass::Stocks, Category::Technology, Exchange::NASDAQ).await?;
-///     println!("{:?}", result);
-///     let result = get_symbols(AssetClass::ETFs, Category::All, Exchange::All).await?;
-///     println!("{:?}", result);
-///     let result = get_symbols(AssetClass::Futures, Category::All, Exchange::All).await?;
-///     println!("{:?}", result);
-///     let result = get_symbols(AssetClass::Indices, Category::All, Exchange::All).await?;
-///     println!("{:?}", result);
-///     let result = get_symbols(AssetClass::MutualFunds, Category::All, Exchange::All).await?;
-///     println!("{:?}", result);
-///     let result = get_symbols(AssetClass::Cryptocurrencies, Category::All, Exchange::All).await?;
-///     println!("{:?}", result);
-///     let result = get_symbols(AssetClass::Currencies, Category::All, Exchange::All).await?;
-///     println!("{:?}", result);
-///     Ok(())
-/// }
-/// ```
-pub async fn get_symbols(asset_class: AssetClass, category: Category, exchange: Exchange) -> Result<Vec<Symbol>> {
-    let pool = get_database_pool().await?;
-    let conn = pool.get().expect("Failed to get connection from pool");
-
-    // Prepare a dynamic number of placeholders and values based on the provided filters
-    let (mut placeholders, mut values): (Vec<String>, Vec<&dyn ToSql>) = (Vec::new(), Vec::new());
-
-    let asset_classes = asset_class.to_string_vec().await;
-    let categories = category.to_string_vec().await;
-    let exchanges = exchange.to_string_vec().await;
-
-    placeholders.push(format!("asset_class IN ({})", (0..asset_classes.len()).map(|_| "?").collect::<Vec<_>>().join(",")));
-    values.extend(asset_classes.iter().map(|s| s as &dyn ToSql));
-
-    placeholders.push(format!("category IN ({})", (0..categories.len()).map(|_| "?").collect::<Vec<_>>().join(",")));
-    values.extend(categories.iter().map(|s| s as &dyn ToSql));
-
-    placeholders.push(format!("exchange IN ({})", (0..exchanges.len()).map(|_| "?").collect::<Vec<_>>().join(",")));
-    values.extend(exchanges.iter().map(|s| s as &dyn ToSql));
-
-    let query = format!("SELECT * FROM symbols WHERE {}", placeholders.join(" AND "));
-
-    let mut stmt = conn.prepare(&query).expect("Failed to prepare statement");
-
-    let rows = stmt.query_map(&*values, |row| {
-        Ok(Symbol {
-            symbol: row.get(0)?,
-            name: row.get(1)?,
-            category: row.get(2)?,
-            asset_class: row.get(3)?,
-            exchange: row.get(4)?,
-        })
-    })?;
+/// Queries returning `SymbolLite` select only the `symbol` and `name` columns, which keeps rows
+/// small for high-frequency uses such as autocomplete. Only `search_lite` returns projected rows;
+/// every other query returns full `Symbol`s.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SymbolLite {
+    pub symbol: String,
+    pub name: String,
+}
 
-    let symbols: Result<Vec<Symbol>> = rows.collect();
-    symbols
+impl From<Symbol> for SymbolLite {
+    fn from(symbol: Symbol) -> Self {
+        SymbolLite { symbol: symbol.symbol, name: symbol.name }
+    }
 }
 
-pub async fn get_symbols_count() -> Result<i64> {
-    let pool = get_database_pool().await?;
-    let conn = pool.get().expect("Failed to get connection from pool");
-    let sql = "SELECT COUNT(*) FROM symbols";
-    let count: i64 = conn.query_row(sql, [], |row| row.get(0))?;
-    Ok(count)
+/// A symbol borrowed from a `SearchResults` buffer instead of owning its strings
+///
+/// Every field points into the `SearchResults` it was read from, so a `SymbolRef` cannot outlive
+/// that buffer, nor be held while the buffer is moved or dropped. Call `to_symbol` to keep a match
+/// beyond that.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SymbolRef<'a> {
+    pub symbol: &'a str,
+    pub name: &'a str,
+    pub category: &'a str,
+    pub asset_class: &'a str,
+    pub exchange: &'a str,
 }
 
-pub async fn get_distinct_exchanges() -> Result<Vec<String>> {
-    let pool = get_database_pool().await?;
-    let conn = pool.get().expect("Failed to get connection from pool");
-    let mut stmt = conn
-        .prepare("SELECT DISTINCT exchange FROM symbols")
-        .expect("Failed to prepare statement");
+impl SymbolRef<'_> {
+    /// Copies the fields into an owned `Symbol`
+    pub fn to_symbol(&self) -> Symbol {
+        Symbol::from(*self)
+    }
+}
 
-    let rows = stmt.query_map([], |row| {
-        Ok( row.get(0)? )
-    })?;
+impl From<SymbolRef<'_>> for Symbol {
+    fn from(symbol: SymbolRef<'_>) -> Self {
+        Symbol {
+            symbol: symbol.symbol.to_string(),
+            name: symbol.name.to_string(),
+            category: symbol.category.to_string(),
+            asset_class: symbol.asset_class.to_string(),
+            exchange: symbol.exchange.to_string(),
+        }
+    }
+}
 
-    let exchanges: Result<Vec<String>> = rows.collect();
-    exchanges
+/// Rows returned by `search_refs`, with the text of every row stored in one shared buffer
+///
+/// Holding N matches costs a couple of growing allocations instead of five `String`s per match.
+/// Rows are read back as `SymbolRef`s borrowing from the buffer.
+#[derive(Debug, Clone, Default)]
+pub struct SearchResults {
+    text: String,
+    /// End offset in `text` of every field, `FIELDS` per row
+    ends: Vec<usize>,
 }
 
-pub async fn get_distinct_categories() -> Result<Vec<String>> {
-    let pool = get_database_pool().await?;
-    let conn = pool.get().expect("Failed to get connection from pool");
-    let mut stmt = conn
-        .prepare("SELECT DISTINCT category FROM symbols")
-        .expect("Failed to prepare statement");
+impl SearchResults {
+    const FIELDS: usize = 5;
 
-    let rows = stmt.query_map([], |row| {
-        Ok( row.get(0)? )
-    })?;
+    /// Number of rows
+    pub fn len(&self) -> usize {
+        self.ends.len() / Self::FIELDS
+    }
 
-    let categories: Result<Vec<String>> = rows.collect();
-    categories
-}
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
 
-pub async fn get_distinct_asset_classes() -> Result<Vec<String>> {
-    let pool = get_database_pool().await?;
-    let conn = pool.get().expect("Failed to get connection from pool");
-    let mut stmt = conn
-        .prepare("SELECT DISTINCT asset_class FROM symbols")
-        .expect("Failed to prepare statement");
+    /// Borrows the row at `index`, or `None` if it is out of range
+    pub fn get(&self, index: usize) -> Option<SymbolRef<'_>> {
+        let ends = self.ends.get(index * Self::FIELDS..(index + 1) * Self::FIELDS)?;
+        let start = if index == 0 { 0 } else { self.ends[index * Self::FIELDS - 1] };
+        let field = |i: usize| &self.text[if i == 0 { start } else { ends[i - 1] }..ends[i]];
+        Some(SymbolRef {
+            symbol: field(0),
+            name: field(1),
+            category: field(2),
+            asset_class: field(3),
+            exchange: field(4),
+        })
+    }
 
-    let rows = stmt.query_map([], |row| {
-        Ok( row.get(0)? )
-    })?;
+    /// Borrows every row, in order
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = SymbolRef<'_>> + '_ {
+        (0..self.len()).map(|index| self.get(index).expect("Row index in range"))
+    }
 
-    let asset_classes: Result<Vec<String>> = rows.collect();
-    asset_classes
+    /// Appends a row given as symbol, name, category, asset class and exchange
+    #[cfg(feature = "full")]
+    pub(crate) fn push(&mut self, fields: [&str; SearchResults::FIELDS]) {
+        for field in fields {
+            self.text.push_str(field);
+            self.ends.push(self.text.len());
+        }
+    }
 }
 
-
-/// Fetches ticker symbols that closely match the specified query and asset class
+/// Pairs each symbol with its price from a map keyed by ticker
+///
+/// Symbols keep their order; those missing from `prices` are paired with `None` rather than dropped.
 ///
 /// # Arguments
 ///
-/// * `query` - ticker symbol query
-/// * `asset_class` - asset class (Equity, ETF, Mutual Fund, Index, Currency, Futures, Crypto)
+/// * `symbols` - symbols to price
+/// * `prices` - prices keyed by ticker symbol
 ///
 /// # Returns
 ///
-/// * `HashMap<String, String>` - dictionary of ticker symbols and names
+/// * `Vec<(Symbol, Option<f64>)>` - every symbol with its price, if known
 ///
 /// # Example
 ///
 /// ```
-/// use yahoo_finance_symbols::search_symbols;
+/// use std::collections::HashMap;
 /// use std::error::Error;
-/// 
+/// use yahoo_finance_symbols::{get_symbol, zip_prices};
+///
 /// #[tokio::main]
 /// async fn main() -> Result<(), Box<dyn Error>> {
-///     let symbols = search_symbols("Apple", "Equity").await?;
-///     println!("{:?}", symbols);
+///     let prices = HashMap::from([("AAPL".to_string(), 189.84)]);
+///     let priced = zip_prices(vec![get_symbol("AAPL").await?], &prices);
+///     println!("{:?}", priced);
 ///     Ok(())
 /// }
 /// ```
-pub async fn search_symbols(query: &str, asset_class: &str) -> Result<HashMap<String, String>> {
-    let asset_class = match asset_class {
-        "Equity" => AssetClass::Stocks,
-        "ETF" => AssetClass::ETFs,
-        "Mutual Fund" => AssetClass::MutualFunds,
-        "Index" => AssetClass::Indices,
-        "Currency" => AssetClass::Currencies,
-        "Futures" => AssetClass::Futures,
-        "Crypto" => AssetClass::Cryptocurrencies,
-        _ => panic!("Asset class must be one of: Equity, ETF, Mutual Fund, Index, Currency, Futures, Crypto"),
-    };
-    let tickers = get_symbols(asset_class, Category::All, Exchange::All).await.unwrap();
-    let symbols = tickers
-        .iter()
-        .filter(|tc| tc.symbol.to_lowercase().contains(&query.to_lowercase())
-            || tc.name.to_lowercase().contains(&query.to_lowercase()))
-        .map(|tc| (tc.symbol.clone(), tc.name.clone()))
-        .collect::<HashMap<String, String>>();
-    Ok(symbols)
+pub fn zip_prices(symbols: Vec<Symbol>, prices: &HashMap<String, f64>) -> Vec<(Symbol, Option<f64>)> {
+    symbols
+        .into_iter()
+        .map(|symbol| {
+            let price = prices.get(&symbol.symbol).copied();
+            (symbol, price)
+        })
+        .collect()
 }
 
-/// Fetches all Symbols into a Polars DataFrame
-/// 
-/// # Returns
-/// 
-/// * `DataFrame` - Polars DataFrame of all Yahoo Finance Symbols
-/// 
+/// Escapes the `GLOB` wildcards in `literal`, so that it matches itself in `get_symbols_matching`
+///
+/// `*`, `?` and `[` are wrapped in brackets; every other character, including `^`, `=`, `.` and
+/// `-`, already matches itself outside brackets and is kept as is.
+///
 /// # Example
-/// 
+///
 /// ```
-/// use yahoo_finance_symbols::get_symbols_df;
-/// use std::error::Error;
-/// 
-/// #[tokio::main]
-/// async fn main() -> Result<(), Box<dyn Error>> {
-///     let symbols_df = get_symbols_df().await?;
-///     println!("{:?}", symbols_df);
-///     Ok(())
-/// }
+/// use yahoo_finance_symbols::escape_glob;
+///
+/// assert_eq!(escape_glob("^GSPC"), "^GSPC");
+/// assert_eq!(escape_glob("A*B?[C]"), "A[*]B[?][[]C]");
 /// ```
-pub async fn get_symbols_df() -> Result<DataFrame, Box<dyn Error>> {
-    let symbols = get_symbols(AssetClass::All, Category::All, Exchange::All).await?;
-
-    let symbols_series: Vec<Series> = vec![
-        Series::new("symbol", symbols.iter().map(|s| s.symbol.as_str()).collect::<Vec<&str>>()),
-        Series::new("name", symbols.iter().map(|s| s.name.as_str()).collect::<Vec<&str>>()),
-        Series::new("category", symbols.iter().map(|s| s.category.as_str()).collect::<Vec<&str>>()),
-        Series::new("asset_class", symbols.iter().map(|s| s.asset_class.as_str()).collect::<Vec<&str>>()),
-        Series::new("exchange", symbols.iter().map(|s| s.exchange.as_str()).collect::<Vec<&str>>()),
-    ];
-
-    let symbols_df = DataFrame::new(symbols_series)?;
-
-    Ok(symbols_df)
+pub fn escape_glob(literal: &str) -> String {
+    let mut escaped = String::with_capacity(literal.len());
+    for c in literal.chars() {
+        match c {
+            '*' | '?' | '[' => {
+                escaped.push('[');
+                escaped.push(c);
+                escaped.push(']');
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
 }
 
-
-#[cfg(test)]
-
-mod tests {
-
-    use crate::{get_symbols_count, get_symbols_df};
-
-    #[tokio::test]
-    async fn check_symbols_count() {
-        let symbols_count = get_symbols_count().await.unwrap();
-        println!("{}", symbols_count);
-
-        let symbols_df = get_symbols_df().await.unwrap();
-        println!("{:?}", symbols_df);
-
-        assert!(symbols_count > 450_000);
+/// Writes symbols as CSV text, e.g. to answer an HTTP request without a temporary file
+///
+/// The first line is the `symbol,name,category,asset_class,exchange` header that
+/// `import::symbols_from_csv` reads back. Records follow RFC 4180: lines end with `\r\n`, and
+/// fields containing a comma, a double quote or a line break, such as `AT&T, Inc.`, are quoted
+/// with their double quotes doubled. Unlike the DataFrame functions this does not need the
+/// `polars` feature.
+///
+/// # Arguments
+///
+/// * `symbols` - symbols to write, in order
+///
+/// # Returns
+///
+/// * `String` - the CSV text
+///
+/// # Example
+///
+/// ```
+/// use yahoo_finance_symbols::{symbols_to_csv_string, Symbol};
+///
+/// let symbols = [Symbol {
+///     symbol: "T".to_string(),
+///     name: "AT&T, Inc.".to_string(),
+///     category: "Communication Services".to_string(),
+///     asset_class: "Stocks".to_string(),
+///     exchange: "NYQ".to_string(),
+/// }];
+/// assert_eq!(
+///     symbols_to_csv_string(&symbols),
+///     "symbol,name,category,asset_class,exchange\r\nT,\"AT&T, Inc.\",Communication Services,Stocks,NYQ\r\n"
+/// );
+/// ```
+pub fn symbols_to_csv_string(symbols: &[Symbol]) -> String {
+    let mut out = String::new();
+    csv::write_csv_record(&mut out, ["symbol", "name", "category", "asset_class", "exchange"]);
+    for symbol in symbols {
+        csv::write_csv_record(
+            &mut out,
+            [symbol.symbol.as_str(), &symbol.name, &symbol.category, &symbol.asset_class, &symbol.exchange],
+        );
     }
+    out
 }
 
-
@@ -0,0 +1,192 @@
+//! A read-only, single-connection interface to a bundled `symbols.db`, for embedded targets.
+//!
+//! Built with `default-features = false, features = ["lite"]`, the crate depends on `rusqlite`,
+//! `serde` and `html-escape` only: no connection pool, Polars, scraper, HTTP client or async
+//! runtime, which cuts build time and binary size severalfold.
+//!
+//! ```toml
+//! yahoo-finance-symbols = { version = "0.1", default-features = false, features = ["lite"] }
+//! ```
+//!
+//! The lite build never downloads, scrapes, migrates or writes the database: ship the file with
+//! the application and point [`use_database`] at it, or leave it at `symbols.db` in the working
+//! directory. Under `lite` alone the crate offers exactly:
+//!
+//! - [`get_symbol`] and [`get_symbols`], with the signatures of the full crate, also exported at
+//!   the crate root;
+//! - [`use_database`], [`LiteDatabase`] and [`DEFAULT_DATABASE_PATH`];
+//! - the `keys` and `error` modules;
+//! - `Symbol`, `SymbolLite`, `SymbolRef`, `SearchResults`, `zip_prices`, `escape_glob` and
+//!   `symbols_to_csv_string`.
+//!
+//! Everything else, search included, needs the `full` feature, and downloading or scraping the
+//! database the `download` feature as well. The query functions are `async`
+//! only to keep their signatures: they run on the calling thread and need no runtime, so any
+//! executor, or a minimal `block_on`, can drive them. Concurrent queries take turns on the one
+//! connection.
+
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use rusqlite::{params_from_iter, Connection, OpenFlags, Result, Row};
+use crate::error::YahooSymbolsError;
+use crate::keys::{AssetClass, Category, Exchange};
+use crate::Symbol;
+
+
+/// File the lite functions read unless `use_database` chose another
+pub const DEFAULT_DATABASE_PATH: &str = "symbols.db";
+
+static DEFAULT_DATABASE: OnceLock<LiteDatabase> = OnceLock::new();
+
+/// A symbols database opened read-only on one connection, guarded by a mutex
+#[derive(Debug)]
+pub struct LiteDatabase {
+    conn: Mutex<Connection>,
+}
+
+impl LiteDatabase {
+    /// Opens an existing `symbols.db` file read-only
+    pub fn open(path: impl AsRef<Path>) -> Result<LiteDatabase> {
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX | OpenFlags::SQLITE_OPEN_URI;
+        let conn = Connection::open_with_flags(path, flags)?;
+        Ok(LiteDatabase { conn: Mutex::new(conn) })
+    }
+
+    /// Fetches a symbol, taking its first stored listing if it is listed under several asset classes
+    pub async fn get_symbol(&self, symbol: &str) -> Result<Symbol> {
+        let conn = self.conn.lock().expect("Connection lock poisoned");
+        let mut stmt = conn.prepare_cached(
+            "SELECT symbol, name, category, asset_class, exchange FROM symbols WHERE symbol = ? ORDER BY rowid LIMIT 1",
+        )?;
+        stmt.query_row([symbol], symbol_from_row)
+    }
+
+    /// Fetches the symbols of an asset class, category and exchange, in table order
+    pub async fn get_symbols(&self, asset_class: AssetClass, category: Category, exchange: Exchange) -> Result<Vec<Symbol>> {
+        // As in the full crate, the `All` variants add no clause and categories only apply where they exist
+        let mut filters = Vec::new();
+        if asset_class != AssetClass::All {
+            filters.push(("asset_class", asset_class.to_string_vec().await));
+        }
+        if exchange != Exchange::All {
+            filters.push(("exchange", exchange.to_string_vec().await));
+        }
+
+        let mut sql = "SELECT symbol, name, category, asset_class, exchange FROM symbols".to_string();
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        for (column, codes) in filters {
+            conditions.push(format!("{} IN ({})", column, vec!["?"; codes.len()].join(",")));
+            values.extend(codes);
+        }
+        if asset_class.has_categories() && category != Category::All {
+            let codes = category.to_string_vec().await;
+            conditions.push(asset_class.category_condition(codes.len()));
+            values.extend(codes);
+        }
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+
+        let conn = self.conn.lock().expect("Connection lock poisoned");
+        let mut stmt = conn.prepare_cached(&sql)?;
+        let rows = stmt.query_map(params_from_iter(&values), symbol_from_row)?;
+        rows.collect()
+    }
+
+    #[cfg(not(feature = "full"))]
+    fn distinct_values(&self, column: &str) -> Result<Vec<String>> {
+        let conn = self.conn.lock().expect("Connection lock poisoned");
+        let mut stmt = conn.prepare_cached(&format!("SELECT DISTINCT {} FROM symbols", column))?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect()
+    }
+}
+
+/// Makes the lite functions read the database at `path` instead of `DEFAULT_DATABASE_PATH`
+///
+/// Call it once, before the first query. Fails with `YahooSymbolsError::DatabaseNotFound` if
+/// there is no file at `path`, `YahooSymbolsError::InvalidSchema` if it cannot be opened as a
+/// database, and `YahooSymbolsError::AlreadyInitialized` once a database is in use.
+pub fn use_database(path: impl AsRef<Path>) -> std::result::Result<(), YahooSymbolsError> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Err(YahooSymbolsError::DatabaseNotFound(path.to_path_buf()));
+    }
+    let db = LiteDatabase::open(path).map_err(|e| YahooSymbolsError::InvalidSchema(e.to_string()))?;
+    DEFAULT_DATABASE.set(db).map_err(|_| YahooSymbolsError::AlreadyInitialized)
+}
+
+/// The database chosen with `use_database`, or else the one at `DEFAULT_DATABASE_PATH`
+fn default_database() -> Result<&'static LiteDatabase> {
+    if let Some(db) = DEFAULT_DATABASE.get() {
+        return Ok(db);
+    }
+    let db = LiteDatabase::open(DEFAULT_DATABASE_PATH)?;
+    Ok(DEFAULT_DATABASE.get_or_init(|| db))
+}
+
+/// Fetches a ticker symbol from the default database, as the full crate's `get_symbol`
+pub async fn get_symbol(symbol: &str) -> Result<Symbol> {
+    default_database()?.get_symbol(symbol).await
+}
+
+/// Fetches the symbols of an asset class, category and exchange from the default database, as
+/// the full crate's `get_symbols`
+pub async fn get_symbols(asset_class: AssetClass, category: Category, exchange: Exchange) -> Result<Vec<Symbol>> {
+    default_database()?.get_symbols(asset_class, category, exchange).await
+}
+
+/// Every distinct value of `column` in the default database, for the `All` keys
+///
+/// The keys fall back to no values when this fails; the query they build then fails on its own.
+#[cfg(not(feature = "full"))]
+pub(crate) fn distinct_values(column: &str) -> Result<Vec<String>> {
+    default_database()?.distinct_values(column)
+}
+
+fn symbol_from_row(row: &Row) -> Result<Symbol> {
+    let name: String = row.get(1)?;
+    Ok(Symbol {
+        symbol: row.get(0)?,
+        name: if name.contains('&') { html_escape::decode_html_entities(&name).into_owned() } else { name },
+        category: row.get(2)?,
+        asset_class: row.get(3)?,
+        exchange: row.get(4)?,
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use crate::keys::{AssetClass, Category, Exchange};
+    use super::{get_symbol, get_symbols, LiteDatabase};
+
+    #[tokio::test]
+    async fn check_lite_queries() {
+        assert_eq!(get_symbol("AAPL").await.unwrap().name, "Apple Inc.");
+        assert!(get_symbol("NO SUCH TICKER").await.is_err());
+
+        let symbols = get_symbols(AssetClass::Stocks, Category::Technology, Exchange::NASDAQ).await.unwrap();
+        assert!(!symbols.is_empty());
+        assert!(symbols.iter().all(|s| (s.asset_class.as_str(), s.category.as_str(), s.exchange.as_str()) == ("Stocks", "Technology", "NMS")));
+        #[cfg(feature = "full")]
+        assert_eq!(symbols.len(), crate::get_symbols(AssetClass::Stocks, Category::Technology, Exchange::NASDAQ).await.unwrap().len());
+
+        // Indices have no categories, so the category is ignored
+        let indices = get_symbols(AssetClass::Indices, Category::Energy, Exchange::All).await.unwrap();
+        assert_eq!(indices.len(), get_symbols(AssetClass::Indices, Category::All, Exchange::All).await.unwrap().len());
+
+        // Under `All` the category only filters the stocks
+        let all = get_symbols(AssetClass::All, Category::Technology, Exchange::NASDAQ).await.unwrap();
+        assert!(all.iter().any(|s| s.asset_class == "ETF"));
+        assert!(all.iter().filter(|s| s.asset_class == "Stocks").all(|s| s.category == "Technology"));
+
+        let db = LiteDatabase::open("symbols.db").unwrap();
+        assert_eq!(db.get_symbol("MSFT").await.unwrap().exchange, "NMS");
+        assert!(db.conn.lock().unwrap().execute("DELETE FROM symbols WHERE symbol = 'MSFT'", []).is_err());
+        assert!(LiteDatabase::open("no-such-file.db").is_err());
+    }
+}
@@ -0,0 +1,181 @@
+//! Process-wide counters for the query path, for exporting to Prometheus or similar.
+//!
+//! Only compiled with the `metrics` feature; without it nothing is counted and the query path
+//! is unchanged. Every query method of every [`SymbolDatabase`](crate::database::SymbolDatabase),
+//! including the default one behind the free functions, checks out a pooled connection per
+//! statement it runs and counts towards the same totals. Counters are plain atomics updated
+//! with relaxed ordering, so reading them never blocks a query.
+//!
+//! No query results are cached, so there are no cache hit or miss counters.
+
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use r2d2::{Pool, PooledConnection};
+use r2d2_sqlite::SqliteConnectionManager;
+
+
+static QUERIES: AtomicU64 = AtomicU64::new(0);
+static POOL_ERRORS: AtomicU64 = AtomicU64::new(0);
+static QUERY_NANOS: AtomicU64 = AtomicU64::new(0);
+static POOL_WAIT_NANOS: AtomicU64 = AtomicU64::new(0);
+
+/// A snapshot of the query counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Metrics {
+    /// Queries served: pooled connections checked out by a query method and returned. Methods
+    /// running several statements concurrently, such as `get_symbols_many`, count once per statement
+    pub queries: u64,
+    /// Attempts to check out a connection that failed, typically because the pool stayed exhausted
+    /// until its timeout
+    pub pool_errors: u64,
+    /// Total time queries held their connection, from checkout to return
+    pub query_time: Duration,
+    /// Total time spent waiting for the pool to hand out a connection, including failed attempts
+    pub pool_wait: Duration,
+}
+
+impl Metrics {
+    /// Mean time a query held its connection, or zero before the first query
+    pub fn average_query_latency(&self) -> Duration {
+        average(self.query_time, self.queries)
+    }
+
+    /// Mean time spent waiting for a connection per checkout attempt, or zero before the first
+    pub fn average_pool_wait(&self) -> Duration {
+        average(self.pool_wait, self.queries + self.pool_errors)
+    }
+}
+
+fn average(total: Duration, count: u64) -> Duration {
+    match u32::try_from(count) {
+        Ok(0) => Duration::ZERO,
+        Ok(count) => total / count,
+        Err(_) => Duration::from_nanos((total.as_nanos() / u128::from(count)) as u64),
+    }
+}
+
+/// Reads the current value of every counter
+///
+/// The counters are read one after the other, so a snapshot taken while queries run may mix
+/// values from slightly different moments.
+///
+/// # Example
+///
+/// ```
+/// use yahoo_finance_symbols::get_symbol;
+/// use yahoo_finance_symbols::metrics::get_metrics;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     get_symbol("AAPL").await?;
+///     let metrics = get_metrics();
+///     println!("{} queries, {:?} on average", metrics.queries, metrics.average_query_latency());
+///     Ok(())
+/// }
+/// ```
+pub fn get_metrics() -> Metrics {
+    Metrics {
+        queries: QUERIES.load(Ordering::Relaxed),
+        pool_errors: POOL_ERRORS.load(Ordering::Relaxed),
+        query_time: Duration::from_nanos(QUERY_NANOS.load(Ordering::Relaxed)),
+        pool_wait: Duration::from_nanos(POOL_WAIT_NANOS.load(Ordering::Relaxed)),
+    }
+}
+
+/// Sets every counter back to zero
+pub fn reset_metrics() {
+    for counter in [&QUERIES, &POOL_ERRORS, &QUERY_NANOS, &POOL_WAIT_NANOS] {
+        counter.store(0, Ordering::Relaxed);
+    }
+}
+
+fn add_duration(counter: &AtomicU64, duration: Duration) {
+    counter.fetch_add(u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX), Ordering::Relaxed);
+}
+
+/// A pooled connection that records how long it was held when returned
+pub(crate) struct TimedConnection {
+    conn: PooledConnection<SqliteConnectionManager>,
+    checked_out: Instant,
+}
+
+impl TimedConnection {
+    /// Checks out a connection from `pool`, recording the wait
+    pub(crate) fn get(pool: &Pool<SqliteConnectionManager>) -> Result<TimedConnection, r2d2::Error> {
+        let started = Instant::now();
+        let conn = pool.get();
+        add_duration(&POOL_WAIT_NANOS, started.elapsed());
+
+        match conn {
+            Ok(conn) => Ok(TimedConnection { conn, checked_out: Instant::now() }),
+            Err(e) => {
+                POOL_ERRORS.fetch_add(1, Ordering::Relaxed);
+                Err(e)
+            }
+        }
+    }
+
+    /// The pooled connection being timed
+    pub(crate) fn pooled_mut(&mut self) -> &mut PooledConnection<SqliteConnectionManager> {
+        &mut self.conn
+    }
+}
+
+impl Deref for TimedConnection {
+    type Target = rusqlite::Connection;
+
+    fn deref(&self) -> &rusqlite::Connection {
+        &self.conn
+    }
+}
+
+impl DerefMut for TimedConnection {
+    fn deref_mut(&mut self) -> &mut rusqlite::Connection {
+        &mut self.conn
+    }
+}
+
+impl Drop for TimedConnection {
+    fn drop(&mut self) {
+        QUERIES.fetch_add(1, Ordering::Relaxed);
+        add_duration(&QUERY_NANOS, self.checked_out.elapsed());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use std::time::Duration;
+    use crate::keys::{AssetClass, Category, Exchange};
+    use crate::{get_symbol, get_symbols_many};
+    use super::{average, get_metrics, Metrics};
+
+    #[tokio::test]
+    async fn check_query_metrics() {
+        get_symbol("AAPL").await.unwrap();
+        let before = get_metrics();
+
+        get_symbol("MSFT").await.unwrap();
+        let queries = vec![(AssetClass::ETFs, Category::All, Exchange::All), (AssetClass::Indices, Category::All, Exchange::All)];
+        get_symbols_many(queries).await.unwrap();
+
+        // Other tests run queries at the same time, so only lower bounds hold
+        let after = get_metrics();
+        assert!(after.queries >= before.queries + 3);
+        assert!(after.query_time > before.query_time);
+        assert!(after.pool_wait >= before.pool_wait);
+        assert!(after.average_query_latency() > Duration::ZERO);
+    }
+
+    #[test]
+    fn check_metrics_averages() {
+        assert_eq!(Metrics::default().average_query_latency(), Duration::ZERO);
+        let metrics = Metrics { queries: 3, pool_errors: 1, query_time: Duration::from_millis(30), pool_wait: Duration::from_millis(8) };
+        assert_eq!(metrics.average_query_latency(), Duration::from_millis(10));
+        assert_eq!(metrics.average_pool_wait(), Duration::from_millis(2));
+        assert_eq!(average(Duration::from_secs(10), 5_000_000_000), Duration::from_nanos(2));
+    }
+}
@@ -0,0 +1,216 @@
+//! Where a `symbols.db` file came from and how it was built, recorded in its `meta` table.
+//!
+//! | Key | Written by | Value |
+//! |-----|------------|-------|
+//! | `source` | `save_symbols`, `scrape_into`, `save_symbols_from` | name of the symbol source, e.g. `yahoo` |
+//! | `scrape_config` | `save_symbols`, `scrape_into` | the `ScrapeConfig` used, as JSON |
+//! | `built_at` | `save_symbols`, `scrape_into`, `save_symbols_from` | seconds since the Unix epoch when the rows were written; left out by deterministic scrapes |
+//! | `download_url` | `export_database` | `DatabaseConfig::resolved_download_url` of the exporting process, where the copy is published |
+//! | `last_updated` | `export_database` | seconds since the Unix epoch when the copy was exported |
+//! | `scrape_pending` | the first query, when it scrapes in the background | present until that scrape completes; `layout_changed` once it stopped on a changed lookup page |
+//! | `crate_version` | `save_symbols`, `scrape_into`, `save_symbols_from` | version of this crate that wrote the rows, e.g. `0.1.4` |
+//!
+//! A redistributed file thereby describes itself; read it back with [`read_build_info`]
+//! (exposed as `get_build_info`). `SymbolDatabase::open` logs a warning when a file was built by
+//! another version of the crate, whose schema or contents may differ from what this one expects.
+
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::{Deserialize, Serialize};
+use crate::schema::read_schema_version;
+use crate::scraper::ScrapeConfig;
+
+
+/// Provenance of a database file, as recorded in its `meta` table
+///
+/// Every field but `schema_version` is `None` when the file does not record it, e.g. because it
+/// was written by an older release or by hand.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BuildInfo {
+    /// Name of the source the rows were fetched from, `yahoo` for the Yahoo Finance scraper
+    pub source: Option<String>,
+    /// Paging parameters of the scrape that built the file
+    pub scrape_config: Option<ScrapeConfig>,
+    /// Seconds since the Unix epoch when the rows were written
+    pub built_at: Option<u64>,
+    /// Where the exported copy is published
+    pub download_url: Option<String>,
+    /// Seconds since the Unix epoch when the copy was exported
+    pub last_updated: Option<u64>,
+    /// Schema version of the file (see `schema`)
+    pub schema_version: u32,
+    /// Version of this crate that wrote the rows
+    pub crate_version: Option<String>,
+    /// Whether a background scrape is still filling the file, so that it holds only part of the symbols
+    #[serde(default)]
+    pub scrape_pending: bool,
+}
+
+impl BuildInfo {
+    /// Whether the file records being built by a version of this crate other than `CRATE_VERSION`
+    pub fn version_mismatch(&self) -> bool {
+        self.crate_version.as_deref().is_some_and(|version| version != CRATE_VERSION)
+    }
+}
+
+/// `meta` entry marking a file that a background scrape is still filling
+pub(crate) const SCRAPE_PENDING: &str = "scrape_pending";
+
+/// Version of this crate, stamped into the files it builds
+pub const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Seconds since the Unix epoch, or 0 if the clock is set before it
+pub(crate) fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Sets the `meta` entry `key` to `value`, creating the table if needed
+pub(crate) fn write_meta(conn: &Connection, key: &str, value: &str) -> Result<()> {
+    conn.execute("CREATE TABLE IF NOT EXISTS meta (key TEXT PRIMARY KEY, value TEXT NOT NULL)", [])?;
+    conn.execute("INSERT OR REPLACE INTO meta (key, value) VALUES (?, ?)", params![key, value])?;
+    Ok(())
+}
+
+/// Reads the `meta` entry `key`, or `None` if it or the table is missing
+pub(crate) fn read_meta(conn: &Connection, key: &str) -> Result<Option<String>> {
+    let has_meta: bool = conn
+        .prepare("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'meta'")?
+        .exists([])?;
+    if !has_meta {
+        return Ok(None);
+    }
+    conn.query_row("SELECT value FROM meta WHERE key = ?", [key], |row| row.get(0)).optional()
+}
+
+/// Records that the rows of `conn` were just written from `source`, scraped with `config` if given
+pub(crate) fn record_build(conn: &Connection, source: &str, config: Option<&ScrapeConfig>) -> Result<()> {
+    write_meta(conn, "source", source)?;
+    match config {
+        Some(config) => {
+            let json = serde_json::to_string(config).expect("ScrapeConfig serializes to JSON");
+            write_meta(conn, "scrape_config", &json)?;
+        }
+        None => {
+            conn.execute("DELETE FROM meta WHERE key = 'scrape_config'", [])?;
+        }
+    }
+    write_meta(conn, "crate_version", CRATE_VERSION)?;
+    // A timestamp would make every deterministic build differ
+    if config.is_some_and(|config| config.deterministic) {
+        delete_meta(conn, "built_at")
+    } else {
+        write_meta(conn, "built_at", &now_secs().to_string())
+    }
+}
+
+/// Logs a warning if the database at `path`, behind `conn`, was built by another crate version
+pub(crate) fn warn_on_version_mismatch(conn: &Connection, path: &Path) {
+    if let Ok(Some(version)) = read_meta(conn, "crate_version") {
+        if version != CRATE_VERSION {
+            log::warn!(
+                "{} was built by yahoo-finance-symbols {}, but this is {}; if queries misbehave, rebuild it with update_database",
+                path.display(),
+                version,
+                CRATE_VERSION
+            );
+        }
+    }
+}
+
+/// Removes the `meta` entry `key`, if there is one
+pub(crate) fn delete_meta(conn: &Connection, key: &str) -> Result<()> {
+    if read_meta(conn, key)?.is_some() {
+        conn.execute("DELETE FROM meta WHERE key = ?", [key])?;
+    }
+    Ok(())
+}
+
+/// Reads the provenance recorded in the database behind `conn`
+///
+/// Entries that are missing or cannot be parsed are reported as `None`.
+pub fn read_build_info(conn: &Connection) -> Result<BuildInfo> {
+    let number = |key| -> Result<Option<u64>> { Ok(read_meta(conn, key)?.and_then(|v| v.parse().ok())) };
+
+    Ok(BuildInfo {
+        source: read_meta(conn, "source")?,
+        scrape_config: read_meta(conn, "scrape_config")?.and_then(|json| serde_json::from_str(&json).ok()),
+        built_at: number("built_at")?,
+        download_url: read_meta(conn, "download_url")?,
+        last_updated: number("last_updated")?,
+        schema_version: read_schema_version(conn)?,
+        crate_version: read_meta(conn, "crate_version")?,
+        scrape_pending: read_meta(conn, SCRAPE_PENDING)?.is_some(),
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use rusqlite::Connection;
+    use crate::database::SymbolDatabase;
+    use crate::export::export_connection;
+    use crate::scraper::{create_symbols_table, ScrapeConfig};
+    use crate::schema::SCHEMA_VERSION;
+    use super::{read_build_info, record_build, write_meta, CRATE_VERSION};
+
+    #[tokio::test]
+    async fn check_build_info_round_trip() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_symbols_table(&conn).unwrap();
+        assert!(read_build_info(&conn).unwrap().source.is_none());
+
+        let config = ScrapeConfig { page_size: 500, concurrency: 3, ..ScrapeConfig::default() };
+        record_build(&conn, "yahoo", Some(&config)).unwrap();
+
+        let path = std::env::temp_dir().join(format!("yahoo-finance-symbols-provenance-{}.db", std::process::id()));
+        export_connection(&conn, &path).unwrap();
+
+        let info = SymbolDatabase::open(&path).unwrap().get_build_info().await.unwrap();
+        assert_eq!(info.crate_version.as_deref(), Some(CRATE_VERSION));
+        assert!(!info.version_mismatch());
+        assert_eq!(info.source.as_deref(), Some("yahoo"));
+        let recorded = info.scrape_config.unwrap();
+        assert_eq!((recorded.page_size, recorded.concurrency), (500, 3));
+        assert!(info.built_at.unwrap() > 0);
+        assert!(info.last_updated.unwrap() >= info.built_at.unwrap());
+        assert!(info.download_url.is_some());
+        assert_eq!(info.schema_version, SCHEMA_VERSION);
+
+        // Rebuilding from another source forgets the scrape settings
+        record_build(&conn, "mock", None).unwrap();
+        let info = read_build_info(&conn).unwrap();
+        assert_eq!(info.source.as_deref(), Some("mock"));
+        assert!(info.scrape_config.is_none());
+
+        std::fs::remove_file(crate::export::checksum_path(&path)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_version_mismatch() {
+        let path = std::env::temp_dir().join(format!("yahoo-finance-symbols-version-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        {
+            let conn = Connection::open(&path).unwrap();
+            create_symbols_table(&conn).unwrap();
+            write_meta(&conn, "crate_version", "0.0.1").unwrap();
+        }
+
+        // Opening the stamped file only warns; the version is reported by `get_build_info`
+        let db = SymbolDatabase::open(&path).unwrap();
+        let info = db.get_build_info().await.unwrap();
+        assert_eq!(info.crate_version.as_deref(), Some("0.0.1"));
+        assert!(info.version_mismatch());
+
+        // Files that predate the stamp are not reported as mismatched
+        db.pool().get().unwrap().execute("DELETE FROM meta WHERE key = 'crate_version'", []).unwrap();
+        let info = db.get_build_info().await.unwrap();
+        assert!(info.crate_version.is_none());
+        assert!(!info.version_mismatch());
+
+        drop(db);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
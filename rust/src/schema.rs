@@ -0,0 +1,295 @@
+//! Versioning of the `symbols.db` layout, so that older files can be upgraded in place.
+//!
+//! The version is stored as the `schema_version` entry of the `meta` table. Files written before
+//! it existed have no such entry and are treated as version 1.
+//!
+//! | Version | Change |
+//! |---------|--------|
+//! | 1 | The original `symbols` table: `symbol`, `name`, `category`, `asset_class`, `exchange` |
+//! | 2 | `source` column telling scraped rows (`yahoo`) from imported ones (`user`) |
+//! | 3 | Indexes on `asset_class`, `category` and `exchange` for the filter queries |
+//! | 4 | Primary key `(symbol, asset_class)` instead of `symbol`, keeping one row per listing |
+//! | 5 | Nullable `isin` column, filled from user-supplied mappings, with an index for lookups |
+//!
+//! [`migrate_connection`] (exposed as `migrate`) applies the missing steps in order, each in its
+//! own transaction together with the new version number, so an interrupted migration resumes
+//! from the last completed step. The default database is migrated when it is first opened.
+//!
+//! [`describe_connection`] (exposed as `describe_database`) reports the layout a file actually
+//! has, for data dictionaries and for checking that a migration took effect.
+
+use rusqlite::{Connection, OptionalExtension, Result, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use crate::import::{ensure_isin_column, ensure_source_column};
+use crate::scraper::{create_symbols_indexes, SYMBOLS_TABLE_SCHEMA};
+
+
+/// Schema version written by this release of the crate
+pub const SCHEMA_VERSION: u32 = 5;
+
+/// Version assumed for files without a `schema_version` entry
+const UNVERSIONED: u32 = 1;
+
+/// Upgrades a database at version `to - 1` to version `to`
+type Step = fn(&Connection) -> Result<()>;
+
+const STEPS: [(u32, Step); 4] = [
+    (2, ensure_source_column),
+    (3, create_symbols_indexes),
+    (4, key_on_asset_class),
+    (5, ensure_isin_column),
+];
+
+/// Rebuilds the `symbols` table with the `(symbol, asset_class)` primary key, keeping row order
+///
+/// SQLite cannot change a primary key in place, so the rows are copied into a new table.
+fn key_on_asset_class(conn: &Connection) -> Result<()> {
+    let keyed: bool = conn
+        .prepare("SELECT 1 FROM pragma_table_info('symbols') WHERE name = 'asset_class' AND pk > 0")?
+        .exists([])?;
+    if keyed {
+        return Ok(());
+    }
+
+    conn.execute_batch(&format!(
+        "CREATE TABLE symbols_keyed {};
+         INSERT INTO symbols_keyed (symbol, name, category, asset_class, exchange, source)
+             SELECT symbol, name, category, asset_class, exchange, source FROM symbols ORDER BY rowid;
+         DROP TABLE symbols;
+         ALTER TABLE symbols_keyed RENAME TO symbols;",
+        SYMBOLS_TABLE_SCHEMA
+    ))?;
+    create_symbols_indexes(conn)
+}
+
+/// Columns every schema version has, which the queries and the migration steps rely on
+const REQUIRED_COLUMNS: [&str; 5] = ["symbol", "name", "category", "asset_class", "exchange"];
+
+/// Lists the columns of the version 1 `symbols` table missing from the database behind `conn`
+///
+/// All of them are reported if there is no `symbols` table at all.
+pub(crate) fn missing_columns(conn: &Connection) -> Result<Vec<&'static str>> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info('symbols')")?;
+    let columns = stmt.query_map([], |row| row.get::<_, String>(0))?.collect::<Result<Vec<_>>>()?;
+
+    Ok(REQUIRED_COLUMNS.into_iter().filter(|required| !columns.iter().any(|c| c == required)).collect())
+}
+
+fn create_meta_table(conn: &Connection) -> Result<()> {
+    conn.execute("CREATE TABLE IF NOT EXISTS meta (key TEXT PRIMARY KEY, value TEXT NOT NULL)", [])?;
+    Ok(())
+}
+
+/// Reads the schema version of the database behind `conn`
+pub fn read_schema_version(conn: &Connection) -> Result<u32> {
+    let has_meta: bool = conn
+        .prepare("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'meta'")?
+        .exists([])?;
+    if !has_meta {
+        return Ok(UNVERSIONED);
+    }
+
+    let version: Option<String> = conn
+        .query_row("SELECT value FROM meta WHERE key = 'schema_version'", [], |row| row.get(0))
+        .optional()?;
+
+    Ok(version.and_then(|v| v.parse().ok()).unwrap_or(UNVERSIONED))
+}
+
+/// Upgrades the database behind `conn` to `SCHEMA_VERSION`, returning the version it ends at
+///
+/// Databases already at or beyond `SCHEMA_VERSION`, e.g. written by a newer release, are left
+/// untouched. Each step takes the write lock before re-reading the version, so processes
+/// migrating the same file concurrently apply every step once.
+pub fn migrate_connection(conn: &mut Connection) -> Result<u32> {
+    for (version, step) in STEPS {
+        if read_schema_version(conn)? >= version {
+            continue;
+        }
+
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        if read_schema_version(&tx)? < version {
+            step(&tx)?;
+            create_meta_table(&tx)?;
+            tx.execute(
+                "INSERT OR REPLACE INTO meta (key, value) VALUES ('schema_version', ?)",
+                [version.to_string()],
+            )?;
+        }
+        tx.commit()?;
+    }
+
+    read_schema_version(conn)
+}
+
+/// Layout and size of a symbols database, as reported by `describe_database`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaDescription {
+    pub schema_version: u32,
+    /// Every table, by name
+    pub tables: Vec<TableDescription>,
+}
+
+/// A table of a database, with its columns in declaration order and its indexes by name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableDescription {
+    pub name: String,
+    pub columns: Vec<ColumnDescription>,
+    pub indexes: Vec<IndexDescription>,
+    pub row_count: i64,
+}
+
+/// A column, from `PRAGMA table_info`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnDescription {
+    pub name: String,
+    /// Declared type, empty if the column has none
+    pub data_type: String,
+    pub not_null: bool,
+    /// Default value as written in the schema, e.g. `'yahoo'`
+    pub default_value: Option<String>,
+    /// Position of the column in the primary key, from 1, or 0 if it is not part of it
+    pub primary_key: u32,
+}
+
+/// An index, from `PRAGMA index_list` and `PRAGMA index_info`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexDescription {
+    pub name: String,
+    /// Indexed columns, in key order
+    pub columns: Vec<String>,
+    pub unique: bool,
+    /// `c` for `CREATE INDEX`, `pk` for the primary key, `u` for a `UNIQUE` constraint
+    pub origin: String,
+}
+
+impl SchemaDescription {
+    /// The description as pretty-printed JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("SchemaDescription serializes to JSON")
+    }
+
+    /// The table called `name`, if there is one
+    pub fn table(&self, name: &str) -> Option<&TableDescription> {
+        self.tables.iter().find(|t| t.name == name)
+    }
+}
+
+/// Describes every table of the database behind `conn`: columns, indexes and row counts
+pub fn describe_connection(conn: &Connection) -> Result<SchemaDescription> {
+    let mut stmt = conn.prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name")?;
+    let names = stmt.query_map([], |row| row.get::<_, String>(0))?.collect::<Result<Vec<_>>>()?;
+
+    let mut tables = Vec::with_capacity(names.len());
+    for name in names {
+        let mut stmt = conn.prepare("SELECT name, type, \"notnull\", dflt_value, pk FROM pragma_table_info(?) ORDER BY cid")?;
+        let columns = stmt
+            .query_map([&name], |row| {
+                Ok(ColumnDescription {
+                    name: row.get(0)?,
+                    data_type: row.get(1)?,
+                    not_null: row.get(2)?,
+                    default_value: row.get(3)?,
+                    primary_key: row.get(4)?,
+                })
+            })?
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut stmt = conn.prepare("SELECT name, \"unique\", origin FROM pragma_index_list(?) ORDER BY name")?;
+        let listed = stmt
+            .query_map([&name], |row| Ok((row.get::<_, String>(0)?, row.get::<_, bool>(1)?, row.get::<_, String>(2)?)))?
+            .collect::<Result<Vec<_>>>()?;
+        let mut indexes = Vec::with_capacity(listed.len());
+        for (index, unique, origin) in listed {
+            let mut stmt = conn.prepare("SELECT name FROM pragma_index_info(?) ORDER BY seqno")?;
+            let columns = stmt.query_map([&index], |row| row.get(0))?.collect::<Result<Vec<_>>>()?;
+            indexes.push(IndexDescription { name: index, columns, unique, origin });
+        }
+
+        let row_count = conn.query_row(&format!("SELECT COUNT(*) FROM \"{}\"", name.replace('"', "\"\"")), [], |row| row.get(0))?;
+        tables.push(TableDescription { name, columns, indexes, row_count });
+    }
+
+    Ok(SchemaDescription { schema_version: read_schema_version(conn)?, tables })
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use rusqlite::Connection;
+    use super::{describe_connection, migrate_connection, missing_columns, read_schema_version, SCHEMA_VERSION};
+
+    #[test]
+    fn check_migrate() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE symbols (symbol TEXT PRIMARY KEY, name TEXT, category TEXT, asset_class TEXT, exchange TEXT);
+             INSERT INTO symbols VALUES ('AAPL', 'Apple Inc.', 'Technology', 'Stocks', 'NMS');",
+        ).unwrap();
+        assert_eq!(read_schema_version(&conn).unwrap(), 1);
+        assert!(missing_columns(&conn).unwrap().is_empty());
+        assert_eq!(missing_columns(&Connection::open_in_memory().unwrap()).unwrap().len(), 5);
+
+        assert_eq!(migrate_connection(&mut conn).unwrap(), SCHEMA_VERSION);
+        assert_eq!(read_schema_version(&conn).unwrap(), SCHEMA_VERSION);
+        let source: String = conn.query_row("SELECT source FROM symbols WHERE symbol = 'AAPL'", [], |row| row.get(0)).unwrap();
+        assert_eq!(source, "yahoo");
+        let indexes: i64 = conn.query_row("SELECT COUNT(*) FROM sqlite_master WHERE type = 'index' AND sql IS NOT NULL", [], |row| row.get(0)).unwrap();
+        assert_eq!(indexes, 4);
+        conn.execute("INSERT INTO symbols (symbol, name, category, asset_class, exchange) VALUES ('AAPL', 'Apple Inc.', 'N/A', 'ETF', 'NMS')", []).unwrap();
+        assert!(conn.execute("INSERT INTO symbols (symbol, name, category, asset_class, exchange) VALUES ('AAPL', 'Apple', 'N/A', 'ETF', 'NMS')", []).is_err());
+
+        // Migrating again changes nothing, and newer files are left alone
+        assert_eq!(migrate_connection(&mut conn).unwrap(), SCHEMA_VERSION);
+        conn.execute("UPDATE meta SET value = '99' WHERE key = 'schema_version'", []).unwrap();
+        assert_eq!(migrate_connection(&mut conn).unwrap(), 99);
+    }
+
+    #[test]
+    fn check_migrate_resumes() {
+        // Interrupted after the first step: the source column exists and version 2 is recorded
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE symbols (symbol TEXT PRIMARY KEY, name TEXT, category TEXT, asset_class TEXT, exchange TEXT,
+                                   source TEXT NOT NULL DEFAULT 'yahoo');
+             CREATE TABLE meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+             INSERT INTO meta VALUES ('schema_version', '2');",
+        ).unwrap();
+        assert_eq!(read_schema_version(&conn).unwrap(), 2);
+        assert_eq!(migrate_connection(&mut conn).unwrap(), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn check_describe() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE symbols (symbol TEXT PRIMARY KEY, name TEXT, category TEXT, asset_class TEXT, exchange TEXT);
+             INSERT INTO symbols VALUES ('AAPL', 'Apple Inc.', 'Technology', 'Stocks', 'NMS');
+             INSERT INTO symbols VALUES ('MSFT', 'Microsoft Corporation', 'Technology', 'Stocks', 'NMS');",
+        ).unwrap();
+        migrate_connection(&mut conn).unwrap();
+
+        let description = describe_connection(&conn).unwrap();
+        assert_eq!(description.schema_version, SCHEMA_VERSION);
+        assert_eq!(description.tables.iter().map(|t| t.name.as_str()).collect::<Vec<_>>(), ["meta", "symbols"]);
+
+        let symbols = description.table("symbols").unwrap();
+        assert_eq!(symbols.row_count, 2);
+        let columns = symbols.columns.iter().map(|c| c.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(columns, ["symbol", "name", "category", "asset_class", "exchange", "source", "isin"]);
+        let key = symbols.columns.iter().filter(|c| c.primary_key > 0).map(|c| (c.name.as_str(), c.primary_key)).collect::<Vec<_>>();
+        assert_eq!(key, [("symbol", 1), ("asset_class", 2)]);
+        let source = &symbols.columns[5];
+        assert!(source.not_null);
+        assert_eq!(source.default_value.as_deref(), Some("'yahoo'"));
+
+        let primary = symbols.indexes.iter().find(|i| i.origin == "pk").unwrap();
+        assert!(primary.unique);
+        assert_eq!(primary.columns, ["symbol", "asset_class"]);
+        assert_eq!(symbols.indexes.iter().filter(|i| i.origin == "c").count(), 4);
+
+        let json: serde_json::Value = serde_json::from_str(&description.to_json()).unwrap();
+        assert_eq!(json["tables"][1]["columns"][0]["name"], "symbol");
+    }
+}
@@ -1,27 +1,21 @@
-use std::fs::File;
-use std::io::{BufWriter, Write};
-use std::path::PathBuf;
-use std::{path::Path, sync::Arc};
+use std::collections::HashMap;
+use std::path::Path;
 use std::error::Error;
-use rusqlite::params;
-use scraper::{Html, Selector};
-use r2d2::Pool;
+use std::time::Duration;
 use rusqlite::{Connection, Result};
-use r2d2_sqlite::SqliteConnectionManager;
 use serde::{Deserialize, Serialize};
-use reqwest::{Client, Url};
-use futures::future::join_all;
-use indicatif::{ProgressBar, ProgressStyle};
-use tokio::sync::Semaphore;
+use crate::import::ensure_isin_column;
+use crate::schema::migrate_connection;
+use crate::{sibling_path, Symbol};
 
+#[cfg(feature = "download")]
+mod fetch;
+#[cfg(feature = "download")]
+pub use fetch::*;
 
-#[derive(Debug, Serialize, Deserialize)]
-struct SymbolList {
-    pub symbols: Vec<Ticker>,
-}
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
-struct Ticker {
+pub(crate) struct Ticker {
     pub symbol: String,
     pub name: String,
     pub category: String,
@@ -29,162 +23,205 @@ struct Ticker {
     pub exchange: String,
 }
 
-pub async fn save_symbols(db_path: &Path) -> Result<(), Box<dyn Error>> {
-    let manager = SqliteConnectionManager::file(db_path);
-    let pool = Pool::new(manager)?;
-    let conn = pool.get()?;
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS symbols (
-             symbol TEXT PRIMARY KEY,
-             name TEXT,
-             category TEXT,
-             asset_class TEXT,
-             exchange TEXT
-         )",
-        [],
-    )?;
-
-    let base_url = "https://finance.yahoo.com/lookup/";
-    let search_set: Vec<String> = (b'A'..=b'Z')
-        .chain(b'0'..=b'9')
-        .map(|c| format!("{}", c as char))
-        .chain(
-            (b'A'..=b'Z')
-                .flat_map(|c1| (b'A'..=b'Z').map(move |c2| format!("{}{}", c1 as char, c2 as char))),
-        )
-        .collect();
-    let total_steps = search_set.len();
-
-    // Create and configure the progress bar
-    let pb = ProgressBar::new(total_steps as u64);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("{msg} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})")?
-            .progress_chars("#>-"),
-    );
-
-    let concurrency_limit = 5; // Set the desired concurrency limit
-    let semaphore = Arc::new(Semaphore::new(concurrency_limit));
-    let mut tasks = Vec::new();
-
-    for symbol in search_set {
-        let pool = pool.clone();
-        let pb = pb.clone();
-        let semaphore = semaphore.clone();
-        pb.set_message("Scraping Symbols from Yahoo Finance");
-
-        let task = tokio::task::spawn(async move {
-            let _permit = semaphore.acquire().await.expect("Semaphore acquire failed");
-
-            match scrape_symbols(base_url, "all", &symbol).await {
-                Ok(result) => {
-                    let conn = pool.get().expect("Failed to get connection from pool");
-                    for doc in result {
-                        if !document_exists_in_db(&conn, &doc) {
-                            insert_document(&conn, &doc).unwrap_or_else(|_| {})
-                        }
-                    }
-                }
-                Err(e) => eprintln!("Error scraping symbols: {:?}", e),
-            }
-
-            pb.inc(1);
-        });
-
-        tasks.push(task);
+impl From<Ticker> for Symbol {
+    fn from(ticker: Ticker) -> Self {
+        Symbol {
+            symbol: ticker.symbol,
+            name: decode_name(ticker.name),
+            category: ticker.category,
+            asset_class: ticker.asset_class,
+            exchange: ticker.exchange,
+        }
     }
-
-    join_all(tasks).await;
-    pb.finish_with_message("Completed symbol scraping");
-
-    Ok(())
 }
 
+/// Decodes the HTML entities Yahoo Finance leaves in names, e.g. `AT&amp;T` to `AT&T`
+///
+/// Every scraped name goes through this before it is stored, and every name read back from the
+/// database does too, which cleans up databases written before names were decoded.
+pub(crate) fn decode_name(name: String) -> String {
+    if name.contains('&') {
+        html_escape::decode_html_entities(&name).into_owned()
+    } else {
+        name
+    }
+}
 
-async fn scrape_symbols(base_url: &str, sector: &str, symbol: &str) -> Result<Vec<Ticker>, Box<dyn Error>> {
-    let url =   format!("{}{}?s={}&t=A&b=0&c=10000", base_url, sector, symbol);
-    let client = Client::new();
-    let response = client
-        .get(url)
-        .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36")
-        .send()
-        .await?;
-    let body = response.text().await?;
-
-    let document = Html::parse_document(&body);
-    let mut result: Vec<Ticker> = Vec::new();
-
-    // Selector for the table rows containing symbol data
-    let row_selector = Selector::parse("table tbody tr").unwrap();
-
-    // Extract symbol data
-    for row in document.select(&row_selector) {
-        let mut columns: Vec<String> = Vec::new();
+/// Lookup sectors queried for every search term
+///
+/// Yahoo Finance's `all` lookup leaves out option contracts, which are only listed under the
+/// separate `options` sector.
+pub const LOOKUP_SECTORS: [&str; 2] = ["all", "options"];
+
+/// Largest page size accepted by the Yahoo Finance lookup endpoint
+pub const MAX_PAGE_SIZE: u32 = 10_000;
+
+/// Most pages fetched for a single lookup query, in case the endpoint keeps returning full pages
+pub const MAX_PAGES: u32 = 1_000;
+
+/// Paging parameters for the Yahoo Finance lookup endpoint
+///
+/// Each lookup request is `.../lookup/{sector}?s={query}&t=A&b={offset}&c={count}`, where `b`
+/// is the zero-based index of the first result and `c` is the number of results returned.
+/// The scraper starts at `b = start_offset` and, whenever a page comes back full, requests the
+/// next one with `b` advanced by `page_size`, until a page returns fewer than `page_size` rows,
+/// `MAX_PAGES` pages have been fetched or `b` would overflow a `u32`.
+///
+/// Scraped rows whose decoded name has fewer than `min_name_len` characters are dropped before
+/// they reach the insert path (`insert_document`, or the source insert for `YahooSource`).
+///
+/// Lookups finish in whatever order the responses arrive, so rows are inserted in a different
+/// order on every scrape. Set `deterministic` to rewrite the file in canonical order once the
+/// scrape completes; see [`canonicalize_database`] for what that guarantees.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScrapeConfig {
+    /// Results requested per page (`c`), between 1 and `MAX_PAGE_SIZE`
+    pub page_size: u32,
+    /// Index of the first result requested (`b`)
+    pub start_offset: u32,
+    /// Maximum number of lookup requests in flight at once
+    pub concurrency: usize,
+    /// Shortest name, in characters after HTML entities are decoded, a scraped row may have to be
+    /// stored. Defaults to 1, which drops rows with an empty name; 0 keeps every row
+    pub min_name_len: usize,
+    /// Whether to sort the rows by key and compact the file after the scrape, and leave out the
+    /// `built_at` time, so that the same data always produces the same bytes. Defaults to false
+    pub deterministic: bool,
+}
 
-        // Extract data from each cell in the row
-        for cell in row.select(&Selector::parse("td").unwrap()) {
-            columns.push(cell.inner_html().trim().to_string());
+impl Default for ScrapeConfig {
+    fn default() -> Self {
+        ScrapeConfig {
+            page_size: MAX_PAGE_SIZE,
+            start_offset: 0,
+            concurrency: 5,
+            min_name_len: 1,
+            deterministic: false,
         }
+    }
+}
 
-        if columns.len() >= 6 {
-            let symbol_struct = Ticker {
-                symbol: {
-                    let symbol_html = &columns[0];
-                    let symbol_document = Html::parse_fragment(&symbol_html);
-                    symbol_document
-                        .select(&Selector::parse("a").unwrap())
-                        .next()
-                        .map(|a| a.value().attr("data-symbol").unwrap_or_default())
-                        .unwrap_or_default()
-                        .to_string()
-                },
-                name: columns[1].clone(),
-                category: {
-                    let category_html = &columns[3];
-                    let category_document = Html::parse_fragment(&category_html);
-                    category_document
-                        .select(&Selector::parse("a").unwrap())
-                        .next()
-                        .map(|a| a.inner_html().trim().to_string())
-                        .unwrap_or("N/A".to_string())
-                },
-                asset_class: columns[4].clone(),
-                exchange: columns[5].clone(),
-            };
-
-            result.push(symbol_struct);
+impl ScrapeConfig {
+    /// Checks that the configuration can be sent to Yahoo Finance
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if self.page_size == 0 || self.page_size > MAX_PAGE_SIZE {
+            return Err(format!("page_size must be between 1 and {}, got {}", MAX_PAGE_SIZE, self.page_size).into());
         }
+        if self.concurrency == 0 {
+            return Err("concurrency must be at least 1".into());
+        }
+        Ok(())
     }
-    Ok(result)
+
+    /// Whether a scraped row has a long enough name to be stored
+    #[cfg(feature = "download")]
+    pub(crate) fn keeps(&self, ticker: &Ticker) -> bool {
+        decode_name(ticker.name.clone()).chars().count() >= self.min_name_len
+    }
+}
+
+/// What a scrape wrote, as returned by `save_symbols`, `resume_scrape`, `update_database` and
+/// `SymbolSource::save_to`
+#[derive(Debug, Clone, Default)]
+pub struct ScrapeResult {
+    /// Number of symbols written, i.e. not already in the database
+    pub total_inserted: usize,
+    /// Symbols written per lookup sector (see `LOOKUP_SECTORS`), or under the source name for
+    /// another `SymbolSource`; the counts add up to `total_inserted`
+    pub per_sector: HashMap<String, usize>,
+    /// Time the scrape took, from the first lookup to the last write
+    pub duration: Duration,
+    /// Sectors, in `LOOKUP_SECTORS` order, with at least one lookup that failed and was skipped
+    pub failed_sectors: Vec<String>,
 }
 
-fn document_exists_in_db(conn: &Connection, doc: &Ticker) -> bool {
-    let sql = "SELECT COUNT(*) FROM symbols WHERE symbol = ?";
-    let count: i64 = conn.query_row(sql, &[&doc.symbol], |row| row.get(0)).unwrap_or(0);
+/// Rewrites the database at `path` so that its bytes only depend on the rows it holds
+///
+/// The `symbols` table is rebuilt in key order (by `symbol`, then `asset_class`) with its
+/// indexes, the `meta` table in key order, any scrape checkpoint is dropped, and the file is then
+/// copied with `VACUUM INTO`, which writes every page afresh and leaves no free pages or
+/// transaction counters behind. Scrapes with `ScrapeConfig::deterministic` set end with this, so
+/// two builds of the same data have the same SHA-256 (see `export_database`) and a `diff_databases`
+/// between them is empty whatever order their lookups completed in.
+///
+/// The guarantee covers the file only, not the data: Yahoo Finance adds and delists symbols
+/// daily, so two scrapes a day apart still differ. Identical bytes also need the same `meta`
+/// entries, i.e. the same scrape settings and crate version, which the file records, and the same
+/// SQLite library, whose version the file header stores; the one bundled with this crate only
+/// changes with its `rusqlite` dependency. Deterministic scrapes leave out `built_at` for that
+/// reason, but a `download_url` or `last_updated` entry added later by `export_database` is kept.
+/// Other tables, e.g. the search history, are copied as they are.
+///
+/// The file is replaced by a new one, so connections already open on it keep reading the old
+/// contents until they are reopened. Older files are migrated to `schema::SCHEMA_VERSION` first.
+pub fn canonicalize_database(path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut conn = Connection::open(path)?;
+    migrate_connection(&mut conn)?;
+    sort_tables(&mut conn)?;
+    compact_in_place(conn, path)
+}
 
-    count > 0
+/// Rebuilds the `symbols` and `meta` tables in key order and drops the scrape checkpoint
+fn sort_tables(conn: &mut Connection) -> Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute_batch(&format!(
+        "DROP TABLE IF EXISTS scrape_checkpoint;
+         CREATE TABLE IF NOT EXISTS meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+         CREATE TABLE meta_sorted (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+         INSERT INTO meta_sorted SELECT key, value FROM meta ORDER BY key;
+         DROP TABLE meta;
+         ALTER TABLE meta_sorted RENAME TO meta;
+         CREATE TABLE symbols_sorted {};
+         INSERT INTO symbols_sorted (symbol, name, category, asset_class, exchange, source, isin)
+             SELECT symbol, name, category, asset_class, exchange, source, isin FROM symbols ORDER BY symbol, asset_class;
+         DROP TABLE symbols;
+         ALTER TABLE symbols_sorted RENAME TO symbols;",
+        SYMBOLS_TABLE_SCHEMA
+    ))?;
+    create_symbols_indexes(&tx)?;
+    ensure_isin_column(&tx)?;
+    tx.commit()
 }
 
-fn insert_document(conn: &Connection, doc: &Ticker) -> Result<()> {
-    let sql = "INSERT INTO symbols (symbol, name, category, asset_class, exchange) VALUES (?, ?, ?, ?, ?)";
-    conn.execute(
-        sql,
-        params![
-            &doc.symbol,
-            html_escape::decode_html_entities(&doc.name).to_string(),
-            &doc.category,
-            &doc.asset_class,
-            &doc.exchange
-        ],
-    )?;
+/// Replaces the database at `path`, behind `conn`, with a compacted copy of itself
+fn compact_in_place(conn: Connection, path: &Path) -> Result<(), Box<dyn Error>> {
+    let compacted = sibling_path(path, ".canonical");
+    let _ = std::fs::remove_file(&compacted);
+    conn.execute("VACUUM INTO ?", [compacted.to_string_lossy()])?;
+    drop(conn);
+    std::fs::rename(&compacted, path)?;
     Ok(())
 }
 
-pub async fn download_file(url: &str, path: &PathBuf) -> Result<(), Box<dyn Error>> {
-    let response = reqwest::get(Url::parse(url)?).await?;
-    let mut dest = BufWriter::new(File::create(path)?);
-    let content = response.bytes().await?;
-    dest.write_all(&content)?;
-    dest.flush()?;
+/// Columns and key of the `symbols` table
+pub(crate) const SYMBOLS_TABLE_SCHEMA: &str = "(
+             symbol TEXT,
+             name TEXT,
+             category TEXT,
+             asset_class TEXT,
+             exchange TEXT,
+             source TEXT NOT NULL DEFAULT 'yahoo',
+             isin TEXT,
+             PRIMARY KEY (symbol, asset_class)
+         )";
+
+/// Creates the `symbols` table if it does not exist yet
+///
+/// The primary key is `(symbol, asset_class)`: Yahoo Finance lists some tickers under several
+/// asset classes, and each of those listings is kept as its own row, so that every asset class
+/// filter finds it. A ticker appears at most once per asset class.
+pub fn create_symbols_table(conn: &Connection) -> Result<()> {
+    conn.execute(&format!("CREATE TABLE IF NOT EXISTS symbols {}", SYMBOLS_TABLE_SCHEMA), [])?;
     Ok(())
 }
+
+/// Creates the indexes used by the asset class, category and exchange filters if they do not exist yet
+pub fn create_symbols_indexes(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_symbols_asset_class ON symbols (asset_class);
+         CREATE INDEX IF NOT EXISTS idx_symbols_category ON symbols (category);
+         CREATE INDEX IF NOT EXISTS idx_symbols_exchange ON symbols (exchange);",
+    )
+}
+
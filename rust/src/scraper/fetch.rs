@@ -0,0 +1,1429 @@
+//! Fetching the lookup pages and the hosted database over HTTP, built with the `download`
+//! feature. Everything here is re-exported from [`scraper`](crate::scraper).
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::error::Error;
+use std::time::Duration;
+use rusqlite::{Connection, Result};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+use rusqlite::params;
+use scraper::{Html, Selector};
+use reqwest::header::{HeaderMap, HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{Client, StatusCode, Url};
+use indicatif::{ProgressBar, ProgressStyle};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use crate::config::DownloadProgress;
+use crate::error::YahooSymbolsError;
+use crate::export::{parse_checksum, verify_checksum, CHECKSUM_EXTENSION};
+use crate::import::YAHOO_SOURCE;
+use crate::keys::NA_CATEGORY;
+use crate::provenance::record_build;
+use super::{compact_in_place, create_symbols_table, decode_name, sort_tables, ScrapeConfig, ScrapeResult, Ticker, LOOKUP_SECTORS, MAX_PAGES};
+
+
+pub(crate) const LOOKUP_URL: &str = "https://finance.yahoo.com/lookup/";
+
+/// Number of answered lookup queries that must all come back empty before the scrape is aborted
+/// with `YahooSymbolsError::ScrapeLayoutChanged`
+pub const LAYOUT_CHECK_QUERIES: usize = 10;
+
+/// Scrapes all symbols into the database at `db_path` using the global scrape settings
+pub async fn save_symbols(db_path: &Path) -> Result<ScrapeResult, Box<dyn Error>> {
+    save_symbols_with_config(db_path, &crate::config::get_config().scrape).await
+}
+
+/// Scrapes all symbols into the database at `db_path` using the given paging parameters
+///
+/// This is always a full pass: the checkpoint of any earlier, interrupted scrape is discarded
+/// first. Use `resume_scrape` to continue one instead.
+///
+/// # Cancellation
+///
+/// The returned future is cancel-safe: dropping it (e.g. on Ctrl-C or a timeout) aborts every
+/// in-flight and pending lookup request. The rows of each lookup are written in one transaction
+/// between await points, together with its checkpoint entry, so rows inserted before
+/// cancellation remain valid and the database is never left with a partially written lookup.
+///
+/// # Errors
+///
+/// Returns `YahooSymbolsError::ScrapeLayoutChanged` if the first `LAYOUT_CHECK_QUERIES` lookup
+/// pages that answered all parsed to zero rows, rather than silently producing an empty database.
+pub async fn save_symbols_with_config(db_path: &Path, config: &ScrapeConfig) -> Result<ScrapeResult, Box<dyn Error>> {
+    scrape_to_database(db_path, LOOKUP_URL, config, false).await
+}
+
+/// Continues an interrupted scrape of the database at `db_path` using the global scrape settings
+///
+/// Every lookup (a search term within a lookup sector) that completed is recorded in the
+/// `scrape_checkpoint` table as its rows are written. Resuming skips those lookups and runs only
+/// the rest; symbols already present are left as they are, so resuming any number of times
+/// converges on the same database as one uninterrupted scrape. The checkpoint is cleared once
+/// every lookup has completed, after which resuming starts a full pass again; a scrape with
+/// failed lookups keeps it, so that resuming retries only those. The returned
+/// `ScrapeResult` only covers the lookups run by this call.
+///
+/// # Example
+///
+/// ```no_run
+/// use yahoo_finance_symbols::scraper::resume_scrape;
+/// use std::error::Error;
+/// use std::path::Path;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     resume_scrape(Path::new("symbols.db")).await?;
+///     Ok(())
+/// }
+/// ```
+pub async fn resume_scrape(db_path: &Path) -> Result<ScrapeResult, Box<dyn Error>> {
+    scrape_to_database(db_path, LOOKUP_URL, &crate::config::get_config().scrape, true).await
+}
+
+/// Scrapes every lookup query against `base_url` into the database at `db_path`
+pub(crate) async fn scrape_to_database(db_path: &Path, base_url: &str, config: &ScrapeConfig, resume: bool) -> Result<ScrapeResult, Box<dyn Error>> {
+    config.validate()?;
+
+    let mut conn = Connection::open(db_path)?;
+    let result = scrape_into_from(&mut conn, base_url, config, resume).await?;
+    if config.deterministic {
+        let db_path = db_path.to_path_buf();
+        crate::run_blocking(move || compact_in_place(conn, &db_path).map_err(|e| e.to_string())).await?;
+    }
+    Ok(result)
+}
+
+/// Scrapes all symbols into a connection owned by the caller
+///
+/// Creates the `symbols` table if needed and inserts every scraped symbol that is not already
+/// present, as `save_symbols_with_config` does for a path. The connection is borrowed mutably so
+/// that the returned future can still be sent across threads; an in-memory connection works too.
+///
+/// # Arguments
+///
+/// * `conn` - connection to write into
+/// * `config` - paging parameters
+///
+/// # Returns
+///
+/// * `usize` - number of symbols written
+///
+/// # Example
+///
+/// ```no_run
+/// use yahoo_finance_symbols::scraper::{scrape_into, ScrapeConfig};
+/// use rusqlite::Connection;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let mut conn = Connection::open_in_memory()?;
+///     let written = scrape_into(&mut conn, ScrapeConfig::default()).await?;
+///     println!("{}", written);
+///     Ok(())
+/// }
+/// ```
+pub async fn scrape_into(conn: &mut Connection, config: ScrapeConfig) -> Result<usize, Box<dyn Error>> {
+    Ok(scrape_into_from(conn, LOOKUP_URL, &config, false).await?.total_inserted)
+}
+
+/// Runs the lookups not yet in the checkpoint (all of them unless `resume`) into `conn`
+async fn scrape_into_from(
+    conn: &mut Connection,
+    base_url: &str,
+    config: &ScrapeConfig,
+    resume: bool,
+) -> Result<ScrapeResult, Box<dyn Error>> {
+    let started = Instant::now();
+    create_symbols_table(conn)?;
+    create_checkpoint_table(conn)?;
+    if !resume {
+        conn.execute("DELETE FROM scrape_checkpoint", [])?;
+    }
+
+    let completed = completed_lookups(conn)?;
+    let lookups = lookup_queries().into_iter().filter(|lookup| !completed.contains(lookup)).collect();
+
+    let mut per_sector: HashMap<String, usize> = LOOKUP_SECTORS.iter().map(|sector| (sector.to_string(), 0)).collect();
+    let failed = {
+        let conn = &mut *conn;
+        let per_sector = &mut per_sector;
+        run_lookups(base_url, config, lookups, move |lookup: &Lookup, result: &[Ticker]| {
+            match store_lookup(conn, lookup, result) {
+                Ok(count) => *per_sector.entry(lookup.sector.to_string()).or_default() += count,
+                Err(e) => log::warn!("Error saving symbols of lookup {} {:?}: {}", lookup.sector, lookup.query, e),
+            }
+        })
+        .await?
+    };
+    // Failed lookups are not in the checkpoint, so it is kept for a resume to retry them
+    if failed.is_empty() {
+        conn.execute("DELETE FROM scrape_checkpoint", [])?;
+    }
+    record_build(conn, YAHOO_SOURCE, Some(config))?;
+    if config.deterministic {
+        sort_tables(conn)?;
+    }
+
+    Ok(scrape_result(per_sector, started, &failed))
+}
+
+/// Re-runs the lookups whose term starts with one of `letters` against `base_url`, upserting
+/// their rows into `conn`
+///
+/// Unlike a full scrape, this neither touches the checkpoint of an interrupted scrape nor
+/// records a new build. Fails if a letter is not an ASCII letter or digit.
+pub(crate) async fn scrape_prefixes_into(
+    conn: &mut Connection,
+    base_url: &str,
+    config: &ScrapeConfig,
+    letters: &[char],
+) -> Result<ScrapeResult, Box<dyn Error>> {
+    if let Some(letter) = letters.iter().find(|letter| !letter.is_ascii_alphanumeric()) {
+        return Err(format!("Lookup prefixes must be ASCII letters or digits, got {:?}", letter).into());
+    }
+    let started = Instant::now();
+    create_symbols_table(conn)?;
+
+    let mut per_sector: HashMap<String, usize> = LOOKUP_SECTORS.iter().map(|sector| (sector.to_string(), 0)).collect();
+    let failed = {
+        let conn = &mut *conn;
+        let per_sector = &mut per_sector;
+        run_lookups(base_url, config, prefix_lookups(letters), move |lookup: &Lookup, result: &[Ticker]| {
+            match upsert_lookup(conn, result) {
+                Ok(count) => *per_sector.entry(lookup.sector.to_string()).or_default() += count,
+                Err(e) => log::warn!("Error saving symbols of lookup {} {:?}: {}", lookup.sector, lookup.query, e),
+            }
+        })
+        .await?
+    };
+
+    Ok(scrape_result(per_sector, started, &failed))
+}
+
+/// Sums up a scrape that started at `started` and wrote `per_sector` rows, with `failed` lookups
+fn scrape_result(per_sector: HashMap<String, usize>, started: Instant, failed: &[Lookup]) -> ScrapeResult {
+    ScrapeResult {
+        total_inserted: per_sector.values().sum(),
+        per_sector,
+        duration: started.elapsed(),
+        failed_sectors: LOOKUP_SECTORS
+            .iter()
+            .filter(|sector| failed.iter().any(|lookup| lookup.sector == **sector))
+            .map(|sector| sector.to_string())
+            .collect(),
+    }
+}
+
+fn create_checkpoint_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS scrape_checkpoint (
+             sector TEXT NOT NULL,
+             query TEXT NOT NULL,
+             PRIMARY KEY (sector, query)
+         )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Lookups recorded as completed by an earlier scrape that did not finish
+fn completed_lookups(conn: &Connection) -> Result<HashSet<Lookup>> {
+    let mut stmt = conn.prepare("SELECT sector, query FROM scrape_checkpoint")?;
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+
+    let mut completed = HashSet::new();
+    for row in rows {
+        let (sector, query) = row?;
+        // Sectors no longer scraped cannot be skipped anyway
+        if let Some(sector) = LOOKUP_SECTORS.iter().find(|s| **s == sector) {
+            completed.insert(Lookup { sector, query });
+        }
+    }
+    Ok(completed)
+}
+
+/// Inserts the new tickers of one lookup and marks it completed, in one transaction
+fn store_lookup(conn: &mut Connection, lookup: &Lookup, tickers: &[Ticker]) -> Result<usize> {
+    let tx = conn.transaction()?;
+    let mut written = 0;
+    for doc in tickers {
+        if !document_exists_in_db(&tx, doc) {
+            insert_document(&tx, doc)?;
+            written += 1;
+        }
+    }
+    tx.execute(
+        "INSERT OR IGNORE INTO scrape_checkpoint (sector, query) VALUES (?, ?)",
+        params![lookup.sector, &lookup.query],
+    )?;
+    tx.commit()?;
+
+    Ok(written)
+}
+
+/// Inserts the tickers of one lookup, or updates the Yahoo rows already stored for them, in one transaction
+///
+/// The name, category and exchange of an existing row are overwritten; user-imported rows are
+/// left as they are. Returns how many tickers were not in the database yet.
+fn upsert_lookup(conn: &mut Connection, tickers: &[Ticker]) -> Result<usize> {
+    let tx = conn.transaction()?;
+    let mut inserted = 0;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO symbols (symbol, name, category, asset_class, exchange, source) VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT (symbol, asset_class) DO UPDATE
+             SET name = excluded.name, category = excluded.category, exchange = excluded.exchange
+             WHERE symbols.source = ?6",
+        )?;
+        for doc in tickers {
+            if !document_exists_in_db(&tx, doc) {
+                inserted += 1;
+            }
+            stmt.execute(params![
+                &doc.symbol,
+                decode_name(doc.name.clone()),
+                &doc.category,
+                &doc.asset_class,
+                &doc.exchange,
+                YAHOO_SOURCE
+            ])?;
+        }
+    }
+    tx.commit()?;
+
+    Ok(inserted)
+}
+
+/// One lookup request: a search term within a lookup sector
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct Lookup {
+    pub sector: &'static str,
+    pub query: String,
+}
+
+/// Every lookup a full scrape runs: each single letter, digit and two-letter term in every sector
+pub(crate) fn lookup_queries() -> Vec<Lookup> {
+    let search_set: Vec<String> = (b'A'..=b'Z')
+        .chain(b'0'..=b'9')
+        .map(|c| format!("{}", c as char))
+        .chain(
+            (b'A'..=b'Z')
+                .flat_map(|c1| (b'A'..=b'Z').map(move |c2| format!("{}{}", c1 as char, c2 as char))),
+        )
+        .collect();
+
+    LOOKUP_SECTORS
+        .iter()
+        .flat_map(|sector| search_set.iter().map(move |query| Lookup { sector, query: query.clone() }))
+        .collect()
+}
+
+/// The lookups of a full scrape whose term starts with one of `letters`, ignoring case
+///
+/// A letter selects its single-letter term and the 26 two-letter terms it starts, in every
+/// sector; a digit only has its single-digit term.
+pub(crate) fn prefix_lookups(letters: &[char]) -> Vec<Lookup> {
+    lookup_queries()
+        .into_iter()
+        .filter(|lookup| letters.iter().any(|letter| lookup.query.starts_with(letter.to_ascii_uppercase())))
+        .collect()
+}
+
+/// Runs `lookups` against `base_url`, handing the tickers of each lookup to `on_result` as soon
+/// as it completes
+///
+/// Tickers with a name shorter than `config.min_name_len` are left out. `on_result` runs on the calling task between awaits, so it may borrow local state such as a
+/// connection. Lookups that fail are logged and skipped without calling it, and returned. Fails with
+/// `YahooSymbolsError::ScrapeLayoutChanged` as soon as the first `LAYOUT_CHECK_QUERIES` answered
+/// lookups have all parsed to zero rows.
+pub(crate) async fn run_lookups<F>(
+    base_url: &str,
+    config: &ScrapeConfig,
+    lookups: Vec<Lookup>,
+    mut on_result: F,
+) -> Result<Vec<Lookup>, Box<dyn Error>>
+where
+    F: FnMut(&Lookup, &[Ticker]),
+{
+    config.validate()?;
+
+    let total_steps = lookups.len();
+
+    // Create and configure the progress bar
+    let pb = ProgressBar::new(total_steps as u64);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("{msg} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})")?
+            .progress_chars("#>-"),
+    );
+
+    // One client for every request, so that connections and TLS sessions are reused
+    let client = Client::new();
+    let semaphore = Arc::new(Semaphore::new(config.concurrency));
+    // Tasks live in a JoinSet so that dropping this future aborts every pending request
+    let mut tasks = JoinSet::new();
+    // Lookups not handed back by their task yet; any left once every task has joined panicked
+    let mut pending = HashSet::new();
+
+    for lookup in lookups {
+        pending.insert(lookup.clone());
+        let pb = pb.clone();
+        let client = client.clone();
+        let semaphore = semaphore.clone();
+        let config = config.clone();
+        let base_url = base_url.to_string();
+        pb.set_message("Scraping Symbols from Yahoo Finance");
+
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire().await.expect("Semaphore acquire failed");
+
+            // Tickers parsed, or None if the query failed
+            let parsed = match scrape_all_pages(&client, &base_url, lookup.sector, &lookup.query, &config).await {
+                Ok(result) => Some(result),
+                Err(e) => {
+                    log::warn!("Skipping lookup {} {:?}: {}", lookup.sector, lookup.query, e);
+                    None
+                }
+            };
+
+            pb.inc(1);
+            (lookup, parsed)
+        });
+    }
+
+    let (mut answered, mut rows) = (0, 0);
+    let mut failed = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((lookup, Some(result))) => {
+                pending.remove(&lookup);
+                // Junk rows still show that the page layout parsed
+                rows += result.len();
+                let kept: Vec<Ticker> = result.into_iter().filter(|ticker| config.keeps(ticker)).collect();
+                on_result(&lookup, &kept);
+                answered += 1;
+                if answered == LAYOUT_CHECK_QUERIES && rows == 0 {
+                    tasks.abort_all();
+                    pb.abandon_with_message("Yahoo Finance lookup page layout changed");
+                    return Err(YahooSymbolsError::ScrapeLayoutChanged { queries: answered }.into());
+                }
+            }
+            Ok((lookup, None)) => {
+                pending.remove(&lookup);
+                failed.push(lookup);
+            }
+            Err(e) => log::warn!("Lookup task did not complete: {}", e),
+        }
+    }
+    for lookup in pending {
+        log::warn!("Counting lookup {} {:?} as failed", lookup.sector, lookup.query);
+        failed.push(lookup);
+    }
+    pb.finish_with_message("Completed symbol scraping");
+
+    Ok(failed)
+}
+
+
+fn lookup_url(base_url: &str, sector: &str, symbol: &str, start: u32, count: u32) -> String {
+    format!("{}{}?s={}&t=A&b={}&c={}", base_url, sector, symbol, start, count)
+}
+
+/// Fetches every page of lookup results for a query, advancing `b` until a page is not full
+///
+/// Stops with a warning, keeping the rows fetched so far, after `MAX_PAGES` pages or once `b`
+/// can no longer be advanced.
+async fn scrape_all_pages(
+    client: &Client,
+    base_url: &str,
+    sector: &str,
+    symbol: &str,
+    config: &ScrapeConfig,
+) -> Result<Vec<Ticker>, Box<dyn Error>> {
+    let mut result = Vec::new();
+    let mut start = config.start_offset;
+
+    for pages in 1.. {
+        let page = scrape_symbols(client, base_url, sector, symbol, start, config.page_size).await?;
+        result.extend(page.tickers);
+
+        // Skipped rows still count, so that one malformed row does not end the pagination early
+        if page.rows < config.page_size as usize {
+            break;
+        }
+        if pages == MAX_PAGES {
+            log::warn!("Stopped lookup {} {:?} after {} full pages", sector, symbol, MAX_PAGES);
+            break;
+        }
+        match start.checked_add(config.page_size) {
+            Some(next) => start = next,
+            None => {
+                log::warn!("Stopped lookup {} {:?} at offset {}, the last one a u32 can hold", sector, symbol, start);
+                break;
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+async fn scrape_symbols(
+    client: &Client,
+    base_url: &str,
+    sector: &str,
+    symbol: &str,
+    start: u32,
+    count: u32,
+) -> Result<LookupPage, Box<dyn Error>> {
+    let url = lookup_url(base_url, sector, symbol, start, count);
+    let response = client
+        .get(url)
+        .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36")
+        .send()
+        .await?;
+    let body = response.text().await?;
+
+    parse_lookup_page(&body)
+}
+
+/// Tickers read from one lookup page
+struct LookupPage {
+    /// Number of rows in the results table, including the ones that could not be read
+    rows: usize,
+    tickers: Vec<Ticker>,
+}
+
+/// CSS selectors locating the results table of a lookup page
+struct LookupSelectors {
+    row: Selector,
+    cell: Selector,
+    link: Selector,
+}
+
+impl LookupSelectors {
+    fn parse() -> Result<LookupSelectors, Box<dyn Error>> {
+        let parse = |css: &str| Selector::parse(css).map_err(|e| format!("Invalid selector {:?}: {}", css, e));
+        Ok(LookupSelectors {
+            row: parse("table tbody tr")?,
+            cell: parse("td")?,
+            link: parse("a")?,
+        })
+    }
+}
+
+/// Extracts the tickers listed in the results table of a lookup page
+///
+/// Rows with fewer than six cells, or without a `data-symbol` link in their first cell, are
+/// logged and skipped, so one malformed row does not cost the rest of the page.
+fn parse_lookup_page(body: &str) -> Result<LookupPage, Box<dyn Error>> {
+    let selectors = LookupSelectors::parse()?;
+    let document = Html::parse_document(body);
+    let mut page = LookupPage { rows: 0, tickers: Vec::new() };
+
+    // Extract symbol data
+    for row in document.select(&selectors.row) {
+        page.rows += 1;
+
+        // Extract data from each cell in the row
+        let columns: Vec<String> = row.select(&selectors.cell).map(|cell| cell.inner_html().trim().to_string()).collect();
+        if columns.len() < 6 {
+            log::warn!("Skipping lookup row with {} cells instead of 6", columns.len());
+            continue;
+        }
+
+        let symbol = Html::parse_fragment(&columns[0])
+            .select(&selectors.link)
+            .next()
+            .and_then(|a| a.value().attr("data-symbol"))
+            .map(str::to_string);
+        let Some(symbol) = symbol.filter(|symbol| !symbol.is_empty()) else {
+            log::warn!("Skipping lookup row without a symbol: {:?}", columns[1]);
+            continue;
+        };
+
+        page.tickers.push(Ticker {
+            symbol,
+            name: columns[1].clone(),
+            category: Html::parse_fragment(&columns[3])
+                .select(&selectors.link)
+                .next()
+                .map(|a| a.inner_html().trim().to_string())
+                .unwrap_or(NA_CATEGORY.to_string()),
+            asset_class: columns[4].clone(),
+            exchange: columns[5].clone(),
+        });
+    }
+    Ok(page)
+}
+
+fn document_exists_in_db(conn: &Connection, doc: &Ticker) -> bool {
+    let sql = "SELECT COUNT(*) FROM symbols WHERE symbol = ? AND asset_class = ?";
+    let count: i64 = conn.query_row(sql, [&doc.symbol, &doc.asset_class], |row| row.get(0)).unwrap_or(0);
+
+    count > 0
+}
+
+pub(crate) fn insert_document(conn: &Connection, doc: &Ticker) -> Result<()> {
+    let sql = "INSERT INTO symbols (symbol, name, category, asset_class, exchange, source) VALUES (?, ?, ?, ?, ?, ?)";
+    conn.execute(
+        sql,
+        params![
+            &doc.symbol,
+            decode_name(doc.name.clone()),
+            &doc.category,
+            &doc.asset_class,
+            &doc.exchange,
+            YAHOO_SOURCE
+        ],
+    )?;
+    Ok(())
+}
+
+pub async fn download_file(url: &str, path: &Path) -> Result<(), Box<dyn Error>> {
+    download_file_with_progress(url, path, None, None).await
+}
+
+/// Downloads `url` to `path`, reporting progress and giving up once `timeout` has elapsed
+///
+/// The body is streamed into a temporary file next to `path` that is only renamed into place once
+/// complete, so a failed or timed out download never leaves a truncated database behind. The
+/// response's `ETag` and `Last-Modified` headers are saved to `<path>.etag` for
+/// `download_file_if_modified`.
+pub async fn download_file_with_progress(
+    url: &str,
+    path: &Path,
+    timeout: Option<Duration>,
+    progress: Option<&DownloadProgress>,
+) -> Result<(), Box<dyn Error>> {
+    let partial = path.with_extension("download");
+    if let Some(validators) = download_to(url, &partial, timeout, progress, None).await? {
+        std::fs::rename(&partial, path)?;
+        validators.write(path)?;
+    }
+    Ok(())
+}
+
+/// Streams `url` into `writer` within `timeout`, returning the number of bytes written
+///
+/// Nothing is written to disk: the body goes to `writer` chunk by chunk as it arrives, and the
+/// writer is flushed at the end. `progress` is called as in `download_file_with_progress`, with
+/// the total size from the `Content-Length` header. Fails if the request fails, the server answers
+/// with an error status, or the body ends before the announced `Content-Length`; `writer` may then
+/// hold a partial file.
+pub async fn download_to_writer<W: AsyncWrite + Unpin>(
+    url: &str,
+    writer: &mut W,
+    timeout: Option<Duration>,
+    progress: Option<&DownloadProgress>,
+) -> Result<u64, Box<dyn Error>> {
+    let download = stream_to_writer(url, writer, progress);
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, download)
+            .await
+            .unwrap_or_else(|_| Err(format!("Download timed out after {:?}", timeout).into())),
+        None => download.await,
+    }
+}
+
+async fn stream_to_writer<W: AsyncWrite + Unpin>(
+    url: &str,
+    writer: &mut W,
+    progress: Option<&DownloadProgress>,
+) -> Result<u64, Box<dyn Error>> {
+    let mut response = Client::new().get(Url::parse(url)?).send().await?.error_for_status()?;
+    let total = response.content_length();
+
+    let mut downloaded = 0;
+    if let Some(progress) = progress {
+        progress.report(downloaded, total);
+    }
+    while let Some(chunk) = response.chunk().await? {
+        writer.write_all(&chunk).await?;
+        downloaded += chunk.len() as u64;
+        if let Some(progress) = progress {
+            progress.report(downloaded, total);
+        }
+    }
+    writer.flush().await?;
+
+    match total {
+        Some(total) if downloaded != total => Err(format!("Download of {} ended after {} of {} bytes", url, downloaded, total).into()),
+        _ => Ok(downloaded),
+    }
+}
+
+/// Re-downloads `url` to `path` only if the remote file changed since the last download
+///
+/// The `ETag` and `Last-Modified` values saved in `<path>.etag` by the previous download are sent
+/// as `If-None-Match` and `If-Modified-Since`. A `304 Not Modified` answer leaves `path` untouched
+/// and skips the transfer entirely. Otherwise the new file is downloaded as in
+/// `download_file_with_progress` and, when the host publishes `<url>.sha256`, checked against it
+/// before it replaces `path`. Without a saved `.etag` file or an existing `path` the download is
+/// unconditional.
+///
+/// Returns whether a new file was downloaded.
+pub async fn download_file_if_modified(
+    url: &str,
+    path: &Path,
+    timeout: Option<Duration>,
+    progress: Option<&DownloadProgress>,
+) -> Result<bool, Box<dyn Error>> {
+    let partial = path.with_extension("download");
+    let Some(validators) = download_if_modified_to(url, path, &partial, timeout, progress).await? else {
+        return Ok(false);
+    };
+    std::fs::rename(&partial, path)?;
+    validators.write(path)?;
+
+    Ok(true)
+}
+
+/// Downloads `url` to `target` unless it is unchanged since the download saved as `cached`
+///
+/// The download is checked against the published `.sha256` as in `download_file_if_modified`.
+/// Returns the validators to save for `cached` once `target` replaces it, or `None` if the server
+/// answered `304 Not Modified`, in which case `target` is not written.
+pub(crate) async fn download_if_modified_to(
+    url: &str,
+    cached: &Path,
+    target: &Path,
+    timeout: Option<Duration>,
+    progress: Option<&DownloadProgress>,
+) -> Result<Option<Validators>, Box<dyn Error>> {
+    let saved = if cached.exists() { Validators::read(cached) } else { None };
+    let Some(validators) = download_to(url, target, timeout, progress, saved.as_ref()).await? else {
+        return Ok(None);
+    };
+
+    if let Some(expected) = fetch_checksum(url).await {
+        if let Err(e) = verify_checksum(target, &expected) {
+            let _ = std::fs::remove_file(target);
+            return Err(e);
+        }
+    }
+
+    Ok(Some(validators))
+}
+
+/// Streams `url` into `partial` within `timeout`, removing it on failure
+///
+/// Returns the response validators, or `None` if the server answered `304 Not Modified` to the
+/// conditional headers built from `cached`.
+async fn download_to(
+    url: &str,
+    partial: &Path,
+    timeout: Option<Duration>,
+    progress: Option<&DownloadProgress>,
+    cached: Option<&Validators>,
+) -> Result<Option<Validators>, Box<dyn Error>> {
+    let download = stream_to_file(url, partial, progress, cached);
+
+    let result = match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, download)
+            .await
+            .unwrap_or_else(|_| Err(format!("Download timed out after {:?}", timeout).into())),
+        None => download.await,
+    };
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(partial);
+    }
+    result
+}
+
+/// Fetches the SHA-256 that `export_database` publishes at `<url>.sha256`, if the host has one
+pub async fn fetch_checksum(url: &str) -> Option<String> {
+    let url = format!("{}.{}", url, CHECKSUM_EXTENSION);
+    let response = reqwest::get(url).await.ok()?.error_for_status().ok()?;
+    parse_checksum(&response.text().await.ok()?)
+}
+
+async fn stream_to_file(
+    url: &str,
+    path: &Path,
+    progress: Option<&DownloadProgress>,
+    cached: Option<&Validators>,
+) -> Result<Option<Validators>, Box<dyn Error>> {
+    let mut request = Client::new().get(Url::parse(url)?);
+    if let Some(cached) = cached {
+        if let Some(etag) = &cached.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &cached.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    let mut response = request.send().await?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+    response = response.error_for_status()?;
+    let validators = Validators::from_headers(response.headers());
+    let total = response.content_length();
+    let mut dest = BufWriter::new(File::create(path)?);
+
+    let mut downloaded = 0;
+    if let Some(progress) = progress {
+        progress.report(downloaded, total);
+    }
+    while let Some(chunk) = response.chunk().await? {
+        dest.write_all(&chunk)?;
+        downloaded += chunk.len() as u64;
+        if let Some(progress) = progress {
+            progress.report(downloaded, total);
+        }
+    }
+    dest.flush()?;
+    Ok(Some(validators))
+}
+
+/// `ETag` and `Last-Modified` of a downloaded file, kept in `<file>.etag` as `etag: ...` and
+/// `last-modified: ...` lines
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Validators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl Validators {
+    fn from_headers(headers: &HeaderMap) -> Validators {
+        let get = |name| headers.get(name).and_then(|v: &HeaderValue| v.to_str().ok()).map(str::to_string);
+        Validators { etag: get(ETAG), last_modified: get(LAST_MODIFIED) }
+    }
+
+    fn sidecar_path(path: &Path) -> PathBuf {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(".etag");
+        path.with_file_name(name)
+    }
+
+    fn read(path: &Path) -> Option<Validators> {
+        let contents = std::fs::read_to_string(Validators::sidecar_path(path)).ok()?;
+        let mut validators = Validators::default();
+        for line in contents.lines() {
+            match line.split_once(": ") {
+                Some(("etag", value)) => validators.etag = Some(value.to_string()),
+                Some(("last-modified", value)) => validators.last_modified = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        Some(validators)
+    }
+
+    /// Saves the validators next to `path`, removing a stale file when the server sent none
+    pub(crate) fn write(&self, path: &Path) -> std::io::Result<()> {
+        let sidecar = Validators::sidecar_path(path);
+        if self == &Validators::default() {
+            return match std::fs::remove_file(sidecar) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+
+        let mut contents = String::new();
+        if let Some(etag) = &self.etag {
+            contents.push_str(&format!("etag: {}\n", etag));
+        }
+        if let Some(last_modified) = &self.last_modified {
+            contents.push_str(&format!("last-modified: {}\n", last_modified));
+        }
+        std::fs::write(sidecar, contents)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use rusqlite::Connection;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use crate::config::DownloadProgress;
+    use crate::database::SymbolDatabase;
+    use crate::error::YahooSymbolsError;
+    use crate::keys::{AssetClass, Category, Exchange};
+    use crate::provenance::read_build_info;
+    use crate::sha256::sha256_hex;
+    use crate::test_util::{html_response, serve_with};
+    use crate::scraper::{canonicalize_database, create_symbols_table, ScrapeConfig, Ticker, MAX_PAGES, MAX_PAGE_SIZE};
+    use super::{
+        create_checkpoint_table, download_file_if_modified, download_file_with_progress, download_to_writer, lookup_queries, lookup_url,
+        parse_lookup_page, run_lookups, scrape_all_pages, scrape_into_from, scrape_prefixes_into, scrape_to_database,
+        store_lookup, Lookup, Validators,
+    };
+
+    /// Serves `response` verbatim to every HTTP request, returning the base URL
+    async fn serve_raw(response: String) -> String {
+        serve_with(move |_| response.clone()).await
+    }
+
+    /// Serves `body` as the HTML response to every HTTP request, returning the base URL
+    async fn serve_html(body: &str) -> String {
+        serve_raw(html_response(body)).await
+    }
+
+    #[test]
+    fn check_scrape_config() {
+        let config = ScrapeConfig::default();
+        assert!(config.validate().is_ok());
+        assert_eq!(
+            lookup_url("https://finance.yahoo.com/lookup/", "all", "AB", 20_000, config.page_size),
+            "https://finance.yahoo.com/lookup/all?s=AB&t=A&b=20000&c=10000"
+        );
+
+        assert!(ScrapeConfig { page_size: 0, ..ScrapeConfig::default() }.validate().is_err());
+        assert!(ScrapeConfig { page_size: MAX_PAGE_SIZE + 1, ..ScrapeConfig::default() }.validate().is_err());
+        assert!(ScrapeConfig { concurrency: 0, ..ScrapeConfig::default() }.validate().is_err());
+        assert!(ScrapeConfig { page_size: 500, start_offset: 1_000, concurrency: 2, min_name_len: 3, deterministic: true }.validate().is_ok());
+    }
+
+    #[tokio::test]
+    async fn check_page_limits() {
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        // Every page comes back full, so only the limits end the pagination
+        let requests = Arc::new(AtomicU32::new(0));
+        let counter = requests.clone();
+        let base_url = serve_with(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            html_response(AAPL_ROW)
+        })
+        .await;
+        let client = reqwest::Client::new();
+
+        let config = ScrapeConfig { page_size: 1, start_offset: u32::MAX - 1, ..ScrapeConfig::default() };
+        let tickers = scrape_all_pages(&client, &base_url, "all", "A", &config).await.unwrap();
+        assert_eq!(tickers.len(), 2);
+        assert_eq!(requests.swap(0, Ordering::SeqCst), 2);
+
+        let config = ScrapeConfig { page_size: 1, ..ScrapeConfig::default() };
+        let tickers = scrape_all_pages(&client, &base_url, "all", "A", &config).await.unwrap();
+        assert_eq!(tickers.len(), MAX_PAGES as usize);
+        assert_eq!(requests.load(Ordering::SeqCst), MAX_PAGES);
+    }
+
+    #[tokio::test]
+    async fn check_cancelled_scrape_leaves_valid_database() {
+        const ANSWERED: i64 = 3;
+        let db_path = std::env::temp_dir().join(format!("yahoo-finance-symbols-cancel-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db_path);
+
+        // The first requests get a row of their own and the rest are left hanging, so the scrape
+        // can only end by being dropped
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut hanging = Vec::new();
+            let mut accepted = 0;
+            while let Ok((mut stream, _)) = listener.accept().await {
+                accepted += 1;
+                if accepted > ANSWERED {
+                    hanging.push(stream);
+                    continue;
+                }
+                let mut request = [0; 4096];
+                let _ = stream.read(&mut request).await;
+                let row = format!(
+                    r#"<table><tbody><tr><td><a data-symbol="SYM{0}">SYM{0}</a></td><td>Symbol {0}</td><td>1</td>
+                    <td><a>N/A</a></td><td>Stocks</td><td>NMS</td></tr></tbody></table>"#,
+                    accepted
+                );
+                let _ = stream.write_all(html_response(&row).as_bytes()).await;
+            }
+        });
+
+        let config = ScrapeConfig { concurrency: 2, ..ScrapeConfig::default() };
+        let scrape = scrape_to_database(&db_path, &base_url, &config, false);
+        // Rows are written on the scrape's own task, so once they show up they are committed
+        let stored = async {
+            loop {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                let count = Connection::open(&db_path)
+                    .and_then(|conn| conn.query_row("SELECT COUNT(*) FROM symbols", [], |row| row.get::<_, i64>(0)));
+                if count.is_ok_and(|count| count == ANSWERED) {
+                    break;
+                }
+            }
+        };
+        tokio::select! {
+            result = scrape => panic!("the scrape finished with {:?}", result.map(|result| result.total_inserted)),
+            _ = tokio::time::timeout(Duration::from_secs(10), stored) => {}
+        }
+
+        let conn = Connection::open(&db_path).unwrap();
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM symbols", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, ANSWERED);
+        let completed: i64 = conn.query_row("SELECT COUNT(*) FROM scrape_checkpoint", [], |row| row.get(0)).unwrap();
+        assert_eq!(completed, ANSWERED);
+        let integrity: String = conn.query_row("PRAGMA integrity_check", [], |row| row.get(0)).unwrap();
+        assert_eq!(integrity, "ok");
+
+        drop(conn);
+        std::fs::remove_file(&db_path).unwrap();
+    }
+
+    const AAPL_ROW: &str = r#"<table><tbody><tr><td><a data-symbol="AAPL">AAPL</a></td><td>Apple Inc.</td><td>1</td>
+        <td><a>Technology</a></td><td>Stocks</td><td>NMS</td></tr></tbody></table>"#;
+
+    #[tokio::test]
+    async fn check_scrape_into_connection() {
+        let base_url = serve_html(AAPL_ROW).await;
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        let config = ScrapeConfig { concurrency: 20, ..ScrapeConfig::default() };
+        assert_eq!(scrape_into_from(&mut conn, &base_url, &config, false).await.unwrap().total_inserted, 1);
+        // Every query returns the same row, which is only written once
+        let name: String = conn.query_row("SELECT name FROM symbols WHERE symbol = 'AAPL'", [], |row| row.get(0)).unwrap();
+        assert_eq!(name, "Apple Inc.");
+        assert_eq!(scrape_into_from(&mut conn, &base_url, &config, false).await.unwrap().total_inserted, 0);
+    }
+
+    #[tokio::test]
+    async fn check_min_name_len() {
+        const MIXED_ROWS: &str = r#"<table><tbody>
+            <tr><td><a data-symbol="AAPL">AAPL</a></td><td>Apple Inc.</td><td>1</td><td><a>Technology</a></td><td>Stocks</td><td>NMS</td></tr>
+            <tr><td><a data-symbol="EMPTY">EMPTY</a></td><td></td><td>1</td><td><a>N/A</a></td><td>Stocks</td><td>NMS</td></tr>
+            <tr><td><a data-symbol="X">X</a></td><td>X</td><td>1</td><td><a>N/A</a></td><td>Stocks</td><td>NYQ</td></tr>
+            <tr><td><a data-symbol="T">T</a></td><td>AT&amp;T Inc.</td><td>1</td><td><a>Communication Services</a></td><td>Stocks</td><td>NYQ</td></tr>
+            <tr><td><a data-symbol="AMP">AMP</a></td><td>&amp;</td><td>1</td><td><a>N/A</a></td><td>Stocks</td><td>NYQ</td></tr>
+            </tbody></table>"#;
+        let base_url = serve_html(MIXED_ROWS).await;
+
+        // The default only drops the empty name
+        let mut conn = Connection::open_in_memory().unwrap();
+        let config = ScrapeConfig { concurrency: 20, ..ScrapeConfig::default() };
+        assert_eq!(scrape_into_from(&mut conn, &base_url, &config, false).await.unwrap().total_inserted, 4);
+        let mut stmt = conn.prepare("SELECT symbol FROM symbols ORDER BY symbol").unwrap();
+        let stored: Vec<String> = stmt.query_map([], |row| row.get(0)).unwrap().map(|s| s.unwrap()).collect();
+        assert_eq!(stored, vec!["AAPL", "AMP", "T", "X"]);
+
+        // `&amp;` decodes to a single character
+        for (min_name_len, expected) in [(2, vec!["AAPL", "T"]), (0, vec!["AAPL", "AMP", "EMPTY", "T", "X"])] {
+            let config = ScrapeConfig { min_name_len, ..ScrapeConfig::default() };
+            let mut kept = Vec::new();
+            let lookups = vec![Lookup { sector: "all", query: "A".to_string() }];
+            run_lookups(&base_url, &config, lookups, |_: &Lookup, result: &[Ticker]| {
+                kept.extend(result.iter().map(|t| t.symbol.clone()))
+            })
+            .await
+            .unwrap();
+            kept.sort();
+            assert_eq!(kept, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn check_symbol_per_asset_class() {
+        let db_path = std::env::temp_dir().join(format!("yahoo-finance-symbols-listings-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db_path);
+        let listing = |asset_class: &str, exchange: &str| Ticker {
+            symbol: "QQQ".to_string(),
+            name: "Invesco QQQ Trust".to_string(),
+            category: "N/A".to_string(),
+            asset_class: asset_class.to_string(),
+            exchange: exchange.to_string(),
+        };
+        {
+            let mut conn = Connection::open(&db_path).unwrap();
+            create_symbols_table(&conn).unwrap();
+            create_checkpoint_table(&conn).unwrap();
+            let lookup = Lookup { sector: "all", query: "Q".to_string() };
+            let tickers = [listing("ETF", "NMS"), listing("Mutual Fund", "NAS"), listing("ETF", "PCX")];
+            // The second ETF listing repeats the key of the first and is skipped
+            assert_eq!(store_lookup(&mut conn, &lookup, &tickers).unwrap(), 2);
+        }
+
+        let db = SymbolDatabase::open(&db_path).unwrap();
+        let etfs = db.get_symbols(AssetClass::ETFs, Category::All, Exchange::All).await.unwrap();
+        assert_eq!(etfs.iter().map(|s| (s.symbol.as_str(), s.exchange.as_str())).collect::<Vec<_>>(), vec![("QQQ", "NMS")]);
+        let funds = db.get_symbols(AssetClass::MutualFunds, Category::All, Exchange::All).await.unwrap();
+        assert_eq!(funds.iter().map(|s| s.symbol.as_str()).collect::<Vec<_>>(), vec!["QQQ"]);
+        assert_eq!(db.get_symbol("QQQ").await.unwrap().asset_class, "ETF");
+        assert_eq!(db.get_multi_class_symbols().await.unwrap().len(), 1);
+
+        drop(db);
+        std::fs::remove_file(&db_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_deterministic_scrape() {
+        // Each query answers with a row of its own, so rows are inserted in completion order
+        let base_url = serve_with(|request| {
+            let query = request.split("s=").nth(1).and_then(|rest| rest.split('&').next()).unwrap_or_default().to_string();
+            let sector = if request.starts_with("GET /options?") { "Options" } else { "Stocks" };
+            html_response(&format!(
+                r#"<table><tbody><tr><td><a data-symbol="{0}">{0}</a></td><td>{0} Corp</td><td>1</td>
+                <td><a>N/A</a></td><td>{1}</td><td>NMS</td></tr></tbody></table>"#,
+                query, sector
+            ))
+        })
+        .await;
+
+        let config = ScrapeConfig { concurrency: 20, deterministic: true, ..ScrapeConfig::default() };
+        let mut checksums = Vec::new();
+        for run in 0..2 {
+            let db_path = std::env::temp_dir().join(format!("yahoo-finance-symbols-deterministic-{}-{}.db", run, std::process::id()));
+            let _ = std::fs::remove_file(&db_path);
+            scrape_to_database(&db_path, &base_url, &config, false).await.unwrap();
+            checksums.push(sha256_hex(&std::fs::read(&db_path).unwrap()));
+
+            let conn = Connection::open(&db_path).unwrap();
+            let info = read_build_info(&conn).unwrap();
+            assert!(info.built_at.is_none());
+            assert!(info.scrape_config.unwrap().deterministic);
+            let mut stmt = conn.prepare("SELECT symbol FROM symbols ORDER BY rowid").unwrap();
+            let stored: Vec<String> = stmt.query_map([], |row| row.get(0)).unwrap().map(|s| s.unwrap()).collect();
+            assert!(stored.len() > 100 && stored.windows(2).all(|w| w[0] <= w[1]));
+            drop(stmt);
+            drop(conn);
+            std::fs::remove_file(&db_path).unwrap();
+        }
+        assert_eq!(checksums[0], checksums[1]);
+
+        // The same rows inserted in opposite orders, with different histories, canonicalize alike
+        let mut checksums = Vec::new();
+        for (run, order) in [["AAPL", "MSFT", "T"], ["T", "MSFT", "AAPL"]].into_iter().enumerate() {
+            let db_path = std::env::temp_dir().join(format!("yahoo-finance-symbols-canonical-{}-{}.db", run, std::process::id()));
+            let _ = std::fs::remove_file(&db_path);
+            {
+                let mut conn = Connection::open(&db_path).unwrap();
+                create_symbols_table(&conn).unwrap();
+                create_checkpoint_table(&conn).unwrap();
+                if run == 1 {
+                    conn.execute_batch("INSERT INTO symbols (symbol, asset_class) VALUES ('JUNK', 'Stocks'); DELETE FROM symbols;").unwrap();
+                }
+                let tickers: Vec<Ticker> = order
+                    .iter()
+                    .map(|symbol| Ticker {
+                        symbol: symbol.to_string(),
+                        name: format!("{} Inc.", symbol),
+                        category: "N/A".to_string(),
+                        asset_class: "Stocks".to_string(),
+                        exchange: "NYQ".to_string(),
+                    })
+                    .collect();
+                store_lookup(&mut conn, &Lookup { sector: "all", query: "A".to_string() }, &tickers).unwrap();
+            }
+            canonicalize_database(&db_path).unwrap();
+            checksums.push(sha256_hex(&std::fs::read(&db_path).unwrap()));
+            let db = SymbolDatabase::open(&db_path).unwrap();
+            assert_eq!(db.get_symbol("MSFT").await.unwrap().name, "MSFT Inc.");
+            drop(db);
+            std::fs::remove_file(&db_path).unwrap();
+        }
+        assert_eq!(checksums[0], checksums[1]);
+    }
+
+    #[tokio::test]
+    async fn check_scrape_prefixes() {
+        // Each query answers with a ticker named after it
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        let base_url = serve_with(move |request| {
+            let query = request.split("s=").nth(1).and_then(|rest| rest.split('&').next()).unwrap_or_default().to_string();
+            let sector = if request.starts_with("GET /options?") { "Options" } else { "Stocks" };
+            seen.lock().unwrap().push(query.clone());
+            html_response(&format!(
+                r#"<table><tbody><tr><td><a data-symbol="{0}">{0}</a></td><td>{0} Corp</td><td>1</td>
+                <td><a>N/A</a></td><td>{1}</td><td>NMS</td></tr></tbody></table>"#,
+                query, sector
+            ))
+        })
+        .await;
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        create_symbols_table(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO symbols VALUES ('Q', 'Old Name', 'N/A', 'Stocks', 'NMS', 'yahoo', NULL);
+             INSERT INTO symbols VALUES ('QQ', 'Mine', 'N/A', 'Stocks', 'NMS', 'user', NULL);",
+        )
+        .unwrap();
+
+        let config = ScrapeConfig { concurrency: 20, ..ScrapeConfig::default() };
+        let result = scrape_prefixes_into(&mut conn, &base_url, &config, &['q', '7']).await.unwrap();
+
+        // "Q", "QA" to "QZ" and "7", in both sectors, and nothing else
+        let requested = requests.lock().unwrap().clone();
+        assert_eq!(requested.len(), 2 * 28);
+        assert!(requested.iter().all(|query| query.starts_with('Q') || query == "7"));
+        assert_eq!(result.total_inserted, 2 * 28 - 2);
+        assert!(result.failed_sectors.is_empty());
+
+        // Yahoo rows are updated, user rows kept
+        let name = |symbol: &str| -> String {
+            conn.query_row("SELECT name FROM symbols WHERE symbol = ? AND asset_class = 'Stocks'", [symbol], |row| row.get(0)).unwrap()
+        };
+        assert_eq!(name("Q"), "Q Corp");
+        assert_eq!(name("QQ"), "Mine");
+
+        // Running it again only updates
+        assert_eq!(scrape_prefixes_into(&mut conn, &base_url, &config, &['Q']).await.unwrap().total_inserted, 0);
+
+        let requests_before = requests.lock().unwrap().len();
+        assert!(scrape_prefixes_into(&mut conn, &base_url, &config, &['*']).await.is_err());
+        assert_eq!(requests.lock().unwrap().len(), requests_before);
+    }
+
+    #[tokio::test]
+    async fn check_resume_scrape() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        // Until cleared, the ZZ lookup drops the connection without answering
+        let failing = Arc::new(AtomicBool::new(true));
+        let fails = failing.clone();
+        let base_url = serve_with(move |request| {
+            seen.lock().unwrap().push(request.lines().next().unwrap_or_default().to_string());
+            if fails.load(Ordering::SeqCst) && request.starts_with("GET /options?s=ZZ&") {
+                return String::new();
+            }
+            html_response(AAPL_ROW)
+        })
+        .await;
+
+        // An interrupted scrape that completed every lookup except two
+        let mut conn = Connection::open_in_memory().unwrap();
+        create_checkpoint_table(&conn).unwrap();
+        let remaining = [Lookup { sector: "options", query: "A".to_string() }, Lookup { sector: "options", query: "ZZ".to_string() }];
+        for lookup in lookup_queries().iter().filter(|lookup| !remaining.contains(lookup)) {
+            store_lookup(&mut conn, lookup, &[]).unwrap();
+        }
+
+        let config = ScrapeConfig::default();
+        assert_eq!(scrape_into_from(&mut conn, &base_url, &config, true).await.unwrap().total_inserted, 1);
+        let mut requested = requests.lock().unwrap().clone();
+        requested.sort();
+        assert_eq!(requested.len(), 2);
+        assert!(requested[0].starts_with("GET /options?s=A&"));
+        assert!(requested[1].starts_with("GET /options?s=ZZ&"));
+
+        // The failed lookup is still missing from the checkpoint, so resuming retries it alone
+        let checkpoints: i64 = conn.query_row("SELECT COUNT(*) FROM scrape_checkpoint", [], |row| row.get(0)).unwrap();
+        assert_eq!(checkpoints as usize, lookup_queries().len() - 1);
+        failing.store(false, Ordering::SeqCst);
+        requests.lock().unwrap().clear();
+        let result = scrape_into_from(&mut conn, &base_url, &config, true).await.unwrap();
+        assert!(result.failed_sectors.is_empty());
+        let requested = requests.lock().unwrap().clone();
+        assert_eq!(requested.len(), 1);
+        assert!(requested[0].starts_with("GET /options?s=ZZ&"));
+
+        // The finished scrape clears its checkpoint
+        let checkpoints: i64 = conn.query_row("SELECT COUNT(*) FROM scrape_checkpoint", [], |row| row.get(0)).unwrap();
+        assert_eq!(checkpoints, 0);
+    }
+
+    #[tokio::test]
+    async fn check_scrape_result() {
+        const OPTION_ROW: &str = r#"<table><tbody><tr><td><a data-symbol="AAPL240119C00150000">AAPL240119C00150000</a></td>
+            <td>AAPL Jan 2024 150.000 call</td><td>1</td><td><a>N/A</a></td><td>Options</td><td>OPR</td></tr></tbody></table>"#;
+        // Every options lookup but one drops the connection without answering
+        let base_url = serve_with(|request| {
+            if request.starts_with("GET /all?") {
+                html_response(AAPL_ROW)
+            } else if request.starts_with("GET /options?s=A&") {
+                html_response(OPTION_ROW)
+            } else {
+                String::new()
+            }
+        })
+        .await;
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        let config = ScrapeConfig { concurrency: 20, ..ScrapeConfig::default() };
+        let result = scrape_into_from(&mut conn, &base_url, &config, false).await.unwrap();
+        assert_eq!(result.total_inserted, 2);
+        assert_eq!(result.per_sector.values().sum::<usize>(), result.total_inserted);
+        assert_eq!((result.per_sector["all"], result.per_sector["options"]), (1, 1));
+        assert_eq!(result.failed_sectors, vec!["options"]);
+        assert!(result.duration > Duration::ZERO);
+
+        // Nothing new the second time, and every sector is still reported
+        let result = scrape_into_from(&mut conn, &base_url, &config, false).await.unwrap();
+        assert_eq!(result.total_inserted, 0);
+        assert_eq!(result.per_sector.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn check_scrape_options() {
+        const OPTION_ROW: &str = r#"<table><tbody><tr><td><a data-symbol="AAPL250117C00150000">AAPL250117C00150000</a></td>
+            <td>AAPL Jan 2025 150.000 call</td><td>1</td><td><a>N/A</a></td><td>Option</td><td>OPR</td></tr></tbody></table>"#;
+        let base_url = serve_with(|request| {
+            html_response(if request.starts_with("GET /options?") { OPTION_ROW } else { AAPL_ROW })
+        })
+        .await;
+
+        let db_path = std::env::temp_dir().join(format!("yahoo-finance-symbols-options-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db_path);
+        scrape_to_database(&db_path, &base_url, &ScrapeConfig { concurrency: 20, ..ScrapeConfig::default() }, false).await.unwrap();
+
+        let db = SymbolDatabase::open(&db_path).unwrap();
+        let info = db.get_build_info().await.unwrap();
+        assert_eq!(info.source.as_deref(), Some("yahoo"));
+        assert_eq!(info.scrape_config.unwrap().concurrency, 20);
+        let options = db.get_symbols(AssetClass::Options, Category::All, Exchange::OPRA).await.unwrap();
+        assert_eq!(options.len(), 1);
+        assert_eq!(options[0].symbol, "AAPL250117C00150000");
+        assert_eq!(options[0].asset_class_enum(), Some(AssetClass::Options));
+        let found = db.try_search_symbols("AAPL", Some("Options")).await.unwrap();
+        assert_eq!(found.keys().collect::<Vec<_>>(), vec!["AAPL250117C00150000"]);
+        assert_eq!(db.get_symbols(AssetClass::Stocks, Category::All, Exchange::All).await.unwrap().len(), 1);
+
+        drop(db);
+        std::fs::remove_file(&db_path).unwrap();
+    }
+
+    #[test]
+    fn check_malformed_lookup_rows() {
+        let page = parse_lookup_page(
+            r#"<table><tbody>
+            <tr><td><a data-symbol="AAPL">AAPL</a></td><td>Apple Inc.</td><td>1</td><td><a>Technology</a></td><td>Stocks</td><td>NMS</td></tr>
+            <tr><td>MSFT</td><td>Microsoft Corporation</td><td>1</td><td></td><td>Stocks</td><td>NMS</td></tr>
+            <tr><td><a data-symbol="GOOG">GOOG</a></td><td>Alphabet Inc.</td></tr>
+            <tr><td><a data-symbol="SPY">SPY</a><td>SPDR S&P 500 ETF Trust<td>1<td><td>ETF<td>PCX
+            <tr><td><a data-symbol="">?</a></td><td>Unknown</td><td>1</td><td></td><td>Stocks</td><td>NMS</td></tr>
+            </tbody></table>"#,
+        ).unwrap();
+        assert_eq!(page.rows, 5);
+        let symbols: Vec<&str> = page.tickers.iter().map(|ticker| ticker.symbol.as_str()).collect();
+        assert_eq!(symbols, ["AAPL", "SPY"]);
+        assert_eq!(page.tickers[1].category, "N/A");
+        assert_eq!(page.tickers[1].exchange, "PCX");
+
+        // Not HTML at all
+        assert_eq!(parse_lookup_page("\u{0}<<>>&&</td></tr>").unwrap().rows, 0);
+    }
+
+    #[tokio::test]
+    async fn check_scrape_layout_changed() {
+        let tickers = parse_lookup_page(AAPL_ROW).unwrap().tickers;
+        assert_eq!(tickers.len(), 1);
+        assert_eq!(tickers[0].symbol, "AAPL");
+        assert_eq!(tickers[0].category, "Technology");
+
+        let redesigned = "<html><body><div class=\"results\"><span>AAPL</span><span>Apple Inc.</span></div></body></html>";
+        assert_eq!(parse_lookup_page(redesigned).unwrap().rows, 0);
+
+        let db_path = std::env::temp_dir().join(format!("yahoo-finance-symbols-layout-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db_path);
+
+        let base_url = serve_html(redesigned).await;
+        let err = scrape_to_database(&db_path, &base_url, &ScrapeConfig::default(), false).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<YahooSymbolsError>(),
+            Some(YahooSymbolsError::ScrapeLayoutChanged { .. })
+        ));
+
+        std::fs::remove_file(&db_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_conditional_download() {
+        let path = std::env::temp_dir().join(format!("yahoo-finance-symbols-etag-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        let url = serve_with(move |request| {
+            seen.lock().unwrap().push(request.to_lowercase());
+            if request.starts_with("GET /symbols.db.sha256 ") {
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+            } else if request.to_lowercase().contains("if-none-match: \"v1\"") {
+                "HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nConnection: close\r\n\r\n".to_string()
+            } else {
+                "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nLast-Modified: Wed, 01 Jan 2025 00:00:00 GMT\r\nContent-Length: 2\r\nConnection: close\r\n\r\nv1".to_string()
+            }
+        })
+        .await;
+        let url = format!("{}symbols.db", url);
+
+        assert!(download_file_if_modified(&url, &path, None, None).await.unwrap());
+        assert_eq!(std::fs::read(&path).unwrap(), b"v1");
+        let sidecar = Validators::sidecar_path(&path);
+        assert_eq!(
+            std::fs::read_to_string(&sidecar).unwrap(),
+            "etag: \"v1\"\nlast-modified: Wed, 01 Jan 2025 00:00:00 GMT\n"
+        );
+
+        std::fs::write(&path, b"local").unwrap();
+        assert!(!download_file_if_modified(&url, &path, None, None).await.unwrap());
+        assert_eq!(std::fs::read(&path).unwrap(), b"local");
+        assert!(requests.lock().unwrap().last().unwrap().contains("if-modified-since: wed, 01 jan 2025 00:00:00 gmt"));
+
+        // Without the saved validators the file is fetched again
+        std::fs::remove_file(&sidecar).unwrap();
+        assert!(download_file_if_modified(&url, &path, None, None).await.unwrap());
+        assert_eq!(std::fs::read(&path).unwrap(), b"v1");
+
+        std::fs::remove_file(&sidecar).unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_download_to_writer() {
+        let reports = Arc::new(Mutex::new(Vec::<(u64, Option<u64>)>::new()));
+        let recorded = reports.clone();
+        let progress = DownloadProgress::new(move |done, total| recorded.lock().unwrap().push((done, total)));
+
+        let body = "0123456789".repeat(1000);
+        let url = serve_html(&body).await;
+        let mut buffer = Vec::new();
+        let written = download_to_writer(&url, &mut buffer, Some(Duration::from_secs(10)), Some(&progress)).await.unwrap();
+        assert_eq!(written, 10_000);
+        assert_eq!(buffer, body.as_bytes());
+        assert_eq!(reports.lock().unwrap().first(), Some(&(0, Some(10_000))));
+        assert_eq!(reports.lock().unwrap().last(), Some(&(10_000, Some(10_000))));
+
+        // Error statuses and truncated bodies fail
+        let url = serve_raw("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()).await;
+        assert!(download_to_writer(&url, &mut Vec::new(), None, None).await.is_err());
+        let url = serve_raw("HTTP/1.1 200 OK\r\nContent-Length: 100\r\nConnection: close\r\n\r\nabc".to_string()).await;
+        assert!(download_to_writer(&url, &mut Vec::new(), None, None).await.is_err());
+        assert!(download_to_writer("http://127.0.0.1:9/symbols.db", &mut Vec::new(), None, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn check_download_progress_and_timeout() {
+        let path = std::env::temp_dir().join(format!("yahoo-finance-symbols-download-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let reports = Arc::new(Mutex::new(Vec::<(u64, Option<u64>)>::new()));
+        let recorded = reports.clone();
+        let progress = DownloadProgress::new(move |done, total| recorded.lock().unwrap().push((done, total)));
+
+        let url = serve_html("0123456789").await;
+        download_file_with_progress(&url, &path, Some(Duration::from_secs(10)), Some(&progress)).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"0123456789");
+        assert_eq!(reports.lock().unwrap().last(), Some(&(10, Some(10))));
+
+        // Without a Content-Length the body runs until the connection closes
+        reports.lock().unwrap().clear();
+        let url = serve_raw("HTTP/1.1 200 OK\r\nConnection: close\r\n\r\nabcdef".to_string()).await;
+        download_file_with_progress(&url, &path, None, Some(&progress)).await.unwrap();
+        assert_eq!(reports.lock().unwrap().last(), Some(&(6, None)));
+
+        // A server that never answers hits the timeout and leaves the existing file untouched
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let _stalled = listener.accept().await;
+            tokio::time::sleep(Duration::from_secs(30)).await;
+        });
+        assert!(download_file_with_progress(&url, &path, Some(Duration::from_millis(200)), None).await.is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), b"abcdef");
+        assert!(!path.with_extension("download").exists());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
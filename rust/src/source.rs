@@ -0,0 +1,261 @@
+//! Pluggable providers of symbol data.
+//!
+//! Yahoo Finance's lookup endpoint is unofficial and breaks from time to time. Implementing
+//! [`SymbolSource`] lets the database be built from any other feed (a CSV export, another API, a
+//! fixed list in tests) through [`save_symbols_from`] and `update_database_from`, while
+//! [`YahooSource`] remains the default used by `save_symbols` and `update_database`.
+//!
+//! A source only has to fetch its symbols; [`SymbolSource::save_to`] writes them, and
+//! `YahooSource` overrides it to write each lookup as it is scraped.
+
+#[cfg(feature = "download")]
+use std::collections::HashSet;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+use std::time::Instant;
+use futures::future::BoxFuture;
+use rusqlite::{params, Connection};
+#[cfg(feature = "download")]
+use crate::config::get_config;
+use crate::import::USER_SOURCE;
+use crate::provenance::record_build;
+use crate::scraper::{create_symbols_table, ScrapeResult};
+#[cfg(feature = "download")]
+use crate::scraper::{lookup_queries, run_lookups, scrape_to_database, Lookup, ScrapeConfig, Ticker, LOOKUP_URL};
+use crate::Symbol;
+
+
+/// A provider of the complete list of symbols
+pub trait SymbolSource: Send + Sync {
+    /// Short identifier stored in the `source` column of every row written from this source
+    ///
+    /// `"user"` is reserved for imported rows and cannot be used.
+    fn name(&self) -> &str;
+
+    /// Fetches every symbol offered by this source
+    fn fetch_symbols(&self) -> BoxFuture<'_, Result<Vec<Symbol>, Box<dyn Error>>>;
+
+    /// Writes the symbols of this source into the database at `db_path`, created if missing
+    ///
+    /// By default every symbol is fetched first, then those not in the database yet are inserted
+    /// in a single transaction and counted in `per_sector` under the source name.
+    fn save_to<'a>(&'a self, db_path: &'a Path) -> BoxFuture<'a, Result<ScrapeResult, Box<dyn Error>>> {
+        Box::pin(async move {
+            let started = Instant::now();
+            let symbols = self.fetch_symbols().await?;
+
+            let conn = Connection::open(db_path)?;
+            create_symbols_table(&conn)?;
+            let inserted = insert_symbols(&conn, &symbols, self.name())?;
+            record_build(&conn, self.name(), None)?;
+
+            Ok(ScrapeResult {
+                total_inserted: inserted,
+                per_sector: HashMap::from([(self.name().to_string(), inserted)]),
+                duration: started.elapsed(),
+                failed_sectors: Vec::new(),
+            })
+        })
+    }
+}
+
+/// The Yahoo Finance lookup scraper
+#[cfg(feature = "download")]
+#[derive(Debug, Clone)]
+pub struct YahooSource {
+    config: ScrapeConfig,
+    base_url: String,
+}
+
+#[cfg(feature = "download")]
+impl YahooSource {
+    /// Scrapes with the given paging parameters
+    pub fn new(config: ScrapeConfig) -> YahooSource {
+        YahooSource { config, base_url: LOOKUP_URL.to_string() }
+    }
+}
+
+#[cfg(feature = "download")]
+impl Default for YahooSource {
+    /// Scrapes with the global scrape settings
+    fn default() -> Self {
+        YahooSource::new(get_config().scrape)
+    }
+}
+
+#[cfg(feature = "download")]
+impl SymbolSource for YahooSource {
+    fn name(&self) -> &str {
+        crate::import::YAHOO_SOURCE
+    }
+
+    fn fetch_symbols(&self) -> BoxFuture<'_, Result<Vec<Symbol>, Box<dyn Error>>> {
+        Box::pin(async move {
+            let mut tickers: Vec<Ticker> = Vec::new();
+            run_lookups(&self.base_url, &self.config, lookup_queries(), |_: &Lookup, result: &[Ticker]| {
+                tickers.extend_from_slice(result)
+            })
+            .await?;
+
+            // Queries overlap, so the same listing is usually returned several times
+            let mut seen = HashSet::new();
+            Ok(tickers
+                .into_iter()
+                .filter(|t| seen.insert((t.symbol.clone(), t.asset_class.clone())))
+                .map(Symbol::from)
+                .collect())
+        })
+    }
+
+    /// Scrapes lookup by lookup, as `scraper::save_symbols_with_config` does, so that the result
+    /// counts rows per lookup sector and an interrupted save can be continued with
+    /// `scraper::resume_scrape`
+    fn save_to<'a>(&'a self, db_path: &'a Path) -> BoxFuture<'a, Result<ScrapeResult, Box<dyn Error>>> {
+        Box::pin(scrape_to_database(db_path, &self.base_url, &self.config, false))
+    }
+}
+
+/// Fetches all symbols from `source` into the database at `db_path`
+///
+/// Rows already in the database, including user-imported ones, are kept; the source only adds
+/// symbols that are missing, through [`SymbolSource::save_to`].
+///
+/// # Arguments
+///
+/// * `db_path` - path of the SQLite file, created if missing
+/// * `source` - where to fetch the symbols from
+///
+/// # Example
+///
+#[cfg_attr(feature = "download", doc = "```no_run")]
+#[cfg_attr(not(feature = "download"), doc = "```ignore")]
+/// use yahoo_finance_symbols::source::{save_symbols_from, YahooSource};
+/// use std::error::Error;
+/// use std::path::Path;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     save_symbols_from(Path::new("symbols.db"), &YahooSource::default()).await?;
+///     Ok(())
+/// }
+/// ```
+pub async fn save_symbols_from(db_path: &Path, source: &dyn SymbolSource) -> Result<ScrapeResult, Box<dyn Error>> {
+    if source.name() == USER_SOURCE {
+        return Err(format!("Symbol source name `{}` is reserved for imported rows", USER_SOURCE).into());
+    }
+
+    source.save_to(db_path).await
+}
+
+/// Inserts the symbols that are not in the database yet, tagged with `source_name`
+fn insert_symbols(conn: &Connection, symbols: &[Symbol], source_name: &str) -> rusqlite::Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    let mut count = 0;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT OR IGNORE INTO symbols (symbol, name, category, asset_class, exchange, source) VALUES (?, ?, ?, ?, ?, ?)",
+        )?;
+        for symbol in symbols {
+            count += stmt.execute(params![
+                &symbol.symbol,
+                &symbol.name,
+                &symbol.category,
+                &symbol.asset_class,
+                &symbol.exchange,
+                source_name
+            ])?;
+        }
+    }
+    tx.commit()?;
+
+    Ok(count)
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use std::error::Error;
+    use futures::future::BoxFuture;
+    use rusqlite::Connection;
+    use crate::import::import_into;
+    use crate::provenance::read_build_info;
+    use crate::rebuild_database;
+    use crate::Symbol;
+    use super::{save_symbols_from, SymbolSource};
+
+    struct MockSource(Vec<Symbol>);
+
+    impl SymbolSource for MockSource {
+        fn name(&self) -> &str {
+            "mock"
+        }
+
+        fn fetch_symbols(&self) -> BoxFuture<'_, Result<Vec<Symbol>, Box<dyn Error>>> {
+            Box::pin(async move { Ok(self.0.clone()) })
+        }
+    }
+
+    fn symbol(ticker: &str, name: &str) -> Symbol {
+        Symbol { symbol: ticker.to_string(), name: name.to_string(), ..Symbol::new() }
+    }
+
+    #[tokio::test]
+    async fn check_save_symbols_from_source() {
+        let db_path = std::env::temp_dir().join(format!("yahoo-finance-symbols-source-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db_path);
+
+        let source = MockSource(vec![symbol("AAPL", "Apple Inc."), symbol("MSFT", "Microsoft Corporation")]);
+        let result = save_symbols_from(&db_path, &source).await.unwrap();
+        assert_eq!(result.total_inserted, 2);
+        assert_eq!(result.per_sector.get("mock"), Some(&2));
+        import_into(&Connection::open(&db_path).unwrap(), &[symbol("AAPL", "My Apple")]).unwrap();
+
+        rebuild_database(&db_path, |path| async move { save_symbols_from(&path, &source).await }).await.unwrap();
+        let conn = Connection::open(&db_path).unwrap();
+        let rows: Vec<(String, String, String)> = conn
+            .prepare("SELECT symbol, name, source FROM symbols ORDER BY symbol").unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap()
+            .collect::<rusqlite::Result<_>>().unwrap();
+        assert_eq!(rows, vec![
+            ("AAPL".to_string(), "My Apple".to_string(), "user".to_string()),
+            ("MSFT".to_string(), "Microsoft Corporation".to_string(), "mock".to_string()),
+        ]);
+        assert_eq!(read_build_info(&conn).unwrap().source.as_deref(), Some("mock"));
+
+        drop(conn);
+        std::fs::remove_file(&db_path).unwrap();
+    }
+
+    #[cfg(feature = "download")]
+    #[tokio::test]
+    async fn check_yahoo_source_saves_lookups() {
+        use crate::test_util::{html_response, serve_with};
+        use crate::scraper::ScrapeConfig;
+        use super::YahooSource;
+
+        let db_path = std::env::temp_dir().join(format!("yahoo-finance-symbols-yahoo-source-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db_path);
+        let base_url = serve_with(|_| html_response(
+            r#"<table><tbody><tr><td><a data-symbol="AAPL">AAPL</a></td><td>Apple Inc.</td><td>1</td>
+            <td><a>Technology</a></td><td>Stocks</td><td>NMS</td></tr></tbody></table>"#,
+        ))
+        .await;
+
+        let source = YahooSource { config: ScrapeConfig { concurrency: 20, ..ScrapeConfig::default() }, base_url };
+        let result = save_symbols_from(&db_path, &source).await.unwrap();
+        // Every lookup returns the same row, written once by whichever sector answered first
+        assert_eq!(result.total_inserted, 1);
+        assert_eq!(result.per_sector.len(), 2);
+        assert!(result.failed_sectors.is_empty());
+
+        let conn = Connection::open(&db_path).unwrap();
+        let checkpoint: i64 = conn.query_row("SELECT COUNT(*) FROM scrape_checkpoint", [], |row| row.get(0)).unwrap();
+        assert_eq!(checkpoint, 0);
+        assert_eq!(read_build_info(&conn).unwrap().source.as_deref(), Some("yahoo"));
+
+        drop(conn);
+        std::fs::remove_file(&db_path).unwrap();
+    }
+}
@@ -0,0 +1,59 @@
+//! Local HTTP servers for the tests of the download and scrape paths.
+
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+
+/// Answers every HTTP request with `respond(request)`, returning the base URL
+pub(crate) async fn serve_with<F, R>(respond: F) -> String
+where
+    F: Fn(&str) -> R + Send + Sync + 'static,
+    R: AsRef<[u8]> + Send,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let respond = Arc::new(respond);
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let respond = respond.clone();
+            tokio::spawn(async move {
+                let mut request = [0; 4096];
+                let read = stream.read(&mut request).await.unwrap_or(0);
+                let response = respond(&String::from_utf8_lossy(&request[..read]));
+                let _ = stream.write_all(response.as_ref()).await;
+            });
+        }
+    });
+    url
+}
+
+/// A `200 OK` response carrying `body` as HTML
+pub(crate) fn html_response(body: &str) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )
+}
+
+/// A response with the given status line and extra `headers` carrying `body` as is, e.g. a database file
+pub(crate) fn bytes_response(status: &str, headers: &[&str], body: &[u8]) -> Vec<u8> {
+    let mut head = format!("HTTP/1.1 {}\r\n", status);
+    for header in headers {
+        head.push_str(header);
+        head.push_str("\r\n");
+    }
+    head.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", body.len()));
+    [head.as_bytes(), body].concat()
+}
+
+/// A response with the given status line carrying `body` as JSON
+pub(crate) fn json_response(status: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
@@ -0,0 +1,477 @@
+//! Checking tickers against Yahoo Finance's quote endpoint, e.g. to audit a portfolio for delistings.
+//!
+//! The quote endpoint only answers requests carrying a session cookie and the crumb Yahoo hands
+//! out for it, so every validation starts by fetching both (see `Session`).
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+use lazy_static::lazy_static;
+use reqwest::header::{COOKIE, RETRY_AFTER, SET_COOKIE};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+
+pub(crate) const QUOTE_URL: &str = "https://query1.finance.yahoo.com/v7/finance/quote";
+/// Page whose answer sets the session cookie the crumb is issued for
+pub(crate) const COOKIE_URL: &str = "https://fc.yahoo.com";
+/// Endpoint handing out the crumb that authenticates quote requests
+pub(crate) const CRUMB_URL: &str = "https://query1.finance.yahoo.com/v1/test/getcrumb";
+const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36";
+
+/// Requests in flight at once when `validate_symbols` is given a concurrency of 0
+pub const DEFAULT_VALIDATE_CONCURRENCY: usize = 5;
+/// Attempts made per request before a rate-limited or failing request is reported as an error
+pub const VALIDATE_RETRY_ATTEMPTS: u32 = 4;
+/// Delay before the first retry; it doubles with every further attempt
+pub const VALIDATE_RETRY_DELAY: Duration = Duration::from_millis(500);
+/// Time allowed for each quote request by default
+pub const DEFAULT_VALIDATE_TIMEOUT: Duration = Duration::from_secs(30);
+/// Tickers sent in one quote request by default
+pub const DEFAULT_VALIDATE_BATCH_SIZE: usize = 50;
+/// Most tickers sent in one quote request, which keeps the request URL a few kilobytes long at most
+pub const MAX_VALIDATE_BATCH_SIZE: usize = 200;
+
+/// How `validate_symbols_with_config` spreads its quote requests
+///
+/// Tickers are sent `batch_size` at a time, comma-separated in the `symbols` parameter of one
+/// request, and at most `concurrency` requests are in flight at once. A request that is rate
+/// limited, answered with a server error, times out or cannot connect is sent again up to
+/// `retries` times, with exponential backoff starting at `VALIDATE_RETRY_DELAY`, or after the
+/// delay given by a `Retry-After` header.
+#[derive(Debug, Clone)]
+pub struct ValidateConfig {
+    /// Maximum number of quote requests in flight at once, at least 1
+    pub concurrency: usize,
+    /// Time allowed for each request, from sending it to reading the whole answer; `None` waits forever
+    pub timeout: Option<Duration>,
+    /// Number of times a failed request is sent again before the call fails
+    pub retries: u32,
+    /// Tickers per request, between 1 and `MAX_VALIDATE_BATCH_SIZE`
+    pub batch_size: usize,
+}
+
+impl Default for ValidateConfig {
+    fn default() -> Self {
+        ValidateConfig {
+            concurrency: DEFAULT_VALIDATE_CONCURRENCY,
+            timeout: Some(DEFAULT_VALIDATE_TIMEOUT),
+            retries: VALIDATE_RETRY_ATTEMPTS - 1,
+            batch_size: DEFAULT_VALIDATE_BATCH_SIZE,
+        }
+    }
+}
+
+impl ValidateConfig {
+    /// Checks that the configuration can be sent to Yahoo Finance
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if self.batch_size == 0 || self.batch_size > MAX_VALIDATE_BATCH_SIZE {
+            return Err(format!("batch_size must be between 1 and {}, got {}", MAX_VALIDATE_BATCH_SIZE, self.batch_size).into());
+        }
+        if self.concurrency == 0 {
+            return Err("concurrency must be at least 1".into());
+        }
+        Ok(())
+    }
+}
+
+lazy_static! {
+    /// Client shared by every validation, so its connections are reused from one call to the next
+    static ref CLIENT: Client = Client::builder().user_agent(USER_AGENT).build().expect("Failed to build the HTTP client");
+}
+
+/// Where a validation fetches its session and its quotes
+struct Endpoints<'a> {
+    cookie: &'a str,
+    crumb: &'a str,
+    quote: &'a str,
+}
+
+const YAHOO_ENDPOINTS: Endpoints<'static> = Endpoints { cookie: COOKIE_URL, crumb: CRUMB_URL, quote: QUOTE_URL };
+
+/// A Yahoo Finance session: the cookies set by `COOKIE_URL` and the crumb issued for them
+#[derive(Debug, Clone)]
+struct Session {
+    cookie: String,
+    crumb: String,
+}
+
+impl Session {
+    /// Collects the session cookies, then asks for a crumb with them
+    async fn start(client: &Client, endpoints: &Endpoints<'_>, timeout: Option<Duration>) -> Result<Session, String> {
+        let with_timeout = |request: reqwest::RequestBuilder| match timeout {
+            Some(timeout) => request.timeout(timeout),
+            None => request,
+        };
+
+        // The cookie page answers with an error status but still sets the cookies
+        let response = with_timeout(client.get(endpoints.cookie)).send().await.map_err(|e| e.to_string())?;
+        let cookie = response
+            .headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok()?.split(';').next())
+            .collect::<Vec<_>>()
+            .join("; ");
+        if cookie.is_empty() {
+            return Err("Yahoo Finance set no session cookie".to_string());
+        }
+
+        let crumb = with_timeout(client.get(endpoints.crumb).header(COOKIE, &cookie))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| e.to_string())?
+            .text()
+            .await
+            .map_err(|e| e.to_string())?;
+        let crumb = crumb.trim().to_string();
+        // A crumb is short and opaque; anything else is an error page
+        if crumb.is_empty() || crumb.len() > 64 || crumb.contains(char::is_whitespace) {
+            return Err(format!("Unexpected crumb from Yahoo Finance: {:.64}", crumb));
+        }
+        Ok(Session { cookie, crumb })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QuoteEnvelope {
+    quote_response: QuoteResponse,
+}
+
+#[derive(Deserialize)]
+struct QuoteResponse {
+    #[serde(default)]
+    result: Vec<Quote>,
+}
+
+#[derive(Deserialize)]
+struct Quote {
+    symbol: String,
+}
+
+/// Checks which tickers Yahoo Finance still quotes
+///
+/// Every ticker is looked up on Yahoo Finance's quote endpoint, with the defaults of
+/// `ValidateConfig` except for the number of requests in flight: at most `concurrency`
+/// requests run at once, all sharing one HTTP client; a concurrency of 0 uses
+/// `DEFAULT_VALIDATE_CONCURRENCY` (5), which stays well within Yahoo's rate limits. Use
+/// `validate_symbols_with_config` to also tune the timeout, retries and batch size.
+///
+/// # Arguments
+///
+/// * `symbols` - tickers to check; duplicates are checked once
+/// * `concurrency` - maximum number of requests in flight
+///
+/// # Returns
+///
+/// * `HashMap<String, bool>` - every ticker, mapped to whether Yahoo Finance still quotes it
+///
+/// # Errors
+///
+/// As for `validate_symbols_with_config`.
+///
+/// # Example
+///
+/// ```no_run
+/// use yahoo_finance_symbols::validate_symbols;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let portfolio = ["AAPL", "MSFT", "TWTR"];
+///     for (symbol, exists) in validate_symbols(&portfolio, 0).await? {
+///         if !exists {
+///             println!("{} is no longer listed", symbol);
+///         }
+///     }
+///     Ok(())
+/// }
+/// ```
+pub async fn validate_symbols(symbols: &[&str], concurrency: usize) -> Result<HashMap<String, bool>, Box<dyn Error>> {
+    let concurrency = if concurrency == 0 { DEFAULT_VALIDATE_CONCURRENCY } else { concurrency };
+    validate_symbols_with_config(symbols, &ValidateConfig { concurrency, ..ValidateConfig::default() }).await
+}
+
+/// Checks which tickers Yahoo Finance still quotes, with explicit request settings
+///
+/// Tickers are sent to the quote endpoint in batches of `config.batch_size`, up to
+/// `MAX_VALIDATE_BATCH_SIZE` (200) per request, so a portfolio of 1,000 tickers takes 20
+/// requests with the default batch of 50. Yahoo answers a batch with the quotes it knows and
+/// leaves out the rest, so a ticker missing from an otherwise valid answer is reported as no
+/// longer quoted. See `ValidateConfig` for the concurrency, timeout and retry settings.
+///
+/// # Arguments
+///
+/// * `symbols` - tickers to check; duplicates are checked once
+/// * `config` - concurrency, timeout, retries and batch size
+///
+/// # Returns
+///
+/// * `HashMap<String, bool>` - every ticker, mapped to whether Yahoo Finance still quotes it
+///
+/// # Errors
+///
+/// Fails if `config` is invalid. A ticker maps to `false` only when Yahoo Finance answered its
+/// batch and did not return it. If the request for any batch still fails after its retries, or
+/// is answered with a client error such as `401 Unauthorized`, the whole call fails with an
+/// error naming the tickers of that batch, rather than reporting them as delisted. The remaining
+/// requests are then aborted. Fails as well if Yahoo Finance hands out no session cookie or
+/// crumb.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use yahoo_finance_symbols::validate::{validate_symbols_with_config, ValidateConfig};
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let config = ValidateConfig { concurrency: 2, timeout: Some(Duration::from_secs(10)), retries: 5, batch_size: 100 };
+///     let results = validate_symbols_with_config(&["AAPL", "MSFT", "TWTR"], &config).await?;
+///     println!("{:?}", results);
+///     Ok(())
+/// }
+/// ```
+pub async fn validate_symbols_with_config(symbols: &[&str], config: &ValidateConfig) -> Result<HashMap<String, bool>, Box<dyn Error>> {
+    validate_symbols_at(&YAHOO_ENDPOINTS, symbols, config, VALIDATE_RETRY_DELAY).await
+}
+
+/// Validates `symbols` against the quote endpoint of `endpoints`, retrying `retry_delay` apart at first
+async fn validate_symbols_at(
+    endpoints: &Endpoints<'_>,
+    symbols: &[&str],
+    config: &ValidateConfig,
+    retry_delay: Duration,
+) -> Result<HashMap<String, bool>, Box<dyn Error>> {
+    config.validate()?;
+    let client = &*CLIENT;
+    let session = Session::start(client, endpoints, config.timeout)
+        .await
+        .map_err(|e| format!("Failed to start a Yahoo Finance session: {}", e))?;
+    let semaphore = Arc::new(Semaphore::new(config.concurrency));
+    // Dropping the JoinSet on the first error aborts the requests still pending
+    let mut tasks = JoinSet::new();
+
+    let mut seen = HashSet::new();
+    let unique: Vec<String> = symbols.iter().filter(|symbol| seen.insert(**symbol)).map(|symbol| symbol.to_string()).collect();
+    for batch in unique.chunks(config.batch_size) {
+        let client = client.clone();
+        let semaphore = semaphore.clone();
+        let config = config.clone();
+        let url = endpoints.quote.to_string();
+        let session = session.clone();
+        let batch = batch.to_vec();
+
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire().await.expect("Semaphore acquire failed");
+            let quoted = quoted_symbols(&client, &url, &session, &batch, &config, retry_delay).await;
+            (batch, quoted)
+        });
+    }
+
+    let mut results = HashMap::new();
+    while let Some(joined) = tasks.join_next().await {
+        let (batch, quoted) = joined.expect("Validation task panicked");
+        match quoted {
+            Ok(quoted) => {
+                for symbol in batch {
+                    let exists = quoted.contains(&symbol.to_uppercase());
+                    results.insert(symbol, exists);
+                }
+            }
+            Err(e) => return Err(format!("Failed to validate {}: {}", batch.join(", "), e).into()),
+        }
+    }
+
+    Ok(results)
+}
+
+/// Asks the quote endpoint at `url` which of `batch` it knows, retrying rate limits and transient failures
+///
+/// Returns the quoted tickers in upper case.
+async fn quoted_symbols(
+    client: &Client,
+    url: &str,
+    session: &Session,
+    batch: &[String],
+    config: &ValidateConfig,
+    retry_delay: Duration,
+) -> Result<HashSet<String>, String> {
+    let symbols = batch.join(",");
+    let mut delay = retry_delay;
+    let mut attempt = 0;
+    loop {
+        let mut request = client
+            .get(url)
+            .query(&[("symbols", &symbols), ("crumb", &session.crumb)])
+            .header(COOKIE, &session.cookie);
+        if let Some(timeout) = config.timeout {
+            request = request.timeout(timeout);
+        }
+
+        let (reason, retry_after) = match request.send().await {
+            Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS || response.status().is_server_error() => {
+                let retry_after = response
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok()?.parse().ok())
+                    .map(Duration::from_secs);
+                (format!("HTTP {}", response.status()), retry_after)
+            }
+            Ok(response) => {
+                let response = response.error_for_status().map_err(|e| e.to_string())?;
+                match response.json::<QuoteEnvelope>().await {
+                    Ok(body) => return Ok(body.quote_response.result.into_iter().map(|quote| quote.symbol.to_uppercase()).collect()),
+                    // The body timed out halfway through
+                    Err(e) if e.is_timeout() => (e.to_string(), None),
+                    Err(e) => return Err(e.to_string()),
+                }
+            }
+            Err(e) if e.is_timeout() || e.is_connect() => (e.to_string(), None),
+            Err(e) => return Err(e.to_string()),
+        };
+
+        attempt += 1;
+        if attempt > config.retries {
+            return Err(format!("{} after {} attempts", reason, attempt));
+        }
+        tokio::time::sleep(retry_after.unwrap_or(delay)).await;
+        delay *= 2;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use crate::test_util::{json_response, serve_with};
+    use super::{validate_symbols_at, Endpoints, ValidateConfig, VALIDATE_RETRY_ATTEMPTS};
+
+    const CRUMB: &str = "Zx9.crumb";
+    const COOKIE: &str = "A3=d=session";
+
+    /// Answers the session handshake, passing quote requests that carry the session to `respond`
+    fn with_session(request: &str, respond: impl Fn(&str) -> String) -> String {
+        let path = request.split(' ').nth(1).unwrap_or_default();
+        let has_cookie = request.lines().any(|line| line.eq_ignore_ascii_case(&format!("cookie: {}", COOKIE)));
+        if path == "/cookie" {
+            format!("HTTP/1.1 404 Not Found\r\nSet-Cookie: {}; Domain=.yahoo.com; Path=/\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", COOKIE)
+        } else if path == "/crumb" && has_cookie {
+            format!("HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", CRUMB.len(), CRUMB)
+        } else if path.starts_with("/quote?") && has_cookie && path.ends_with(&format!("&crumb={}", CRUMB)) {
+            respond(request)
+        } else {
+            json_response("401 Unauthorized", "")
+        }
+    }
+
+    fn endpoints(base: &str) -> (String, String, String) {
+        (format!("{}cookie", base), format!("{}crumb", base), format!("{}quote", base))
+    }
+
+    fn quote_response(symbols: &[&str]) -> String {
+        let results: Vec<String> = symbols.iter().map(|s| format!("{{\"symbol\":\"{}\"}}", s)).collect();
+        json_response("200 OK", &format!("{{\"quoteResponse\":{{\"result\":[{}],\"error\":null}}}}", results.join(",")))
+    }
+
+    /// Tickers requested by an HTTP request to the quote endpoint
+    fn requested(request: &str) -> Vec<String> {
+        let line = request.lines().next().unwrap_or_default();
+        let query = line.split("symbols=").nth(1).and_then(|rest| rest.split(['&', ' ']).next()).unwrap_or_default();
+        query.split("%2C").map(str::to_string).collect()
+    }
+
+    fn config(concurrency: usize, retries: u32, batch_size: usize) -> ValidateConfig {
+        ValidateConfig { concurrency, retries, batch_size, ..ValidateConfig::default() }
+    }
+
+    #[tokio::test]
+    async fn check_validate_symbols() {
+        let rate_limited = Arc::new(AtomicUsize::new(0));
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let (counter, seen) = (rate_limited.clone(), batches.clone());
+        let base = serve_with(move |request| with_session(request, |request| {
+            let symbols = requested(request);
+            seen.lock().unwrap().push(symbols.clone());
+            // The batch with MSFT is rate limited twice before answering
+            if symbols.iter().any(|s| s == "MSFT") && counter.fetch_add(1, Ordering::SeqCst) < 2 {
+                return json_response("429 Too Many Requests", "");
+            }
+            // Yahoo answers with the tickers in upper case
+            let known: Vec<String> = symbols.iter().map(|s| s.to_uppercase()).filter(|s| ["AAPL", "MSFT", "BRK-B"].contains(&s.as_str())).collect();
+            quote_response(&known.iter().map(String::as_str).collect::<Vec<_>>())
+        }))
+        .await;
+        let (cookie, crumb, quote) = endpoints(&base);
+        let endpoints = Endpoints { cookie: &cookie, crumb: &crumb, quote: &quote };
+
+        let symbols = ["AAPL", "MSFT", "TWTR", "AAPL", "brk-b"];
+        let results = validate_symbols_at(&endpoints, &symbols, &config(2, 3, 2), Duration::from_millis(1)).await.unwrap();
+        assert_eq!(results.len(), 4);
+        assert!(results["AAPL"] && results["MSFT"]);
+        // Missing from the answer to its batch
+        assert!(!results["TWTR"]);
+        assert!(results["brk-b"]);
+        assert_eq!(rate_limited.load(Ordering::SeqCst), 3);
+
+        let mut requested: Vec<Vec<String>> = batches.lock().unwrap().clone();
+        requested.sort();
+        requested.dedup();
+        assert_eq!(requested, vec![vec!["AAPL".to_string(), "MSFT".to_string()], vec!["TWTR".to_string(), "brk-b".to_string()]]);
+    }
+
+    #[tokio::test]
+    async fn check_validate_symbols_errors() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+        let base = serve_with(move |request| with_session(request, |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            json_response("503 Service Unavailable", "")
+        }))
+        .await;
+        let (cookie, crumb, quote) = endpoints(&base);
+        let endpoints = Endpoints { cookie: &cookie, crumb: &crumb, quote: &quote };
+
+        let error = validate_symbols_at(&endpoints, &["AAPL", "MSFT"], &ValidateConfig::default(), Duration::from_millis(1)).await.unwrap_err();
+        assert!(error.to_string().contains("AAPL, MSFT"), "{}", error);
+        assert_eq!(attempts.load(Ordering::SeqCst), VALIDATE_RETRY_ATTEMPTS as usize);
+
+        attempts.store(0, Ordering::SeqCst);
+        assert!(validate_symbols_at(&endpoints, &["AAPL"], &config(1, 0, 1), Duration::from_millis(1)).await.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+        // Client errors are not retried and are not mistaken for a delisting
+        let quote = format!("{}unauthorized", base);
+        let unauthorized = Endpoints { quote: &quote, ..endpoints };
+        assert!(validate_symbols_at(&unauthorized, &["AAPL"], &config(1, 3, 1), Duration::from_millis(1)).await.is_err());
+
+        // Without a crumb no quote is requested
+        let crumb_url = format!("{}nocrumb", base);
+        let no_crumb = Endpoints { crumb: &crumb_url, ..endpoints };
+        attempts.store(0, Ordering::SeqCst);
+        let error = validate_symbols_at(&no_crumb, &["AAPL"], &config(1, 3, 1), Duration::from_millis(1)).await.unwrap_err();
+        assert!(error.to_string().contains("session"), "{}", error);
+        assert_eq!(attempts.load(Ordering::SeqCst), 0);
+
+        // A server that never answers quotes times out
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let quote = format!("http://{}/", listener.local_addr().unwrap());
+        let silent = Endpoints { quote: &quote, ..endpoints };
+        let timeout_config = ValidateConfig { timeout: Some(Duration::from_millis(50)), ..config(1, 1, 1) };
+        let error = validate_symbols_at(&silent, &["AAPL"], &timeout_config, Duration::from_millis(1)).await.unwrap_err();
+        assert!(error.to_string().contains("after 2 attempts"), "{}", error);
+        drop(listener);
+
+        assert!(validate_symbols_at(&endpoints, &["AAPL"], &config(1, 0, 0), Duration::from_millis(1)).await.is_err());
+        assert!(validate_symbols_at(&endpoints, &["AAPL"], &config(0, 0, 1), Duration::from_millis(1)).await.is_err());
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use rusqlite::{Connection, Result};
+use serde::{Deserialize, Serialize};
+
+
+/// Minimum number of rows a complete database is expected to hold.
+///
+/// Derived from the bundled `symbols.db` (465,000+ rows at the time of writing),
+/// rounded down to leave headroom for delistings between scrapes. Also exported as
+/// `EXPECTED_MIN_SYMBOLS`, so tests outside this crate can assert completeness against the
+/// same number `verify_database` uses.
+pub const MIN_TOTAL_SYMBOLS: i64 = 450_000;
+
+/// Minimum number of rows expected per stored asset class.
+///
+/// Each threshold is roughly 75% of the count found in the bundled database, so a
+/// sector that was only partially scraped is flagged while normal churn is not.
+pub const MIN_ASSET_CLASS_SYMBOLS: &[(&str, i64)] = &[
+    ("Stocks", 100_000),
+    ("Mutual Fund", 150_000),
+    ("Index", 45_000),
+    ("ETF", 35_000),
+    ("CRYPTOCURRENCY", 8_000),
+    ("Currency", 2_000),
+    ("Future", 1_000),
+];
+
+/// Result of a database integrity and completeness check
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifyReport {
+    pub passed: bool,
+    pub total_count: i64,
+    pub asset_class_counts: HashMap<String, i64>,
+    pub anomalies: Vec<String>,
+}
+
+/// Runs the integrity and completeness checks against an open connection
+pub fn verify_connection(conn: &Connection) -> Result<VerifyReport> {
+    let mut report = VerifyReport {
+        passed: false,
+        total_count: 0,
+        asset_class_counts: HashMap::new(),
+        anomalies: Vec::new(),
+    };
+
+    let table_count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'symbols'",
+        [],
+        |row| row.get(0),
+    )?;
+    if table_count == 0 {
+        report.anomalies.push("Table `symbols` does not exist".to_string());
+        return Ok(report);
+    }
+
+    report.total_count = conn.query_row("SELECT COUNT(*) FROM symbols", [], |row| row.get(0))?;
+    if report.total_count == 0 {
+        report.anomalies.push("Table `symbols` is empty".to_string());
+    } else if report.total_count < MIN_TOTAL_SYMBOLS {
+        report.anomalies.push(format!(
+            "Only {} symbols found, expected at least {}", report.total_count, MIN_TOTAL_SYMBOLS
+        ));
+    }
+
+    let mut stmt = conn.prepare("SELECT asset_class, COUNT(*) FROM symbols GROUP BY asset_class")?;
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))?;
+    for row in rows {
+        let (asset_class, count) = row?;
+        report.asset_class_counts.insert(asset_class, count);
+    }
+
+    for (asset_class, min_count) in MIN_ASSET_CLASS_SYMBOLS {
+        let count = report.asset_class_counts.get(*asset_class).copied().unwrap_or(0);
+        if count < *min_count {
+            report.anomalies.push(format!(
+                "Only {} symbols found for asset class {}, expected at least {}", count, asset_class, min_count
+            ));
+        }
+    }
+
+    let empty_symbols: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM symbols WHERE symbol IS NULL OR TRIM(symbol) = ''",
+        [],
+        |row| row.get(0),
+    )?;
+    if empty_symbols > 0 {
+        report.anomalies.push(format!("{} rows have an empty symbol", empty_symbols));
+    }
+
+    report.passed = report.anomalies.is_empty();
+    Ok(report)
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use rusqlite::Connection;
+    use super::verify_connection;
+
+    #[test]
+    fn check_verify_flags_incomplete_database() {
+        let conn = Connection::open_in_memory().unwrap();
+        let report = verify_connection(&conn).unwrap();
+        assert!(!report.passed);
+
+        conn.execute_batch(
+            "CREATE TABLE symbols (symbol TEXT PRIMARY KEY, name TEXT, category TEXT, asset_class TEXT, exchange TEXT);
+             INSERT INTO symbols VALUES ('AAPL', 'Apple Inc.', 'Technology', 'Stocks', 'NMS');
+             INSERT INTO symbols VALUES ('', 'Blank', 'N/A', 'Stocks', 'NMS');",
+        ).unwrap();
+        let report = verify_connection(&conn).unwrap();
+        assert!(!report.passed);
+        assert_eq!(report.total_count, 2);
+        assert_eq!(report.asset_class_counts.get("Stocks"), Some(&2));
+        assert!(report.anomalies.iter().any(|a| a.contains("empty symbol")));
+    }
+}
@@ -0,0 +1,93 @@
+//! An in-memory snapshot of a subset of symbols for latency-sensitive prefix search.
+//!
+//! [`warm_subset`] loads the symbols of one asset class and exchange from the default database
+//! once and keeps them sorted by ticker. [`search_warmed`] then answers ticker prefix lookups with
+//! a binary search over that snapshot, without touching SQLite, which suits autocomplete boxes
+//! that query on every keystroke.
+//!
+//! Each warmed symbol costs the length of its five strings plus about 120 bytes of overhead,
+//! around 160 bytes in all, so the 1,700 NASDAQ stocks take under 300 KB. The snapshot is not
+//! refreshed: after `update_database` or an import, call `warm_subset` again to pick up changes.
+
+use std::sync::RwLock;
+use rusqlite::Result;
+use crate::keys::{AssetClass, Category, Exchange};
+use crate::Symbol;
+
+
+static WARMED: RwLock<Vec<Symbol>> = RwLock::new(Vec::new());
+
+/// Loads the symbols of `asset_class` listed on `exchange` into memory, replacing any earlier subset
+///
+/// Returns the number of symbols warmed.
+///
+/// # Example
+///
+/// ```
+/// use yahoo_finance_symbols::keys::{AssetClass, Exchange};
+/// use yahoo_finance_symbols::{search_warmed, warm_subset};
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     warm_subset(AssetClass::Stocks, Exchange::NASDAQ).await?;
+///     for symbol in search_warmed("MS", 5) {
+///         println!("{} {}", symbol.symbol, symbol.name);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub async fn warm_subset(asset_class: AssetClass, exchange: Exchange) -> Result<usize> {
+    let mut symbols = crate::get_symbols(asset_class, Category::All, exchange).await?;
+    symbols.sort_unstable_by(|a, b| a.symbol.cmp(&b.symbol));
+    let count = symbols.len();
+
+    *WARMED.write().expect("Warmed symbols lock poisoned") = symbols;
+
+    Ok(count)
+}
+
+/// Returns up to `limit` warmed symbols whose ticker starts with `prefix`, in ticker order
+///
+/// The prefix is upper-cased first, since Yahoo Finance tickers are upper case. Returns nothing
+/// until `warm_subset` has been called.
+pub fn search_warmed(prefix: &str, limit: usize) -> Vec<Symbol> {
+    let prefix = prefix.to_uppercase();
+    let warmed = WARMED.read().expect("Warmed symbols lock poisoned");
+
+    let start = warmed.partition_point(|s| s.symbol.as_str() < prefix.as_str());
+    warmed[start..]
+        .iter()
+        .take_while(|s| s.symbol.starts_with(&prefix))
+        .take(limit)
+        .cloned()
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use crate::keys::{AssetClass, Category, Exchange};
+    use crate::get_symbols;
+    use super::{search_warmed, warm_subset};
+
+    #[tokio::test]
+    async fn check_search_warmed() {
+        let count = warm_subset(AssetClass::Stocks, Exchange::NASDAQ).await.unwrap();
+        let nasdaq = get_symbols(AssetClass::Stocks, Category::All, Exchange::NASDAQ).await.unwrap();
+        assert_eq!(count, nasdaq.len());
+
+        let warmed = search_warmed("aap", 100);
+        assert_eq!(warmed.first().map(|s| s.symbol.as_str()), Some("AAPL"));
+        assert!(warmed.windows(2).all(|pair| pair[0].symbol < pair[1].symbol));
+
+        let mut expected: Vec<String> = nasdaq.into_iter().map(|s| s.symbol).filter(|s| s.starts_with("MS")).collect();
+        expected.sort();
+        expected.truncate(3);
+        assert_eq!(search_warmed("MS", 3).into_iter().map(|s| s.symbol).collect::<Vec<_>>(), expected);
+
+        assert!(search_warmed("ZZZZZZZZ", 10).is_empty());
+        assert!(search_warmed("A", 0).is_empty());
+    }
+}